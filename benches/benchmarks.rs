@@ -2,11 +2,11 @@
 
 extern crate test;
 
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 use backtrace::Backtrace;
 
 #[bench]
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 fn trace(b: &mut test::Bencher) {
     #[inline(never)]
     fn the_function() {
@@ -20,7 +20,7 @@ fn trace(b: &mut test::Bencher) {
 }
 
 #[bench]
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 fn trace_and_resolve_callback(b: &mut test::Bencher) {
     #[inline(never)]
     fn the_function() {
@@ -36,7 +36,7 @@ fn trace_and_resolve_callback(b: &mut test::Bencher) {
 }
 
 #[bench]
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 fn trace_and_resolve_separate(b: &mut test::Bencher) {
     #[inline(never)]
     fn the_function(frames: &mut Vec<*mut std::ffi::c_void>) {
@@ -58,7 +58,7 @@ fn trace_and_resolve_separate(b: &mut test::Bencher) {
 }
 
 #[bench]
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 fn new_unresolved(b: &mut test::Bencher) {
     #[inline(never)]
     fn the_function() {
@@ -69,7 +69,7 @@ fn new_unresolved(b: &mut test::Bencher) {
 }
 
 #[bench]
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 fn new(b: &mut test::Bencher) {
     #[inline(never)]
     fn the_function() {
@@ -80,7 +80,7 @@ fn new(b: &mut test::Bencher) {
 }
 
 #[bench]
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 fn new_unresolved_and_resolve_separate(b: &mut test::Bencher) {
     #[inline(never)]
     fn the_function() {
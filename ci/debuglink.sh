@@ -72,4 +72,26 @@ mv $dwzfile.tmp $dwzfile
 
 # Cleanup
 rm $idfile $debugfile3 $dwzfile
+
+# MiniDebugInfo, made the same way as Fedora's find-debuginfo: a symbol table
+# of the functions missing from .dynsym, compressed into .gnu_debugdata
+cargo build --manifest-path crates/debuglink/Cargo.toml --features backtrace/gnu-debugdata
+minidir=`dirname $exefile`/minidebuginfo
+mkdir -p $minidir
+nm -D $exefile --format=posix --defined-only | awk '{ print $1 }' | sort > $minidir/dynsyms
+nm $exefile --format=posix --defined-only | awk '{ if ($2 == "T" || $2 == "t") print $1 }' | sort > $minidir/funcsyms
+comm -13 $minidir/dynsyms $minidir/funcsyms > $minidir/keep_symbols
+objcopy --only-keep-debug $exefile $minidir/debug
+objcopy -S --remove-section .gdb_index --remove-section .comment \
+  --keep-symbols=$minidir/keep_symbols $minidir/debug $minidir/mini_debuginfo
+strip --strip-all -R .comment $exefile
+xz $minidir/mini_debuginfo
+objcopy --add-section .gnu_debugdata=$minidir/mini_debuginfo.xz $exefile
+$exefile $cratedir --symbols-only
+
+# Missing MiniDebugInfo should fail
+objcopy --remove-section .gnu_debugdata $exefile
+! $exefile $cratedir --symbols-only
+
+rm -r $minidir
 echo Success
@@ -4,3 +4,10 @@ set -ex
 
 cargo test --target $TARGET
 cargo build --target $TARGET --manifest-path crates/as-if-std/Cargo.toml
+
+# Also test the unwinder which reads `.eh_frame` itself where it's supported.
+case "$TARGET" in
+  x86_64-unknown-linux-gnu|aarch64-unknown-linux-gnu|riscv64gc-unknown-linux-gnu)
+    cargo test --target $TARGET --features unwind-dwarf
+    ;;
+esac
@@ -0,0 +1,151 @@
+//! Finding the range of addresses that the current thread's stack occupies.
+
+#[cfg(feature = "std-base")]
+use std::cell::Cell;
+
+/// Returns the range of addresses occupied by the stack of the current
+/// thread, as its lowest address and the address one past its highest.
+///
+/// Stacks grow downwards on every supported platform, so the frames of
+/// callers are at higher addresses than those of their callees, up to the end
+/// of the range. This allows telling whether a value read from the stack, such
+/// as a saved frame pointer, can point into it at all. The range includes the
+/// guard pages at the bottom of the stack, if there are any.
+///
+/// The bounds are looked up with `pthread_getattr_np` on Linux and Android,
+/// `pthread_get_stackaddr_np` on macOS and iOS and
+/// `GetCurrentThreadStackLimits` on Windows 8 and later. `None` is returned on
+/// other platforms or if the lookup fails. With the `std` feature the result
+/// is remembered for each thread, as for the main thread the lookup may read
+/// `/proc`.
+///
+/// These are the bounds of the stack the thread was created with, so code
+/// running on another stack, such as a signal handler on an alternate signal
+/// stack or a coroutine, isn't within them.
+///
+/// # Examples
+///
+/// ```
+/// let local = 0u8;
+/// if let Some((low, high)) = backtrace::stack_bounds() {
+///     let addr = &local as *const u8 as usize;
+///     assert!(low <= addr && addr < high);
+/// }
+/// ```
+pub fn stack_bounds() -> Option<(usize, usize)> {
+    #[cfg(feature = "std-base")]
+    {
+        // The slot may be gone while the thread is exiting, in which case the
+        // bounds are looked up again.
+        BOUNDS
+            .try_with(|slot| match slot.get() {
+                Some(bounds) => bounds,
+                None => {
+                    let bounds = unsafe { lookup() };
+                    slot.set(Some(bounds));
+                    bounds
+                }
+            })
+            .unwrap_or_else(|_| unsafe { lookup() })
+    }
+    #[cfg(not(feature = "std-base"))]
+    unsafe {
+        lookup()
+    }
+}
+
+// The bounds of the current thread's stack, once they've been looked up.
+#[cfg(feature = "std-base")]
+thread_local!(static BOUNDS: Cell<Option<Option<(usize, usize)>>> = Cell::new(None));
+
+/// Returns the bounds that a stack walk which reads the stack itself should
+/// stay within, if they're known.
+///
+/// Looking the bounds up isn't async-signal-safe, as it may allocate, so walks
+/// which may be running in a signal handler can't do it. The bounds are only
+/// looked up when this thread holds the crate's lock, which isn't the case for
+/// `trace_unsynchronized` called from a signal handler unless it interrupted
+/// this crate, and are otherwise only used if `stack_bounds` has already been
+/// called on this thread. Without
+/// the `std` feature there's nowhere to remember them, so they're never used.
+#[allow(dead_code)] // only used by some backends
+pub(super) fn for_walk() -> Option<(usize, usize)> {
+    #[cfg(feature = "std-base")]
+    {
+        if super::super::lock::is_held() {
+            stack_bounds()
+        } else {
+            BOUNDS.try_with(|slot| slot.get()).ok().flatten().flatten()
+        }
+    }
+    #[cfg(not(feature = "std-base"))]
+    {
+        None
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn lookup() -> Option<(usize, usize)> {
+    use core::{mem, ptr};
+
+    let mut attr: libc::pthread_attr_t = mem::zeroed();
+    if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+        return None;
+    }
+    let mut addr = ptr::null_mut();
+    let mut size = 0;
+    let ret = libc::pthread_attr_getstack(&attr, &mut addr, &mut size);
+    libc::pthread_attr_destroy(&mut attr);
+    if ret != 0 || addr.is_null() {
+        return None;
+    }
+    Some((addr as usize, (addr as usize).wrapping_add(size)))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+unsafe fn lookup() -> Option<(usize, usize)> {
+    // This is the top of the stack, rather than its lowest address.
+    let thread = libc::pthread_self();
+    let high = libc::pthread_get_stackaddr_np(thread) as usize;
+    let size = libc::pthread_get_stacksize_np(thread);
+    if high == 0 || size == 0 {
+        return None;
+    }
+    Some((high.wrapping_sub(size), high))
+}
+
+#[cfg(all(windows, not(target_vendor = "uwp")))]
+unsafe fn lookup() -> Option<(usize, usize)> {
+    use super::super::windows::*;
+    use core::mem;
+
+    // This is only available since Windows 8, so it's looked up at runtime
+    // rather than linked against.
+    let kernel32 = GetModuleHandleA(b"kernel32.dll\0".as_ptr() as *const i8);
+    if kernel32.is_null() {
+        return None;
+    }
+    let f = GetProcAddress(
+        kernel32,
+        b"GetCurrentThreadStackLimits\0".as_ptr() as *const i8,
+    );
+    if f.is_null() {
+        return None;
+    }
+    let f = mem::transmute::<_, unsafe extern "system" fn(*mut usize, *mut usize)>(f);
+    let mut low = 0;
+    let mut high = 0;
+    f(&mut low, &mut high);
+    Some((low, high))
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    all(windows, not(target_vendor = "uwp")),
+)))]
+unsafe fn lookup() -> Option<(usize, usize)> {
+    None
+}
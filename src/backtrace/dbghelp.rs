@@ -41,6 +41,10 @@ impl Frame {
         self.addr_stack().Offset as *mut _
     }
 
+    pub fn fp(&self) -> *mut c_void {
+        self.addr_frame().Offset as *mut _
+    }
+
     pub fn symbol_address(&self) -> *mut c_void {
         self.ip()
     }
@@ -49,6 +53,10 @@ impl Frame {
         Some(self.base_address)
     }
 
+    pub fn is_signal_frame(&self) -> bool {
+        false
+    }
+
     fn addr_pc(&self) -> &ADDRESS64 {
         match self.stack_frame {
             StackFrame::New(ref new) => &new.AddrPC,
@@ -63,6 +71,13 @@ impl Frame {
         }
     }
 
+    fn addr_frame(&self) -> &ADDRESS64 {
+        match self.stack_frame {
+            StackFrame::New(ref new) => &new.AddrFrame,
+            StackFrame::Old(ref old) => &old.AddrFrame,
+        }
+    }
+
     fn addr_frame_mut(&mut self) -> &mut ADDRESS64 {
         match self.stack_frame {
             StackFrame::New(ref mut new) => &mut new.AddrFrame,
@@ -89,16 +104,111 @@ impl Frame {
 struct MyContext(CONTEXT);
 
 #[inline(always)]
-pub unsafe fn trace(cb: &mut dyn FnMut(&super::Frame) -> bool) {
-    // Allocate necessary structures for doing the stack walk
-    let process = GetCurrentProcess();
-    let thread = GetCurrentThread();
-
+pub unsafe fn trace(cb: &mut dyn FnMut(&super::Frame) -> bool) -> bool {
     let mut context = mem::zeroed::<MyContext>();
     RtlCaptureContext(&mut context.0);
+    trace_with_context(&mut context, GetCurrentThread(), cb);
+    // `StackWalkEx` doesn't say whether it stopped because it reached the
+    // end of the stack or because it couldn't unwind any further.
+    true
+}
+
+pub unsafe fn trace_from_context(
+    context: *const c_void,
+    cb: &mut dyn FnMut(&super::Frame) -> bool,
+) -> bool {
+    // The stack walk updates the context as it goes, so work on a copy to
+    // leave the caller's context untouched (and correctly aligned).
+    let mut copy = mem::zeroed::<MyContext>();
+    copy.0 = core::ptr::read(context as *const CONTEXT);
+    trace_with_context(&mut copy, GetCurrentThread(), cb);
+    // See `trace` for why this can't tell whether the walk failed.
+    true
+}
+
+#[cfg(feature = "std-base")]
+pub fn current_thread_id() -> u64 {
+    unsafe { GetCurrentThreadId() as u64 }
+}
+
+#[cfg(feature = "std-base")]
+pub fn thread_ids() -> std::vec::Vec<u64> {
+    let mut ret = std::vec::Vec::new();
+    unsafe {
+        let snap = CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0);
+        if snap == INVALID_HANDLE_VALUE {
+            return ret;
+        }
+        let process = GetCurrentProcessId();
+        let mut te = mem::zeroed::<THREADENTRY32>();
+        te.dwSize = mem::size_of_val(&te) as DWORD;
+        if Thread32First(snap, &mut te) == TRUE {
+            loop {
+                if te.th32OwnerProcessID == process {
+                    ret.push(te.th32ThreadID as u64);
+                }
+                if Thread32Next(snap, &mut te) != TRUE {
+                    break;
+                }
+            }
+        }
+        CloseHandle(snap);
+    }
+    ret
+}
+
+#[cfg(feature = "std-base")]
+pub unsafe fn trace_thread(thread_id: u64, cb: &mut dyn FnMut(&super::Frame) -> bool) -> bool {
+    // The suspended thread may hold locks, such as the heap lock, which `cb`
+    // would need, so frames are buffered in preallocated storage and only
+    // handed to `cb` once the thread has been resumed. For the same reason
+    // dbghelp is initialized (and possibly loaded) up front.
+    const MAX_FRAMES: usize = 256;
+
+    let _dbghelp = match load_dbghelp() {
+        Ok(dbghelp) => dbghelp,
+        Err(()) => return false,
+    };
+    let access = THREAD_SUSPEND_RESUME | THREAD_GET_CONTEXT | THREAD_QUERY_INFORMATION;
+    let thread = OpenThread(access, FALSE, thread_id as DWORD);
+    if thread.is_null() {
+        return false;
+    }
+
+    let mut frames = std::vec::Vec::with_capacity(MAX_FRAMES);
+    let mut captured = false;
+    if SuspendThread(thread) != !0 {
+        let mut context = mem::zeroed::<MyContext>();
+        context.0.ContextFlags = CONTEXT_FULL;
+        if GetThreadContext(thread, &mut context.0) == TRUE {
+            captured = true;
+            trace_with_context(&mut context, thread, &mut |frame| {
+                frames.push(frame.clone());
+                frames.len() < MAX_FRAMES
+            });
+        }
+        ResumeThread(thread);
+    }
+    CloseHandle(thread);
+
+    for frame in frames.iter() {
+        if !cb(frame) {
+            break;
+        }
+    }
+    captured
+}
 
-    // Ensure this process's symbols are initialized
-    let dbghelp = match dbghelp::init() {
+/// Walks the stack of `thread`, whose registers are in `context`.
+unsafe fn trace_with_context(
+    context: &mut MyContext,
+    thread: HANDLE,
+    cb: &mut dyn FnMut(&super::Frame) -> bool,
+) {
+    // Allocate necessary structures for doing the stack walk
+    let process = GetCurrentProcess();
+
+    let dbghelp = match load_dbghelp() {
         Ok(dbghelp) => dbghelp,
         Err(()) => return, // oh well...
     };
@@ -206,6 +316,20 @@ pub unsafe fn trace(cb: &mut dyn FnMut(&super::Frame) -> bool) {
     }
 }
 
+/// Ensures `dbghelp.dll` is loaded for walking the stack.
+///
+/// On 64-bit platforms function tables and module bases are found without
+/// dbghelp's symbol handler, as explained in `trace_with_context`, so
+/// initializing it, with all of the symbols it loads, is left until something
+/// is symbolicated. The `Sym*` functions used on 32-bit x86 need it, though.
+fn load_dbghelp() -> Result<dbghelp::Init, ()> {
+    if cfg!(target_pointer_width = "64") {
+        dbghelp::load()
+    } else {
+        dbghelp::init()
+    }
+}
+
 #[cfg(target_arch = "x86_64")]
 fn init_frame(frame: &mut Frame, ctx: &CONTEXT) -> WORD {
     frame.addr_pc_mut().Offset = ctx.Rip as u64;
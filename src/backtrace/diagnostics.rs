@@ -0,0 +1,156 @@
+//! Finding out why a stack walk ended, with `trace_with_diagnostics`.
+
+#[cfg(feature = "std-base")]
+use super::{Frame, TraceLimit};
+#[cfg(feature = "std-base")]
+use std::cell::Cell;
+
+/// The reason a stack walk ended, as returned by `trace_with_diagnostics`.
+///
+/// Addresses are those of the frame the walk couldn't get past, which is the
+/// last frame passed to the closure unless the closure skipped it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The outermost frame of the stack was reached.
+    ///
+    /// Some unwinders, such as libgcc's, report a frame without unwind
+    /// information the same way, and backends which can't tell the reasons
+    /// apart, such as dbghelp's, always report this.
+    EndOfStack,
+    /// The closure returned `false`.
+    Stopped,
+    /// The `TraceLimit` given to `trace_with_diagnostics` was exhausted, by
+    /// reaching its maximum number of frames or its timeout.
+    LimitReached,
+    /// The walk started while this thread was already walking its stack, so
+    /// nothing was walked, see `is_reentrant`.
+    Reentrant,
+    /// No unwind information was found for the instruction at `ip`, for
+    /// example because the code it's in was compiled without it or was
+    /// generated at runtime.
+    NoUnwindInfo {
+        /// The instruction pointer of the frame.
+        ip: usize,
+    },
+    /// The unwind information for the instruction at `ip` was found, but it
+    /// can't be evaluated or doesn't say how to recover the caller's registers,
+    /// such as when it uses DWARF expressions.
+    UnsupportedUnwindInfo {
+        /// The instruction pointer of the frame.
+        ip: usize,
+    },
+    /// The caller of the frame at `ip` would have its stack or frame pointer
+    /// at or below that of the frame, so the walk wasn't making progress up
+    /// the stack and was stopped rather than loop forever.
+    StackNotIncreasing {
+        /// The instruction pointer of the frame.
+        ip: usize,
+    },
+    /// The frame pointer `fp` doesn't point at a plausible frame record, as
+    /// it's misaligned, outside the bounds of the thread's stack or too far
+    /// from the previous one. This usually means some code on the stack was
+    /// compiled without frame pointers.
+    InvalidFramePointer {
+        /// The value read as a frame pointer.
+        fp: usize,
+    },
+    /// The platform's unwinder failed to find the caller of the frame at `ip`
+    /// without saying why.
+    UnwindFailed {
+        /// The instruction pointer of the frame.
+        ip: usize,
+    },
+    /// The stack can't be walked from a saved register context with the
+    /// selected backend on this platform, so nothing was walked, see
+    /// `trace_from_context`.
+    ContextUnsupported,
+    #[doc(hidden)]
+    __Nonexhaustive,
+}
+
+// Why the last walk on this thread which didn't end normally failed.
+#[cfg(feature = "std-base")]
+thread_local!(static LAST_FAILURE: Cell<Option<StopReason>> = Cell::new(None));
+
+/// Records why a stack walk on this thread couldn't get any further, just
+/// before a backend returns `false`.
+///
+/// This is only read by `trace_with_diagnostics`, so without the `std` feature
+/// it does nothing. Recording it is async-signal-safe.
+#[allow(dead_code)] // only used by some backends
+pub(super) fn report(reason: StopReason) {
+    #[cfg(feature = "std-base")]
+    let _ = LAST_FAILURE.try_with(|last| last.set(Some(reason)));
+    #[cfg(not(feature = "std-base"))]
+    let _ = reason;
+}
+
+/// Same as `trace_with_limit`, but returns why the stack walk ended.
+///
+/// This is meant for debugging broken unwinding in a build: when a trace
+/// stops after a couple of frames it tells whether unwind information was
+/// missing for some frame, the stack corrupted, frame pointers omitted, and
+/// at which address. `TraceLimit::new()` doesn't limit the walk.
+///
+/// How much detail there is depends on the backend. The unwinders of the
+/// `trace-frame-pointer` and `unwind-dwarf` features report each of their
+/// reasons, while the platform's unwinders only report that they failed, and
+/// some can't even tell failing from reaching the end of the stack.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+///
+/// # Panics
+///
+/// See information on `trace` for caveats on `cb` panicking.
+///
+/// # Example
+///
+/// ```
+/// use backtrace::{StopReason, TraceLimit};
+///
+/// let mut frames = 0;
+/// let reason = backtrace::trace_with_diagnostics(TraceLimit::new(), |_| {
+///     frames += 1;
+///     true
+/// });
+/// if reason != StopReason::EndOfStack {
+///     eprintln!("the stack walk stopped early after {} frames: {:?}", frames, reason);
+/// }
+/// ```
+#[cfg(feature = "std-base")]
+pub fn trace_with_diagnostics<F: FnMut(&Frame) -> bool>(
+    limit: TraceLimit,
+    mut cb: F,
+) -> StopReason {
+    let _guard = crate::lock::lock();
+    let _ = LAST_FAILURE.try_with(|last| last.set(None));
+    let mut budget = limit.start();
+    let mut end = None;
+    let mut last_ip = 0;
+    let complete = unsafe {
+        super::walk(&mut |frame| {
+            if !budget.spend() {
+                end = Some(StopReason::LimitReached);
+                return false;
+            }
+            last_ip = frame.ip() as usize;
+            if !cb(frame) {
+                end = Some(StopReason::Stopped);
+                return false;
+            }
+            true
+        })
+    };
+    match end {
+        Some(reason) => reason,
+        None if complete => StopReason::EndOfStack,
+        None => LAST_FAILURE
+            .try_with(Cell::take)
+            .ok()
+            .flatten()
+            .unwrap_or(StopReason::UnwindFailed { ip: last_ip }),
+    }
+}
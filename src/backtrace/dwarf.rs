@@ -0,0 +1,740 @@
+//! Backtrace support by interpreting DWARF call frame information in Rust.
+//!
+//! Every loaded ELF object on Linux carries a `.eh_frame` section describing,
+//! for each instruction, how to recover the caller's registers. This is what
+//! libunwind reads as well, but here the tables are parsed with `gimli` and
+//! evaluated directly, so tracing doesn't depend on the platform's unwinder
+//! being present (for example in statically linked musl binaries) and the
+//! whole walk is plain Rust which can be audited and fuzzed.
+//!
+//! Objects are located with `dl_iterate_phdr`, and their unwind tables are
+//! found through the `PT_GNU_EH_FRAME` segment, which points at the binary
+//! search table in `.eh_frame_hdr`. Objects without that segment, or which only
+//! have `.debug_frame` (which isn't loaded into memory), can't be unwound
+//! through and end the trace. No memory is allocated while unwinding, so this
+//! can also be used from signal handlers.
+//!
+//! Only the subset of CFI needed for compiler-generated code is evaluated. A
+//! frame whose CFA or registers are described by DWARF expressions ends the
+//! trace, except for the kernel's signal return trampoline which is recognized
+//! by its instructions and unwound through using the saved `ucontext_t`, much
+//! like libgcc does.
+//!
+//! This is opt-in through the `unwind-dwarf` feature, and with the standard
+//! library another backend can be used instead with `set_trace_backend`.
+//!
+//! On iOS this isn't a backend of its own but a fallback for the libunwind
+//! backend. Binaries rebuilt from bitcode or stripped for the App Store can
+//! lack the compact unwind information the system unwinder relies on for some
+//! functions, which ends its trace even though their `__eh_frame` is intact,
+//! so such traces are continued from there with `resume`. Objects are then
+//! located with `dladdr`, and since `__eh_frame` has no search table it's
+//! scanned for the right entry.
+
+use super::StopReason;
+#[cfg(target_os = "linux")]
+use addr2line::gimli::Pointer;
+use addr2line::gimli::{
+    BaseAddresses, CfaRule, EhFrame, EhFrameHdr, EndianSlice, NativeEndian, Reader, Register,
+    RegisterRule, UnwindContext, UnwindContextStorage, UnwindSection, UnwindTableRow,
+};
+#[cfg(target_os = "linux")]
+use core::arch::asm;
+use core::ffi::c_void;
+use core::mem;
+use core::slice;
+
+#[derive(Clone)]
+#[cfg(all(target_os = "linux", not(feature = "std-base")))]
+pub struct Frame {
+    ip: *mut c_void,
+    sp: *mut c_void,
+    fp: *mut c_void,
+    symbol_address: *mut c_void,
+    is_signal_frame: bool,
+}
+
+// Frames only contain addresses and never dereference them, so they're safe
+// to send and share across threads.
+#[cfg(all(target_os = "linux", not(feature = "std-base")))]
+unsafe impl Send for Frame {}
+#[cfg(all(target_os = "linux", not(feature = "std-base")))]
+unsafe impl Sync for Frame {}
+
+#[cfg(all(target_os = "linux", not(feature = "std-base")))]
+impl Frame {
+    pub fn ip(&self) -> *mut c_void {
+        self.ip
+    }
+
+    pub fn sp(&self) -> *mut c_void {
+        self.sp
+    }
+
+    pub fn fp(&self) -> *mut c_void {
+        self.fp
+    }
+
+    pub fn symbol_address(&self) -> *mut c_void {
+        self.symbol_address
+    }
+
+    pub fn module_base_address(&self) -> Option<*mut c_void> {
+        None
+    }
+
+    pub fn is_signal_frame(&self) -> bool {
+        self.is_signal_frame
+    }
+}
+
+/// Number of register slots tracked. These are indexed by DWARF register
+/// number, and on AArch64 and RISC-V the last slot holds the pc which has no
+/// DWARF number.
+const NREGS: usize = 33;
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    /// `rsp`
+    pub const SP: usize = 7;
+    /// `rbp`
+    pub const FP: usize = 6;
+    /// The return address column, which is also where `rip` is kept.
+    pub const RA: usize = 16;
+    pub const IP: usize = 16;
+    /// `rbx`, `rbp` and `r12` to `r15`, the registers which are preserved
+    /// across calls and so still hold the values of callers once a frame is
+    /// unwound.
+    #[cfg(feature = "std-base")]
+    pub const CALLEE_SAVED: &[usize] = &[3, 6, 12, 13, 14, 15];
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    /// `sp`
+    pub const SP: usize = 31;
+    /// `x29`
+    pub const FP: usize = 29;
+    /// `x30`, the link register.
+    pub const RA: usize = 30;
+    pub const IP: usize = 32;
+    /// `x19` to `x29`, the registers which are preserved across calls and
+    /// so still hold the values of callers once a frame is unwound.
+    #[cfg(any(feature = "std-base", target_os = "ios"))]
+    pub const CALLEE_SAVED: &[usize] = &[19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29];
+}
+
+#[cfg(target_arch = "riscv64")]
+mod arch {
+    /// `x2`
+    pub const SP: usize = 2;
+    /// `x8`, also known as `s0`.
+    pub const FP: usize = 8;
+    /// `x1`, the return address register.
+    pub const RA: usize = 1;
+    pub const IP: usize = 32;
+    /// `s0` to `s11`, the registers which are preserved across calls and so
+    /// still hold the values of callers once a frame is unwound.
+    #[cfg(feature = "std-base")]
+    pub const CALLEE_SAVED: &[usize] = &[8, 9, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27];
+}
+
+type Regs = [Option<usize>; NREGS];
+#[cfg(all(target_os = "linux", feature = "std-base"))]
+type Callback<'a> = dyn FnMut(&super::Frame, &[(u16, usize)]) -> bool + 'a;
+type Slice = EndianSlice<'static, NativeEndian>;
+
+// Unwind rows are kept on the stack rather than the heap so that tracing
+// doesn't allocate. Compiler-generated CFI only describes a handful of
+// callee-saved registers per row, so this is plenty, and a row which doesn't
+// fit just ends the trace.
+struct StoreOnStack;
+
+impl<R: Reader> UnwindContextStorage<R> for StoreOnStack {
+    type Rules = [(Register, RegisterRule<R>); 32];
+    type Stack = [UnwindTableRow<R, Self>; 4];
+}
+
+#[inline(never)]
+#[cfg(target_os = "linux")]
+pub unsafe fn trace(cb: &mut dyn FnMut(&super::Frame) -> bool) -> bool {
+    // The first pc is exactly where our registers were captured, while every
+    // other one is a return address which may be just past the end of the
+    // calling function, so those are looked up one byte earlier. Frames
+    // resumed after a signal are the exception, as they were interrupted
+    // rather than making a call.
+    walk(capture(), false, false, &mut |frame, _, _| cb(frame))
+}
+
+/// Same as `trace`, but starts from the registers saved in the `ucontext_t`
+/// at `uc` rather than from the current call site.
+#[cfg(target_os = "linux")]
+pub unsafe fn trace_from_context(
+    uc: *const libc::ucontext_t,
+    cb: &mut dyn FnMut(&super::Frame) -> bool,
+) -> bool {
+    // The saved pc is where the thread was interrupted, not a return address.
+    walk(context_regs(uc), false, false, &mut |frame, _, _| cb(frame))
+}
+
+/// Same as `trace`, but also passes `cb` the registers recovered for each
+/// frame, as pairs of DWARF register number and value.
+///
+/// The innermost frame and frames interrupted by a signal have all of the
+/// registers which were saved for them, while for other frames only the
+/// stack pointer and the callee-saved registers are known, since the others
+/// aren't preserved across calls. The instruction pointer is the frame's own.
+#[inline(never)]
+#[cfg(all(target_os = "linux", feature = "std-base"))]
+pub unsafe fn trace_with_registers(cb: &mut Callback<'_>) -> bool {
+    walk(capture(), false, false, &mut |frame, regs, complete| {
+        let mut recovered = [(0, 0); NREGS];
+        let mut len = 0;
+        for (i, &reg) in regs.iter().enumerate() {
+            let known = if complete {
+                i != arch::IP && i != arch::RA
+            } else {
+                i == arch::SP || arch::CALLEE_SAVED.contains(&i)
+            };
+            if let (true, Some(value)) = (known, reg) {
+                recovered[len] = (i as u16, value);
+                len += 1;
+            }
+        }
+        cb(frame, &recovered[..len])
+    })
+}
+
+/// The registers of a frame which a trace can be continued from with
+/// `resume`.
+#[cfg(target_os = "ios")]
+pub struct Registers(Regs);
+
+#[cfg(target_os = "ios")]
+impl Registers {
+    /// Records the registers of the frame at `ip` and `sp`, reading the
+    /// callee-saved ones with `get` by their DWARF register number.
+    pub fn new(ip: usize, sp: usize, get: impl Fn(usize) -> usize) -> Registers {
+        let mut regs = [None; NREGS];
+        for &i in arch::CALLEE_SAVED {
+            regs[i] = Some(get(i));
+        }
+        regs[arch::SP] = Some(sp);
+        regs[arch::IP] = Some(ip);
+        Registers(regs)
+    }
+}
+
+/// Continues a trace which another unwinder couldn't take any further than
+/// the frame with the registers `regs`. That frame has already been yielded,
+/// so this starts with its caller.
+#[cfg(target_os = "ios")]
+pub unsafe fn resume(
+    regs: &Registers,
+    is_return_address: bool,
+    cb: &mut dyn FnMut(&super::Frame) -> bool,
+) -> bool {
+    walk(regs.0, is_return_address, true, &mut |frame, _, _| {
+        cb(frame)
+    })
+}
+
+/// Walks the stack from the frame with the registers `regs`, passing `cb`
+/// each frame along with its registers and whether all of them are known,
+/// rather than only the callee-saved ones.
+unsafe fn walk(
+    mut regs: Regs,
+    mut is_return_address: bool,
+    mut skip: bool,
+    cb: &mut dyn FnMut(&super::Frame, &Regs, bool) -> bool,
+) -> bool {
+    let mut ctx = UnwindContext::<Slice, StoreOnStack>::new_in();
+    let mut interrupted = false;
+    let mut complete = true;
+    loop {
+        let ip = match regs[arch::IP] {
+            Some(0) | None => return true,
+            Some(ip) => ip,
+        };
+        let sp = regs[arch::SP].unwrap_or(0);
+        let fp = regs[arch::FP].unwrap_or(0);
+        let pc = if is_return_address { ip - 1 } else { ip };
+
+        let (next, symbol_address) = step(&mut ctx, &regs, ip, pc);
+        let frame = super::Frame {
+            inner: frame(ip, sp, fp, symbol_address, interrupted),
+        };
+        if !skip && !cb(&frame, &regs, complete) {
+            return true;
+        }
+        skip = false;
+
+        // Why there's no caller has been reported by `step` already.
+        let (next, was_signal) = match next {
+            Some(next) => next,
+            None => return false,
+        };
+        // Stacks grow downwards, so the caller's stack pointer must be above
+        // ours. Requiring progress also guarantees that the walk terminates.
+        match next[arch::SP] {
+            Some(next_sp) if next_sp > sp || was_signal => {}
+            Some(_) => {
+                super::diagnostics::report(StopReason::StackNotIncreasing { ip });
+                return false;
+            }
+            None => {
+                super::diagnostics::report(StopReason::UnsupportedUnwindInfo { ip });
+                return false;
+            }
+        }
+        regs = next;
+        is_return_address = !was_signal;
+        interrupted = was_signal;
+        complete = was_signal;
+    }
+}
+
+#[cfg(all(target_os = "linux", not(feature = "std-base")))]
+fn frame(ip: usize, sp: usize, fp: usize, symbol_address: usize, is_signal_frame: bool) -> Frame {
+    Frame {
+        ip: ip as *mut c_void,
+        sp: sp as *mut c_void,
+        fp: fp as *mut c_void,
+        symbol_address: symbol_address as *mut c_void,
+        is_signal_frame,
+    }
+}
+
+// When continuing a trace of the libunwind backend, or when that backend is
+// there to fall back to, frames are yielded in the form it uses for cloned
+// frames.
+#[cfg(any(target_os = "ios", feature = "std-base"))]
+fn frame(
+    ip: usize,
+    sp: usize,
+    fp: usize,
+    symbol_address: usize,
+    is_signal_frame: bool,
+) -> super::FrameImp {
+    super::FrameImp::Cloned {
+        ip: ip as *mut c_void,
+        sp: sp as *mut c_void,
+        fp: fp as *mut c_void,
+        symbol_address: symbol_address as *mut c_void,
+        is_signal_frame,
+    }
+}
+
+/// Computes the caller's registers for the frame at `ip`, where `pc` is the
+/// address to look up unwind information for.
+///
+/// Returns the caller's registers along with whether this frame is a signal
+/// trampoline, or `None` if they can't be recovered, in which case the reason
+/// is reported, and the address of the function containing `pc`, or `ip` if
+/// that isn't known.
+unsafe fn step(
+    ctx: &mut UnwindContext<Slice, StoreOnStack>,
+    regs: &Regs,
+    ip: usize,
+    pc: usize,
+) -> (Option<(Regs, bool)>, usize) {
+    let object = match find_object(pc) {
+        Some(object) => object,
+        None => return (signal_return_or_report(regs, ip), ip),
+    };
+    let mut bases = BaseAddresses::default()
+        .set_eh_frame(object.eh_frame.as_ptr() as u64)
+        .set_text(object.bias as u64);
+    let eh_frame = EhFrame::new(object.eh_frame, NativeEndian);
+    let fde = match object.eh_frame_hdr {
+        Some(eh_frame_hdr) => {
+            bases = bases.set_eh_frame_hdr(eh_frame_hdr.as_ptr() as u64);
+            EhFrameHdr::new(eh_frame_hdr, NativeEndian)
+                .parse(&bases, mem::size_of::<usize>() as u8)
+                .ok()
+                .and_then(|hdr| {
+                    hdr.table()?
+                        .fde_for_address(&eh_frame, &bases, pc as u64, EhFrame::cie_from_offset)
+                        .ok()
+                })
+        }
+        None => eh_frame
+            .fde_for_address(&bases, pc as u64, EhFrame::cie_from_offset)
+            .ok(),
+    };
+    let fde = match fde {
+        Some(fde) => fde,
+        None => return (signal_return_or_report(regs, ip), ip),
+    };
+    let symbol_address = fde.initial_address() as usize;
+
+    // Not every trampoline has unwind information, so they're checked for
+    // even when there's no FDE above, but if there is one it can't be used.
+    if let Some(next) = signal_return(regs, ip) {
+        return (Some((next, true)), symbol_address);
+    }
+    let next = fde
+        .unwind_info_for_address(&eh_frame, &bases, ctx, pc as u64)
+        .ok()
+        .and_then(|row| apply(row, regs));
+    if next.is_none() {
+        super::diagnostics::report(StopReason::UnsupportedUnwindInfo { ip });
+    }
+    (next.map(|next| (next, false)), symbol_address)
+}
+
+/// Recovers the caller's registers if `ip` is in the signal return
+/// trampoline, which is all that can be done for code without unwind
+/// information, and otherwise reports that there's none.
+unsafe fn signal_return_or_report(regs: &Regs, ip: usize) -> Option<(Regs, bool)> {
+    let next = signal_return(regs, ip);
+    if next.is_none() {
+        super::diagnostics::report(StopReason::NoUnwindInfo { ip });
+    }
+    next.map(|next| (next, true))
+}
+
+/// Evaluates an unwind table row against the callee's registers.
+unsafe fn apply(row: &UnwindTableRow<Slice, StoreOnStack>, regs: &Regs) -> Option<Regs> {
+    let cfa = match *row.cfa() {
+        CfaRule::RegisterAndOffset { register, offset } => {
+            let base = (*regs.get(register.0 as usize)?)?;
+            (base as i64).wrapping_add(offset) as usize
+        }
+        CfaRule::Expression(_) => return None,
+    };
+
+    // Registers without a rule are callee-saved ones which this frame didn't
+    // touch, so they're carried over to the caller as-is.
+    let mut next = *regs;
+    for &(register, ref rule) in row.registers() {
+        let slot = match next.get_mut(register.0 as usize) {
+            Some(slot) => slot,
+            None => continue,
+        };
+        *slot = match *rule {
+            RegisterRule::SameValue => regs[register.0 as usize],
+            RegisterRule::Offset(offset) => {
+                Some(*((cfa as i64).wrapping_add(offset) as usize as *const usize))
+            }
+            RegisterRule::ValOffset(offset) => Some((cfa as i64).wrapping_add(offset) as usize),
+            RegisterRule::Register(other) => regs.get(other.0 as usize).copied().flatten(),
+            _ => None,
+        };
+    }
+    // Registers explicitly marked as undefined have no rule either, which is
+    // how the outermost frame says there's no caller. The return address
+    // always has a rule on x86_64 so that can be detected there, while on
+    // AArch64 and RISC-V the outermost frame instead clears the link register.
+    if cfg!(target_arch = "x86_64") && !row.registers().any(|&(r, _)| r.0 as usize == arch::RA) {
+        next[arch::RA] = None;
+    }
+    next[arch::SP] = Some(cfa);
+    next[arch::IP] = next[arch::RA];
+    Some(next)
+}
+
+/// The unwind tables of a loaded object.
+struct Object {
+    bias: usize,
+    eh_frame_hdr: Option<&'static [u8]>,
+    eh_frame: &'static [u8],
+}
+
+#[cfg(target_os = "linux")]
+struct Search {
+    pc: usize,
+    object: Option<Object>,
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn find_object(pc: usize) -> Option<Object> {
+    let mut search = Search { pc, object: None };
+    libc::dl_iterate_phdr(Some(callback), &mut search as *mut Search as *mut _);
+    search.object
+}
+
+// `info` should be a valid pointer.
+// `search` should be a valid pointer to a `Search`.
+#[cfg(target_os = "linux")]
+unsafe extern "C" fn callback(
+    info: *mut libc::dl_phdr_info,
+    _size: libc::size_t,
+    search: *mut libc::c_void,
+) -> libc::c_int {
+    let info = &*info;
+    let search = &mut *(search as *mut Search);
+    let bias = info.dlpi_addr as usize;
+    let headers = slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize);
+    let loads = || {
+        headers
+            .iter()
+            .filter(|header| header.p_type == libc::PT_LOAD)
+            .map(|header| {
+                let start = bias.wrapping_add(header.p_vaddr as usize);
+                start..start.wrapping_add(header.p_memsz as usize)
+            })
+    };
+    if !loads().any(|segment| segment.contains(&search.pc)) {
+        return 0;
+    }
+
+    // This is the object containing `pc`, so stop iterating whether or not
+    // its unwind tables can be found.
+    let header = match headers
+        .iter()
+        .find(|header| header.p_type == libc::PT_GNU_EH_FRAME)
+    {
+        Some(header) => header,
+        None => return 1,
+    };
+    let eh_frame_hdr = slice::from_raw_parts(
+        bias.wrapping_add(header.p_vaddr as usize) as *const u8,
+        header.p_memsz as usize,
+    );
+
+    // The header records where `.eh_frame` starts but not how long it is, so
+    // allow parsing up to the end of the segment containing it. Entries are
+    // only ever read by offset, so nothing past the section is touched.
+    let bases = BaseAddresses::default().set_eh_frame_hdr(eh_frame_hdr.as_ptr() as u64);
+    let parsed = match EhFrameHdr::new(eh_frame_hdr, NativeEndian)
+        .parse(&bases, mem::size_of::<usize>() as u8)
+    {
+        Ok(parsed) => parsed,
+        Err(_) => return 1,
+    };
+    let eh_frame = match parsed.eh_frame_ptr() {
+        Pointer::Direct(addr) => addr as usize,
+        Pointer::Indirect(_) => return 1,
+    };
+    let end = match loads().find(|segment| segment.contains(&eh_frame)) {
+        Some(segment) => segment.end,
+        None => return 1,
+    };
+    search.object = Some(Object {
+        bias,
+        eh_frame_hdr: Some(eh_frame_hdr),
+        eh_frame: slice::from_raw_parts(eh_frame as *const u8, end - eh_frame),
+    });
+    1
+}
+
+#[cfg(target_os = "ios")]
+unsafe fn find_object(pc: usize) -> Option<Object> {
+    extern "C" {
+        fn getsectiondata(
+            header: *const c_void,
+            segment: *const libc::c_char,
+            section: *const libc::c_char,
+            size: *mut libc::c_ulong,
+        ) -> *mut u8;
+    }
+
+    let mut info: libc::Dl_info = mem::zeroed();
+    if libc::dladdr(pc as *const c_void, &mut info) == 0 || info.dli_fbase.is_null() {
+        return None;
+    }
+    let mut size = 0;
+    let eh_frame = getsectiondata(
+        info.dli_fbase,
+        b"__TEXT\0".as_ptr().cast(),
+        b"__eh_frame\0".as_ptr().cast(),
+        &mut size,
+    );
+    if eh_frame.is_null() {
+        return None;
+    }
+    // The header is at the start of `__TEXT`, which is what text-relative
+    // pointers are relative to.
+    Some(Object {
+        bias: info.dli_fbase as usize,
+        eh_frame_hdr: None,
+        eh_frame: slice::from_raw_parts(eh_frame, size as usize),
+    })
+}
+
+/// Reads the registers of the current function, as of just before this
+/// function returns.
+#[inline(always)]
+#[cfg(target_os = "linux")]
+unsafe fn capture() -> Regs {
+    let mut raw = [0usize; NREGS];
+    #[cfg(target_arch = "x86_64")]
+    asm!(
+        "mov [rdi + 3 * 8], rbx",
+        "mov [rdi + 6 * 8], rbp",
+        "mov [rdi + 7 * 8], rsp",
+        "mov [rdi + 12 * 8], r12",
+        "mov [rdi + 13 * 8], r13",
+        "mov [rdi + 14 * 8], r14",
+        "mov [rdi + 15 * 8], r15",
+        "lea rax, [rip]",
+        "mov [rdi + 16 * 8], rax",
+        in("rdi") raw.as_mut_ptr(),
+        out("rax") _,
+        options(nostack, preserves_flags),
+    );
+    #[cfg(target_arch = "x86_64")]
+    let saved: &[usize] = &[3, 6, 7, 12, 13, 14, 15, 16];
+
+    #[cfg(target_arch = "aarch64")]
+    asm!(
+        "stp x19, x20, [x0, #19 * 8]",
+        "stp x21, x22, [x0, #21 * 8]",
+        "stp x23, x24, [x0, #23 * 8]",
+        "stp x25, x26, [x0, #25 * 8]",
+        "stp x27, x28, [x0, #27 * 8]",
+        "stp x29, x30, [x0, #29 * 8]",
+        "mov x1, sp",
+        "str x1, [x0, #31 * 8]",
+        "adr x1, .",
+        "str x1, [x0, #32 * 8]",
+        in("x0") raw.as_mut_ptr(),
+        out("x1") _,
+        options(nostack, preserves_flags),
+    );
+    #[cfg(target_arch = "aarch64")]
+    let saved: &[usize] = &[19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32];
+
+    #[cfg(target_arch = "riscv64")]
+    asm!(
+        "sd ra, 8(a0)",
+        "sd sp, 16(a0)",
+        "sd s0, 64(a0)",
+        "sd s1, 72(a0)",
+        "sd s2, 144(a0)",
+        "sd s3, 152(a0)",
+        "sd s4, 160(a0)",
+        "sd s5, 168(a0)",
+        "sd s6, 176(a0)",
+        "sd s7, 184(a0)",
+        "sd s8, 192(a0)",
+        "sd s9, 200(a0)",
+        "sd s10, 208(a0)",
+        "sd s11, 216(a0)",
+        "auipc t0, 0",
+        "sd t0, 256(a0)",
+        in("a0") raw.as_mut_ptr(),
+        out("t0") _,
+        options(nostack, preserves_flags),
+    );
+    #[cfg(target_arch = "riscv64")]
+    let saved: &[usize] = &[1, 2, 8, 9, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 32];
+
+    let mut regs = [None; NREGS];
+    for &i in saved {
+        regs[i] = Some(raw[i]);
+    }
+    regs
+}
+
+/// If `ip` is the start of the signal return trampoline, recovers the
+/// interrupted registers from the `ucontext_t` saved on the stack.
+///
+/// The trampolines' unwind information is written with DWARF expressions, so
+/// like other unwinders they're recognized by their instructions instead.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+unsafe fn signal_return(regs: &Regs, ip: usize) -> Option<Regs> {
+    // mov $15, %rax; syscall
+    const SIGRETURN: [u8; 9] = [0x48, 0xc7, 0xc0, 0x0f, 0x00, 0x00, 0x00, 0x0f, 0x05];
+    if *(ip as *const [u8; 9]) != SIGRETURN {
+        return None;
+    }
+
+    // The handler returned into the trampoline, popping the return address
+    // which is the first field of the signal frame, so the stack pointer is
+    // left pointing at the `ucontext_t` which follows it.
+    let sp = regs[arch::SP]?;
+    Some(context_regs(sp as *const libc::ucontext_t))
+}
+
+/// Reads the registers saved in the `ucontext_t` at `uc`.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+unsafe fn context_regs(uc: *const libc::ucontext_t) -> Regs {
+    let gregs = &(*uc).uc_mcontext.gregs;
+    let get = |index: libc::c_int| Some(gregs[index as usize] as usize);
+    let mut next = [None; NREGS];
+    next[0] = get(libc::REG_RAX);
+    next[1] = get(libc::REG_RDX);
+    next[2] = get(libc::REG_RCX);
+    next[3] = get(libc::REG_RBX);
+    next[4] = get(libc::REG_RSI);
+    next[5] = get(libc::REG_RDI);
+    next[6] = get(libc::REG_RBP);
+    next[7] = get(libc::REG_RSP);
+    next[8] = get(libc::REG_R8);
+    next[9] = get(libc::REG_R9);
+    next[10] = get(libc::REG_R10);
+    next[11] = get(libc::REG_R11);
+    next[12] = get(libc::REG_R12);
+    next[13] = get(libc::REG_R13);
+    next[14] = get(libc::REG_R14);
+    next[15] = get(libc::REG_R15);
+    next[16] = get(libc::REG_RIP);
+    next
+}
+
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+unsafe fn signal_return(regs: &Regs, ip: usize) -> Option<Regs> {
+    // mov x8, #139; svc #0
+    const SIGRETURN: [u32; 2] = [0xd2801168, 0xd4000001];
+    if *(ip as *const [u32; 2]) != SIGRETURN {
+        return None;
+    }
+
+    // The stack pointer is left pointing at the signal frame, which starts
+    // with the `siginfo_t` followed by the `ucontext_t`.
+    let sp = regs[arch::SP]?;
+    let uc = (sp + mem::size_of::<libc::siginfo_t>()) as *const libc::ucontext_t;
+    Some(context_regs(uc))
+}
+
+/// Reads the registers saved in the `ucontext_t` at `uc`.
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+unsafe fn context_regs(uc: *const libc::ucontext_t) -> Regs {
+    let mcontext = &(*uc).uc_mcontext;
+    let mut next = [None; NREGS];
+    for (slot, value) in next.iter_mut().zip(mcontext.regs.iter()) {
+        *slot = Some(*value as usize);
+    }
+    next[arch::SP] = Some(mcontext.sp as usize);
+    next[arch::IP] = Some(mcontext.pc as usize);
+    next
+}
+
+#[cfg(all(target_os = "linux", target_arch = "riscv64"))]
+unsafe fn signal_return(regs: &Regs, ip: usize) -> Option<Regs> {
+    // li a7, 139; ecall
+    const SIGRETURN: [u32; 2] = [0x08b00893, 0x00000073];
+    // With compressed instructions code is only 2-byte aligned.
+    if (ip as *const [u32; 2]).read_unaligned() != SIGRETURN {
+        return None;
+    }
+
+    // As on AArch64 the stack pointer is left pointing at the signal frame,
+    // a `siginfo_t` followed by the `ucontext_t`.
+    let sp = regs[arch::SP]?;
+    let uc = (sp + mem::size_of::<libc::siginfo_t>()) as *const libc::ucontext_t;
+    Some(context_regs(uc))
+}
+
+/// Reads the registers saved in the `ucontext_t` at `uc`, which are indexed
+/// by register number, except that `x0` is replaced by the pc.
+#[cfg(all(target_os = "linux", target_arch = "riscv64"))]
+unsafe fn context_regs(uc: *const libc::ucontext_t) -> Regs {
+    let gregs = &(*uc).uc_mcontext.__gregs;
+    let mut next = [None; NREGS];
+    for (slot, value) in next.iter_mut().zip(gregs.iter()).skip(1) {
+        *slot = Some(*value as usize);
+    }
+    next[0] = Some(0);
+    next[arch::IP] = Some(gregs[0] as usize);
+    next
+}
+
+// The system unwinder gets through `_sigtramp` itself, so a trace which is
+// continued here never needs to.
+#[cfg(target_os = "ios")]
+unsafe fn signal_return(_regs: &Regs, _ip: usize) -> Option<Regs> {
+    None
+}
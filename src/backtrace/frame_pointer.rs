@@ -0,0 +1,220 @@
+//! Backtrace support by walking the chain of frame pointers.
+//!
+//! When code is compiled with frame pointers each function's prologue pushes
+//! the return address and the caller's frame pointer, and then points the
+//! frame pointer register at that pair. The stack can then be walked by simply
+//! following the linked list of these "frame records", which is far cheaper
+//! than consulting unwind tables like libunwind does.
+//!
+//! This is only correct if *every* function on the stack maintains a frame
+//! pointer, which typically requires compiling with
+//! `-C force-frame-pointers=yes` (and the equivalent for any C code). If that
+//! isn't the case frames will be missing, and a register which happens to not
+//! hold a frame pointer may be followed into arbitrary memory, possibly
+//! crashing the process. Some sanity checks are done on each frame record to
+//! stop at obviously-bogus values, such as checking that it's within the
+//! bounds of the thread's stack if `stack_bounds` knows them, but they can't
+//! catch everything. For this reason this backend is opt-in through the
+//! `trace-frame-pointer` feature, and with the standard library it can be
+//! swapped for libunwind at runtime through `set_trace_backend`.
+//!
+//! The layout of a frame record is the same on the architectures supported
+//! here: the caller's frame pointer followed by the return address.
+
+use super::StopReason;
+use core::arch::asm;
+use core::ffi::c_void;
+use core::mem;
+
+/// Frame records more than this far apart are assumed to be bogus, as it's
+/// more likely to be a stray value than a function with a frame this large.
+const MAX_FRAME_SIZE: usize = 1 << 20;
+
+#[derive(Clone)]
+#[cfg(not(feature = "std-base"))]
+pub struct Frame {
+    ip: *mut c_void,
+    sp: *mut c_void,
+    fp: *mut c_void,
+}
+
+// Frames only contain addresses and never dereference them, so they're safe
+// to send and share across threads.
+#[cfg(not(feature = "std-base"))]
+unsafe impl Send for Frame {}
+#[cfg(not(feature = "std-base"))]
+unsafe impl Sync for Frame {}
+
+#[cfg(not(feature = "std-base"))]
+impl Frame {
+    pub fn ip(&self) -> *mut c_void {
+        self.ip
+    }
+
+    pub fn sp(&self) -> *mut c_void {
+        self.sp
+    }
+
+    pub fn fp(&self) -> *mut c_void {
+        self.fp
+    }
+
+    pub fn symbol_address(&self) -> *mut c_void {
+        // Frame records don't say anything about which function they belong
+        // to, so like some other backends we just return the ip.
+        self.ip
+    }
+
+    pub fn module_base_address(&self) -> Option<*mut c_void> {
+        None
+    }
+
+    pub fn is_signal_frame(&self) -> bool {
+        false
+    }
+}
+
+#[inline(always)]
+pub unsafe fn trace(cb: &mut dyn FnMut(&super::Frame) -> bool) -> bool {
+    walk(frame_pointer(), cb)
+}
+
+/// Same as `trace`, but starts from the registers saved in the `ucontext_t`
+/// at `uc` rather than from the current call site.
+///
+/// The frame that was interrupted is yielded first, and its callers are then
+/// found through its frame pointer. A function interrupted before it pushed
+/// its frame record, or which doesn't have one, takes the place of its
+/// caller in the chain, so the caller is missing from the trace.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64"),
+))]
+pub unsafe fn trace_from_context(
+    uc: *const libc::ucontext_t,
+    cb: &mut dyn FnMut(&super::Frame) -> bool,
+) -> bool {
+    let (ip, sp, fp) = context_registers(uc);
+    let frame = super::Frame {
+        inner: frame(ip, sp, fp),
+    };
+    if !cb(&frame) {
+        return true;
+    }
+    walk(fp, cb)
+}
+
+/// Follows the chain of frame records starting at `fp`.
+unsafe fn walk(mut fp: usize, cb: &mut dyn FnMut(&super::Frame) -> bool) -> bool {
+    // Where the bounds of the thread's stack are known, frame records outside
+    // of them are refused rather than read. The walk may have started on
+    // another stack though, such as an alternate signal stack, in which case
+    // the bounds say nothing about where its frame records are.
+    let bounds = super::bounds::for_walk().filter(|&(low, high)| low <= fp && fp < high);
+    loop {
+        // The outermost frame has a null frame pointer or return address.
+        // Frame records are always word-aligned and stacks are never at the
+        // bottom of the address space, so anything else is treated as a
+        // broken chain.
+        if fp < mem::size_of::<usize>() || fp % mem::size_of::<usize>() != 0 {
+            if fp != 0 {
+                super::diagnostics::report(StopReason::InvalidFramePointer { fp });
+            }
+            return fp == 0;
+        }
+        if let Some((low, high)) = bounds {
+            if fp < low || fp >= high || high - fp < 2 * mem::size_of::<usize>() {
+                super::diagnostics::report(StopReason::InvalidFramePointer { fp });
+                return false;
+            }
+        }
+        let record = fp as *const usize;
+        let next = *record;
+        let ip = *record.add(1);
+        if ip == 0 {
+            return true;
+        }
+
+        // The frame record is the last thing pushed before the callee's
+        // locals, so just past it is where the caller's stack pointer was
+        // when it made the call.
+        let frame = super::Frame {
+            inner: frame(ip, fp + 2 * mem::size_of::<usize>(), fp),
+        };
+        if !cb(&frame) {
+            return true;
+        }
+
+        // Stacks grow downwards, so the caller's frame record must be above
+        // ours. Requiring progress also guarantees that the walk terminates.
+        if next != 0 && next <= fp {
+            super::diagnostics::report(StopReason::StackNotIncreasing { ip });
+            return false;
+        }
+        if next != 0 && next - fp > MAX_FRAME_SIZE {
+            super::diagnostics::report(StopReason::InvalidFramePointer { fp: next });
+            return false;
+        }
+        fp = next;
+    }
+}
+
+#[cfg(not(feature = "std-base"))]
+fn frame(ip: usize, sp: usize, fp: usize) -> Frame {
+    Frame {
+        ip: ip as *mut c_void,
+        sp: sp as *mut c_void,
+        fp: fp as *mut c_void,
+    }
+}
+
+// When the libunwind backend is there to fall back to, frames are yielded in
+// the form it uses for cloned frames.
+#[cfg(feature = "std-base")]
+fn frame(ip: usize, sp: usize, fp: usize) -> super::FrameImp {
+    super::FrameImp::Cloned {
+        ip: ip as *mut c_void,
+        sp: sp as *mut c_void,
+        fp: fp as *mut c_void,
+        // Frame records don't say anything about which function they belong
+        // to, so like some other backends we just return the ip.
+        symbol_address: ip as *mut c_void,
+        is_signal_frame: false,
+    }
+}
+
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe {
+        #[cfg(target_arch = "x86_64")]
+        asm!("mov {}, rbp", out(reg) fp, options(nomem, nostack, preserves_flags));
+        #[cfg(target_arch = "x86")]
+        asm!("mov {}, ebp", out(reg) fp, options(nomem, nostack, preserves_flags));
+        #[cfg(target_arch = "aarch64")]
+        asm!("mov {}, x29", out(reg) fp, options(nomem, nostack, preserves_flags));
+    }
+    fp
+}
+
+/// Reads the instruction, stack and frame pointers saved in the `ucontext_t`
+/// at `uc`.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+unsafe fn context_registers(uc: *const libc::ucontext_t) -> (usize, usize, usize) {
+    let gregs = &(*uc).uc_mcontext.gregs;
+    (
+        gregs[libc::REG_RIP as usize] as usize,
+        gregs[libc::REG_RSP as usize] as usize,
+        gregs[libc::REG_RBP as usize] as usize,
+    )
+}
+
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+unsafe fn context_registers(uc: *const libc::ucontext_t) -> (usize, usize, usize) {
+    let mcontext = &(*uc).uc_mcontext;
+    (
+        mcontext.pc as usize,
+        mcontext.sp as usize,
+        mcontext.regs[29] as usize,
+    )
+}
@@ -23,7 +23,9 @@ pub enum Frame {
     Cloned {
         ip: *mut c_void,
         sp: *mut c_void,
+        fp: *mut c_void,
         symbol_address: *mut c_void,
+        is_signal_frame: bool,
     },
 }
 
@@ -50,6 +52,13 @@ impl Frame {
         }
     }
 
+    pub fn fp(&self) -> *mut c_void {
+        match *self {
+            Frame::Raw(ctx) => unsafe { uw::get_fp(ctx) as *mut c_void },
+            Frame::Cloned { fp, .. } => fp,
+        }
+    }
+
     pub fn symbol_address(&self) -> *mut c_void {
         if let Frame::Cloned { symbol_address, .. } = *self {
             return symbol_address;
@@ -76,6 +85,25 @@ impl Frame {
     pub fn module_base_address(&self) -> Option<*mut c_void> {
         None
     }
+
+    pub fn is_signal_frame(&self) -> bool {
+        let ctx = match *self {
+            Frame::Raw(ctx) => ctx,
+            Frame::Cloned {
+                is_signal_frame, ..
+            } => return is_signal_frame,
+        };
+        // Unwinders record whether a frame was reached by unwinding through a
+        // signal trampoline (through the `S` augmentation in its CIE or by
+        // recognizing the `sigreturn` instructions) so that the ip isn't
+        // treated like a return address, and the only way to get at that
+        // flag is through `_Unwind_GetIPInfo`.
+        let mut ip_before_insn = 0;
+        unsafe {
+            uw::_Unwind_GetIPInfo(ctx, &mut ip_before_insn);
+        }
+        ip_before_insn != 0
+    }
 }
 
 impl Clone for Frame {
@@ -83,15 +111,58 @@ impl Clone for Frame {
         Frame::Cloned {
             ip: self.ip(),
             sp: self.sp(),
+            fp: self.fp(),
             symbol_address: self.symbol_address(),
+            is_signal_frame: self.is_signal_frame(),
         }
     }
 }
 
 #[inline(always)]
-pub unsafe fn trace(mut cb: &mut dyn FnMut(&super::Frame) -> bool) {
-    uw::_Unwind_Backtrace(trace_fn, &mut cb as *mut _ as *mut _);
+#[cfg(not(all(feature = "addr2line", target_os = "ios", target_arch = "aarch64")))]
+pub unsafe fn trace(cb: &mut dyn FnMut(&super::Frame) -> bool) -> bool {
+    backtrace(cb)
+}
+
+// See `super::dwarf` for why traces are continued on iOS, which is done for
+// any trace which ends before the callback asked it to, as libunwind reports
+// running out of unwind information the same as reaching the end of the
+// stack. If it really was the end, the fallback simply doesn't find anything
+// either.
+#[inline(always)]
+#[cfg(all(feature = "addr2line", target_os = "ios", target_arch = "aarch64"))]
+pub unsafe fn trace(cb: &mut dyn FnMut(&super::Frame) -> bool) -> bool {
+    if super::is_conservative() {
+        return backtrace(cb);
+    }
+    let mut last = None;
+    let mut stopped = false;
+    backtrace(&mut |frame| {
+        if !cb(frame) {
+            stopped = true;
+            return false;
+        }
+        // The registers need to be read now, as the context is gone once
+        // `_Unwind_Backtrace` returns.
+        if let Frame::Raw(ctx) = frame.inner {
+            let regs =
+                super::dwarf::Registers::new(frame.ip() as usize, frame.sp() as usize, |i| {
+                    uw::_Unwind_GetGR(ctx, i as libc::c_int)
+                });
+            last = Some((regs, !frame.is_signal_frame()));
+        }
+        true
+    });
+    match (stopped, last) {
+        (false, Some((regs, is_return_address))) => {
+            super::dwarf::resume(&regs, is_return_address, cb)
+        }
+        _ => true,
+    }
+}
 
+#[inline(always)]
+unsafe fn backtrace(cb: &mut dyn FnMut(&super::Frame) -> bool) -> bool {
     extern "C" fn trace_fn(
         ctx: *mut uw::_Unwind_Context,
         arg: *mut c_void,
@@ -111,6 +182,25 @@ pub unsafe fn trace(mut cb: &mut dyn FnMut(&super::Frame) -> bool) {
             uw::_URC_FAILURE
         }
     }
+
+    let mut stopped = false;
+    let mut last_ip = 0;
+    let mut cb = |frame: &super::Frame| {
+        last_ip = frame.ip() as usize;
+        let keep_going = cb(frame);
+        stopped = !keep_going;
+        keep_going
+    };
+    let mut cb: &mut dyn FnMut(&super::Frame) -> bool = &mut cb;
+    let code = uw::_Unwind_Backtrace(trace_fn, &mut cb as *mut _ as *mut _);
+    // Unwinders agree on how reaching the end of the stack is reported, but
+    // not on what's returned when the callback stops the walk, which on ARM
+    // is the same as failing to unwind a frame.
+    let complete = stopped || matches!(code, uw::_URC_END_OF_STACK);
+    if !complete {
+        super::diagnostics::report(super::StopReason::UnwindFailed { ip: last_ip });
+    }
+    complete
 }
 
 /// Unwind library interface used for backtraces
@@ -163,8 +253,14 @@ mod uw {
         ))] {
             extern "C" {
                 pub fn _Unwind_GetIP(ctx: *mut _Unwind_Context) -> libc::uintptr_t;
+                pub fn _Unwind_GetIPInfo(
+                    ctx: *mut _Unwind_Context,
+                    ip_before_insn: *mut libc::c_int,
+                ) -> libc::uintptr_t;
                 pub fn _Unwind_FindEnclosingFunction(pc: *mut c_void) -> *mut c_void;
 
+                pub fn _Unwind_GetGR(ctx: *mut _Unwind_Context, index: libc::c_int) -> libc::uintptr_t;
+
                 #[cfg(not(all(target_os = "linux", target_arch = "s390x")))]
                 // This function is a misnomer: rather than getting this frame's
                 // Canonical Frame Address (aka the caller frame's SP) it
@@ -181,11 +277,25 @@ mod uw {
             // instead of relying on _Unwind_GetCFA.
             #[cfg(all(target_os = "linux", target_arch = "s390x"))]
             pub unsafe fn get_sp(ctx: *mut _Unwind_Context) -> libc::uintptr_t {
-                extern "C" {
-                    pub fn _Unwind_GetGR(ctx: *mut _Unwind_Context, index: libc::c_int) -> libc::uintptr_t;
-                }
                 _Unwind_GetGR(ctx, 15)
             }
+
+            // Reads the frame pointer by its DWARF register number on the
+            // architectures which have a conventional one.
+            pub unsafe fn get_fp(ctx: *mut _Unwind_Context) -> libc::uintptr_t {
+                let index = if cfg!(target_arch = "x86_64") {
+                    6 // rbp
+                } else if cfg!(target_arch = "x86") {
+                    5 // ebp
+                } else if cfg!(target_arch = "aarch64") {
+                    29 // x29
+                } else if cfg!(target_arch = "riscv64") {
+                    8 // s0
+                } else {
+                    return 0;
+                };
+                _Unwind_GetGR(ctx, index)
+            }
         } else {
             // On android and arm, the function `_Unwind_GetIP` and a bunch of
             // others are macros, so we define functions containing the
@@ -242,6 +352,16 @@ mod uw {
                 (val & !1) as libc::uintptr_t
             }
 
+            // ARM EHABI has no notion of signal frames, so like in libgcc's
+            // `unwind-arm-common.h` this never reports one.
+            pub unsafe fn _Unwind_GetIPInfo(
+                ctx: *mut _Unwind_Context,
+                ip_before_insn: *mut libc::c_int,
+            ) -> libc::uintptr_t {
+                *ip_before_insn = 0;
+                _Unwind_GetIP(ctx)
+            }
+
             // R13 is the stack pointer on arm.
             const SP: _Unwind_Word = 13;
 
@@ -258,6 +378,12 @@ mod uw {
                 val as libc::uintptr_t
             }
 
+            // Whether r7 or r11 is the frame pointer depends on whether the
+            // code is Thumb, which isn't known here.
+            pub unsafe fn get_fp(_ctx: *mut _Unwind_Context) -> libc::uintptr_t {
+                0
+            }
+
             // This function also doesn't exist on Android or ARM/Linux, so make it
             // a no-op.
             pub unsafe fn _Unwind_FindEnclosingFunction(pc: *mut c_void) -> *mut c_void {
@@ -0,0 +1,80 @@
+//! Bounding how long a stack walk may take, with `trace_with_limit`.
+
+use std::time::{Duration, Instant};
+
+/// A budget for a stack walk, passed to `trace_with_limit` and
+/// `BacktraceBuilder::limit`.
+///
+/// Walking the stack normally takes time proportional to its depth, but deep
+/// recursion or a corrupted stack can make it take much longer, which may not
+/// be acceptable in places like crash handlers. A walk with a budget stops
+/// once it has produced the maximum number of frames or once the time allowed
+/// has passed, whichever comes first, and reports that it was cut short.
+///
+/// The budget is checked between frames, so the time taken to unwind a single
+/// frame can still exceed it.
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct TraceLimit {
+    max_frames: Option<usize>,
+    timeout: Option<Duration>,
+}
+
+impl TraceLimit {
+    /// Returns a budget without any limits, which lets the walk run to the
+    /// end of the stack.
+    pub fn new() -> TraceLimit {
+        TraceLimit::default()
+    }
+
+    /// Stops the walk after `n` frames.
+    pub fn max_frames(mut self, n: usize) -> Self {
+        self.max_frames = Some(n);
+        self
+    }
+
+    /// Stops the walk once `timeout` has passed since it started.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Starts spending this budget on a walk.
+    pub(crate) fn start(&self) -> Budget {
+        Budget {
+            frames_left: self.max_frames,
+            // Not every platform has a clock, so it's only read if needed. A
+            // timeout too large to represent is no limit at all.
+            deadline: self
+                .timeout
+                .and_then(|timeout| Instant::now().checked_add(timeout)),
+        }
+    }
+}
+
+/// What's left of a `TraceLimit` during a walk.
+pub(crate) struct Budget {
+    frames_left: Option<usize>,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    /// Takes one frame out of the budget, returning `false` if it's been
+    /// exhausted and the walk should stop before that frame.
+    pub(crate) fn spend(&mut self) -> bool {
+        if let Some(ref mut left) = self.frames_left {
+            if *left == 0 {
+                return false;
+            }
+            *left -= 1;
+        }
+        match self.deadline {
+            Some(deadline) => Instant::now() < deadline,
+            None => true,
+        }
+    }
+}
@@ -47,6 +47,10 @@ impl Frame {
         core::ptr::null_mut()
     }
 
+    pub fn fp(&self) -> *mut c_void {
+        core::ptr::null_mut()
+    }
+
     pub fn symbol_address(&self) -> *mut c_void {
         self.inner.fn_ptr
     }
@@ -54,12 +58,17 @@ impl Frame {
     pub fn module_base_address(&self) -> Option<*mut c_void> {
         None
     }
+
+    pub fn is_signal_frame(&self) -> bool {
+        false
+    }
 }
 
-pub fn trace<F: FnMut(&super::Frame) -> bool>(cb: F) {
+pub fn trace<F: FnMut(&super::Frame) -> bool>(cb: F) -> bool {
     // SAFETY: Miri guarantees that the backtrace API functions
     // can be called from any thread.
     unsafe { trace_unsynchronized(cb) };
+    true
 }
 
 pub fn resolve_addr(ptr: *mut c_void) -> Frame {
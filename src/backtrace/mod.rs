@@ -1,5 +1,6 @@
 use core::ffi::c_void;
 use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 /// Inspects the current call-stack, passing all active frames into the closure
 /// provided to calculate a stack trace.
@@ -47,7 +48,7 @@ use core::fmt;
 ///     });
 /// }
 /// ```
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 pub fn trace<F: FnMut(&Frame) -> bool>(cb: F) {
     let _guard = crate::lock::lock();
     unsafe { trace_unsynchronized(cb) }
@@ -63,9 +64,617 @@ pub fn trace<F: FnMut(&Frame) -> bool>(cb: F) {
 ///
 /// See information on `trace` for caveats on `cb` panicking.
 pub unsafe fn trace_unsynchronized<F: FnMut(&Frame) -> bool>(mut cb: F) {
-    trace_imp(&mut cb)
+    walk(&mut cb);
 }
 
+/// Walks the stack with the backend, unless this thread is already walking it,
+/// returning whether the walk ended normally.
+///
+/// A walk nested in another one on the same thread, from a signal handler or
+/// from the closure given to `trace`, could deadlock on locks the other one
+/// holds inside the unwinder, so it yields nothing and isn't complete.
+unsafe fn walk(cb: &mut dyn FnMut(&Frame) -> bool) -> bool {
+    walk_with(|| trace_imp(cb))
+}
+
+/// Same as `walk`, with `trace` walking the stack.
+unsafe fn walk_with(trace: impl FnOnce() -> bool) -> bool {
+    #[cfg(feature = "std-base")]
+    let _guard = match crate::reentrancy::enter(crate::reentrancy::Activity::Walking) {
+        Some(guard) => guard,
+        None => {
+            diagnostics::report(StopReason::Reentrant);
+            return false;
+        }
+    };
+    trace()
+}
+
+/// The ways of walking the stack which `set_trace_backend` chooses between.
+///
+/// Which of these are available depends on the platform and on the features
+/// this crate was compiled with, see `Backend::is_available`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// Walks the stack the way this build of the crate does by default: by
+    /// following frame pointers with the `trace-frame-pointer` feature, with
+    /// the unwinder of the `unwind-dwarf` feature, and otherwise with the
+    /// platform's unwinder.
+    Auto,
+    /// Follows the chain of frame pointers, as the `trace-frame-pointer`
+    /// feature does. This is the fastest way of walking the stack, which suits
+    /// sampling profilers, but it's only accurate if all code on the stack was
+    /// compiled with frame pointers.
+    FramePointer,
+    /// Interprets the `.eh_frame` unwind tables of loaded objects in Rust, as
+    /// the `unwind-dwarf` feature does.
+    Dwarf,
+    /// Uses only the platform's unwinder, such as libunwind or dbghelp. This is
+    /// slower, but only reads what unwind information describes, which suits
+    /// crash reports.
+    Unwind,
+    #[doc(hidden)]
+    __Nonexhaustive,
+}
+
+impl Backend {
+    /// Returns whether this backend is compiled into this build of the crate,
+    /// so that selecting it with `set_trace_backend` has an effect.
+    ///
+    /// `Backend::Auto` is always available. `Backend::FramePointer` requires
+    /// the `trace-frame-pointer` feature and a unix platform on x86, x86_64
+    /// or AArch64, and `Backend::Dwarf` requires the `unwind-dwarf` feature
+    /// and Linux on x86_64, AArch64 or 64-bit RISC-V. Where either of these
+    /// is the default, the others also require the `std` feature.
+    pub fn is_available(self) -> bool {
+        let frame_pointer = cfg!(all(
+            not(miri),
+            feature = "trace-frame-pointer",
+            unix,
+            any(
+                target_arch = "x86_64",
+                target_arch = "x86",
+                target_arch = "aarch64"
+            ),
+        ));
+        let dwarf = cfg!(all(
+            not(miri),
+            feature = "unwind-dwarf",
+            target_os = "linux",
+            any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "riscv64"
+            ),
+        ));
+        let unwind = cfg!(all(
+            not(miri),
+            any(
+                all(
+                    unix,
+                    not(target_os = "emscripten"),
+                    not(all(target_os = "ios", target_arch = "arm")),
+                ),
+                all(target_env = "sgx", target_vendor = "fortanix"),
+                all(windows, not(target_vendor = "uwp")),
+            ),
+        ));
+        match self {
+            Backend::Auto => true,
+            Backend::FramePointer => frame_pointer,
+            // The frame pointer walker takes precedence as the default, and
+            // the other backends are compiled in next to it and the DWARF
+            // unwinder along with the standard library.
+            Backend::Dwarf if frame_pointer => dwarf && cfg!(feature = "std-base"),
+            Backend::Dwarf => dwarf,
+            Backend::Unwind if frame_pointer || dwarf => unwind && cfg!(feature = "std-base"),
+            Backend::Unwind => unwind,
+            Backend::__Nonexhaustive => false,
+        }
+    }
+}
+
+/// The backend `set_trace_backend` selected, as a `Backend` cast to `usize`.
+static BACKEND: AtomicUsize = AtomicUsize::new(Backend::Auto as usize);
+
+/// Selects how the stack is walked by `trace` and every other function of
+/// this crate which walks it.
+///
+/// The default is chosen at compile time through the `trace-frame-pointer`
+/// and `unwind-dwarf` features, but where more than one backend is compiled
+/// in, applications may want a different one depending on the situation,
+/// such as walking frame pointers while sampling and using the platform's
+/// unwinder for an accurate trace when crashing. Selecting a backend which
+/// isn't available, see `Backend::is_available`, is the same as selecting
+/// `Backend::Auto`.
+///
+/// The setting applies to the whole process and can be changed at any time.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::Backend;
+///
+/// if Backend::FramePointer.is_available() {
+///     backtrace::set_trace_backend(Backend::FramePointer);
+/// }
+/// let mut ips = [0; 64];
+/// backtrace::trace_raw(&mut ips);
+/// backtrace::set_trace_backend(Backend::Auto);
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[cfg(feature = "std-base")]
+pub fn set_trace_backend(backend: Backend) {
+    BACKEND.store(backend as usize, Ordering::Relaxed);
+}
+
+/// Returns the backend `set_trace_backend` selected.
+#[allow(dead_code)] // only some platforms have a choice
+fn selected() -> Backend {
+    match BACKEND.load(Ordering::Relaxed) {
+        n if n == Backend::FramePointer as usize => Backend::FramePointer,
+        n if n == Backend::Dwarf as usize => Backend::Dwarf,
+        n if n == Backend::Unwind as usize => Backend::Unwind,
+        _ => Backend::Auto,
+    }
+}
+
+/// Selects whether stack traces are taken conservatively.
+///
+/// The `trace-frame-pointer` and `unwind-dwarf` features make this crate
+/// walk the stack itself, which is fast but reads memory that code built
+/// with instrumentation such as AddressSanitizer or run under Valgrind may
+/// not lay out as expected, producing garbage frames or tripping the tool's
+/// checks. With conservative traces enabled the platform's unwinder is used
+/// instead, which is slower but only reads what unwind information
+/// describes, and on iOS traces which the system unwinder ends early aren't
+/// continued by reading `__eh_frame` either.
+///
+/// The setting applies to the whole process and can be changed at any time,
+/// for example when an environment variable says the program runs under a
+/// sanitizer. It has no effect with the default backends on other platforms,
+/// which already use the platform's unwinder.
+///
+/// This is the same as `set_trace_backend(Backend::Unwind)`, or
+/// `set_trace_backend(Backend::Auto)` when `conservative` is false.
+///
+/// # Examples
+///
+/// ```
+/// if std::env::var_os("ASAN_OPTIONS").is_some() {
+///     backtrace::set_conservative(true);
+/// }
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[cfg(feature = "std-base")]
+pub fn set_conservative(conservative: bool) {
+    set_trace_backend(if conservative {
+        Backend::Unwind
+    } else {
+        Backend::Auto
+    });
+}
+
+// Only some backends have a conservative mode.
+#[allow(dead_code)]
+fn is_conservative() -> bool {
+    selected() == Backend::Unwind
+}
+
+/// Same as `trace`, but returns whether the stack walk ended normally, either
+/// at the end of the stack or because `cb` returned `false`, rather than
+/// because the unwinder couldn't get any further.
+///
+/// Backends which can't tell the difference always return `true`.
+#[cfg(feature = "std-base")]
+pub(crate) fn trace_checked(cb: &mut dyn FnMut(&Frame) -> bool) -> bool {
+    let _guard = crate::lock::lock();
+    unsafe { walk(cb) }
+}
+
+/// What `trace_checked_with_registers` calls for each frame, with the
+/// registers recovered for it if any.
+#[cfg(feature = "std-base")]
+pub(crate) type RegistersCallback<'a> = dyn FnMut(&Frame, Option<&[(u16, usize)]>) -> bool + 'a;
+
+/// Same as `trace_checked`, but also passes `cb` the registers recovered for
+/// each frame, as pairs of DWARF register number and value, when the stack is
+/// walked by interpreting DWARF CFI. Other backends pass `None` instead.
+#[cfg(feature = "std-base")]
+pub(crate) fn trace_checked_with_registers(cb: &mut RegistersCallback<'_>) -> bool {
+    let _guard = crate::lock::lock();
+    unsafe { walk_with(|| trace_with_registers_imp(cb)) }
+}
+
+/// Same as `trace`, but stops walking the stack once `limit` is exhausted,
+/// returning whether it did.
+///
+/// This is useful where an unbounded stack walk isn't acceptable, such as in a
+/// crash handler, as a stack with runaway recursion or corrupted frames can
+/// take a very long time to walk. `cb` is called for at most as many frames
+/// as `limit` allows, and for none after its timeout has passed. If there
+/// were frames left to walk at that point `true` is returned, meaning the
+/// trace was truncated. Otherwise, if the stack is walked to its end or `cb`
+/// returns `false` first, `false` is returned.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+///
+/// # Panics
+///
+/// See information on `trace` for caveats on `cb` panicking.
+///
+/// # Example
+///
+/// ```
+/// use backtrace::TraceLimit;
+/// use std::time::Duration;
+///
+/// let limit = TraceLimit::new()
+///     .max_frames(2)
+///     .timeout(Duration::from_millis(10));
+/// let mut frames = 0;
+/// let truncated = backtrace::trace_with_limit(limit, |_| {
+///     frames += 1;
+///     true
+/// });
+/// assert!(frames <= 2);
+/// if frames < 2 {
+///     assert!(!truncated);
+/// }
+/// ```
+#[cfg(feature = "std-base")]
+pub fn trace_with_limit<F: FnMut(&Frame) -> bool>(limit: TraceLimit, mut cb: F) -> bool {
+    let _guard = crate::lock::lock();
+    let mut budget = limit.start();
+    let mut truncated = false;
+    unsafe {
+        walk(&mut |frame| {
+            if !budget.spend() {
+                truncated = true;
+                return false;
+            }
+            cb(frame)
+        });
+    }
+    truncated
+}
+
+/// Records the instruction pointers of the current call-stack into `ips`,
+/// returning how many were written.
+///
+/// This is a convenience over `trace` for callers which only need addresses,
+/// such as sampling profilers. Frames are written top-down like `trace` yields
+/// them, and the stack walk stops once `ips` is full. Nothing is allocated, so
+/// the addresses can later be passed to `resolve` as needed.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+///
+/// # Example
+///
+/// ```
+/// let mut ips = [0; 64];
+/// let n = backtrace::trace_raw(&mut ips);
+/// assert!(n > 0);
+/// ```
+#[cfg(feature = "std-base")]
+pub fn trace_raw(ips: &mut [usize]) -> usize {
+    let _guard = crate::lock::lock();
+    unsafe { trace_raw_unsynchronized(ips) }
+}
+
+/// Same as `trace_raw`, only unsafe as it's unsynchronized.
+///
+/// This function does not have synchronization guarantees but is available
+/// when the `std` feature of this crate isn't compiled in. As it neither
+/// allocates nor takes a lock it may be called from a signal handler, on
+/// platforms whose unwinder can itself be used from one. See the `trace_raw`
+/// function for more documentation.
+pub unsafe fn trace_raw_unsynchronized(ips: &mut [usize]) -> usize {
+    let mut n = 0;
+    if ips.is_empty() {
+        return n;
+    }
+    walk(&mut |frame| {
+        ips[n] = frame.ip() as usize;
+        n += 1;
+        n < ips.len()
+    });
+    n
+}
+
+/// Returns how many frames are on the current call-stack.
+///
+/// This walks the stack like `trace` does, but only counts the frames, so
+/// nothing is resolved or allocated and no frames are recorded. This makes it
+/// cheap enough for diagnosing runaway recursion, or for algorithms which
+/// adapt to how much stack they're using, though it still takes time
+/// proportional to the depth of the stack.
+///
+/// The count is the number of frames `trace` would yield if it were called
+/// instead, so it includes the frames of this crate doing the walk. Only
+/// differences between counts taken in the same program are meaningful, and
+/// inlining can make a function's callers share its frame.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+///
+/// # Example
+///
+/// ```
+/// const MAX_DEPTH: usize = 10_000;
+///
+/// if backtrace::depth() > MAX_DEPTH {
+///     panic!("recursed too deeply");
+/// }
+/// ```
+#[cfg(feature = "std-base")]
+pub fn depth() -> usize {
+    let _guard = crate::lock::lock();
+    unsafe { depth_unsynchronized() }
+}
+
+/// Same as `depth`, only unsafe as it's unsynchronized.
+///
+/// This function does not have synchronization guarantees but is available
+/// when the `std` feature of this crate isn't compiled in. Like
+/// `trace_raw_unsynchronized` it neither allocates nor takes a lock. See the
+/// `depth` function for more documentation.
+pub unsafe fn depth_unsynchronized() -> usize {
+    let mut n = 0;
+    walk(&mut |_| {
+        n += 1;
+        true
+    });
+    n
+}
+
+/// Inspects the call-stack of a thread starting from a saved register context,
+/// passing all frames into the closure provided.
+///
+/// This is the same as `trace` except that instead of starting at the current
+/// call site the stack walk starts at the state described by `context`. This
+/// is typically useful in exception handlers and crash reporters which are
+/// handed the register state of the faulting code. The first frame yielded is
+/// the one whose registers are in `context`.
+///
+/// The `context` argument must be a pointer to a platform-specific register
+/// context structure, namely a `CONTEXT` on Windows and a `ucontext_t` on
+/// Linux, such as the one passed to a signal handler installed with
+/// `SA_SIGINFO` or filled in by `getcontext`.
+///
+/// Returns whether the stack walk ended normally, like `trace_checked`.
+///
+/// # Caveats
+///
+/// The platform's unwinders other than dbghelp can only walk the stack from
+/// the current call site, so starting a stack walk from a saved context is
+/// supported on:
+///
+/// * Windows, with the dbghelp backend.
+/// * Linux, with the unwinders of the `trace-frame-pointer` and `unwind-dwarf`
+///   features, on the architectures they support, unless the system unwinder
+///   is selected with `set_trace_backend` or `set_conservative`.
+///
+/// Elsewhere, including Linux with the default backend, no frames are yielded
+/// to the closure and `false` is returned, with
+/// `StopReason::ContextUnsupported` as the reason.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+///
+/// # Safety
+///
+/// The `context` pointer must point to a valid, initialized context for a
+/// thread of the current process whose stack remains valid for the duration of
+/// the call.
+///
+/// # Panics
+///
+/// See information on `trace` for caveats on `cb` panicking.
+#[cfg(feature = "std-base")]
+pub unsafe fn trace_from_context<F: FnMut(&Frame) -> bool>(context: *const c_void, cb: F) -> bool {
+    let _guard = crate::lock::lock();
+    trace_from_context_unsynchronized(context, cb)
+}
+
+/// Same as `trace_from_context`, only unsynchronized.
+///
+/// This function does not have synchronization guarantees but is available
+/// when the `std` feature of this crate isn't compiled in. See the
+/// `trace_from_context` function for more documentation.
+///
+/// # Safety
+///
+/// See information on `trace_from_context` for the requirements on `context`.
+///
+/// # Panics
+///
+/// See information on `trace` for caveats on `cb` panicking.
+pub unsafe fn trace_from_context_unsynchronized<F: FnMut(&Frame) -> bool>(
+    context: *const c_void,
+    mut cb: F,
+) -> bool {
+    trace_from_context_imp(context, &mut cb)
+}
+
+/// Inspects the call-stack of another thread in the current process, passing
+/// all frames into the closure provided.
+///
+/// This is the same as `trace` except that the stack walked is that of the
+/// thread identified by `thread`, an operating system thread identifier as
+/// returned by `current_thread_id` or `thread_ids`. The first frame yielded is
+/// the one the thread was executing when it was interrupted.
+///
+/// Returns whether the thread's stack was captured, which is `false` if the
+/// thread couldn't be interrupted, for example because it has exited, if it
+/// took too long to record its frames, or if it was interrupted while walking
+/// its own stack with this crate, in which case no frames are yielded.
+///
+/// # Caveats
+///
+/// The thread is interrupted for as short a time as possible: its frames are
+/// recorded while it's stopped and are only passed to `cb` once it has been
+/// allowed to continue, and at most 256 frames are recorded.
+///
+/// * On Linux and Android the thread is sent a `SIGURG` signal and its stack
+///   is walked from the signal handler. Any `SIGURG` handler installed by the
+///   application is replaced for the duration of the call, so `SIGURG`
+///   signals sent to the process in the meantime don't reach it. If the
+///   thread blocks `SIGURG`, or doesn't finish recording its frames within a
+///   second, it's given up on and not captured. Only this wait is bounded:
+///   the interrupted thread walks its stack from the signal handler, so if
+///   it was interrupted while holding a lock of the unwinder or of the
+///   dynamic loader, such as while loading a library or unwinding for a
+///   panic, the handler deadlocks and the thread never resumes, even though
+///   this function returns.
+///   With the `trace-frame-pointer` feature the function which was
+///   interrupted is missing, as it hasn't necessarily saved its frame
+///   pointer yet.
+/// * On Windows the thread is suspended with `SuspendThread` while its stack
+///   is walked from the context returned by `GetThreadContext`.
+/// * Other platforms will not yield any frames to the closure, and no thread
+///   is captured.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+///
+/// # Panics
+///
+/// See information on `trace` for caveats on `cb` panicking.
+#[cfg(feature = "std-base")]
+pub fn trace_thread<F: FnMut(&Frame) -> bool>(thread: u64, mut cb: F) -> bool {
+    let _guard = crate::lock::lock();
+    unsafe {
+        if thread == current_thread_id() {
+            walk(&mut cb);
+            true
+        } else {
+            trace_thread_imp(thread, &mut cb)
+        }
+    }
+}
+
+/// Returns the operating system's identifier for the calling thread, as
+/// accepted by `trace_thread`.
+///
+/// This is the thread id returned by `gettid` on Linux and Android and by
+/// `GetCurrentThreadId` on Windows. On other platforms `0` is returned.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[cfg(feature = "std-base")]
+pub fn current_thread_id() -> u64 {
+    current_thread_id_imp()
+}
+
+/// Returns the operating system identifiers of all threads in the current
+/// process, as accepted by `trace_thread`.
+///
+/// On platforms where `trace_thread` isn't supported an empty list is
+/// returned.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[cfg(feature = "std-base")]
+pub fn thread_ids() -> std::vec::Vec<u64> {
+    thread_ids_imp()
+}
+
+/// Returns an iterator over the frames of the current call-stack, starting
+/// with the innermost one.
+///
+/// This is the same as `trace`, except that frames are yielded by an iterator
+/// rather than to a closure, so they can be processed with iterator adapters
+/// and `?`. The frames are cloned while the stack is walked, which happens in
+/// full when this function is called, so the iterator is independent of the
+/// stack and may be kept around or sent to other threads. Use `trace` or
+/// `trace_with_limit` instead to stop the walk early.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+///
+/// # Example
+///
+/// ```
+/// let ips = backtrace::frames()
+///     .take(10)
+///     .map(|frame| frame.ip())
+///     .collect::<Vec<_>>();
+/// assert!(ips.len() <= 10);
+/// ```
+#[cfg(feature = "std-base")]
+pub fn frames() -> TraceIter {
+    let mut frames = std::vec::Vec::new();
+    trace(|frame| {
+        frames.push(frame.clone());
+        true
+    });
+    TraceIter {
+        frames: frames.into_iter(),
+    }
+}
+
+/// An iterator over the frames of a stack, returned by `frames`.
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[cfg(feature = "std-base")]
+#[derive(Clone, Debug)]
+pub struct TraceIter {
+    frames: std::vec::IntoIter<Frame>,
+}
+
+#[cfg(feature = "std-base")]
+impl Iterator for TraceIter {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        self.frames.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.frames.size_hint()
+    }
+}
+
+#[cfg(feature = "std-base")]
+impl DoubleEndedIterator for TraceIter {
+    fn next_back(&mut self) -> Option<Frame> {
+        self.frames.next_back()
+    }
+}
+
+#[cfg(feature = "std-base")]
+impl ExactSizeIterator for TraceIter {}
+
 /// A trait representing one frame of a backtrace, yielded to the `trace`
 /// function of this crate.
 ///
@@ -90,6 +699,29 @@ impl Frame {
         self.inner.ip()
     }
 
+    /// Returns the address to look up to find the symbols of this frame.
+    ///
+    /// The instruction pointer of most frames is a return address, pointing
+    /// just past the call which is executing, which can be attributed to the
+    /// line after the call or, at the end of a function, to the function after
+    /// it. For those frames this returns an address inside the call
+    /// instruction, or at its start on architectures whose instructions have
+    /// a fixed size. Frames interrupted by a signal, see `is_signal_frame`,
+    /// were about to execute the instruction at their instruction pointer and
+    /// return it as it is.
+    ///
+    /// This is the address `resolve_frame` looks up. Addresses passed to
+    /// `resolve` are always adjusted this way, so use `resolve_precise` for
+    /// instruction pointers which already point at the instruction of
+    /// interest.
+    pub fn ip_for_symbolication(&self) -> *mut c_void {
+        if self.is_signal_frame() {
+            self.ip()
+        } else {
+            super::adjust_ip(self.ip())
+        }
+    }
+
     /// Returns the current stack pointer of this frame.
     ///
     /// In the case that a backend cannot recover the stack pointer for this
@@ -98,6 +730,19 @@ impl Frame {
         self.inner.sp()
     }
 
+    /// Returns the frame pointer of this frame.
+    ///
+    /// This is the value of the frame pointer register while the frame's
+    /// function was running, which for functions that maintain a frame
+    /// pointer is the address of their frame record on the stack. Together
+    /// with `sp` this allows a frame to be located in a dump of stack memory.
+    ///
+    /// In the case that a backend cannot recover the frame pointer for this
+    /// frame, a null pointer is returned.
+    pub fn fp(&self) -> *mut c_void {
+        self.inner.fp()
+    }
+
     /// Returns the starting symbol address of the frame of this function.
     ///
     /// This will attempt to rewind the instruction pointer returned by `ip` to
@@ -114,6 +759,59 @@ impl Frame {
     pub fn module_base_address(&self) -> Option<*mut c_void> {
         self.inner.module_base_address()
     }
+
+    /// Returns whether this frame was interrupted by a signal.
+    ///
+    /// Such a frame is the boundary between a signal handler and the code
+    /// which was running when the signal arrived: the frames before it belong
+    /// to the handler and the signal trampoline, and the frames from it onward
+    /// are those of the interrupted context. Its instruction pointer is the
+    /// instruction which was about to execute rather than a return address.
+    ///
+    /// Not every backend can detect this, and those which can't always return
+    /// `false`. Tracing with frame pointers also can't get past the signal
+    /// trampoline in the first place.
+    pub fn is_signal_frame(&self) -> bool {
+        self.inner.is_signal_frame()
+    }
+
+    /// Returns information about the module (executable or shared library)
+    /// which contains this frame's instruction pointer.
+    ///
+    /// This includes the module's path, load address and build identifier,
+    /// which is what's needed to symbolicate this frame at a later point or
+    /// on another machine. `None` is returned if no loaded module contains
+    /// the frame or the platform has no way of enumerating modules.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` and `symbolize` features of the
+    /// `backtrace` crate to be enabled, and both are enabled by default.
+    #[cfg(all(feature = "std-base", feature = "symbolize"))]
+    pub fn module(&self) -> Option<crate::Module> {
+        crate::symbolize::module_for_frame(self)
+    }
+
+    /// Returns the load bias of the module which contains this frame's
+    /// instruction pointer.
+    ///
+    /// The bias is the difference between the addresses a module is loaded
+    /// at and the addresses stated in its object file, so subtracting it from
+    /// a runtime address yields the address to look up in the binary on disk,
+    /// as returned by `Symbol::offset_in_module`. Unlike the module's base
+    /// address this is zero for executables which aren't position
+    /// independent. With dbghelp on MSVC this is the address the
+    /// module is loaded at, since tools working with PDBs expect addresses
+    /// relative to it.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` and `symbolize` features of the
+    /// `backtrace` crate to be enabled, and both are enabled by default.
+    #[cfg(all(feature = "std-base", feature = "symbolize"))]
+    pub fn module_base(&self) -> Option<*mut c_void> {
+        self.module().map(|module| module.bias() as *mut c_void)
+    }
 }
 
 impl fmt::Debug for Frame {
@@ -132,6 +830,75 @@ cfg_if::cfg_if! {
         pub(crate) mod miri;
         use self::miri::trace as trace_imp;
         pub(crate) use self::miri::Frame as FrameImp;
+    } else if #[cfg(all(
+        feature = "trace-frame-pointer",
+        unix,
+        any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64"),
+    ))] {
+        mod frame_pointer;
+        #[cfg(not(feature = "std-base"))]
+        use self::frame_pointer::trace as trace_imp;
+        #[cfg(not(feature = "std-base"))]
+        pub(crate) use self::frame_pointer::Frame as FrameImp;
+        // The other backends can be selected with `set_trace_backend`. The
+        // system unwinder is available whenever the standard library is.
+        #[cfg(feature = "std-base")]
+        mod libunwind;
+        #[cfg(feature = "std-base")]
+        pub(crate) use self::libunwind::Frame as FrameImp;
+        #[cfg(all(
+            feature = "std-base",
+            feature = "addr2line",
+            target_os = "ios",
+            target_arch = "aarch64",
+        ))]
+        mod dwarf;
+        #[cfg(all(
+            feature = "std-base",
+            feature = "unwind-dwarf",
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64"),
+        ))]
+        mod dwarf;
+
+        #[cfg(feature = "std-base")]
+        #[inline(always)]
+        unsafe fn trace_imp(cb: &mut dyn FnMut(&Frame) -> bool) -> bool {
+            match selected() {
+                Backend::Unwind => libunwind::trace(cb),
+                #[cfg(all(
+                    feature = "unwind-dwarf",
+                    target_os = "linux",
+                    any(target_arch = "x86_64", target_arch = "aarch64"),
+                ))]
+                Backend::Dwarf => dwarf::trace(cb),
+                _ => frame_pointer::trace(cb),
+            }
+        }
+    } else if #[cfg(all(
+        feature = "unwind-dwarf",
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "riscv64"),
+    ))] {
+        mod dwarf;
+        #[cfg(not(feature = "std-base"))]
+        use self::dwarf::trace as trace_imp;
+        #[cfg(not(feature = "std-base"))]
+        pub(crate) use self::dwarf::Frame as FrameImp;
+        // See above for selecting the system unwinder.
+        #[cfg(feature = "std-base")]
+        mod libunwind;
+        #[cfg(feature = "std-base")]
+        pub(crate) use self::libunwind::Frame as FrameImp;
+
+        #[cfg(feature = "std-base")]
+        #[inline(always)]
+        unsafe fn trace_imp(cb: &mut dyn FnMut(&Frame) -> bool) -> bool {
+            match selected() {
+                Backend::Unwind => libunwind::trace(cb),
+                _ => dwarf::trace(cb),
+            }
+        }
     } else if #[cfg(
         any(
             all(
@@ -148,6 +915,11 @@ cfg_if::cfg_if! {
         mod libunwind;
         use self::libunwind::trace as trace_imp;
         pub(crate) use self::libunwind::Frame as FrameImp;
+        // Traces which the system unwinder can't finish are continued by
+        // reading `__eh_frame` ourselves, which needs `addr2line`'s copy of
+        // gimli, so builds with only the `trace` feature go without.
+        #[cfg(all(feature = "addr2line", target_os = "ios", target_arch = "aarch64"))]
+        mod dwarf;
     } else if #[cfg(all(windows, not(target_vendor = "uwp")))] {
         mod dbghelp;
         use self::dbghelp::trace as trace_imp;
@@ -160,3 +932,146 @@ cfg_if::cfg_if! {
         pub(crate) use self::noop::Frame as FrameImp;
     }
 }
+
+cfg_if::cfg_if! {
+    if #[cfg(all(not(miri), windows, not(target_vendor = "uwp")))] {
+        use self::dbghelp::trace_from_context as trace_from_context_imp;
+    } else if #[cfg(all(
+        not(miri),
+        target_os = "linux",
+        any(
+            all(
+                feature = "trace-frame-pointer",
+                any(target_arch = "x86_64", target_arch = "aarch64"),
+            ),
+            all(
+                feature = "unwind-dwarf",
+                any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "riscv64"),
+            ),
+        ),
+    ))] {
+        // Our own unwinders can start from any registers, while the system
+        // unwinder can't, so picking it leaves nothing to walk with.
+        unsafe fn trace_from_context_imp(
+            context: *const c_void,
+            cb: &mut dyn FnMut(&Frame) -> bool,
+        ) -> bool {
+            let uc = context as *const libc::ucontext_t;
+            match selected() {
+                Backend::Unwind => {}
+                #[cfg(all(
+                    feature = "unwind-dwarf",
+                    not(all(
+                        feature = "trace-frame-pointer",
+                        not(feature = "std-base"),
+                        any(target_arch = "x86_64", target_arch = "aarch64"),
+                    )),
+                ))]
+                Backend::Dwarf => return dwarf::trace_from_context(uc, cb),
+                #[cfg(all(
+                    feature = "trace-frame-pointer",
+                    any(target_arch = "x86_64", target_arch = "aarch64"),
+                ))]
+                _ => return frame_pointer::trace_from_context(uc, cb),
+                #[cfg(not(all(
+                    feature = "trace-frame-pointer",
+                    any(target_arch = "x86_64", target_arch = "aarch64"),
+                )))]
+                _ => return dwarf::trace_from_context(uc, cb),
+            }
+            diagnostics::report(StopReason::ContextUnsupported);
+            false
+        }
+    } else {
+        // The other backends are only able to walk the stack starting at the
+        // current call site.
+        unsafe fn trace_from_context_imp(
+            _context: *const c_void,
+            _cb: &mut dyn FnMut(&Frame) -> bool,
+        ) -> bool {
+            diagnostics::report(StopReason::ContextUnsupported);
+            false
+        }
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(
+        not(miri),
+        feature = "std-base",
+        feature = "unwind-dwarf",
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "riscv64"),
+    ))] {
+        unsafe fn trace_with_registers_imp(
+            cb: &mut RegistersCallback<'_>,
+        ) -> bool {
+            // The DWARF unwinder is only the default where frame pointers
+            // aren't followed instead.
+            let dwarf = match selected() {
+                Backend::Dwarf => true,
+                Backend::Unwind => false,
+                _ => !cfg!(all(
+                    feature = "trace-frame-pointer",
+                    any(target_arch = "x86_64", target_arch = "aarch64"),
+                )),
+            };
+            if dwarf {
+                dwarf::trace_with_registers(&mut |frame, regs| cb(frame, Some(regs)))
+            } else {
+                trace_imp(&mut |frame| cb(frame, None))
+            }
+        }
+    } else if #[cfg(feature = "std-base")] {
+        // Only the DWARF unwinder knows the registers of callers.
+        unsafe fn trace_with_registers_imp(
+            cb: &mut RegistersCallback<'_>,
+        ) -> bool {
+            trace_imp(&mut |frame| cb(frame, None))
+        }
+    }
+}
+
+mod bounds;
+pub use self::bounds::stack_bounds;
+
+mod diagnostics;
+#[cfg(feature = "std-base")]
+pub use self::diagnostics::trace_with_diagnostics;
+pub use self::diagnostics::StopReason;
+
+#[cfg(feature = "std-base")]
+mod limit;
+#[cfg(feature = "std-base")]
+pub use self::limit::TraceLimit;
+
+cfg_if::cfg_if! {
+    if #[cfg(all(
+        feature = "std-base",
+        not(miri),
+        any(target_os = "linux", target_os = "android"),
+    ))] {
+        mod signal;
+        use self::signal::trace_thread as trace_thread_imp;
+        use self::signal::current_thread_id as current_thread_id_imp;
+        use self::signal::thread_ids as thread_ids_imp;
+    } else if #[cfg(all(feature = "std-base", not(miri), windows, not(target_vendor = "uwp")))] {
+        use self::dbghelp::trace_thread as trace_thread_imp;
+        use self::dbghelp::current_thread_id as current_thread_id_imp;
+        use self::dbghelp::thread_ids as thread_ids_imp;
+    } else if #[cfg(feature = "std-base")] {
+        // Other backends have no way of interrupting another thread, so
+        // there's nothing to yield here.
+        unsafe fn trace_thread_imp(_thread: u64, _cb: &mut dyn FnMut(&Frame) -> bool) -> bool {
+            false
+        }
+
+        fn current_thread_id_imp() -> u64 {
+            0
+        }
+
+        fn thread_ids_imp() -> std::vec::Vec<u64> {
+            std::vec::Vec::new()
+        }
+    }
+}
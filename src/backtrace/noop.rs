@@ -4,7 +4,9 @@
 use core::ffi::c_void;
 
 #[inline(always)]
-pub fn trace(_cb: &mut dyn FnMut(&super::Frame) -> bool) {}
+pub fn trace(_cb: &mut dyn FnMut(&super::Frame) -> bool) -> bool {
+    true
+}
 
 #[derive(Clone)]
 pub struct Frame;
@@ -18,6 +20,10 @@ impl Frame {
         0 as *mut _
     }
 
+    pub fn fp(&self) -> *mut c_void {
+        0 as *mut _
+    }
+
     pub fn symbol_address(&self) -> *mut c_void {
         0 as *mut _
     }
@@ -25,4 +31,8 @@ impl Frame {
     pub fn module_base_address(&self) -> Option<*mut c_void> {
         None
     }
+
+    pub fn is_signal_frame(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,228 @@
+//! Capturing the stack of another thread by interrupting it with a signal.
+//!
+//! `_Unwind_Backtrace` can only walk the stack of the thread calling it, so to
+//! inspect another thread we send it a signal and walk the stack from within
+//! the signal handler. The frames are recorded into a static buffer, as
+//! nothing can be allocated from a signal handler, and once the handler has
+//! finished the requesting thread hands them to the caller's closure. This
+//! works with whichever unwinder `trace` uses, as long as it can unwind
+//! through a signal frame.
+//!
+//! `SIGURG` is used as its default disposition is to be ignored, so a signal
+//! which is delivered late (for example after we've given up waiting for a
+//! thread which blocks signals) is harmless once the previous handler has
+//! been restored. Any handler the application installed for it is replaced
+//! while we wait, so signals it's sent in the meantime are lost.
+//!
+//! The wait is bounded even once the handler has started, as the walk can
+//! take arbitrarily long, for example if the thread is descheduled or the
+//! unwinder spins on a corrupt stack. A handler which is given up on stops at
+//! its next frame without reporting anything, and capturing the next
+//! thread's stack waits for it to return, as its frames would share the same
+//! buffer.
+//!
+//! A thread which is interrupted while it's walking its own stack can't walk
+//! it again from the handler, as the nested walk could deadlock on locks held
+//! by the one in progress, so its handler reports failure straight away.
+//!
+//! All of this state is global, so it must only be used while holding the
+//! crate's global lock.
+
+use super::Frame;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+use core::sync::atomic::{AtomicI32, AtomicUsize, Ordering::SeqCst};
+use std::time::{Duration, Instant};
+
+/// The maximum number of frames recorded for another thread.
+const MAX_FRAMES: usize = 256;
+
+/// How long to wait for the target thread to record its stack.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+// `TARGET` is the thread id whose handler should record its stack, or one of
+// the markers below once that handler has started, finished, been given up on
+// while running, or found that it couldn't walk the stack.
+const RUNNING: i32 = -1;
+const DONE: i32 = -2;
+const ABANDONED: i32 = -3;
+const FAILED: i32 = -4;
+
+static TARGET: AtomicI32 = AtomicI32::new(0);
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+// The number of handlers which haven't returned yet, including those of
+// signals which turn out not to be for them.
+static IN_HANDLER: AtomicUsize = AtomicUsize::new(0);
+static mut FRAMES: MaybeUninit<[Frame; MAX_FRAMES]> = MaybeUninit::uninit();
+
+pub fn current_thread_id() -> u64 {
+    unsafe { libc::syscall(libc::SYS_gettid) as u64 }
+}
+
+pub fn thread_ids() -> std::vec::Vec<u64> {
+    let entries = match std::fs::read_dir("/proc/self/task") {
+        Ok(entries) => entries,
+        Err(_) => return std::vec::Vec::new(),
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect()
+}
+
+pub unsafe fn trace_thread(thread: u64, cb: &mut dyn FnMut(&Frame) -> bool) -> bool {
+    // The previous thread's handler may not have returned yet, or if it was
+    // given up on may still be writing frames.
+    let start = Instant::now();
+    while IN_HANDLER.load(SeqCst) != 0 {
+        if start.elapsed() > TIMEOUT {
+            return false;
+        }
+        std::thread::yield_now();
+    }
+    let tid = thread as i32;
+    COUNT.store(0, SeqCst);
+    TARGET.store(tid, SeqCst);
+
+    let mut action: libc::sigaction = mem::zeroed();
+    action.sa_sigaction = handler as usize;
+    action.sa_flags = libc::SA_RESTART;
+    libc::sigemptyset(&mut action.sa_mask);
+    let mut previous: libc::sigaction = mem::zeroed();
+    if libc::sigaction(libc::SIGURG, &action, &mut previous) != 0 {
+        TARGET.store(0, SeqCst);
+        return false;
+    }
+
+    let mut completed = false;
+    if libc::syscall(libc::SYS_tgkill, libc::getpid(), tid, libc::SIGURG) == 0 {
+        let start = Instant::now();
+        loop {
+            match TARGET.load(SeqCst) {
+                DONE => {
+                    completed = true;
+                    break;
+                }
+                FAILED => break,
+                // A handler which is running when the timeout expires is told
+                // to stop, unless it finishes first.
+                RUNNING
+                    if start.elapsed() > TIMEOUT
+                        && TARGET
+                            .compare_exchange(RUNNING, ABANDONED, SeqCst, SeqCst)
+                            .is_ok() =>
+                {
+                    break
+                }
+                _ if start.elapsed() > TIMEOUT
+                    && TARGET.compare_exchange(tid, 0, SeqCst, SeqCst).is_ok() =>
+                {
+                    break
+                }
+                _ => {}
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    libc::sigaction(libc::SIGURG, &previous, ptr::null_mut());
+    TARGET.store(0, SeqCst);
+    if !completed {
+        return false;
+    }
+
+    // Frames are only read back after the handler has finished writing them,
+    // and since frames are plain data they're simply left in place afterwards
+    // rather than being dropped.
+    let frames = ptr::addr_of!(FRAMES) as *const Frame;
+    for i in 0..COUNT.load(SeqCst) {
+        if !cb(&*frames.add(i)) {
+            break;
+        }
+    }
+    true
+}
+
+extern "C" fn handler(_signum: libc::c_int) {
+    struct Leave;
+
+    impl Drop for Leave {
+        fn drop(&mut self) {
+            IN_HANDLER.fetch_sub(1, SeqCst);
+        }
+    }
+
+    IN_HANDLER.fetch_add(1, SeqCst);
+    let _leave = Leave;
+    unsafe {
+        // Ignore signals meant for another thread, or which arrive after the
+        // requesting thread has stopped waiting.
+        let tid = libc::syscall(libc::SYS_gettid) as i32;
+        if TARGET
+            .compare_exchange(tid, RUNNING, SeqCst, SeqCst)
+            .is_err()
+        {
+            return;
+        }
+        // The nested walk would yield nothing, which mustn't be mistaken for
+        // a stack without frames.
+        if crate::reentrancy::is_walking() {
+            TARGET.store(FAILED, SeqCst);
+            return;
+        }
+
+        let errno = *errno_location();
+        // The innermost frames are those of the unwinder, this handler and
+        // the signal trampoline which invoked it, none of which are interesting
+        // to the caller, so recording starts with the frame that was
+        // interrupted. Unwinders which know about signal frames say exactly
+        // which one that is. Otherwise the handler's frame is recognized by
+        // its symbol address where the unwinder knows it, or else the first
+        // frame whose stack pointer is above this handler's locals is taken
+        // to be the trampoline.
+        let marker = 0u8;
+        let marker = &marker as *const u8 as usize;
+        let handler_address = handler as usize;
+        let frames = ptr::addr_of_mut!(FRAMES) as *mut Frame;
+        let mut skip = None;
+        let mut count = 0;
+        super::trace_unsynchronized(|frame| {
+            if TARGET.load(SeqCst) != RUNNING {
+                return false;
+            }
+            match skip {
+                None if frame.is_signal_frame() => skip = Some(0),
+                None if frame.symbol_address() as usize == handler_address => {
+                    skip = Some(1);
+                    return true;
+                }
+                None if frame.sp() as usize > marker => {
+                    skip = Some(0);
+                    return true;
+                }
+                None => return true,
+                Some(0) => {}
+                Some(n) => {
+                    skip = Some(n - 1);
+                    return true;
+                }
+            }
+            ptr::write(frames.add(count), frame.clone());
+            count += 1;
+            count < MAX_FRAMES
+        });
+        *errno_location() = errno;
+
+        COUNT.store(count, SeqCst);
+        let _ = TARGET.compare_exchange(RUNNING, DONE, SeqCst, SeqCst);
+    }
+}
+
+#[cfg(target_os = "android")]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__errno()
+}
+
+#[cfg(not(target_os = "android"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__errno_location()
+}
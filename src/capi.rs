@@ -0,0 +1,261 @@
+//! A C interface for capturing and symbolicating backtraces.
+//!
+//! C and C++ code linked into the same process as this crate can use the
+//! functions of this module to get the same backtraces that Rust code does,
+//! with demangled Rust and C++ names and inlined frames. The functions are
+//! exported unmangled, and the types have a C layout which won't change, so
+//! they can be declared in C as follows:
+//!
+//! ```c
+//! #include <stddef.h>
+//! #include <stdint.h>
+//!
+//! typedef struct {
+//!     void *ip;
+//!     void *symbol_address;
+//!     void *module_base;
+//! } backtrace_frame_t;
+//!
+//! typedef struct {
+//!     const char *name;
+//!     const char *filename;
+//!     uint32_t lineno;
+//!     uint32_t colno;
+//!     void *addr;
+//! } backtrace_symbol_t;
+//!
+//! typedef void (*backtrace_symbol_cb)(const backtrace_symbol_t *symbol, void *data);
+//!
+//! size_t backtrace_capture(backtrace_frame_t *frames, size_t len, size_t skip);
+//! size_t backtrace_resolve(void *ip, backtrace_symbol_cb cb, void *data);
+//! size_t backtrace_format(const backtrace_frame_t *frames, size_t len, char *buf, size_t buf_len);
+//! ```
+//!
+//! All of the functions are synchronized like `trace` and `resolve` are, so
+//! they can be called from any thread, but they aren't async-signal-safe.
+//! Panics never unwind into the caller.
+//!
+//! # Required features
+//!
+//! This module requires the `capi` feature of the `backtrace` crate to be
+//! enabled, along with the `std` feature which is enabled by default.
+
+#![allow(non_camel_case_types)]
+
+use crate::{Backtrace, BacktraceFmt, BytesOrWideString, PrintFmt};
+use core::ffi::c_void;
+use core::{fmt, ptr, slice};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::prelude::v1::*;
+
+/// A frame recorded by `backtrace_capture`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct backtrace_frame_t {
+    /// The instruction pointer of the frame, as returned by `Frame::ip`.
+    pub ip: *mut c_void,
+    /// The starting address of the function of the frame, as returned by
+    /// `Frame::symbol_address`.
+    pub symbol_address: *mut c_void,
+    /// The base address of the module containing the frame, or null if it
+    /// isn't known, as returned by `Frame::module_base_address`.
+    pub module_base: *mut c_void,
+}
+
+/// A symbol passed to the callback of `backtrace_resolve`.
+///
+/// The strings are NUL-terminated and only valid during the callback.
+#[repr(C)]
+#[derive(Debug)]
+pub struct backtrace_symbol_t {
+    /// The demangled name of the function, without the hash of Rust symbols,
+    /// or null if it isn't known.
+    pub name: *const c_char,
+    /// The file the function was defined in, or null if it isn't known.
+    pub filename: *const c_char,
+    /// The line number, or zero if it isn't known.
+    pub lineno: u32,
+    /// The column number, or zero if it isn't known.
+    pub colno: u32,
+    /// The starting address of the function, or null if it isn't known.
+    pub addr: *mut c_void,
+}
+
+/// The callback of `backtrace_resolve`, which is called with each symbol and
+/// the `data` passed to it.
+pub type backtrace_symbol_cb = unsafe extern "C" fn(*const backtrace_symbol_t, *mut c_void);
+
+/// Records the frames of the calling thread's stack into `frames`, which has
+/// room for `len` frames, returning how many frames were written.
+///
+/// The first frame is that of the function calling `backtrace_capture`,
+/// after skipping `skip` more frames. Frames beyond `len` aren't walked.
+///
+/// # Safety
+///
+/// `frames` must be valid for writing `len` frames, or may be null if `len`
+/// is zero.
+#[no_mangle]
+#[inline(never)] // want to make sure there's a frame here to remove
+pub unsafe extern "C" fn backtrace_capture(
+    frames: *mut backtrace_frame_t,
+    len: usize,
+    skip: usize,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    let frames = slice::from_raw_parts_mut(frames, len);
+    let ip = backtrace_capture as usize;
+    let marker = 0u8;
+    let bound = &marker as *const u8 as usize;
+    catch(0, move || {
+        let mut found = false;
+        let mut skip = skip;
+        let mut count = 0;
+        crate::trace(|frame| {
+            // Everything up to this function's frame is internal to this
+            // crate. Its frame is recognized by its symbol address where the
+            // backend knows it, and otherwise the caller's frame is the first
+            // one whose stack pointer lies above this function's locals, like
+            // `Backtrace::new` does. If neither can be told, all frames are
+            // taken to be the caller's.
+            if !found {
+                let sp = frame.sp() as usize;
+                if frame.symbol_address() as usize == ip {
+                    found = true;
+                    return true;
+                }
+                if sp != 0 && sp <= bound {
+                    return true;
+                }
+                if sp == 0 && frame.symbol_address() != frame.ip() {
+                    return true;
+                }
+                found = true;
+            }
+            if skip > 0 {
+                skip -= 1;
+                return true;
+            }
+            frames[count] = backtrace_frame_t {
+                ip: frame.ip(),
+                symbol_address: frame.symbol_address(),
+                module_base: frame.module_base_address().unwrap_or(ptr::null_mut()),
+            };
+            count += 1;
+            count < frames.len()
+        });
+        count
+    })
+}
+
+/// Resolves the instruction pointer `ip` of a frame, calling `cb` with each
+/// of its symbols and `data`, and returns the number of symbols.
+///
+/// Several symbols are found where functions were inlined, innermost first.
+///
+/// # Safety
+///
+/// `cb` must be safe to call with `data`.
+#[no_mangle]
+pub unsafe extern "C" fn backtrace_resolve(
+    ip: *mut c_void,
+    cb: backtrace_symbol_cb,
+    data: *mut c_void,
+) -> usize {
+    catch(0, || {
+        let mut count = 0;
+        crate::resolve(ip, |symbol| {
+            let name = symbol
+                .name()
+                .and_then(|name| c_string(format!("{:#}", name)));
+            let filename = symbol
+                .filename()
+                .and_then(|path| c_string(path.to_string_lossy().into_owned()));
+            let symbol = backtrace_symbol_t {
+                name: name.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                filename: filename.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                lineno: symbol.lineno().unwrap_or(0),
+                colno: symbol.colno().unwrap_or(0),
+                addr: symbol.addr().unwrap_or(ptr::null_mut()),
+            };
+            cb(&symbol, data);
+            count += 1;
+        });
+        count
+    })
+}
+
+/// Writes the `len` frames in `frames` to `buf` as a resolved backtrace,
+/// printed in the `PrintFmt::Short` format with the innermost frame first.
+///
+/// The output doesn't depend on `RUST_BACKTRACE` or the settings for printing
+/// `Backtrace`, such as `set_frame_order` and `set_backtrace_style`, and all
+/// of the frames are printed, without trimming those of the runtime.
+///
+/// Like `snprintf`, the result is truncated to fit the `buf_len` bytes of
+/// `buf` including a terminating NUL, and the length of the whole backtrace
+/// without the NUL is returned, so a buffer of the right size can be
+/// allocated for a second call if the first one was too small.
+///
+/// # Safety
+///
+/// `frames` must be valid for reading `len` frames and `buf` for writing
+/// `buf_len` bytes. Either may be null if its length is zero.
+#[no_mangle]
+pub unsafe extern "C" fn backtrace_format(
+    frames: *const backtrace_frame_t,
+    len: usize,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> usize {
+    let frames = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(frames, len)
+    };
+    let text = catch(String::new(), || {
+        let ips = frames.iter().map(|f| f.ip as usize).collect::<Vec<_>>();
+        let mut bt = Backtrace::from_ips(&ips, 0);
+        bt.resolve();
+        Formatted(&bt).to_string()
+    });
+    if buf_len > 0 {
+        let n = text.len().min(buf_len - 1);
+        ptr::copy_nonoverlapping(text.as_ptr(), buf as *mut u8, n);
+        *buf.add(n) = 0;
+    }
+    text.len()
+}
+
+/// Prints a backtrace for `backtrace_format`, with `BacktraceFmt` set up
+/// explicitly rather than like the `Debug` implementation of `Backtrace`.
+struct Formatted<'a>(&'a Backtrace);
+
+impl fmt::Display for Formatted<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut print_path = |fmt: &mut fmt::Formatter<'_>, path: BytesOrWideString<'_>| {
+            fmt::Display::fmt(&path, fmt)
+        };
+        let mut f = BacktraceFmt::new(fmt, PrintFmt::Short, &mut print_path);
+        f.add_context()?;
+        for frame in self.0.frames() {
+            f.frame().backtrace_frame(frame)?;
+        }
+        f.finish()
+    }
+}
+
+/// Runs `f`, returning `default` instead if it panics, as unwinding into C
+/// is undefined behavior.
+fn catch<R>(default: R, f: impl FnOnce() -> R) -> R {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(default)
+}
+
+/// Returns `s` as a C string, or `None` if it contains a NUL.
+fn c_string(s: String) -> Option<CString> {
+    CString::new(s).ok()
+}
@@ -1,20 +1,58 @@
-use crate::PrintFmt;
-use crate::{resolve, resolve_frame, trace, BacktraceFmt, Symbol, SymbolName};
+use crate::backtrace::{trace_checked, trace_checked_with_registers, RegistersCallback};
+use crate::print::recursion;
+use crate::TraceLimit;
+use crate::{resolve, resolve_frame, BacktraceFmt, Language, Symbol, SymbolCache, SymbolName};
+use crate::{FrameOrder, PrintFmt};
+use std::borrow::{Borrow, Cow};
+use std::cell::Cell;
 use std::ffi::c_void;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::prelude::v1::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+mod annotated;
+mod binary;
+mod config;
+pub(crate) mod folded;
+mod json;
+mod lazy;
+mod normalized;
+mod panic_hook;
+mod registers;
+mod snapshot;
+mod stream;
+
+pub use self::annotated::AnnotatedBacktrace;
+pub use self::config::{with_config, CaptureConfig};
+pub use self::lazy::LazyBacktrace;
+pub use self::normalized::{NormalizedBacktrace, NormalizedFrame, NormalizedModule};
+pub use self::panic_hook::{install_panic_hook, install_panic_hook_with, PanicReport};
+pub use self::registers::FrameRegisters;
+pub use self::snapshot::ModuleSnapshot;
+
 /// Representation of an owned and self-contained backtrace.
 ///
 /// This structure can be used to capture a backtrace at various points in a
 /// program and later used to inspect what the backtrace was at that time.
 ///
-/// `Backtrace` supports pretty-printing of backtraces through its `Debug`
-/// implementation.
+/// `Backtrace` supports pretty-printing of backtraces through its `Debug` and
+/// `Display` implementations. These print a short backtrace by default, which
+/// omits addresses and is trimmed like `Backtrace::trim`, the same way the
+/// standard library prints backtraces for panics. The alternate flag (`{:#?}`
+/// and `{:#}`) or setting the `RUST_BACKTRACE` environment variable to `full`
+/// prints every frame with its address instead. Frames of a backtrace which
+/// hasn't been resolved are resolved while they're printed, without storing
+/// their symbols in the backtrace.
+///
+/// Backtraces compare equal, and hash the same, if their frames do. Frames
+/// internal to this crate which aren't returned from `frames` are ignored.
 ///
 /// # Required features
 ///
@@ -29,6 +67,18 @@ pub struct Backtrace {
     // The index we believe is the actual start of the backtrace, omitting
     // frames like `Backtrace::new` and `backtrace::trace`.
     actual_start_index: usize,
+    // The number of frames past a limit on the number of frames, which were
+    // walked but not recorded.
+    #[cfg_attr(feature = "serde", serde(default))]
+    frames_dropped: usize,
+    // Whether frames are missing from the bottom of the stack, because of a
+    // limit or because the unwinder couldn't get any further.
+    #[cfg_attr(feature = "serde", serde(default))]
+    truncated: bool,
+    // Whether this thread was already walking its stack when the capture
+    // started, so no frames were walked.
+    #[cfg_attr(feature = "serde", serde(default))]
+    reentrant: bool,
 }
 
 fn _assert_send_sync() {
@@ -41,6 +91,10 @@ fn _assert_send_sync() {
 /// This type is returned as a list from `Backtrace::frames` and represents one
 /// stack frame in a captured backtrace.
 ///
+/// Frames are compared and hashed by their instruction pointer and symbol
+/// address, so a frame compares equal to itself whether or not it has been
+/// resolved.
+///
 /// # Required features
 ///
 /// This function requires the `std` feature of the `backtrace` crate to be
@@ -49,6 +103,12 @@ fn _assert_send_sync() {
 pub struct BacktraceFrame {
     frame: Frame,
     symbols: Option<Vec<BacktraceSymbol>>,
+    // The module the frame was in when it was captured, if recorded.
+    module: Option<ModuleSnapshot>,
+    // Whether the frame wasn't resolved because that module was gone.
+    stale: bool,
+    // The registers recovered for the frame, if they were requested.
+    registers: Option<FrameRegisters>,
 }
 
 #[derive(Clone)]
@@ -57,6 +117,8 @@ enum Frame {
     #[allow(dead_code)]
     Deserialized {
         ip: usize,
+        sp: usize,
+        fp: usize,
         symbol_address: usize,
         module_base_address: Option<usize>,
     },
@@ -70,6 +132,20 @@ impl Frame {
         }
     }
 
+    fn sp(&self) -> *mut c_void {
+        match *self {
+            Frame::Raw(ref f) => f.sp(),
+            Frame::Deserialized { sp, .. } => sp as *mut c_void,
+        }
+    }
+
+    fn fp(&self) -> *mut c_void {
+        match *self {
+            Frame::Raw(ref f) => f.fp(),
+            Frame::Deserialized { fp, .. } => fp as *mut c_void,
+        }
+    }
+
     fn symbol_address(&self) -> *mut c_void {
         match *self {
             Frame::Raw(ref f) => f.symbol_address(),
@@ -97,7 +173,7 @@ impl Frame {
 ///
 /// This function requires the `std` feature of the `backtrace` crate to be
 /// enabled, and the `std` feature is enabled by default.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serialize-rustc", derive(RustcDecodable, RustcEncodable))]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct BacktraceSymbol {
@@ -106,6 +182,11 @@ pub struct BacktraceSymbol {
     filename: Option<PathBuf>,
     lineno: Option<u32>,
     colno: Option<u32>,
+    // How many functions this one is inlined into within its frame. This is
+    // derived from the symbol's position in its frame, so it's recomputed
+    // rather than deserialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    inline_depth: usize,
 }
 
 impl Backtrace {
@@ -123,6 +204,9 @@ impl Backtrace {
     /// avoids the symbol resolution step (which typically takes the longest)
     /// and allows deferring that to a later date.
     ///
+    /// The number of frames recorded and whether symbols are resolved can be
+    /// configured for the current thread with `backtrace::with_config`.
+    ///
     /// # Examples
     ///
     /// ```
@@ -137,8 +221,67 @@ impl Backtrace {
     /// enabled, and the `std` feature is enabled by default.
     #[inline(never)] // want to make sure there's a frame here to remove
     pub fn new() -> Backtrace {
-        let mut bt = Self::create(Self::new as usize);
-        bt.resolve();
+        Self::new_from(Self::new as usize)
+    }
+
+    /// Same as `new`, but for functions of this crate which capture on behalf
+    /// of their caller: the frames up to and including the function at `ip`
+    /// are omitted, rather than those up to `new`.
+    pub(crate) fn new_from(ip: usize) -> Backtrace {
+        let config = config::current();
+        let mut bt = Self::create(ip, &BacktraceBuilder::from_config(&config));
+        if config.resolve {
+            bt.resolve();
+        }
+        bt
+    }
+
+    /// Same as `new`, but the backtrace starts at the caller of the function
+    /// calling this one, or `skip_frames` frames further up the stack.
+    ///
+    /// This is meant for libraries which capture backtraces on behalf of their
+    /// users through functions of their own, so that those functions don't
+    /// show up at the top of every backtrace. With `skip_frames` set to the
+    /// depth of the library's call chain the backtrace starts exactly at the
+    /// user's code. Unlike trimming frames by their symbol names afterwards
+    /// this doesn't rely on symbols being resolved at all: the frame of this
+    /// function is found by its address, or by position on the stack where
+    /// the platform's unwinder doesn't know where functions start, and the
+    /// frames up to it and those skipped after it are never recorded.
+    ///
+    /// The library's functions must not be inlined into their callers for
+    /// the skipped frames to be the right ones, so they should be marked
+    /// `#[inline(never)]`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::Backtrace;
+    ///
+    /// #[inline(never)]
+    /// fn capture_for_caller() -> Backtrace {
+    ///     // Starts at whoever called `capture_for_caller`.
+    ///     Backtrace::new_from_caller(0)
+    /// }
+    ///
+    /// let bt = capture_for_caller();
+    /// println!("{:?}", bt);
+    /// ```
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub fn new_from_caller(skip_frames: usize) -> Backtrace {
+        let marker = 0u8;
+        let config = config::current();
+        let mut opts = BacktraceBuilder::from_config(&config).skip(skip_frames.saturating_add(1));
+        opts.stack_bound = Some(&marker as *const u8 as usize);
+        let mut bt = Self::create(Self::new_from_caller as usize, &opts);
+        if config.resolve {
+            bt.resolve();
+        }
         bt
     }
 
@@ -150,13 +293,20 @@ impl Backtrace {
     /// the resolution process can sometimes take a significant amount of time
     /// whereas any one backtrace may only be rarely printed.
     ///
+    /// The number of frames recorded can be limited for the current thread
+    /// with `backtrace::with_config`.
+    ///
+    /// The module each frame is in is recorded as well, see
+    /// `BacktraceFrame::module_snapshot`, so that frames whose module has
+    /// since been unloaded aren't resolved to the wrong symbols.
+    ///
     /// # Examples
     ///
     /// ```
     /// use backtrace::Backtrace;
     ///
     /// let mut current_backtrace = Backtrace::new_unresolved();
-    /// println!("{:?}", current_backtrace); // no symbol names
+    /// assert!(current_backtrace.frames()[0].symbols().is_empty());
     /// current_backtrace.resolve();
     /// println!("{:?}", current_backtrace); // symbol names now present
     /// ```
@@ -167,17 +317,175 @@ impl Backtrace {
     /// enabled, and the `std` feature is enabled by default.
     #[inline(never)] // want to make sure there's a frame here to remove
     pub fn new_unresolved() -> Backtrace {
-        Self::create(Self::new_unresolved as usize)
+        let opts = BacktraceBuilder::from_config(&config::current());
+        let mut bt = Self::create(Self::new_unresolved as usize, &opts);
+        snapshot::record(&mut bt.frames);
+        bt
+    }
+
+    /// Returns a builder which can be used to configure how a backtrace is
+    /// captured, for example skipping frames or limiting the number of frames
+    /// recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::Backtrace;
+    ///
+    /// // Skip our immediate caller and record at most 10 frames.
+    /// let bt = Backtrace::builder().skip(1).max_frames(10).capture();
+    /// assert!(bt.frames().len() <= 10);
+    /// ```
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn builder() -> BacktraceBuilder {
+        BacktraceBuilder::default()
+    }
+
+    /// Captures a backtrace starting from a saved register context, resolving
+    /// all symbols.
+    ///
+    /// This is the owned equivalent of `backtrace::trace_from_context`, see its
+    /// documentation for which kinds of contexts are accepted and on which
+    /// platforms this is supported. The first frame of the returned backtrace
+    /// is the one described by `context`. Where walking from a context isn't
+    /// supported the backtrace has no frames and is marked as truncated.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    ///
+    /// # Safety
+    ///
+    /// See `backtrace::trace_from_context` for the requirements on `context`.
+    pub unsafe fn from_context(context: *const c_void) -> Backtrace {
+        let mut bt = Self::create_with(0, &BacktraceBuilder::default(), |cb| {
+            crate::trace_from_context(context, |frame| cb(frame, None))
+        });
+        bt.resolve();
+        bt
+    }
+
+    /// Captures a backtrace of every thread in the current process, resolving
+    /// all symbols.
+    ///
+    /// Each backtrace is paired with the operating system identifier of its
+    /// thread. This is the owned equivalent of calling
+    /// `backtrace::trace_thread` for each of `backtrace::thread_ids`, see
+    /// `trace_thread` for how other threads are interrupted and on which
+    /// platforms this is supported. On unsupported platforms the returned list
+    /// is empty, and threads whose stacks couldn't be captured have no frames
+    /// and are marked as truncated.
+    ///
+    /// This is mostly useful for diagnosing deadlocks, where the stacks of the
+    /// threads involved show which locks they're waiting on.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub fn all_threads() -> Vec<(u64, Backtrace)> {
+        let mut ret = crate::thread_ids()
+            .into_iter()
+            .map(|id| {
+                let opts = BacktraceBuilder::default();
+                let bt = Self::create_with(Self::all_threads as usize, &opts, |cb| {
+                    crate::trace_thread(id, |frame| cb(frame, None))
+                });
+                (id, bt)
+            })
+            .collect::<Vec<_>>();
+        for (_, bt) in ret.iter_mut() {
+            bt.resolve();
+        }
+        ret
+    }
+
+    fn create(ip: usize, opts: &BacktraceBuilder) -> Backtrace {
+        if opts.registers {
+            Self::create_with(ip, opts, |cb| trace_checked_with_registers(cb))
+        } else {
+            Self::create_with(ip, opts, |cb| trace_checked(&mut |frame| cb(frame, None)))
+        }
+    }
+
+    /// Captures the frames produced by `walk`, along with the registers it
+    /// recovered for them if any, where `walk` returns whether it ended
+    /// normally as `backtrace::trace_checked` does.
+    fn create_with(
+        ip: usize,
+        opts: &BacktraceBuilder,
+        walk: impl FnOnce(&mut RegistersCallback<'_>) -> bool,
+    ) -> Backtrace {
+        let mut bt = Backtrace::from(Vec::new());
+        Self::record(ip, opts, walk, &mut bt);
+        call_capture_hook(&bt);
+        bt
     }
 
-    fn create(ip: usize) -> Backtrace {
-        let mut frames = Vec::new();
+    /// Records the frames produced by `walk` into `bt`, replacing whatever it
+    /// held before.
+    fn record(
+        ip: usize,
+        opts: &BacktraceBuilder,
+        walk: impl FnOnce(&mut RegistersCallback<'_>) -> bool,
+        bt: &mut Backtrace,
+    ) {
+        let frames = &mut bt.frames;
+        frames.clear();
         let mut actual_start_index = None;
-        trace(|frame| {
-            frames.push(BacktraceFrame {
-                frame: Frame::Raw(frame.clone()),
-                symbols: None,
-            });
+        let mut skipped = 0;
+        let mut dropped = 0;
+        let mut budget = opts.limit.start();
+        let mut limited = false;
+        let reentrant = crate::reentrancy::is_walking();
+        let complete = walk(&mut |frame, registers| {
+            if !budget.spend() {
+                limited = true;
+                return false;
+            }
+
+            // Where the frame that requested the capture can't be found by
+            // its address, an address in it tells where it is instead. Stacks
+            // grow downwards, so the first frame whose stack pointer lies
+            // above that address already belongs to the caller.
+            if actual_start_index.is_none() {
+                if let Some(bound) = opts.stack_bound {
+                    if frame.sp() as usize > bound {
+                        actual_start_index = Some(frames.len());
+                    }
+                }
+            }
+
+            // Once we've found the frame that requested the capture we know
+            // which frames are the caller's, so start applying the requested
+            // limits. Skipped frames aren't recorded at all, and frames past
+            // the limit end the walk unless they're to be counted.
+            if let Some(start) = actual_start_index {
+                if skipped < opts.skip {
+                    skipped += 1;
+                    return true;
+                }
+                if let Some(max) = opts.max_frames {
+                    if frames.len() - start >= max {
+                        if !opts.count_dropped {
+                            limited = true;
+                            return false;
+                        }
+                        dropped += 1;
+                        return true;
+                    }
+                }
+            }
+
+            let mut captured = BacktraceFrame::from(frame.clone());
+            captured.registers = registers.map(FrameRegisters::new);
+            frames.push(captured);
 
             if frame.symbol_address() as usize == ip && actual_start_index.is_none() {
                 actual_start_index = Some(frames.len());
@@ -185,10 +493,67 @@ impl Backtrace {
             true
         });
 
-        Backtrace {
-            frames,
-            actual_start_index: actual_start_index.unwrap_or(0),
-        }
+        // Some platforms can't tell us where the capturing frame is, in which
+        // case all frames are considered to belong to the caller and the
+        // limits are applied after the fact.
+        bt.actual_start_index = match actual_start_index {
+            Some(start) => start,
+            None => {
+                let skip = opts.skip.min(frames.len());
+                frames.drain(..skip);
+                if let Some(max) = opts.max_frames {
+                    let excess = frames.len().saturating_sub(max);
+                    if opts.count_dropped {
+                        dropped = excess;
+                    } else {
+                        limited |= excess > 0;
+                    }
+                    frames.truncate(max);
+                }
+                0
+            }
+        };
+        bt.frames_dropped = dropped;
+        bt.truncated = dropped > 0 || limited || !complete;
+        bt.reentrant = reentrant;
+    }
+
+    /// Captures a backtrace at the callsite of this function into `buffer`,
+    /// without resolving any symbols.
+    ///
+    /// This is the same as `new_unresolved` except that the frames are
+    /// recorded into the memory already owned by `buffer`, replacing whatever
+    /// it previously held. Once `buffer` has grown large enough for the
+    /// deepest stack captured into it, further captures don't allocate, which
+    /// makes this suitable for taking a stack very frequently, for example on
+    /// every allocation in a heap profiler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::{Backtrace, BacktraceBuffer};
+    ///
+    /// let mut buffer = BacktraceBuffer::with_capacity(64);
+    /// for _ in 0..10 {
+    ///     Backtrace::capture_into(&mut buffer);
+    ///     assert!(!buffer.frames().is_empty());
+    /// }
+    /// ```
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub fn capture_into(buffer: &mut BacktraceBuffer) {
+        let bt = &mut buffer.backtrace;
+        Self::record(
+            Self::capture_into as usize,
+            &BacktraceBuilder::default(),
+            |cb| trace_checked(&mut |frame| cb(frame, None)),
+            bt,
+        );
+        call_capture_hook(bt);
     }
 
     /// Returns the frames from when this backtrace was captured.
@@ -205,176 +570,1276 @@ impl Backtrace {
         &self.frames[self.actual_start_index..]
     }
 
-    /// If this backtrace was created from `new_unresolved` then this function
-    /// will resolve all addresses in the backtrace to their symbolic names.
+    /// Returns whether frames at the bottom of the stack are missing from
+    /// this backtrace.
     ///
-    /// If this backtrace has been previously resolved or was created through
-    /// `new`, this function does nothing.
+    /// This is the case if a limit on the number of frames was reached, such
+    /// as `BacktraceBuilder::max_frames`, if the stack walk ran out of the budget given to
+    /// `BacktraceBuilder::limit`, or if the unwinder couldn't get any
+    /// further, for example because some code on the stack has no unwind
+    /// information.
+    /// Not every unwinder can tell the latter apart from reaching the end of
+    /// the stack, and backtraces of other threads or from a saved context are
+    /// otherwise only reported as truncated if their stack couldn't be walked
+    /// at all.
     ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    pub fn resolve(&mut self) {
-        for frame in self.frames.iter_mut().filter(|f| f.symbols.is_none()) {
-            let mut symbols = Vec::new();
-            {
-                let sym = |symbol: &Symbol| {
-                    symbols.push(BacktraceSymbol {
-                        name: symbol.name().map(|m| m.as_bytes().to_vec()),
-                        addr: symbol.addr().map(|a| a as usize),
-                        filename: symbol.filename().map(|m| m.to_owned()),
-                        lineno: symbol.lineno(),
-                        colno: symbol.colno(),
-                    });
-                };
-                match frame.frame {
-                    Frame::Raw(ref f) => resolve_frame(f, sym),
-                    Frame::Deserialized { ip, .. } => {
-                        resolve(ip as *mut c_void, sym);
-                    }
-                }
-            }
-            frame.symbols = Some(symbols);
-        }
-    }
-}
-
-impl From<Vec<BacktraceFrame>> for Backtrace {
-    fn from(frames: Vec<BacktraceFrame>) -> Self {
-        Backtrace {
-            frames,
-            actual_start_index: 0,
-        }
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
     }
-}
 
-impl From<crate::Frame> for BacktraceFrame {
-    fn from(frame: crate::Frame) -> BacktraceFrame {
-        BacktraceFrame {
-            frame: Frame::Raw(frame),
-            symbols: None,
-        }
+    /// Returns whether this backtrace was captured while the same thread was
+    /// already walking its stack, in which case it has no frames.
+    ///
+    /// This happens when a backtrace is captured from a signal handler which
+    /// interrupted a stack walk, or from the closure given to `trace`. Rather
+    /// than risk deadlocking on locks held by the walk in progress, nothing is
+    /// walked, and the backtrace is reported as truncated too. See
+    /// `backtrace::is_reentrant` for checking this beforehand.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::Backtrace;
+    ///
+    /// assert!(!Backtrace::new_unresolved().is_reentrant());
+    /// backtrace::trace(|_| {
+    ///     let bt = Backtrace::new_unresolved();
+    ///     assert!(bt.is_reentrant() && bt.frames().is_empty());
+    ///     false
+    /// });
+    /// ```
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn is_reentrant(&self) -> bool {
+        self.reentrant
     }
-}
 
-impl Into<Vec<BacktraceFrame>> for Backtrace {
-    fn into(self) -> Vec<BacktraceFrame> {
-        self.frames
+    /// Returns the number of frames which weren't recorded because of a limit
+    /// on the number of frames, such as `BacktraceBuilder::max_frames`.
+    ///
+    /// Frames past `BacktraceBuilder::max_frames` are only counted with
+    /// `BacktraceBuilder::count_dropped`, as otherwise the stack walk stops at
+    /// the limit, in which case this is zero even if frames are missing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::Backtrace;
+    ///
+    /// let bt = Backtrace::builder()
+    ///     .max_frames(1)
+    ///     .count_dropped(true)
+    ///     .capture_unresolved();
+    /// if bt.frames_dropped() > 0 {
+    ///     assert!(bt.is_truncated());
+    /// }
+    /// ```
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn frames_dropped(&self) -> usize {
+        self.frames_dropped
     }
-}
 
-impl BacktraceFrame {
-    /// Same as `Frame::ip`
+    /// Same as `resolve`, except that symbols are looked up through `cache`.
+    ///
+    /// Frames whose instruction pointer has been resolved through `cache`
+    /// before reuse those symbols, which makes resolving backtraces captured
+    /// at the same call sites over and over much cheaper.
     ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    pub fn ip(&self) -> *mut c_void {
-        self.frame.ip() as *mut c_void
+    pub fn resolve_with_cache(&mut self, cache: &mut SymbolCache) {
+        let mut loaded = snapshot::Loaded::default();
+        for frame in self.frames.iter_mut() {
+            if frame.symbols.is_none() && !frame.check_stale(&mut loaded) {
+                frame.symbols = Some(cache.resolve(frame.ip()).to_vec());
+            }
+        }
     }
 
-    /// Same as `Frame::symbol_address`
+    /// Consumes this backtrace, returning the frames that `frames` returns.
+    ///
+    /// Together with the `From<Vec<BacktraceFrame>>` implementation this can
+    /// be used to filter or otherwise edit the frames of a backtrace and then
+    /// reassemble it, with `Backtrace::from(bt.into_frames())` having the same
+    /// frames as `bt`.
     ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    pub fn symbol_address(&self) -> *mut c_void {
-        self.frame.symbol_address() as *mut c_void
+    pub fn into_frames(mut self) -> Vec<BacktraceFrame> {
+        self.frames.drain(..self.actual_start_index);
+        self.frames
     }
 
-    /// Same as `Frame::module_base_address`
+    /// If this backtrace was created from `new_unresolved` then this function
+    /// will resolve all addresses in the backtrace to their symbolic names.
+    ///
+    /// If this backtrace has been previously resolved or was created through
+    /// `new`, this function does nothing.
+    ///
+    /// Frames whose module was recorded when this backtrace was captured, but
+    /// isn't loaded anymore, are left without symbols rather than resolved
+    /// with whatever is loaded at their address now, see
+    /// `BacktraceFrame::is_stale`.
     ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    pub fn module_base_address(&self) -> Option<*mut c_void> {
-        self.frame
-            .module_base_address()
-            .map(|addr| addr as *mut c_void)
+    pub fn resolve(&mut self) {
+        resolve_frames(&mut self.frames);
     }
 
-    /// Returns the list of symbols that this frame corresponds to.
+    /// Removes the frames around the interesting part of this backtrace, the
+    /// same way that the standard library shortens the backtraces it prints
+    /// for panics.
     ///
-    /// Normally there is only one symbol per frame, but sometimes if a number
-    /// of functions are inlined into one frame then multiple symbols will be
-    /// returned. The first symbol listed is the "innermost function", whereas
-    /// the last symbol is the outermost (last caller).
+    /// The standard library marks the boundaries of user code with the
+    /// `__rust_end_short_backtrace` and `__rust_begin_short_backtrace`
+    /// functions. Frames up to and including the innermost end marker (such as
+    /// the panic machinery) are removed, as are the first begin marker and
+    /// every frame after it (such as `main`'s runtime or the thread spawning
+    /// code). If there's no begin marker then trailing frames of well-known
+    /// process and thread entry points, like `_start` or `start_thread`, are
+    /// removed instead.
     ///
-    /// Note that if this frame came from an unresolved backtrace then this will
-    /// return an empty list.
+    /// Markers are recognized by symbol name, so this does nothing for frames
+    /// which haven't been resolved.
     ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    pub fn symbols(&self) -> &[BacktraceSymbol] {
-        self.symbols.as_ref().map(|s| &s[..]).unwrap_or(&[])
+    pub fn trim(&mut self) {
+        let range = trimmed(self.frames());
+        self.frames.truncate(self.actual_start_index + range.end);
+        self.frames
+            .drain(self.actual_start_index..self.actual_start_index + range.start);
     }
-}
 
-impl BacktraceSymbol {
-    /// Same as `Symbol::name`
+    /// Returns a new backtrace with only the frames of `frames` for which
+    /// `keep` returns `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::Backtrace;
+    ///
+    /// let bt = Backtrace::new();
+    /// let resolved = bt.filter(|frame| !frame.symbols().is_empty());
+    /// assert!(resolved.frames().len() <= bt.frames().len());
+    /// ```
     ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    pub fn name(&self) -> Option<SymbolName<'_>> {
-        self.name.as_ref().map(|s| SymbolName::new(s))
+    pub fn filter<F>(&self, mut keep: F) -> Backtrace
+    where
+        F: FnMut(&BacktraceFrame) -> bool,
+    {
+        let frames = self.frames().iter().filter(|f| keep(f)).cloned();
+        let mut bt = Backtrace::from(frames.collect::<Vec<_>>());
+        bt.frames_dropped = self.frames_dropped;
+        bt.truncated = self.truncated;
+        bt.reentrant = self.reentrant;
+        bt
     }
 
-    /// Same as `Symbol::addr`
+    /// Returns a new backtrace without the frames whose symbol names contain
+    /// any of `patterns`, for example to hide the frames of an async runtime
+    /// with `&["tokio::runtime", "core::ops::function"]`.
+    ///
+    /// Patterns are matched against the demangled names without their hashes.
+    /// A frame is only removed if every one of its symbols matches, so frames
+    /// which have interesting code inlined into them are kept, as are frames
+    /// which haven't been resolved.
     ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    pub fn addr(&self) -> Option<*mut c_void> {
-        self.addr.map(|s| s as *mut c_void)
+    pub fn prune_matching(&self, patterns: &[&str]) -> Backtrace {
+        self.filter(|frame| {
+            let symbols = frame.symbols();
+            symbols.is_empty()
+                || !symbols.iter().all(|symbol| match symbol.name() {
+                    Some(name) => {
+                        let name = format!("{:#}", name);
+                        patterns.iter().any(|p| name.contains(p))
+                    }
+                    None => false,
+                })
+        })
     }
 
-    /// Same as `Symbol::filename`
+    /// Returns a JSON representation of this backtrace, for machine-readable
+    /// logs.
+    ///
+    /// The result is an array with an object for each frame in `frames`, with
+    /// the following fields:
+    ///
+    /// * `ip` and `symbol_address` - the frame's addresses, as hexadecimal
+    ///   strings.
+    /// * `module` - the path of the module containing the frame, or `null` if
+    ///   it isn't known.
+    /// * `symbols` - an array of the frame's symbols, which is empty if the
+    ///   backtrace hasn't been resolved. Each symbol is an object with `name`
+    ///   (the raw symbol name), `demangled`, `file`, `line` and `column`
+    ///   fields, any of which may be `null`, and an `inline_depth` field as
+    ///   returned by `BacktraceSymbol::inline_depth`.
+    ///
+    /// With `set_collapse_recursion` the repetitions of a cycle of frames are
+    /// replaced by a single object with a `repeat` field instead, which is an
+    /// object with the `start` and `end` indices into `frames` of the frames
+    /// it replaces and how many `times` the cycle was repeated in them.
     ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    pub fn filename(&self) -> Option<&Path> {
-        self.filename.as_ref().map(|p| &**p)
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        // Writing to a `String` can't fail.
+        let _ = json::write(&mut out, self);
+        out
     }
 
-    /// Same as `Symbol::lineno`
+    /// Returns this backtrace in the folded stack format read by flamegraph
+    /// tools such as `flamegraph.pl` and inferno.
+    ///
+    /// This is a single line, ending in a newline, with the names of the
+    /// frames in `frames` from the outermost to the innermost and separated
+    /// by semicolons, followed by a weight of 1. Inlined functions are frames of their own, and
+    /// frames without a symbol are named by their address. Symbols are
+    /// resolved as needed, without changing this backtrace.
+    ///
+    /// Many backtraces can be combined into one flamegraph by concatenating
+    /// their lines, or by inserting them into a `CallTree` first.
     ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    pub fn lineno(&self) -> Option<u32> {
-        self.lineno
+    pub fn to_folded(&self) -> String {
+        let mut path = String::new();
+        for frame in self.frames().iter().rev() {
+            folded::push_frame(&mut path, frame);
+        }
+        let mut out = String::new();
+        folded::push_line(&mut out, &path, 1);
+        out
     }
 
-    /// Same as `Symbol::colno`
+    /// Returns a compact binary representation of this backtrace, which can
+    /// be turned back into a `Backtrace` with `from_bytes`.
+    ///
+    /// This is meant for sending backtraces to another process, for example
+    /// from a crash handler to a crash reporter, where JSON or `serde` would
+    /// be too heavy. The encoding is versioned and addresses are stored as
+    /// differences to nearby addresses, so frames of an unresolved backtrace
+    /// usually take only a few bytes each. The symbols of resolved frames are
+    /// included, so a backtrace should be encoded before it's resolved if only
+    /// the addresses are needed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::Backtrace;
+    ///
+    /// let bt = Backtrace::new_unresolved();
+    /// let bytes = bt.to_bytes();
+    /// assert_eq!(Backtrace::from_bytes(&bytes), Some(bt));
+    /// ```
     ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    pub fn colno(&self) -> Option<u32> {
-        self.colno
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        binary::write(&mut out, self);
+        out
+    }
+
+    /// Decodes a backtrace which was encoded with `to_bytes`.
+    ///
+    /// `None` is returned if `bytes` isn't a backtrace in the encoding used by
+    /// this version of the crate, for example because it was truncated.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Backtrace> {
+        binary::read(bytes)
+    }
+
+    /// Returns a hash of this backtrace suitable for grouping identical
+    /// stacks, for example when aggregating crash reports.
+    ///
+    /// Frames which have been resolved contribute the names and line numbers
+    /// of their symbols, and frames without any symbols contribute their
+    /// instruction pointer. The hash function is fixed, so fingerprints of
+    /// resolved backtraces are stable across runs and builds of this crate as
+    /// long as the program's symbols don't change. Instruction pointers
+    /// generally differ from run to run due to address space layout
+    /// randomization, so backtraces should be resolved before they're
+    /// fingerprinted.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        for frame in self.frames() {
+            match frame.symbols() {
+                [] => hasher.write_u64(frame.ip() as usize as u64),
+                symbols => {
+                    for symbol in symbols {
+                        let name = symbol.name.as_deref().unwrap_or(&[]);
+                        hasher.write_u64(name.len() as u64);
+                        hasher.write(name);
+                        hasher.write_u64(symbol.lineno.map_or(0, u64::from));
+                    }
+                }
+            }
+            // Separate frames so inlined symbols can't be mistaken for a
+            // separate frame.
+            hasher.write_u8(0xff);
+        }
+        hasher.finish()
+    }
+
+    /// Returns this backtrace with the address of each frame made relative to
+    /// the module it's in, for comparing backtraces across runs.
+    ///
+    /// `Backtrace` compares instruction pointers, which generally differ from
+    /// run to run due to address space layout randomization, while
+    /// `fingerprint` needs the symbols to be resolved. Normalized backtraces
+    /// instead compare the module of each frame and its offset into it,
+    /// which are the same in every run of the same build of a program, so
+    /// they can be used to deduplicate backtraces without resolving them.
+    ///
+    /// Frames use the module recorded by `Backtrace::new_unresolved` if there
+    /// is one, and otherwise the module loaded at their address now, so this
+    /// should be called before modules the backtrace went through are
+    /// unloaded. Frames which aren't in a known module, including those of
+    /// deserialized backtraces, keep their instruction pointer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::Backtrace;
+    ///
+    /// let normalized = Backtrace::new_unresolved().normalize();
+    /// for frame in normalized.frames() {
+    ///     match frame.module() {
+    ///         Some(i) => println!("{:?}+{:#x}", normalized.modules()[i].path(), frame.offset()),
+    ///         None => println!("{:#x}", frame.offset()),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn normalize(&self) -> NormalizedBacktrace {
+        normalized::normalize(self)
+    }
+
+    /// Prints this backtrace to `out` without allocating, for example from an
+    /// out-of-memory handler.
+    ///
+    /// The output is in the same style as the `Display` implementation with
+    /// `format` chosen instead of looking at the alternate flag and
+    /// `RUST_BACKTRACE`, and it's written frame by frame through a small
+    /// buffer on the stack. Anything that would need memory is left out:
+    ///
+    /// * Frames which haven't been resolved are printed as just their
+    ///   address, so a backtrace should be resolved beforehand, while memory
+    ///   is still available, if its symbols are wanted.
+    /// * Filenames are printed as they are, rather than relative to the
+    ///   current directory.
+    /// * Recursion isn't collapsed, even with `set_collapse_recursion`.
+    /// * Symbols aren't highlighted, even with `set_backtrace_style`.
+    ///
+    /// Names of C++ symbols are still demangled with an allocation with the
+    /// `cpp_demangle` feature, as that's how `cpp_demangle` works.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::{Backtrace, PrintFmt};
+    ///
+    /// let bt = Backtrace::new();
+    /// bt.write_to(&mut std::io::stderr(), PrintFmt::Short).unwrap();
+    /// ```
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn write_to(&self, out: &mut dyn io::Write, format: PrintFmt) -> io::Result<()> {
+        stream::write_io(out, self, format)
+    }
+
+    /// Same as `write_to`, except that the backtrace is printed to a
+    /// `fmt::Write`, such as a fixed size buffer.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn write_to_fmt(&self, out: &mut dyn fmt::Write, format: PrintFmt) -> fmt::Result {
+        stream::write_fmt(out, self, format)
+    }
+}
+
+/// Symbols of the functions which start processes and threads, and which are
+/// always the outermost frames of a stack.
+const RUNTIME_FRAMES: &[&[u8]] = &[
+    b"_start",
+    b"__libc_start_main",
+    b"__libc_start_call_main",
+    b"start_thread",
+    b"clone",
+    b"clone3",
+    b"__clone",
+    b"thread_start",
+    b"_pthread_start",
+    b"start",
+    b"BaseThreadInitThunk",
+    b"RtlUserThreadStart",
+];
+
+/// Returns the range of `frames` which `Backtrace::trim` keeps.
+fn trimmed<F: Borrow<BacktraceFrame>>(frames: &[F]) -> Range<usize> {
+    let start = frames
+        .iter()
+        .map(F::borrow)
+        .rposition(|f| f.has_symbol(|name| contains(name, b"__rust_end_short_backtrace")))
+        .map_or(0, |i| i + 1);
+    let end = match frames[start..]
+        .iter()
+        .map(F::borrow)
+        .position(|f| f.has_symbol(|name| contains(name, b"__rust_begin_short_backtrace")))
+    {
+        Some(i) => start + i,
+        None => frames[start..]
+            .iter()
+            .map(F::borrow)
+            .rposition(|f| !f.has_symbol(|name| RUNTIME_FRAMES.contains(&name)))
+            .map_or(start, |i| start + i + 1),
+    };
+    start..end
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// The 64-bit FNV-1a hash, used for `Backtrace::fingerprint` as unlike the
+/// hashers in the standard library its output is guaranteed to be stable.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Fnv1a {
+        Fnv1a(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // The default implementations use native endianness, so these are fixed
+    // to make fingerprints the same on every platform.
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl PartialEq for Backtrace {
+    fn eq(&self, other: &Backtrace) -> bool {
+        self.frames() == other.frames()
+    }
+}
+
+impl Eq for Backtrace {}
+
+impl Hash for Backtrace {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.frames().hash(state)
+    }
+}
+
+/// The function registered through `set_capture_hook`, or 0 if there is none.
+static CAPTURE_HOOK: AtomicUsize = AtomicUsize::new(0);
+
+thread_local!(static IN_CAPTURE_HOOK: Cell<bool> = Cell::new(false));
+
+/// Registers a function which is called for every `Backtrace` captured in
+/// this process.
+///
+/// `hook` is called on the capturing thread as soon as the frames have been
+/// recorded, before any symbols are resolved, by every function of this
+/// crate which captures a `Backtrace`: `Backtrace::new`,
+/// `Backtrace::new_unresolved`, `BacktraceBuilder`, `Backtrace::capture_into`
+/// and the others. This allows observability layers to count or sample
+/// captures in production. Backtraces captured by `hook` itself don't call it
+/// again, and neither do those which are empty because a stack walk was
+/// already in progress on the thread, see `Backtrace::is_reentrant`.
+///
+/// There is only one hook per process, and each call replaces the one
+/// previously registered.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static CAPTURES: AtomicUsize = AtomicUsize::new(0);
+///
+/// fn count(_: &backtrace::Backtrace) {
+///     CAPTURES.fetch_add(1, Ordering::Relaxed);
+/// }
+///
+/// backtrace::set_capture_hook(count);
+/// let _bt = backtrace::Backtrace::new_unresolved();
+/// assert!(CAPTURES.load(Ordering::Relaxed) >= 1);
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn set_capture_hook(hook: fn(&Backtrace)) {
+    CAPTURE_HOOK.store(hook as usize, Ordering::Release);
+}
+
+fn call_capture_hook(bt: &Backtrace) {
+    let hook = CAPTURE_HOOK.load(Ordering::Acquire);
+    // Reentrant captures may come from a signal handler, or from a closure
+    // which can't unwind, where running arbitrary code isn't wise.
+    if hook == 0 || bt.reentrant {
+        return;
+    }
+    // SAFETY: `CAPTURE_HOOK` is only ever set from a function pointer of this
+    // type, in `set_capture_hook`.
+    let hook: fn(&Backtrace) = unsafe { core::mem::transmute(hook) };
+    // The hook may well capture a backtrace of its own, for example to log
+    // where it's called from, which mustn't recurse.
+    let _ = IN_CAPTURE_HOOK.try_with(|in_hook| {
+        if !in_hook.replace(true) {
+            hook(bt);
+            in_hook.set(false);
+        }
+    });
+}
+
+/// A builder for capturing a `Backtrace` with custom options.
+///
+/// This type is created through the `Backtrace::builder` function and can be
+/// used to skip frames at the top of the stack or to bound the number of
+/// frames which are recorded.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct BacktraceBuilder {
+    skip: usize,
+    max_frames: Option<usize>,
+    count_dropped: bool,
+    limit: TraceLimit,
+    // An address in the frame of the function which requested the capture,
+    // for finding that frame where `symbol_address` can't.
+    stack_bound: Option<usize>,
+    registers: bool,
+}
+
+impl BacktraceBuilder {
+    fn from_config(config: &CaptureConfig) -> BacktraceBuilder {
+        BacktraceBuilder {
+            skip: 0,
+            max_frames: config.max_frames,
+            count_dropped: false,
+            limit: TraceLimit::new(),
+            stack_bound: None,
+            registers: false,
+        }
+    }
+
+    /// Skips the `n` frames directly above the caller of `capture`.
+    ///
+    /// Frames internal to this crate are always omitted from `frames()`, so
+    /// this is typically used by wrapper libraries to hide their own frames.
+    /// Skipped frames are not recorded at all.
+    pub fn skip(mut self, n: usize) -> Self {
+        self.skip = n;
+        self
+    }
+
+    /// Limits the number of frames recorded to at most `n`.
+    ///
+    /// The stack walk stops once `n` frames have been recorded, which avoids
+    /// walking and allocating for the entirety of very deep stacks. If there
+    /// were more frames the backtrace is reported as truncated by
+    /// `Backtrace::is_truncated`, but how many there were isn't known, so
+    /// `Backtrace::frames_dropped` is zero unless `count_dropped` is enabled.
+    pub fn max_frames(mut self, n: usize) -> Self {
+        self.max_frames = Some(n);
+        self
+    }
+
+    /// Sets whether the rest of the stack is walked once `max_frames` frames
+    /// have been recorded, to count the frames which weren't for
+    /// `Backtrace::frames_dropped`.
+    ///
+    /// This is off by default, as counting costs as much as walking the
+    /// whole stack, which is what `max_frames` otherwise avoids.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::Backtrace;
+    ///
+    /// let bt = Backtrace::builder()
+    ///     .max_frames(1)
+    ///     .count_dropped(true)
+    ///     .capture_unresolved();
+    /// println!("{} more frames", bt.frames_dropped());
+    /// ```
+    pub fn count_dropped(mut self, count_dropped: bool) -> Self {
+        self.count_dropped = count_dropped;
+        self
+    }
+
+    /// Stops walking the stack once `limit` is exhausted, like
+    /// `backtrace::trace_with_limit`.
+    ///
+    /// Like `max_frames` without `count_dropped`, the rest of the stack isn't
+    /// walked at all, so the frames which weren't recorded aren't counted in
+    /// `Backtrace::frames_dropped`, but the backtrace is reported as
+    /// truncated by `Backtrace::is_truncated`. Unlike with `max_frames`, the
+    /// frames of this crate and those skipped with `skip` count towards the
+    /// limit too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::{Backtrace, TraceLimit};
+    /// use std::time::Duration;
+    ///
+    /// let limit = TraceLimit::new().timeout(Duration::from_millis(5));
+    /// let bt = Backtrace::builder().limit(limit).capture_unresolved();
+    /// ```
+    pub fn limit(mut self, limit: TraceLimit) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Records the registers recovered for each frame while unwinding, which
+    /// are returned by `BacktraceFrame::registers`.
+    ///
+    /// This is useful for crash analysis, where the values of callee-saved
+    /// registers in the callers of the crashing function can tell what they
+    /// were working on. Registers are only recovered when the stack is walked
+    /// by interpreting DWARF CFI, with the `unwind-dwarf` feature on Linux,
+    /// and frames walked by other backends have none. Recording them
+    /// allocates for every frame, so this is off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::Backtrace;
+    ///
+    /// let bt = Backtrace::builder().registers(true).capture();
+    /// for frame in bt.frames() {
+    ///     if let Some(registers) = frame.registers() {
+    ///         for (reg, value) in registers.iter() {
+    ///             println!("{:?}: r{} = {:#x}", frame.ip(), reg, value);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn registers(mut self, registers: bool) -> Self {
+        self.registers = registers;
+        self
+    }
+
+    /// Captures a backtrace at the callsite of this function with the
+    /// configured options, resolving all symbols.
+    ///
+    /// This is the same as `Backtrace::new` except for the options applied.
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub fn capture(&self) -> Backtrace {
+        let mut bt = Backtrace::create(Self::capture as usize, self);
+        bt.resolve();
+        bt
+    }
+
+    /// Captures a backtrace at the callsite of this function with the
+    /// configured options, without resolving any symbols.
+    ///
+    /// This is the same as `Backtrace::new_unresolved` except for the options
+    /// applied.
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub fn capture_unresolved(&self) -> Backtrace {
+        let mut bt = Backtrace::create(Self::capture_unresolved as usize, self);
+        snapshot::record(&mut bt.frames);
+        bt
+    }
+}
+
+/// Reusable storage for repeatedly capturing backtraces.
+///
+/// Backtraces captured with `Backtrace::capture_into` are recorded into a
+/// buffer rather than a newly allocated `Backtrace`, so once the buffer is
+/// large enough capturing doesn't allocate. The most recent capture can be
+/// inspected through `frames`, or through `backtrace` for everything else
+/// `Backtrace` offers, such as printing it.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone)]
+pub struct BacktraceBuffer {
+    backtrace: Backtrace,
+}
+
+impl BacktraceBuffer {
+    /// Creates an empty buffer, which allocates on its first capture.
+    pub fn new() -> BacktraceBuffer {
+        BacktraceBuffer::with_capacity(0)
+    }
+
+    /// Creates an empty buffer with room for `frames` frames, including the
+    /// few frames internal to this crate which each capture records.
+    pub fn with_capacity(frames: usize) -> BacktraceBuffer {
+        BacktraceBuffer {
+            backtrace: Backtrace::from(Vec::with_capacity(frames)),
+        }
+    }
+
+    /// Returns the frames of the most recent capture, which is empty if
+    /// nothing has been captured since this buffer was created or cleared.
+    pub fn frames(&self) -> &[BacktraceFrame] {
+        self.backtrace.frames()
+    }
+
+    /// Returns the most recent capture.
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+
+    /// Returns the most recent capture mutably, for example to resolve its
+    /// symbols.
+    ///
+    /// Resolving allocates, and the resolved symbols are discarded by the
+    /// next capture.
+    pub fn backtrace_mut(&mut self) -> &mut Backtrace {
+        &mut self.backtrace
+    }
+
+    /// Discards the most recent capture, keeping the memory it used.
+    pub fn clear(&mut self) {
+        self.backtrace.frames.clear();
+        self.backtrace.actual_start_index = 0;
+        self.backtrace.frames_dropped = 0;
+        self.backtrace.truncated = false;
+        self.backtrace.reentrant = false;
+    }
+
+    /// Consumes this buffer, returning the most recent capture.
+    pub fn into_backtrace(self) -> Backtrace {
+        self.backtrace
+    }
+}
+
+impl Default for BacktraceBuffer {
+    fn default() -> BacktraceBuffer {
+        BacktraceBuffer::new()
+    }
+}
+
+impl fmt::Debug for BacktraceBuffer {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.backtrace, fmt)
+    }
+}
+
+impl From<Vec<BacktraceFrame>> for Backtrace {
+    fn from(frames: Vec<BacktraceFrame>) -> Self {
+        Backtrace {
+            frames,
+            actual_start_index: 0,
+            frames_dropped: 0,
+            truncated: false,
+            reentrant: false,
+        }
+    }
+}
+
+/// Copies the frames of a `BacktraceFixed`, which are resolved like those of
+/// `new_unresolved`.
+impl<const N: usize> From<&crate::BacktraceFixed<N>> for Backtrace {
+    fn from(bt: &crate::BacktraceFixed<N>) -> Self {
+        Backtrace::from_ips(bt.ips(), bt.frames_dropped())
+    }
+}
+
+impl Backtrace {
+    /// Creates an unresolved backtrace of the frames at `ips`, after which
+    /// `frames_dropped` more frames weren't recorded.
+    pub(crate) fn from_ips(ips: &[usize], frames_dropped: usize) -> Backtrace {
+        let frames = ips
+            .iter()
+            .map(|&ip| BacktraceFrame {
+                frame: Frame::Deserialized {
+                    ip,
+                    sp: 0,
+                    fp: 0,
+                    symbol_address: ip,
+                    module_base_address: None,
+                },
+                symbols: None,
+                module: None,
+                stale: false,
+                registers: None,
+            })
+            .collect();
+        Backtrace {
+            frames,
+            actual_start_index: 0,
+            frames_dropped,
+            truncated: frames_dropped > 0,
+            reentrant: false,
+        }
+    }
+}
+
+impl From<crate::Frame> for BacktraceFrame {
+    fn from(frame: crate::Frame) -> BacktraceFrame {
+        BacktraceFrame {
+            frame: Frame::Raw(frame),
+            symbols: None,
+            module: None,
+            stale: false,
+            registers: None,
+        }
+    }
+}
+
+impl Into<Vec<BacktraceFrame>> for Backtrace {
+    fn into(self) -> Vec<BacktraceFrame> {
+        self.frames
+    }
+}
+
+impl BacktraceFrame {
+    fn resolve_symbols(&mut self, loaded: &mut snapshot::Loaded) {
+        // Symbols can't be resolved while this thread is already resolving
+        // some, so the frame is left to be resolved later.
+        if crate::reentrancy::is_resolving() || self.check_stale(loaded) {
+            return;
+        }
+        let mut symbols = Vec::new();
+        {
+            let sym = |symbol: &Symbol| {
+                symbols.push(BacktraceSymbol::new(symbol));
+            };
+            match self.frame {
+                Frame::Raw(ref f) => resolve_frame(f, sym),
+                Frame::Deserialized { ip, .. } => {
+                    resolve(ip as *mut c_void, sym);
+                }
+            }
+        }
+        set_inline_depths(&mut symbols);
+        self.symbols = Some(symbols);
+    }
+
+    /// Checks whether the module this frame was captured in is gone, in
+    /// which case it's marked as stale and resolved to no symbols.
+    fn check_stale(&mut self, loaded: &mut snapshot::Loaded) -> bool {
+        match self.module {
+            Some(ref module) if !loaded.check(module) => {
+                self.stale = true;
+                self.symbols = Some(Vec::new());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns this frame if it's resolved, or a resolved copy of it if not.
+    fn resolved(&self) -> Cow<'_, BacktraceFrame> {
+        if self.symbols.is_some() {
+            return Cow::Borrowed(self);
+        }
+        let mut frame = self.clone();
+        frame.resolve_symbols(&mut snapshot::Loaded::default());
+        Cow::Owned(frame)
+    }
+
+    /// Resolves the symbols of this frame if they haven't been resolved yet.
+    ///
+    /// This is the single frame equivalent of `Backtrace::resolve`, which
+    /// allows resolving only the frames that are needed, for example the
+    /// innermost few for a log message, out of an unresolved backtrace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::Backtrace;
+    ///
+    /// let mut frames = Backtrace::new_unresolved().into_frames();
+    /// for frame in frames.iter_mut().take(5) {
+    ///     frame.resolve();
+    /// }
+    /// ```
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn resolve(&mut self) {
+        if self.symbols.is_none() {
+            self.resolve_symbols(&mut snapshot::Loaded::default());
+        }
+    }
+
+    fn has_symbol(&self, mut f: impl FnMut(&[u8]) -> bool) -> bool {
+        self.symbols().iter().any(|s| match s.name {
+            Some(ref name) => f(name),
+            None => false,
+        })
+    }
+
+    /// Same as `Frame::ip`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn ip(&self) -> *mut c_void {
+        self.frame.ip() as *mut c_void
+    }
+
+    /// Same as `Frame::sp`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn sp(&self) -> *mut c_void {
+        self.frame.sp()
+    }
+
+    /// Same as `Frame::fp`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn fp(&self) -> *mut c_void {
+        self.frame.fp()
+    }
+
+    /// Same as `Frame::symbol_address`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn symbol_address(&self) -> *mut c_void {
+        self.frame.symbol_address() as *mut c_void
+    }
+
+    /// Same as `Frame::module_base_address`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn module_base_address(&self) -> Option<*mut c_void> {
+        self.frame
+            .module_base_address()
+            .map(|addr| addr as *mut c_void)
+    }
+
+    /// Returns the list of symbols that this frame corresponds to.
+    ///
+    /// Normally there is only one symbol per frame, but sometimes if a number
+    /// of functions are inlined into one frame then multiple symbols will be
+    /// returned. The first symbol listed is the "innermost function", whereas
+    /// the last symbol is the outermost (last caller).
+    ///
+    /// Note that if this frame came from an unresolved backtrace then this will
+    /// return an empty list.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn symbols(&self) -> &[BacktraceSymbol] {
+        self.symbols.as_ref().map(|s| &s[..]).unwrap_or(&[])
+    }
+
+    /// Returns the module this frame was in when it was captured.
+    ///
+    /// This is only recorded for frames of backtraces captured without
+    /// resolving their symbols, such as with `Backtrace::new_unresolved`, and
+    /// `None` is returned for other frames or if the module couldn't be
+    /// found.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn module_snapshot(&self) -> Option<&ModuleSnapshot> {
+        self.module.as_ref()
+    }
+
+    /// Returns whether this frame was left unresolved because the module it
+    /// was captured in, as recorded in `module_snapshot`, had been unloaded
+    /// or replaced by the time it was resolved.
+    ///
+    /// Stale frames have no symbols, since resolving their address would
+    /// give the symbols of whatever is loaded there now.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Returns the registers which were recovered for this frame while
+    /// unwinding.
+    ///
+    /// This is only recorded for backtraces captured with
+    /// `BacktraceBuilder::registers` enabled, and only when the stack was
+    /// walked by interpreting DWARF CFI. `None` is returned for other frames,
+    /// including deserialized ones.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn registers(&self) -> Option<&FrameRegisters> {
+        self.registers.as_ref()
+    }
+}
+
+impl PartialEq for BacktraceFrame {
+    fn eq(&self, other: &BacktraceFrame) -> bool {
+        self.ip() == other.ip() && self.symbol_address() == other.symbol_address()
+    }
+}
+
+impl Eq for BacktraceFrame {}
+
+impl Hash for BacktraceFrame {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.ip() as usize).hash(state);
+        (self.symbol_address() as usize).hash(state);
+    }
+}
+
+impl BacktraceSymbol {
+    pub(crate) fn new(symbol: &Symbol) -> BacktraceSymbol {
+        let path_map = crate::print::current_path_map();
+        BacktraceSymbol {
+            name: symbol.name().map(|m| m.as_bytes().to_vec()),
+            addr: symbol.addr().map(|a| a as usize),
+            filename: symbol.filename().map(|m| match &path_map {
+                Some(map) => map.apply(m).into_owned(),
+                None => m.to_owned(),
+            }),
+            lineno: symbol.lineno(),
+            colno: symbol.colno(),
+            inline_depth: 0,
+        }
+    }
+
+    /// Same as `Symbol::name`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn name(&self) -> Option<SymbolName<'_>> {
+        self.name.as_ref().map(|s| SymbolName::new(s))
+    }
+
+    /// Same as `Symbol::language`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn language(&self) -> Option<Language> {
+        self.name().map(|name| name.language())
+    }
+
+    /// Same as `Symbol::addr`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn addr(&self) -> Option<*mut c_void> {
+        self.addr.map(|s| s as *mut c_void)
+    }
+
+    /// Same as `Symbol::filename`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn filename(&self) -> Option<&Path> {
+        self.filename.as_ref().map(|p| &**p)
+    }
+
+    /// Same as `Symbol::lineno`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn lineno(&self) -> Option<u32> {
+        self.lineno
+    }
+
+    /// Same as `Symbol::colno`
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn colno(&self) -> Option<u32> {
+        self.colno
+    }
+
+    /// Returns how many functions this symbol's function is inlined into
+    /// within its frame.
+    ///
+    /// The last symbol of `BacktraceFrame::symbols` is the function whose code
+    /// the frame is actually executing, and has a depth of zero. Each symbol
+    /// before it was inlined into the one after it, so the first symbol has
+    /// the greatest depth.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn inline_depth(&self) -> usize {
+        self.inline_depth
+    }
+
+    /// Returns whether this symbol's function was inlined into another one,
+    /// rather than being the function of a physical frame on the stack.
+    ///
+    /// This is the same as `inline_depth() > 0`.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn is_inline(&self) -> bool {
+        self.inline_depth > 0
+    }
+}
+
+/// Resolves every frame in `frames` which hasn't been resolved yet.
+pub(crate) fn resolve_frames(frames: &mut [BacktraceFrame]) {
+    // Like `resolve_many` we only synchronize once and resolve frames in
+    // address order, so every module's debug information is only loaded
+    // once even when the stack bounces between modules.
+    let _guard = crate::lock::lock();
+    let mut order = (0..frames.len())
+        .filter(|&i| frames[i].symbols.is_none())
+        .collect::<Vec<_>>();
+    order.sort_by_key(|&i| frames[i].ip() as usize);
+    let mut loaded = snapshot::Loaded::default();
+    unsafe {
+        crate::symbolize::batch(&mut || {
+            for &i in &order {
+                frames[i].resolve_symbols(&mut loaded);
+            }
+        })
+    }
+}
+
+/// Sets the inline depth of each of the symbols of a frame, which are listed
+/// innermost first.
+pub(crate) fn set_inline_depths(symbols: &mut [BacktraceSymbol]) {
+    let len = symbols.len();
+    for (i, symbol) in symbols.iter_mut().enumerate() {
+        symbol.inline_depth = len - 1 - i;
     }
 }
 
 impl fmt::Debug for Backtrace {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let full = fmt.alternate();
-        let (frames, style) = if full {
-            (&self.frames[..], PrintFmt::Full)
+        self.fmt_pretty(fmt)
+    }
+}
+
+/// Prints the backtrace in the same style as the standard library's panic
+/// output for `RUST_BACKTRACE=1`.
+///
+/// The alternate flag (`{:#}`) prints a full backtrace including the frames
+/// for capturing the backtrace and instruction pointer addresses, matching
+/// `RUST_BACKTRACE=full`. The output is the same as the `Debug`
+/// implementation.
+impl fmt::Display for Backtrace {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_pretty(fmt)
+    }
+}
+
+impl Backtrace {
+    fn fmt_pretty(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Like the standard library, `RUST_BACKTRACE=full` asks for
+        // everything, so it's treated the same as the alternate flag.
+        let full = fmt.alternate()
+            || std::env::var_os("RUST_BACKTRACE").as_deref() == Some("full".as_ref());
+        let style = if full {
+            PrintFmt::Full
         } else {
-            (&self.frames[self.actual_start_index..], PrintFmt::Short)
+            PrintFmt::Short
         };
 
         // When printing paths we try to strip the cwd if it exists, otherwise
@@ -395,16 +1860,86 @@ impl fmt::Debug for Backtrace {
                 fmt::Display::fmt(&path.display(), fmt)
             };
 
+        // Frames which haven't been resolved yet are resolved one at a time
+        // as they're printed, leaving this backtrace unresolved. The short
+        // format needs the names of all frames to know which ones to trim.
+        let order = crate::print::frame_order();
+        let resolved;
+        let (frames, printed_last) = if full {
+            (in_order(&self.frames, order), true)
+        } else {
+            resolved = self
+                .frames()
+                .iter()
+                .map(BacktraceFrame::resolved)
+                .collect::<Vec<_>>();
+            let range = trimmed(&resolved);
+            let printed_last = range.end == resolved.len();
+            (in_order(&resolved[range], order), printed_last)
+        };
+        // The frames which were dropped are the outermost ones, so they're
+        // mentioned wherever those are printed.
+        let omitted = if printed_last { self.frames_dropped } else { 0 };
+        if omitted > 0 && order == FrameOrder::OutermostFirst {
+            writeln!(fmt, "      ... {} frames omitted", omitted)?;
+        }
+
+        let highlight = crate::print::current_style();
         let mut f = BacktraceFmt::new(fmt, style, &mut print_path);
-        f.add_context()?;
-        for frame in frames {
-            f.frame().backtrace_frame(frame)?;
+        if let Some(highlight) = &highlight {
+            f.style(highlight);
         }
+        f.add_context()?;
+        f.frame_order(order, frames.len());
+        fmt_collapsed(&mut f, &frames, |f, frame| {
+            if full {
+                f.frame().backtrace_frame(&frame.resolved())
+            } else {
+                f.frame().backtrace_frame(frame)
+            }
+        })?;
         f.finish()?;
+        if omitted > 0 && order == FrameOrder::InnermostFirst {
+            writeln!(fmt, "      ... {} frames omitted", omitted)?;
+        }
         Ok(())
     }
 }
 
+/// Returns references to `frames`, which are innermost first, in `order`.
+fn in_order<F: Borrow<BacktraceFrame>>(frames: &[F], order: FrameOrder) -> Vec<&BacktraceFrame> {
+    if order == FrameOrder::OutermostFirst {
+        frames.iter().rev().map(F::borrow).collect()
+    } else {
+        frames.iter().map(F::borrow).collect()
+    }
+}
+
+/// Prints `frames` with `print`, except for the repetitions of cycles that
+/// `set_collapse_recursion` asked to collapse.
+fn fmt_collapsed<F: Borrow<BacktraceFrame>>(
+    f: &mut BacktraceFmt<'_, '_>,
+    frames: &[F],
+    mut print: impl FnMut(&mut BacktraceFmt<'_, '_>, &BacktraceFrame) -> fmt::Result,
+) -> fmt::Result {
+    let ips = frames
+        .iter()
+        .map(|frame| frame.borrow().ip() as usize)
+        .collect::<Vec<_>>();
+    let mut next = 0;
+    for repeat in recursion::find(&ips) {
+        for frame in &frames[next..repeat.collapsed_start()] {
+            print(f, frame.borrow())?;
+        }
+        f.repeated(repeat.end() - repeat.collapsed_start(), repeat.times - 1)?;
+        next = repeat.end();
+    }
+    for frame in &frames[next..] {
+        print(f, frame.borrow())?;
+    }
+    Ok(())
+}
+
 impl Default for Backtrace {
     fn default() -> Backtrace {
         Backtrace::new()
@@ -428,6 +1963,7 @@ impl fmt::Debug for BacktraceSymbol {
             .field("filename", &self.filename())
             .field("lineno", &self.lineno())
             .field("colno", &self.colno())
+            .field("inline_depth", &self.inline_depth())
             .finish()
     }
 }
@@ -440,6 +1976,8 @@ mod rustc_serialize_impls {
     #[derive(RustcEncodable, RustcDecodable)]
     struct SerializedFrame {
         ip: usize,
+        sp: usize,
+        fp: usize,
         symbol_address: usize,
         module_base_address: Option<usize>,
         symbols: Option<Vec<BacktraceSymbol>>,
@@ -454,10 +1992,18 @@ mod rustc_serialize_impls {
             Ok(BacktraceFrame {
                 frame: Frame::Deserialized {
                     ip: frame.ip,
+                    sp: frame.sp,
+                    fp: frame.fp,
                     symbol_address: frame.symbol_address,
                     module_base_address: frame.module_base_address,
                 },
-                symbols: frame.symbols,
+                symbols: frame.symbols.map(|mut symbols| {
+                    set_inline_depths(&mut symbols);
+                    symbols
+                }),
+                module: None,
+                stale: false,
+                registers: None,
             })
         }
     }
@@ -467,9 +2013,11 @@ mod rustc_serialize_impls {
         where
             E: Encoder,
         {
-            let BacktraceFrame { frame, symbols } = self;
+            let BacktraceFrame { frame, symbols, .. } = self;
             SerializedFrame {
                 ip: frame.ip() as usize,
+                sp: frame.sp() as usize,
+                fp: frame.fp() as usize,
                 symbol_address: frame.symbol_address() as usize,
                 module_base_address: frame.module_base_address().map(|addr| addr as usize),
                 symbols: symbols.clone(),
@@ -489,6 +2037,11 @@ mod serde_impls {
     #[derive(Serialize, Deserialize)]
     struct SerializedFrame {
         ip: usize,
+        // Older versions didn't record these.
+        #[serde(default)]
+        sp: usize,
+        #[serde(default)]
+        fp: usize,
         symbol_address: usize,
         module_base_address: Option<usize>,
         symbols: Option<Vec<BacktraceSymbol>>,
@@ -499,9 +2052,11 @@ mod serde_impls {
         where
             S: Serializer,
         {
-            let BacktraceFrame { frame, symbols } = self;
+            let BacktraceFrame { frame, symbols, .. } = self;
             SerializedFrame {
                 ip: frame.ip() as usize,
+                sp: frame.sp() as usize,
+                fp: frame.fp() as usize,
                 symbol_address: frame.symbol_address() as usize,
                 module_base_address: frame.module_base_address().map(|addr| addr as usize),
                 symbols: symbols.clone(),
@@ -519,10 +2074,18 @@ mod serde_impls {
             Ok(BacktraceFrame {
                 frame: Frame::Deserialized {
                     ip: frame.ip,
+                    sp: frame.sp,
+                    fp: frame.fp,
                     symbol_address: frame.symbol_address,
                     module_base_address: frame.module_base_address,
                 },
-                symbols: frame.symbols,
+                symbols: frame.symbols.map(|mut symbols| {
+                    set_inline_depths(&mut symbols);
+                    symbols
+                }),
+                module: None,
+                stale: false,
+                registers: None,
             })
         }
     }
@@ -0,0 +1,185 @@
+use super::{Backtrace, BacktraceBuilder};
+use std::fmt;
+use std::prelude::v1::*;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A `Backtrace` together with when and on which thread it was captured.
+///
+/// This is useful for logging, where backtraces are often printed long after
+/// and far away from where they were captured. The `Debug` and `Display`
+/// implementations print a header line with the capture time and thread
+/// followed by the backtrace, formatted the same as `Backtrace` is:
+///
+/// ```text
+/// backtrace of thread 'main' (id 1234) captured at 2023-10-14T08:30:12.345678901Z:
+///    0: my_crate::main
+///              at ./src/main.rs:5:13
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone)]
+pub struct AnnotatedBacktrace {
+    backtrace: Backtrace,
+    time: SystemTime,
+    thread_id: u64,
+    thread_name: Option<String>,
+}
+
+impl AnnotatedBacktrace {
+    /// Captures a backtrace at the callsite of this function along with the
+    /// current time and thread, resolving all symbols.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::AnnotatedBacktrace;
+    ///
+    /// let bt = AnnotatedBacktrace::new();
+    /// println!("{:?}", bt);
+    /// ```
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub fn new() -> AnnotatedBacktrace {
+        let mut bt = Self::create(Self::new as usize);
+        bt.backtrace.resolve();
+        bt
+    }
+
+    /// Same as `new`, except that symbols aren't resolved, like
+    /// `Backtrace::new_unresolved`.
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub fn new_unresolved() -> AnnotatedBacktrace {
+        Self::create(Self::new_unresolved as usize)
+    }
+
+    fn create(ip: usize) -> AnnotatedBacktrace {
+        let time = SystemTime::now();
+        let thread = thread::current();
+        AnnotatedBacktrace {
+            backtrace: Backtrace::create(ip, &BacktraceBuilder::default()),
+            time,
+            thread_id: crate::current_thread_id(),
+            thread_name: thread.name().map(|name| name.to_string()),
+        }
+    }
+
+    /// Returns the captured backtrace.
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+
+    /// Returns the captured backtrace mutably, for example to resolve it.
+    pub fn backtrace_mut(&mut self) -> &mut Backtrace {
+        &mut self.backtrace
+    }
+
+    /// Consumes this value, returning the captured backtrace.
+    pub fn into_backtrace(self) -> Backtrace {
+        self.backtrace
+    }
+
+    /// Returns the time at which the backtrace was captured.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// Returns the operating system's identifier for the thread the backtrace
+    /// was captured on, as returned by `backtrace::current_thread_id`.
+    pub fn thread_id(&self) -> u64 {
+        self.thread_id
+    }
+
+    /// Returns the name of the thread the backtrace was captured on, if it
+    /// has one.
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+
+    fn fmt_header(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("backtrace of thread ")?;
+        match &self.thread_name {
+            Some(name) => write!(fmt, "'{}'", name)?,
+            None => fmt.write_str("<unnamed>")?,
+        }
+        write!(fmt, " (id {}) captured at ", self.thread_id)?;
+        write_time(fmt, self.time)?;
+        fmt.write_str(":\n")
+    }
+}
+
+impl fmt::Debug for AnnotatedBacktrace {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_header(fmt)?;
+        fmt::Debug::fmt(&self.backtrace, fmt)
+    }
+}
+
+impl fmt::Display for AnnotatedBacktrace {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_header(fmt)?;
+        fmt::Display::fmt(&self.backtrace, fmt)
+    }
+}
+
+impl Default for AnnotatedBacktrace {
+    fn default() -> AnnotatedBacktrace {
+        AnnotatedBacktrace::new()
+    }
+}
+
+/// Writes `time` as an RFC 3339 timestamp in UTC.
+fn write_time(fmt: &mut fmt::Formatter<'_>, time: SystemTime) -> fmt::Result {
+    let since_epoch = match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => since_epoch,
+        Err(_) => return fmt.write_str("<before the Unix epoch>"),
+    };
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days(secs / 86400);
+    let secs_of_day = secs % 86400;
+    write!(
+        fmt,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_nanos(),
+    )
+}
+
+/// Converts a number of days since 1970-01-01 to a year, month and day in the
+/// proleptic Gregorian calendar, using Howard Hinnant's `civil_from_days`
+/// algorithm.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Shift the epoch to 0000-03-01 so leap days are at the end of each
+    // 400-year era.
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19644), (2023, 10, 14));
+        assert_eq!(civil_from_days(2932896), (9999, 12, 31));
+    }
+}
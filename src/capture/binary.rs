@@ -0,0 +1,332 @@
+//! A compact binary encoding for `Backtrace::to_bytes` and
+//! `Backtrace::from_bytes`.
+//!
+//! The encoding starts with the magic bytes `BT` and a version byte, followed
+//! by a flags byte saying whether the backtrace is truncated or reentrant, the
+//! number of frames dropped past a limit, the number of frames and then the
+//! frames themselves. All integers are
+//! LEB128 varints, and signed differences are zigzag encoded first. Addresses
+//! are stored as the difference to a nearby address, which keeps most of them
+//! to a couple of bytes:
+//!
+//! * A frame is a flags byte, its instruction pointer relative to the one of
+//!   the previous frame, and its symbol address relative to its instruction
+//!   pointer. If `FRAME_MODULE` is set the module base address follows,
+//!   relative to the previous module base address, and likewise for the
+//!   stack and frame pointers with `FRAME_SP` and `FRAME_FP`. If
+//!   `FRAME_SYMBOLS` is set the number of symbols and the symbols themselves
+//!   follow.
+//! * A symbol is a flags byte saying which of its fields are present, then
+//!   its name and filename as a length followed by the bytes, its address
+//!   relative to the frame's instruction pointer, and its line and column.
+
+use super::{set_inline_depths, Backtrace, BacktraceFrame, BacktraceSymbol, Frame};
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::prelude::v1::*;
+
+const MAGIC: &[u8] = b"BT";
+const VERSION: u8 = 2;
+
+const BACKTRACE_TRUNCATED: u8 = 1 << 0;
+const BACKTRACE_REENTRANT: u8 = 1 << 1;
+
+const FRAME_MODULE: u8 = 1 << 0;
+const FRAME_SYMBOLS: u8 = 1 << 1;
+const FRAME_SP: u8 = 1 << 2;
+const FRAME_FP: u8 = 1 << 3;
+
+const SYMBOL_NAME: u8 = 1 << 0;
+const SYMBOL_ADDR: u8 = 1 << 1;
+const SYMBOL_FILENAME: u8 = 1 << 2;
+const SYMBOL_LINENO: u8 = 1 << 3;
+const SYMBOL_COLNO: u8 = 1 << 4;
+
+pub(super) fn write(out: &mut Vec<u8>, bt: &Backtrace) {
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    let mut flags = 0;
+    if bt.truncated {
+        flags |= BACKTRACE_TRUNCATED;
+    }
+    if bt.reentrant {
+        flags |= BACKTRACE_REENTRANT;
+    }
+    out.push(flags);
+    write_uint(out, bt.frames_dropped as u64);
+    let frames = bt.frames();
+    write_uint(out, frames.len() as u64);
+    let mut prev_ip = 0;
+    let mut prev_module = 0;
+    let mut prev_sp = 0;
+    let mut prev_fp = 0;
+    for frame in frames {
+        let ip = frame.ip() as usize;
+        let module = frame.module_base_address().map(|addr| addr as usize);
+        let sp = frame.sp() as usize;
+        let fp = frame.fp() as usize;
+        let mut flags = 0;
+        if module.is_some() {
+            flags |= FRAME_MODULE;
+        }
+        if frame.symbols.is_some() {
+            flags |= FRAME_SYMBOLS;
+        }
+        if sp != 0 {
+            flags |= FRAME_SP;
+        }
+        if fp != 0 {
+            flags |= FRAME_FP;
+        }
+        out.push(flags);
+        write_delta(out, prev_ip, ip);
+        write_delta(out, ip, frame.symbol_address() as usize);
+        if let Some(module) = module {
+            write_delta(out, prev_module, module);
+            prev_module = module;
+        }
+        if sp != 0 {
+            write_delta(out, prev_sp, sp);
+            prev_sp = sp;
+        }
+        if fp != 0 {
+            write_delta(out, prev_fp, fp);
+            prev_fp = fp;
+        }
+        if let Some(ref symbols) = frame.symbols {
+            write_uint(out, symbols.len() as u64);
+            for symbol in symbols {
+                write_symbol(out, ip, symbol);
+            }
+        }
+        prev_ip = ip;
+    }
+}
+
+fn write_symbol(out: &mut Vec<u8>, ip: usize, symbol: &BacktraceSymbol) {
+    let flags = [
+        (symbol.name.is_some(), SYMBOL_NAME),
+        (symbol.addr.is_some(), SYMBOL_ADDR),
+        (symbol.filename.is_some(), SYMBOL_FILENAME),
+        (symbol.lineno.is_some(), SYMBOL_LINENO),
+        (symbol.colno.is_some(), SYMBOL_COLNO),
+    ];
+    out.push(
+        flags
+            .iter()
+            .filter(|&&(present, _)| present)
+            .fold(0, |flags, &(_, flag)| flags | flag),
+    );
+    if let Some(ref name) = symbol.name {
+        write_bytes(out, name);
+    }
+    if let Some(addr) = symbol.addr {
+        write_delta(out, ip, addr);
+    }
+    if let Some(ref filename) = symbol.filename {
+        write_path(out, filename);
+    }
+    if let Some(lineno) = symbol.lineno {
+        write_uint(out, lineno.into());
+    }
+    if let Some(colno) = symbol.colno {
+        write_uint(out, colno.into());
+    }
+}
+
+fn write_uint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_delta(out: &mut Vec<u8>, from: usize, to: usize) {
+    let delta = to.wrapping_sub(from) as isize as i64;
+    write_uint(out, ((delta << 1) ^ (delta >> 63)) as u64);
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_uint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+#[cfg(unix)]
+fn write_path(out: &mut Vec<u8>, path: &Path) {
+    use std::os::unix::prelude::*;
+    write_bytes(out, path.as_os_str().as_bytes());
+}
+
+#[cfg(not(unix))]
+fn write_path(out: &mut Vec<u8>, path: &Path) {
+    write_bytes(out, path.to_string_lossy().as_bytes());
+}
+
+pub(super) fn read(bytes: &[u8]) -> Option<Backtrace> {
+    let mut r = Reader(bytes);
+    if r.bytes(MAGIC.len())? != MAGIC || r.byte()? != VERSION {
+        return None;
+    }
+    let flags = r.byte()?;
+    let frames_dropped = usize::try_from(r.uint()?).ok()?;
+    let len = r.len()?;
+    let mut frames = Vec::with_capacity(len);
+    let mut prev_ip = 0;
+    let mut prev_module = 0;
+    let mut prev_sp = 0;
+    let mut prev_fp = 0;
+    for _ in 0..len {
+        let flags = r.byte()?;
+        let ip = r.delta(prev_ip)?;
+        let symbol_address = r.delta(ip)?;
+        let mut module_base_address = None;
+        if flags & FRAME_MODULE != 0 {
+            prev_module = r.delta(prev_module)?;
+            module_base_address = Some(prev_module);
+        }
+        let mut sp = 0;
+        if flags & FRAME_SP != 0 {
+            prev_sp = r.delta(prev_sp)?;
+            sp = prev_sp;
+        }
+        let mut fp = 0;
+        if flags & FRAME_FP != 0 {
+            prev_fp = r.delta(prev_fp)?;
+            fp = prev_fp;
+        }
+        let mut symbols = None;
+        if flags & FRAME_SYMBOLS != 0 {
+            let len = r.len()?;
+            let mut list = Vec::with_capacity(len);
+            for _ in 0..len {
+                list.push(r.symbol(ip)?);
+            }
+            set_inline_depths(&mut list);
+            symbols = Some(list);
+        }
+        frames.push(BacktraceFrame {
+            frame: Frame::Deserialized {
+                ip,
+                sp,
+                fp,
+                symbol_address,
+                module_base_address,
+            },
+            symbols,
+            module: None,
+            stale: false,
+            registers: None,
+        });
+        prev_ip = ip;
+    }
+    if !r.0.is_empty() {
+        return None;
+    }
+    let mut bt = Backtrace::from(frames);
+    bt.frames_dropped = frames_dropped;
+    bt.truncated = flags & BACKTRACE_TRUNCATED != 0;
+    bt.reentrant = flags & BACKTRACE_REENTRANT != 0;
+    Some(bt)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.0.len() {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn uint(&mut self) -> Option<u64> {
+        let mut n = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift > 63 {
+                return None;
+            }
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(n);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Reads the length of a list or a string, which can't be larger than
+    /// the rest of the input as every element takes up at least a byte.
+    fn len(&mut self) -> Option<usize> {
+        let len = self.uint()?;
+        if len > self.0.len() as u64 {
+            return None;
+        }
+        Some(len as usize)
+    }
+
+    fn delta(&mut self, from: usize) -> Option<usize> {
+        let n = self.uint()?;
+        let delta = ((n >> 1) as i64) ^ -((n & 1) as i64);
+        Some(from.wrapping_add(delta as usize))
+    }
+
+    fn symbol(&mut self, ip: usize) -> Option<BacktraceSymbol> {
+        let flags = self.byte()?;
+        let mut symbol = BacktraceSymbol {
+            name: None,
+            addr: None,
+            filename: None,
+            lineno: None,
+            colno: None,
+            inline_depth: 0,
+        };
+        if flags & SYMBOL_NAME != 0 {
+            let len = self.len()?;
+            symbol.name = Some(self.bytes(len)?.to_vec());
+        }
+        if flags & SYMBOL_ADDR != 0 {
+            symbol.addr = Some(self.delta(ip)?);
+        }
+        if flags & SYMBOL_FILENAME != 0 {
+            let len = self.len()?;
+            symbol.filename = Some(path(self.bytes(len)?));
+        }
+        if flags & SYMBOL_LINENO != 0 {
+            symbol.lineno = Some(self.u32()?);
+        }
+        if flags & SYMBOL_COLNO != 0 {
+            symbol.colno = Some(self.u32()?);
+        }
+        Some(symbol)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let n = self.uint()?;
+        if n > u64::from(u32::MAX) {
+            return None;
+        }
+        Some(n as u32)
+    }
+}
+
+#[cfg(unix)]
+fn path(bytes: &[u8]) -> PathBuf {
+    use std::ffi::OsStr;
+    use std::os::unix::prelude::*;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
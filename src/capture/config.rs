@@ -0,0 +1,98 @@
+use std::cell::RefCell;
+
+thread_local!(static CONFIG: RefCell<CaptureConfig> = RefCell::new(CaptureConfig::new()));
+
+/// Options which `Backtrace::new` and `Backtrace::new_unresolved` capture
+/// with, set for a scope on the current thread with `with_config`.
+///
+/// Unlike `BacktraceBuilder`, which configures a single capture, this lets a
+/// subsystem choose how every backtrace captured on its behalf is taken,
+/// including those captured by libraries it calls into, without affecting
+/// other threads or the rest of the process.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Debug)]
+pub struct CaptureConfig {
+    pub(super) max_frames: Option<usize>,
+    pub(super) resolve: bool,
+}
+
+impl CaptureConfig {
+    /// Returns the default configuration, which records every frame and
+    /// resolves symbols in `Backtrace::new`.
+    pub fn new() -> CaptureConfig {
+        CaptureConfig {
+            max_frames: None,
+            resolve: true,
+        }
+    }
+
+    /// Limits the number of frames recorded to at most `n`, like
+    /// `BacktraceBuilder::max_frames`.
+    pub fn max_frames(mut self, n: usize) -> Self {
+        self.max_frames = Some(n);
+        self
+    }
+
+    /// Sets whether `Backtrace::new` resolves symbols as it captures.
+    ///
+    /// When this is `false` it behaves like `Backtrace::new_unresolved`, and
+    /// symbols are only looked up once the backtrace is resolved or printed.
+    pub fn resolve(mut self, resolve: bool) -> Self {
+        self.resolve = resolve;
+        self
+    }
+}
+
+impl Default for CaptureConfig {
+    fn default() -> CaptureConfig {
+        CaptureConfig::new()
+    }
+}
+
+/// Runs `f` with `config` as the capture configuration of the current thread.
+///
+/// Backtraces captured through `Backtrace::new` and
+/// `Backtrace::new_unresolved` while `f` runs use `config`. The previous
+/// configuration is restored once `f` returns or panics, so calls can be
+/// nested and the innermost one applies.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::{Backtrace, CaptureConfig};
+///
+/// let config = CaptureConfig::new().max_frames(8).resolve(false);
+/// let bt = backtrace::with_config(config, Backtrace::new);
+/// assert!(bt.frames().len() <= 8);
+/// assert!(bt.frames().iter().all(|f| f.symbols().is_empty()));
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn with_config<R>(config: CaptureConfig, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<CaptureConfig>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if let Some(config) = self.0.take() {
+                CONFIG.with(|c| *c.borrow_mut() = config);
+            }
+        }
+    }
+
+    let _restore = Restore(Some(CONFIG.with(|c| c.replace(config))));
+    f()
+}
+
+/// Returns the capture configuration of the current thread.
+pub(super) fn current() -> CaptureConfig {
+    // The configuration may be unavailable while the thread is exiting, in
+    // which case the defaults apply.
+    CONFIG.try_with(|c| c.borrow().clone()).unwrap_or_default()
+}
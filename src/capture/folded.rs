@@ -0,0 +1,49 @@
+//! Brendan Gregg's folded stack format, for `Backtrace::to_folded` and
+//! `CallTree::to_folded`.
+//!
+//! Each line of this format is one call path, with the names of its frames
+//! from the outermost to the innermost separated by semicolons, followed by a
+//! space and the path's weight. This is what `flamegraph.pl` and inferno draw
+//! flamegraphs from.
+
+use super::BacktraceFrame;
+use core::fmt::Write;
+use std::prelude::v1::*;
+
+/// Appends the names of `frame` to the call path in `out`, starting with a
+/// separator unless `out` is empty.
+pub(crate) fn push_frame(out: &mut String, frame: &BacktraceFrame) {
+    let frame = frame.resolved();
+    // Symbols are listed from the innermost inlined function outwards, the
+    // reverse of the order of a call path.
+    let names = frame
+        .symbols()
+        .iter()
+        .rev()
+        .map(|symbol| symbol.name().map(|name| format!("{:#}", name)))
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        push(out, &format!("{:#x}", frame.ip() as usize));
+    }
+    for name in names {
+        match name {
+            Some(name) => push(out, &name),
+            None => push(out, &format!("{:#x}", frame.ip() as usize)),
+        }
+    }
+}
+
+fn push(out: &mut String, name: &str) {
+    if !out.is_empty() {
+        out.push(';');
+    }
+    // Semicolons separate frames, but they also appear in names of array
+    // types such as `[u8; 4]`.
+    out.push_str(&name.replace(';', ","));
+}
+
+/// Appends the line for a call path to `out`.
+pub(crate) fn push_line(out: &mut String, path: &str, weight: u64) {
+    // Writing to a `String` can't fail.
+    let _ = writeln!(out, "{} {}", path, weight);
+}
@@ -0,0 +1,118 @@
+//! A small JSON writer for `Backtrace::to_json`.
+//!
+//! This is written by hand rather than through `serde_json` so that it's
+//! always available, and because the format is fixed: an array with an object
+//! for each frame, or for each run of frames collapsed by
+//! `set_collapse_recursion`.
+
+use super::{Backtrace, BacktraceFrame, BacktraceSymbol, Frame};
+use crate::print::recursion;
+use core::fmt::{self, Write};
+use std::prelude::v1::*;
+
+pub(super) fn write(out: &mut String, bt: &Backtrace) -> fmt::Result {
+    let frames = bt.frames();
+    let ips = frames.iter().map(|f| f.ip() as usize).collect::<Vec<_>>();
+    let mut repeats = recursion::find(&ips).into_iter().peekable();
+    out.push('[');
+    let mut i = 0;
+    while i < frames.len() {
+        if i > 0 {
+            out.push(',');
+        }
+        match repeats.peek() {
+            Some(repeat) if repeat.collapsed_start() == i => {
+                write!(
+                    out,
+                    "{{\"repeat\":{{\"start\":{},\"end\":{},\"times\":{}}}}}",
+                    i,
+                    repeat.end(),
+                    repeat.times - 1
+                )?;
+                i = repeat.end();
+                repeats.next();
+            }
+            _ => {
+                write_frame(out, &frames[i])?;
+                i += 1;
+            }
+        }
+    }
+    out.push(']');
+    Ok(())
+}
+
+fn write_frame(out: &mut String, frame: &BacktraceFrame) -> fmt::Result {
+    // Addresses are written as hex strings since they might not fit in the
+    // integers which JSON parsers can represent exactly.
+    write!(out, "{{\"ip\":\"{:#x}\"", frame.ip() as usize)?;
+    write!(
+        out,
+        ",\"symbol_address\":\"{:#x}\"",
+        frame.symbol_address() as usize
+    )?;
+    out.push_str(",\"module\":");
+    let module = match frame.frame {
+        Frame::Raw(ref f) => f.module(),
+        Frame::Deserialized { ip, .. } => crate::symbolize::module_for_address(ip as *mut _),
+    };
+    match module {
+        Some(module) => write_str(out, &module.path().to_string_lossy()),
+        None => out.push_str("null"),
+    }
+    out.push_str(",\"symbols\":[");
+    for (i, symbol) in frame.symbols().iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_symbol(out, symbol)?;
+    }
+    out.push_str("]}");
+    Ok(())
+}
+
+fn write_symbol(out: &mut String, symbol: &BacktraceSymbol) -> fmt::Result {
+    let name = symbol.name();
+    out.push_str("{\"name\":");
+    match name {
+        Some(ref name) => write_str(out, &String::from_utf8_lossy(name.as_bytes())),
+        None => out.push_str("null"),
+    }
+    out.push_str(",\"demangled\":");
+    match name {
+        Some(ref name) => write_str(out, &name.to_string()),
+        None => out.push_str("null"),
+    }
+    out.push_str(",\"file\":");
+    match symbol.filename() {
+        Some(file) => write_str(out, &file.to_string_lossy()),
+        None => out.push_str("null"),
+    }
+    for (key, value) in [("line", symbol.lineno()), ("column", symbol.colno())].iter() {
+        match value {
+            Some(value) => write!(out, ",\"{}\":{}", key, value)?,
+            None => write!(out, ",\"{}\":null", key)?,
+        }
+    }
+    write!(out, ",\"inline_depth\":{}", symbol.inline_depth())?;
+    out.push('}');
+    Ok(())
+}
+
+fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,149 @@
+use super::Backtrace;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::prelude::v1::*;
+use std::sync::Once;
+
+/// A backtrace which only records instruction pointers when it's captured,
+/// and turns into a resolved `Backtrace` the first time it's looked at.
+///
+/// This is meant to be embedded in error types: creating an error then costs
+/// a stack walk and one allocation for the addresses, while symbols are only
+/// resolved, and the frames and their symbols allocated, for the errors that
+/// actually get printed or inspected. Access is synchronized, so errors
+/// containing a `LazyBacktrace` can be shared between threads and any of
+/// them may be the first to look at it.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::LazyBacktrace;
+///
+/// #[derive(Debug)]
+/// struct Error {
+///     backtrace: LazyBacktrace,
+/// }
+///
+/// let err = Error {
+///     backtrace: LazyBacktrace::new(),
+/// };
+/// assert!(!err.backtrace.is_resolved());
+/// // Printing the error resolves the backtrace.
+/// println!("{:?}", err);
+/// assert!(err.backtrace.is_resolved());
+/// ```
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub struct LazyBacktrace {
+    ips: Box<[usize]>,
+    once: Once,
+    // Only written once, by whichever thread runs `once`, and only read after
+    // it has completed.
+    backtrace: UnsafeCell<Option<Backtrace>>,
+}
+
+unsafe impl Sync for LazyBacktrace {}
+
+impl LazyBacktrace {
+    /// Captures the instruction pointers of the stack at the callsite of this
+    /// function, without resolving them.
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub fn new() -> LazyBacktrace {
+        let ip = Self::new as usize;
+        let mut ips = Vec::new();
+        let mut found = false;
+        crate::trace(|frame| {
+            ips.push(frame.ip() as usize);
+            // Everything up to the frame which requested the capture is
+            // internal to this crate, so start over after it, like
+            // `BacktraceFixed` does.
+            if !found && frame.symbol_address() as usize == ip {
+                found = true;
+                ips.clear();
+            }
+            true
+        });
+        LazyBacktrace::from_ips(ips.into_boxed_slice())
+    }
+
+    fn from_ips(ips: Box<[usize]>) -> LazyBacktrace {
+        LazyBacktrace {
+            ips,
+            once: Once::new(),
+            backtrace: UnsafeCell::new(None),
+        }
+    }
+
+    /// Returns the instruction pointers of the captured frames, innermost
+    /// first, which are available without resolving anything.
+    pub fn ips(&self) -> &[usize] {
+        &self.ips
+    }
+
+    /// Returns the captured backtrace with all symbols resolved, resolving it
+    /// first if this is the first time it's accessed.
+    ///
+    /// If several threads call this at once, one of them resolves the
+    /// backtrace while the others wait for it.
+    pub fn get(&self) -> &Backtrace {
+        self.once.call_once(|| {
+            let mut bt = Backtrace::from_ips(&self.ips, 0);
+            bt.resolve();
+            unsafe { *self.backtrace.get() = Some(bt) }
+        });
+        unsafe { (*self.backtrace.get()).as_ref().unwrap() }
+    }
+
+    /// Returns whether the backtrace has been resolved by `get` yet.
+    pub fn is_resolved(&self) -> bool {
+        self.once.is_completed()
+    }
+
+    /// Consumes this value, returning the captured backtrace with all
+    /// symbols resolved.
+    pub fn into_backtrace(self) -> Backtrace {
+        self.get();
+        self.backtrace.into_inner().unwrap()
+    }
+}
+
+impl Clone for LazyBacktrace {
+    fn clone(&self) -> LazyBacktrace {
+        let clone = LazyBacktrace::from_ips(self.ips.clone());
+        // Don't resolve the clone again if this one already has been.
+        if self.is_resolved() {
+            let bt = self.get().clone();
+            clone
+                .once
+                .call_once(|| unsafe { *clone.backtrace.get() = Some(bt) });
+        }
+        clone
+    }
+}
+
+impl Default for LazyBacktrace {
+    fn default() -> LazyBacktrace {
+        LazyBacktrace::new()
+    }
+}
+
+impl From<LazyBacktrace> for Backtrace {
+    fn from(bt: LazyBacktrace) -> Backtrace {
+        bt.into_backtrace()
+    }
+}
+
+impl fmt::Debug for LazyBacktrace {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.get(), fmt)
+    }
+}
+
+impl fmt::Display for LazyBacktrace {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.get(), fmt)
+    }
+}
@@ -0,0 +1,148 @@
+//! Addresses of backtraces made relative to their modules, for comparing
+//! backtraces captured by different runs of a program.
+
+use super::{Backtrace, Frame};
+use crate::symbolize::{module_for_address, Module};
+use std::path::{Path, PathBuf};
+use std::prelude::v1::*;
+
+/// A backtrace with the address of each frame made relative to the module it
+/// was in, returned by `Backtrace::normalize`.
+///
+/// Where modules are loaded differs from run to run with address space
+/// layout randomization, so the instruction pointers of the same stack
+/// captured by two runs of a program generally differ. Offsets into the
+/// modules don't, so normalized backtraces compare equal, and hash the same,
+/// if they went through the same code. This allows grouping backtraces of
+/// many runs without resolving their symbols.
+///
+/// Modules are identified by their path and build ID, and listed in the
+/// order their frames first appear in, so the same stack always lists the
+/// same modules.
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NormalizedBacktrace {
+    modules: Vec<NormalizedModule>,
+    frames: Vec<NormalizedFrame>,
+}
+
+impl NormalizedBacktrace {
+    /// Returns the frames of the backtrace, in the same order as
+    /// `Backtrace::frames`.
+    pub fn frames(&self) -> &[NormalizedFrame] {
+        &self.frames
+    }
+
+    /// Returns the modules which the frames are in, which their
+    /// `NormalizedFrame::module` indexes.
+    pub fn modules(&self) -> &[NormalizedModule] {
+        &self.modules
+    }
+}
+
+/// A frame of a `NormalizedBacktrace`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NormalizedFrame {
+    module: Option<usize>,
+    offset: usize,
+}
+
+impl NormalizedFrame {
+    /// Returns the index into `NormalizedBacktrace::modules` of the module
+    /// this frame is in, or `None` if it isn't in a known module.
+    pub fn module(&self) -> Option<usize> {
+        self.module
+    }
+
+    /// Returns the instruction pointer of this frame relative to the base
+    /// address of its module, or the instruction pointer itself if it isn't
+    /// in a known module.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+/// A module of a `NormalizedBacktrace`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NormalizedModule {
+    path: PathBuf,
+    build_id: Option<Vec<u8>>,
+}
+
+impl NormalizedModule {
+    /// Same as `Module::path`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Same as `Module::build_id`.
+    pub fn build_id(&self) -> Option<&[u8]> {
+        self.build_id.as_deref()
+    }
+}
+
+pub(super) fn normalize(bt: &Backtrace) -> NormalizedBacktrace {
+    let mut modules = Vec::new();
+    // The modules looked up so far, with the index of each in `modules`.
+    let mut seen: Vec<(Module, usize)> = Vec::new();
+    let mut frames = Vec::with_capacity(bt.frames().len());
+    for frame in bt.frames() {
+        let ip = frame.ip() as usize;
+        let module = match (frame.module_snapshot(), &frame.frame) {
+            (Some(snapshot), _) => Some(snapshot.module().clone()),
+            // Like symbolication, this looks at the call instruction rather
+            // than the return address, which may be just past the end of the
+            // module.
+            (None, Frame::Raw(raw)) => {
+                let addr = raw.ip_for_symbolication() as usize;
+                match seen.iter().find(|(m, _)| contains(m, addr)) {
+                    Some((module, _)) => Some(module.clone()),
+                    None => module_for_address(addr as *mut _),
+                }
+            }
+            // The modules of other processes aren't known.
+            (None, Frame::Deserialized { .. }) => None,
+        };
+        let module = module.map(|module| {
+            let index = match seen.iter().find(|(m, _)| *m == module) {
+                Some(&(_, index)) => index,
+                None => {
+                    let normalized = NormalizedModule {
+                        path: module.path().to_path_buf(),
+                        build_id: module.build_id().map(|id| id.to_vec()),
+                    };
+                    let index = match modules.iter().position(|m| *m == normalized) {
+                        Some(index) => index,
+                        None => {
+                            modules.push(normalized);
+                            modules.len() - 1
+                        }
+                    };
+                    seen.push((module.clone(), index));
+                    index
+                }
+            };
+            (index, module.base_address() as usize)
+        });
+        frames.push(match module {
+            Some((index, base)) => NormalizedFrame {
+                module: Some(index),
+                offset: ip.wrapping_sub(base),
+            },
+            None => NormalizedFrame {
+                module: None,
+                offset: ip,
+            },
+        });
+    }
+    NormalizedBacktrace { modules, frames }
+}
+
+fn contains(module: &Module, addr: usize) -> bool {
+    let base = module.base_address() as usize;
+    base <= addr && addr - base < module.size()
+}
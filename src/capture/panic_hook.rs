@@ -0,0 +1,169 @@
+use super::Backtrace;
+use std::fmt;
+use std::panic::{self, Location};
+use std::prelude::v1::*;
+use std::thread;
+
+/// A panic along with the backtrace of where it happened, as passed to the
+/// callback of `install_panic_hook_with`.
+///
+/// The `Display` implementation prints it like the standard library prints
+/// panics, with the message and location followed by the backtrace in this
+/// crate's format. The alternate flag (`{:#}`) prints the full backtrace, as
+/// it does for `Backtrace`.
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub struct PanicReport<'a> {
+    message: Option<&'a str>,
+    location: Option<&'a Location<'a>>,
+    thread: Option<&'a str>,
+    backtrace: Backtrace,
+}
+
+impl<'a> PanicReport<'a> {
+    /// Returns the message the panic was raised with, if it was a string,
+    /// which is the case for `panic!` with or without formatting arguments.
+    pub fn message(&self) -> Option<&str> {
+        self.message
+    }
+
+    /// Returns where the panic was raised, if that's known.
+    pub fn location(&self) -> Option<&Location<'_>> {
+        self.location
+    }
+
+    /// Returns the name of the thread which panicked, if it has one.
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread
+    }
+
+    /// Returns the backtrace of the panicking thread, captured by the hook and
+    /// resolved.
+    ///
+    /// Its innermost frames are those of the hook and of the standard
+    /// library's panic machinery, which `Backtrace::trim` removes and which
+    /// aren't printed by default.
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+}
+
+impl fmt::Display for PanicReport<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "thread '{}' panicked",
+            self.thread.unwrap_or("<unnamed>")
+        )?;
+        if let Some(location) = self.location {
+            write!(fmt, " at {}", location)?;
+        }
+        writeln!(fmt, ":\n{}", self.message.unwrap_or("Box<dyn Any>"))?;
+        writeln!(fmt, "stack backtrace:")?;
+        fmt::Display::fmt(&self.backtrace, fmt)
+    }
+}
+
+impl fmt::Debug for PanicReport<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PanicReport")
+            .field("message", &self.message)
+            .field("location", &self.location)
+            .field("thread", &self.thread)
+            .field("backtrace", &self.backtrace)
+            .finish()
+    }
+}
+
+/// Replaces the panic hook with one which prints panics along with a
+/// backtrace in this crate's format.
+///
+/// The backtrace is printed like `Backtrace`'s `Display` implementation
+/// prints it, so it has the inlined functions, source lines and other
+/// details this crate finds, and follows settings such as `set_path_map`,
+/// `set_frame_order` or `RUST_BACKTRACE=full`. It's printed for every panic,
+/// whether or not `RUST_BACKTRACE` is set.
+///
+/// The hook which was installed before, which is the standard library's
+/// unless something else replaced it, is dropped, as it would print the panic
+/// a second time. Use `install_panic_hook_with` to keep it.
+///
+/// # Examples
+///
+/// ```no_run
+/// backtrace::install_panic_hook();
+/// panic!("this is printed with a backtrace");
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        report(info.payload(), info.location(), |report| {
+            eprint!("{}", report)
+        })
+    }));
+}
+
+/// Wraps the panic hook with one which captures a resolved backtrace of each
+/// panic and passes it to `callback`.
+///
+/// The hook installed before, which is the standard library's unless
+/// something else replaced it, is called first, so panics are still printed
+/// as they were, and `callback` is called after it, on the panicking thread.
+/// This suits sending panics to a log or a crash reporter.
+///
+/// If `callback` panics itself the process is aborted, as with any panic
+/// hook.
+///
+/// # Examples
+///
+/// ```
+/// backtrace::install_panic_hook_with(|report| {
+///     let location = report.location().map(|l| l.to_string());
+///     eprintln!("{:?} at {:?}", report.message(), location);
+///     eprintln!("{:?}", report.backtrace());
+/// });
+/// # let _ = std::panic::take_hook();
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn install_panic_hook_with<F>(callback: F)
+where
+    F: Fn(&PanicReport<'_>) + Send + Sync + 'static,
+{
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+        report(info.payload(), info.location(), &callback);
+    }));
+}
+
+/// Captures the backtrace of a panic raised with `payload` at `location`, and
+/// passes the report to `f`.
+#[inline(never)]
+fn report(
+    payload: &(dyn std::any::Any + Send),
+    location: Option<&Location<'_>>,
+    f: impl FnOnce(&PanicReport<'_>),
+) {
+    let message = match payload.downcast_ref::<&'static str>() {
+        Some(s) => Some(*s),
+        None => payload.downcast_ref::<String>().map(|s| &s[..]),
+    };
+    let thread = thread::current();
+    f(&PanicReport {
+        message,
+        location,
+        thread: thread.name(),
+        backtrace: Backtrace::new(),
+    })
+}
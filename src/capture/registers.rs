@@ -0,0 +1,49 @@
+//! Recording the registers which were recovered for each frame while
+//! unwinding, for backtraces captured with `BacktraceBuilder::registers`.
+
+use std::prelude::v1::*;
+
+/// The registers of a captured frame, as they were recovered while unwinding
+/// the stack.
+///
+/// Registers are identified by their DWARF register number, as used by the
+/// platform's ABI and by debuggers, such as 3 for `rbx` on x86_64 or 19 for
+/// `x19` on AArch64. The innermost frame, and frames which were interrupted
+/// by a signal, have all the registers which were saved for them. Other
+/// frames only have the stack pointer and the callee-saved registers, such
+/// as `rbx`, `rbp` and `r12` to `r15` on x86_64, since the values of the
+/// others were lost once their callee was called. The instruction pointer is
+/// `BacktraceFrame::ip`.
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameRegisters {
+    // Sorted by register number.
+    regs: Vec<(u16, usize)>,
+}
+
+impl FrameRegisters {
+    pub(super) fn new(regs: &[(u16, usize)]) -> FrameRegisters {
+        let mut regs = regs.to_vec();
+        regs.sort_unstable_by_key(|&(reg, _)| reg);
+        FrameRegisters { regs }
+    }
+
+    /// Returns the value of the register with the DWARF register number
+    /// `reg`, if it was recovered for this frame.
+    pub fn get(&self, reg: u16) -> Option<usize> {
+        self.regs
+            .binary_search_by_key(&reg, |&(r, _)| r)
+            .ok()
+            .map(|i| self.regs[i].1)
+    }
+
+    /// Returns the recovered registers as pairs of DWARF register number and
+    /// value, ordered by register number.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, usize)> + '_ {
+        self.regs.iter().copied()
+    }
+}
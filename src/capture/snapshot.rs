@@ -0,0 +1,93 @@
+//! Recording the module that each frame of an unresolved backtrace was in, so
+//! that resolving it later can tell whether that module is still loaded.
+
+use super::{BacktraceFrame, Frame};
+use crate::symbolize::{module_for_address, module_for_frame, Module};
+use std::prelude::v1::*;
+use std::sync::Arc;
+
+/// The module which a frame of an unresolved backtrace was in when it was
+/// captured.
+///
+/// Backtraces captured with `Backtrace::new_unresolved` record this for each
+/// of their frames. If the module is unloaded before the backtrace is
+/// resolved, for example with `dlclose`, or replaced by another module loaded
+/// at the same address, resolving the frame's address would give whatever
+/// now happens to be there. Such frames are instead left without symbols,
+/// and reported by `BacktraceFrame::is_stale`.
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Debug)]
+pub struct ModuleSnapshot {
+    module: Arc<Module>,
+}
+
+impl ModuleSnapshot {
+    /// Returns the module as it was when the frame was captured.
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// Returns whether the same module is still loaded at the same address.
+    pub fn is_loaded(&self) -> bool {
+        module_for_address(self.module.base_address()).as_ref() == Some(&*self.module)
+    }
+
+    fn contains(&self, addr: usize) -> bool {
+        let base = self.module.base_address() as usize;
+        base <= addr && addr - base < self.module.size()
+    }
+}
+
+/// Records the module of each of `frames`.
+pub(super) fn record(frames: &mut [BacktraceFrame]) {
+    let _guard = crate::lock::lock();
+
+    // Stacks usually only pass through a few modules, so each is only looked
+    // up once and then shared by all of its frames.
+    let mut seen: Vec<ModuleSnapshot> = Vec::new();
+    for frame in frames {
+        let raw = match frame.frame {
+            Frame::Raw(ref f) => f,
+            Frame::Deserialized { .. } => continue,
+        };
+        // Like symbolication, this looks at the call instruction rather than
+        // the return address, which may be just past the end of the module.
+        let addr = raw.ip_for_symbolication() as usize;
+        frame.module = match seen.iter().find(|m| m.contains(addr)) {
+            Some(module) => Some(module.clone()),
+            None => module_for_frame(raw).map(|module| {
+                let module = ModuleSnapshot {
+                    module: Arc::new(module),
+                };
+                seen.push(module.clone());
+                module
+            }),
+        };
+    }
+}
+
+/// Whether modules recorded by `record` are still loaded, remembering the
+/// answer for each module.
+#[derive(Default)]
+pub(super) struct Loaded {
+    checked: Vec<(Arc<Module>, bool)>,
+}
+
+impl Loaded {
+    pub(super) fn check(&mut self, module: &ModuleSnapshot) -> bool {
+        if let Some((_, loaded)) = self
+            .checked
+            .iter()
+            .find(|(m, _)| Arc::ptr_eq(m, &module.module))
+        {
+            return *loaded;
+        }
+        let loaded = module.is_loaded();
+        self.checked.push((module.module.clone(), loaded));
+        loaded
+    }
+}
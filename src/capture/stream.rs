@@ -0,0 +1,131 @@
+//! Printing backtraces without allocating, for `Backtrace::write_to` and
+//! `Backtrace::write_to_fmt`.
+//!
+//! Unlike the `Display` implementation this never resolves frames, looks at
+//! the environment or the current directory, collapses recursion or
+//! highlights symbols, all of which allocate. Frames are printed one at a time as they're formatted, and
+//! output to an `io::Write` goes through a buffer on the stack so that it's
+//! written in a few large writes rather than many small ones.
+
+use super::{trimmed, Backtrace};
+use crate::{BacktraceFmt, BytesOrWideString, FrameOrder, PrintFmt};
+use core::fmt;
+use std::io;
+
+/// How much output is gathered before it's written to an `io::Write`.
+const BUF_SIZE: usize = 512;
+
+pub(super) fn write_fmt(out: &mut dyn fmt::Write, bt: &Backtrace, format: PrintFmt) -> fmt::Result {
+    // `BacktraceFmt` prints to a `Formatter`, which only formatting a value
+    // gets hold of.
+    struct Streamed<'a> {
+        bt: &'a Backtrace,
+        format: PrintFmt,
+    }
+
+    impl fmt::Display for Streamed<'_> {
+        fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+            print(fmt, self.bt, self.format)
+        }
+    }
+
+    write!(out, "{}", Streamed { bt, format })
+}
+
+pub(super) fn write_io(
+    out: &mut dyn io::Write,
+    bt: &Backtrace,
+    format: PrintFmt,
+) -> io::Result<()> {
+    let mut buf = StackBuf {
+        out,
+        buf: [0; BUF_SIZE],
+        len: 0,
+        error: None,
+    };
+    match write_fmt(&mut buf, bt, format) {
+        Ok(()) => buf.flush(),
+        // Formatting itself only fails if the output does, but an error
+        // without a message is made up otherwise, which unlike
+        // `io::Error::new` doesn't allocate.
+        Err(fmt::Error) => Err(buf
+            .error
+            .take()
+            .unwrap_or_else(|| io::ErrorKind::Other.into())),
+    }
+}
+
+fn print(fmt: &mut fmt::Formatter<'_>, bt: &Backtrace, format: PrintFmt) -> fmt::Result {
+    // Like the `Display` implementation, the full format includes the frames
+    // for capturing the backtrace.
+    let (frames, printed_last) = if format == PrintFmt::Full {
+        (&bt.frames[..], true)
+    } else {
+        let range = trimmed(bt.frames());
+        let printed_last = range.end == bt.frames().len();
+        (&bt.frames()[range], printed_last)
+    };
+    let omitted = if printed_last { bt.frames_dropped() } else { 0 };
+    let order = crate::print::frame_order();
+    if omitted > 0 && order == FrameOrder::OutermostFirst {
+        writeln!(fmt, "      ... {} frames omitted", omitted)?;
+    }
+
+    let mut print_path =
+        |fmt: &mut fmt::Formatter<'_>, path: BytesOrWideString<'_>| fmt::Display::fmt(&path, fmt);
+    let mut f = BacktraceFmt::new(fmt, format, &mut print_path);
+    f.add_context()?;
+    f.frame_order(order, frames.len());
+    if order == FrameOrder::OutermostFirst {
+        for frame in frames.iter().rev() {
+            f.frame().backtrace_frame(frame)?;
+        }
+    } else {
+        for frame in frames {
+            f.frame().backtrace_frame(frame)?;
+        }
+    }
+    f.finish()?;
+    if omitted > 0 && order == FrameOrder::InnermostFirst {
+        writeln!(fmt, "      ... {} frames omitted", omitted)?;
+    }
+    Ok(())
+}
+
+/// Gathers formatted output on the stack before writing it to `out`.
+struct StackBuf<'a> {
+    out: &'a mut dyn io::Write,
+    buf: [u8; BUF_SIZE],
+    len: usize,
+    // The error which made formatting fail, as `fmt::Error` can't carry it.
+    error: Option<io::Error>,
+}
+
+impl StackBuf<'_> {
+    fn flush(&mut self) -> io::Result<()> {
+        let len = self.len;
+        self.len = 0;
+        self.out.write_all(&self.buf[..len])
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.len + bytes.len() > BUF_SIZE {
+            self.flush()?;
+        }
+        if bytes.len() > BUF_SIZE {
+            return self.out.write_all(bytes);
+        }
+        self.buf[self.len..][..bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+}
+
+impl fmt::Write for StackBuf<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
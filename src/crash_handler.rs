@@ -0,0 +1,527 @@
+//! Reporting crashes from a signal handler.
+//!
+//! `CrashHandler` installs handlers for the signals raised when a process
+//! crashes, such as `SIGSEGV` and `SIGABRT`. When one arrives the stack of the
+//! crashing thread is walked from within the handler, a short report with the
+//! signal, the faulting address and the instruction pointers of the frames is
+//! formatted, and the report is written to a file descriptor or passed to a
+//! callback. The previous handler is then restored and the signal raised
+//! again, so the process still dies of it, dumping core or reaching whatever
+//! handled it before.
+//!
+//! Nothing in the handler allocates or takes a lock, as the crash may have
+//! happened while the thread held the allocator's. That rules out resolving
+//! symbols, so the report only lists addresses, which can be resolved later
+//! with `resolve` by a process loading the same binaries at the same
+//! addresses, or with tools such as `addr2line`. Walking the stack is only async-signal-safe
+//! with unwinders which are themselves, see `trace_raw_unsynchronized`.
+//!
+//! Handlers run on an alternate signal stack, so that a stack overflow can be
+//! reported too. The standard library installs one for the main thread and
+//! the threads it spawns, but it's only a few pages, which walking the stack
+//! by interpreting DWARF CFI can overflow, so `CrashHandler::install` sets up
+//! a larger one for the thread calling it if its own is smaller. Threads
+//! created in other ways, or whose stack walks need more room, can call
+//! `ensure_alt_stack`.
+//!
+//! # Caveats
+//!
+//! Crash handlers can currently only be installed on Linux and Android. On
+//! Windows, `exception_handler` reports crashes instead.
+//!
+//! With the `trace-frame-pointer` and `unwind-dwarf` features the stack is
+//! walked from the registers of the code which crashed, see
+//! `trace_from_context`. Otherwise it's walked from the handler, through the
+//! signal frame, which needs an unwinder that knows about signal trampolines.
+//!
+//! # Required features
+//!
+//! This module requires the `crash-handler` feature of the `backtrace` crate
+//! to be enabled, along with the `std` feature which is enabled by default.
+
+use core::fmt;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::prelude::v1::*;
+
+/// The maximum number of frames in a crash report.
+pub const MAX_FRAMES: usize = 128;
+
+/// A crash, as passed to the callback of `CrashHandler::callback`.
+///
+/// The report only lives as long as the callback runs, which is from within
+/// the signal handler, so the callback must be async-signal-safe as well.
+pub struct CrashReport<'a> {
+    signal: i32,
+    fault_address: Option<usize>,
+    ips: &'a [usize],
+    text: &'a [u8],
+}
+
+impl<'a> CrashReport<'a> {
+    /// Returns the number of the signal which was raised.
+    pub fn signal(&self) -> i32 {
+        self.signal
+    }
+
+    /// Returns the name of the signal which was raised, such as `SIGSEGV`, or
+    /// `None` for signals other than those handled by default.
+    pub fn signal_name(&self) -> Option<&'static str> {
+        signal_name(self.signal)
+    }
+
+    /// Returns the address whose access raised the signal, for signals
+    /// raised by a faulting instruction, such as `SIGSEGV` and `SIGBUS`.
+    pub fn fault_address(&self) -> Option<usize> {
+        self.fault_address
+    }
+
+    /// Returns the instruction pointers of the frames of the crashing thread,
+    /// innermost first, starting with the frame which was interrupted by the
+    /// signal.
+    ///
+    /// At most `MAX_FRAMES` frames are recorded.
+    pub fn ips(&self) -> &[usize] {
+        self.ips
+    }
+
+    /// Returns the report as text, as it's written to a file descriptor.
+    ///
+    /// The text is truncated if it doesn't fit in the buffer of the handler,
+    /// which holds 8 KiB.
+    pub fn text(&self) -> &[u8] {
+        self.text
+    }
+}
+
+impl fmt::Debug for CrashReport<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("CrashReport")
+            .field("signal", &self.signal)
+            .field("fault_address", &self.fault_address)
+            .field("ips", &self.ips)
+            .finish()
+    }
+}
+
+/// Where crash reports are sent.
+#[derive(Clone, Copy, Debug)]
+enum Output {
+    Fd(RawFd),
+    Callback(fn(&CrashReport<'_>)),
+}
+
+/// A builder for installing crash handlers, see the module documentation.
+///
+/// # Examples
+///
+/// ```no_run
+/// use backtrace::crash_handler::CrashHandler;
+///
+/// CrashHandler::new().install().unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct CrashHandler {
+    signals: Vec<i32>,
+    output: Output,
+    alt_stack_size: usize,
+}
+
+impl CrashHandler {
+    /// Returns a crash handler for `SIGSEGV`, `SIGBUS`, `SIGILL`, `SIGFPE` and
+    /// `SIGABRT`, which writes reports to standard error.
+    pub fn new() -> CrashHandler {
+        CrashHandler {
+            signals: vec![
+                libc::SIGSEGV,
+                libc::SIGBUS,
+                libc::SIGILL,
+                libc::SIGFPE,
+                libc::SIGABRT,
+            ],
+            output: Output::Fd(libc::STDERR_FILENO),
+            alt_stack_size: 64 * 1024,
+        }
+    }
+
+    /// Sets the signals to handle, replacing the default ones.
+    pub fn signals(mut self, signals: &[i32]) -> Self {
+        self.signals = signals.to_vec();
+        self
+    }
+
+    /// Writes reports to the file descriptor `fd`, which must stay open for
+    /// as long as the handler is installed.
+    pub fn fd(mut self, fd: RawFd) -> Self {
+        self.output = Output::Fd(fd);
+        self
+    }
+
+    /// Passes reports to `callback` instead of writing them out.
+    ///
+    /// `callback` is called from the signal handler, so it must only do what
+    /// is async-signal-safe, which excludes allocating, locking and most of
+    /// the standard library. Handing the report to an already open pipe or
+    /// socket with `libc::write` is fine.
+    pub fn callback(mut self, callback: fn(&CrashReport<'_>)) -> Self {
+        self.output = Output::Callback(callback);
+        self
+    }
+
+    /// Sets the size of the alternate signal stack set up by `install`, which
+    /// is 64 KiB by default.
+    pub fn alt_stack_size(mut self, size: usize) -> Self {
+        self.alt_stack_size = size;
+        self
+    }
+
+    /// Installs the handlers, and an alternate signal stack for the calling
+    /// thread if it has none or a smaller one, see `ensure_alt_stack`.
+    ///
+    /// Crash handlers can only be installed once per process, as the handlers
+    /// may run at any time afterwards, so the signals and output they use are
+    /// never changed. The handlers which were installed before this crate's
+    /// are remembered, and restored when a crash is reported.
+    ///
+    /// Returns an error if a signal can't be handled, such as `SIGKILL`, if
+    /// crash handlers have already been installed, or if they aren't
+    /// supported on this platform.
+    pub fn install(self) -> io::Result<()> {
+        imp::install(&self)
+    }
+}
+
+impl Default for CrashHandler {
+    fn default() -> CrashHandler {
+        CrashHandler::new()
+    }
+}
+
+/// Sets up an alternate signal stack of `size` bytes for the calling thread,
+/// unless it already has one at least that large.
+///
+/// Crash handlers run on the alternate stack of the thread which crashed, and
+/// without one a stack overflow can't be reported. A smaller stack, such as
+/// the one the standard library sets up, is replaced rather than freed, and
+/// the new stack is never freed either, so this is meant to be called once
+/// per thread.
+pub fn ensure_alt_stack(size: usize) -> io::Result<()> {
+    imp::ensure_alt_stack(size)
+}
+
+/// Returns the name of the signal `signal`, for those handled by default.
+fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGILL => "SIGILL",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGSYS => "SIGSYS",
+        _ => return None,
+    })
+}
+
+/// Formats the report of a crash into `buf`, truncating it if it doesn't fit,
+/// and returns the number of bytes written.
+fn format(buf: &mut [u8], signal: i32, fault_address: Option<usize>, ips: &[usize]) -> usize {
+    struct Writer<'a> {
+        buf: &'a mut [u8],
+        len: usize,
+    }
+
+    impl fmt::Write for Writer<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let n = s.len().min(self.buf.len() - self.len);
+            self.buf[self.len..][..n].copy_from_slice(&s.as_bytes()[..n]);
+            self.len += n;
+            if n < s.len() {
+                Err(fmt::Error)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    let mut w = Writer { buf, len: 0 };
+    let _ = write_report(&mut w, signal, fault_address, ips);
+    w.len
+}
+
+fn write_report(
+    w: &mut dyn fmt::Write,
+    signal: i32,
+    fault_address: Option<usize>,
+    ips: &[usize],
+) -> fmt::Result {
+    write!(w, "crashed with signal {}", signal)?;
+    if let Some(name) = signal_name(signal) {
+        write!(w, " ({})", name)?;
+    }
+    if let Some(addr) = fault_address {
+        write!(w, " at address {:#x}", addr)?;
+    }
+    writeln!(w, "\nstack backtrace (unresolved):")?;
+    for (i, ip) in ips.iter().enumerate() {
+        writeln!(w, "{:4}: {:#x}", i, ip)?;
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use super::{CrashHandler, CrashReport, Output, MAX_FRAMES};
+    use core::ffi::c_void;
+    use core::mem::{self, MaybeUninit};
+    use core::ptr;
+    use core::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use std::io;
+    use std::prelude::v1::*;
+
+    /// Signals numbered from 1 up to this can be handled.
+    const MAX_SIGNAL: usize = 32;
+
+    const BUF_LEN: usize = 8 * 1024;
+
+    // Written once by the only successful `install`, before any of the
+    // handlers are installed, and only read from the handlers afterwards.
+    static INSTALLED: AtomicBool = AtomicBool::new(false);
+    static mut PREVIOUS: MaybeUninit<[libc::sigaction; MAX_SIGNAL]> = MaybeUninit::zeroed();
+    static mut OUTPUT: Output = Output::Fd(libc::STDERR_FILENO);
+
+    // Whether a crash is being reported, so a second one, from the handler or
+    // another thread, goes straight to the previous handler.
+    static HANDLING: AtomicBool = AtomicBool::new(false);
+    static mut IPS: [usize; MAX_FRAMES] = [0; MAX_FRAMES];
+    static mut BUF: [u8; BUF_LEN] = [0; BUF_LEN];
+
+    pub fn install(config: &CrashHandler) -> io::Result<()> {
+        if let Some(&signal) = config.signals.iter().find(|&&s| {
+            s <= 0 || s as usize >= MAX_SIGNAL || s == libc::SIGKILL || s == libc::SIGSTOP
+        }) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("signal {} can't be handled", signal),
+            ));
+        }
+        ensure_alt_stack(config.alt_stack_size)?;
+
+        if INSTALLED
+            .compare_exchange(false, true, SeqCst, SeqCst)
+            .is_err()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "crash handlers have already been installed",
+            ));
+        }
+        unsafe {
+            // Everything the handlers read is written before the first of
+            // them is installed.
+            *ptr::addr_of_mut!(OUTPUT) = config.output;
+            let previous = ptr::addr_of_mut!(PREVIOUS) as *mut libc::sigaction;
+            for &signal in config.signals.iter() {
+                if libc::sigaction(signal, ptr::null(), previous.add(signal as usize)) != 0 {
+                    INSTALLED.store(false, SeqCst);
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            for (i, &signal) in config.signals.iter().enumerate() {
+                let mut action: libc::sigaction = mem::zeroed();
+                action.sa_sigaction = handler as *const () as usize;
+                action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+                libc::sigemptyset(&mut action.sa_mask);
+                if libc::sigaction(signal, &action, ptr::null_mut()) != 0 {
+                    let err = io::Error::last_os_error();
+                    // Once no handler is left installed the state can be
+                    // written again.
+                    for &signal in config.signals[..i].iter() {
+                        libc::sigaction(signal, previous.add(signal as usize), ptr::null_mut());
+                    }
+                    INSTALLED.store(false, SeqCst);
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn ensure_alt_stack(size: usize) -> io::Result<()> {
+        unsafe {
+            let mut current: libc::stack_t = mem::zeroed();
+            if libc::sigaltstack(ptr::null(), &mut current) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let size = size.max(libc::SIGSTKSZ);
+            if current.ss_flags & libc::SS_DISABLE == 0 && current.ss_size >= size {
+                return Ok(());
+            }
+            let stack = libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if stack == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            let new = libc::stack_t {
+                ss_sp: stack,
+                ss_flags: 0,
+                ss_size: size,
+            };
+            if libc::sigaltstack(&new, ptr::null_mut()) != 0 {
+                let err = io::Error::last_os_error();
+                libc::munmap(stack, size);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    unsafe extern "C" fn handler(
+        signal: libc::c_int,
+        info: *mut libc::siginfo_t,
+        context: *mut c_void,
+    ) {
+        if HANDLING
+            .compare_exchange(false, true, SeqCst, SeqCst)
+            .is_ok()
+        {
+            report(signal, info, context);
+        }
+
+        // Hand the signal over to the previous handler. Signals raised by a
+        // faulting instruction are raised again when it's retried once this
+        // handler returns.
+        let previous = &*(ptr::addr_of!(PREVIOUS) as *const libc::sigaction).add(signal as usize);
+        libc::sigaction(signal, previous, ptr::null_mut());
+        if (*info).si_code > 0 {
+            return;
+        }
+
+        // Signals which were sent aren't, so the previous handler is called
+        // directly. Handlers such as the standard library's `SIGSEGV` one
+        // expect to be called again for a fault they let through, which
+        // won't happen either, so the signal is then raised once more with
+        // its default disposition, which is delivered as soon as this
+        // handler returns.
+        match previous.sa_sigaction {
+            libc::SIG_IGN => return,
+            libc::SIG_DFL => {}
+            f if previous.sa_flags & libc::SA_SIGINFO != 0 => {
+                let f: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut c_void) =
+                    mem::transmute(f);
+                f(signal, info, context);
+            }
+            f => {
+                let f: extern "C" fn(libc::c_int) = mem::transmute(f);
+                f(signal);
+            }
+        }
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+
+    #[inline(never)]
+    unsafe fn report(signal: libc::c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+        let errno = *errno_location();
+        let fault_address = match signal {
+            libc::SIGSEGV | libc::SIGBUS | libc::SIGILL | libc::SIGFPE => {
+                Some((*info).si_addr() as usize)
+            }
+            _ => None,
+        };
+
+        // Where the unwinder can start from the registers of the faulting
+        // code the walk starts with the frame the signal interrupted.
+        let ips = &mut *ptr::addr_of_mut!(IPS);
+        let mut count = 0;
+        crate::trace_from_context_unsynchronized(context, |frame| {
+            ips[count] = frame.ip() as usize;
+            count += 1;
+            count < MAX_FRAMES
+        });
+
+        // Otherwise the handler's own stack is walked, skipping the frames
+        // of the unwinder, of this handler and of the signal trampoline like
+        // `trace_thread` does: from the frame interrupted by the signal where
+        // the unwinder can tell signal frames, or else from the first frame
+        // above this function's locals.
+        if count == 0 {
+            let marker = 0u8;
+            let marker = &marker as *const u8 as usize;
+            let mut started = false;
+            crate::trace_unsynchronized(|frame| {
+                if !started {
+                    started = frame.is_signal_frame() || frame.sp() as usize > marker;
+                    if !started {
+                        return true;
+                    }
+                }
+                ips[count] = frame.ip() as usize;
+                count += 1;
+                count < MAX_FRAMES
+            });
+        }
+        let ips = &ips[..count];
+
+        let buf = &mut *ptr::addr_of_mut!(BUF);
+        let len = super::format(buf, signal, fault_address, ips);
+        let report = CrashReport {
+            signal,
+            fault_address,
+            ips,
+            text: &buf[..len],
+        };
+        match *ptr::addr_of!(OUTPUT) {
+            Output::Fd(fd) => {
+                let mut text = report.text;
+                while !text.is_empty() {
+                    let n = libc::write(fd, text.as_ptr() as *const c_void, text.len());
+                    if n <= 0 {
+                        break;
+                    }
+                    text = &text[n as usize..];
+                }
+            }
+            Output::Callback(callback) => callback(&report),
+        }
+        *errno_location() = errno;
+    }
+
+    #[cfg(target_os = "android")]
+    unsafe fn errno_location() -> *mut libc::c_int {
+        libc::__errno()
+    }
+
+    #[cfg(not(target_os = "android"))]
+    unsafe fn errno_location() -> *mut libc::c_int {
+        libc::__errno_location()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+mod imp {
+    use super::CrashHandler;
+    use std::io;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Other,
+            "crash handlers aren't supported on this platform",
+        )
+    }
+
+    pub fn install(_config: &CrashHandler) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn ensure_alt_stack(_size: usize) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
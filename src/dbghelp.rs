@@ -15,11 +15,20 @@
 //! `verify-winapi` which asserts that all bindings match those in winapi and
 //! this feature is enabled on CI.
 //!
-//! Finally, you'll note here that the dll for `dbghelp.dll` is never unloaded,
-//! and that's currently intentional. The thinking is that we can globally cache
-//! it and use it between calls to the API, avoiding expensive loads/unloads. If
-//! this is a problem for leak detectors or something like that we can cross the
-//! bridge when we get there.
+//! Nothing here happens until it's first needed: `dbghelp.dll` is loaded the
+//! first time a backtrace is captured, and the symbol handler is only
+//! initialized with `SymInitializeW`, which loads the symbols of every module
+//! in the process, the first time an address is symbolicated (or on 32-bit
+//! x86 where unwinding needs it). Once loaded the dll is cached globally and
+//! used between calls to the API, avoiding expensive loads/unloads, until
+//! `cleanup` is called.
+//!
+//! Other code in the process which also calls into `dbghelp.dll` (for example
+//! a minidump writer) can use `init` to cooperate with this crate. The
+//! returned `Init` guard holds the same locks that this crate takes around
+//! its own use of dbghelp, so while it's alive no backtrace will be captured
+//! or symbolicated concurrently, whether through this crate or through the
+//! standard library.
 
 #![allow(non_snake_case)]
 
@@ -40,6 +49,7 @@ mod dbghelp {
 
     extern "system" {
         // Not defined in winapi yet
+        pub fn SymSetSearchPathW(hProcess: HANDLE, SearchPath: PCWSTR) -> BOOL;
         pub fn SymFromInlineContextW(
             hProcess: HANDLE,
             Address: DWORD64,
@@ -68,7 +78,7 @@ macro_rules! dbghelp {
     (extern "system" {
         $(fn $name:ident($($arg:ident: $argty:ty),*) -> $ret: ty;)*
     }) => (
-        pub struct Dbghelp {
+        pub(crate) struct Dbghelp {
             /// The loaded DLL for `dbghelp.dll`
             dll: HMODULE,
 
@@ -85,7 +95,7 @@ macro_rules! dbghelp {
         };
 
         // Convenience typedef for each function type.
-        $(pub type $name = unsafe extern "system" fn($($argty),*) -> $ret;)*
+        $(pub(crate) type $name = unsafe extern "system" fn($($argty),*) -> $ret;)*
 
         impl Dbghelp {
             /// Attempts to open `dbghelp.dll`. Returns success if it works or
@@ -110,7 +120,7 @@ macro_rules! dbghelp {
             // Function for each method we'd like to use. When called it will
             // either read the cached function pointer or load it and return the
             // loaded value. Loads are asserted to succeed.
-            $(pub fn $name(&mut self) -> Option<$name> {
+            $(pub(crate) fn $name(&mut self) -> Option<$name> {
                 unsafe {
                     if self.$name == 0 {
                         let name = concat!(stringify!($name), "\0");
@@ -123,6 +133,20 @@ macro_rules! dbghelp {
                 }
             })*
 
+            /// Unloads `dbghelp.dll`, forgetting all loaded function
+            /// pointers so they're loaded again if it's reopened.
+            fn close(&mut self) {
+                if !self.dll.is_null() {
+                    unsafe {
+                        FreeLibrary(self.dll);
+                    }
+                }
+                *self = Dbghelp {
+                    dll: 0 as *mut _,
+                    $($name: 0,)*
+                };
+            }
+
             fn symbol(&self, symbol: &[u8]) -> Option<usize> {
                 unsafe {
                     match GetProcAddress(self.dll, symbol.as_ptr() as *const _) as usize {
@@ -137,13 +161,13 @@ macro_rules! dbghelp {
         // functions.
         #[allow(dead_code)]
         impl Init {
-            $(pub fn $name(&self) -> $name {
+            $(pub(crate) fn $name(&self) -> $name {
                 unsafe {
                     DBGHELP.$name().unwrap()
                 }
             })*
 
-            pub fn dbghelp(&self) -> *mut Dbghelp {
+            pub(crate) fn dbghelp(&self) -> *mut Dbghelp {
                 unsafe {
                     &mut DBGHELP
                 }
@@ -165,6 +189,7 @@ dbghelp! {
             invade: BOOL
         ) -> BOOL;
         fn SymCleanup(handle: HANDLE) -> BOOL;
+        fn SymSetSearchPathW(handle: HANDLE, path: PCWSTR) -> BOOL;
         fn StackWalk64(
             MachineType: DWORD,
             hProcess: HANDLE,
@@ -226,8 +251,16 @@ dbghelp! {
     }
 }
 
+/// A guard providing exclusive access to `dbghelp.dll` within this process.
+///
+/// `dbghelp.dll` is not thread-safe, so all of its functions must only be
+/// called while this guard is alive. Dropping the guard releases access.
 pub struct Init {
     lock: HANDLE,
+    // Taken before the named mutex, and released after it, to match the order
+    // in which this crate's own entry points acquire the two locks.
+    #[cfg(feature = "std-base")]
+    _lock: crate::lock::LockGuard,
 }
 
 /// Initialize all support necessary to access `dbghelp` API functions from this
@@ -236,9 +269,165 @@ pub struct Init {
 /// Note that this function is **safe**, it internally has its own
 /// synchronization. Also note that it is safe to call this function multiple
 /// times recursively.
+///
+/// Once this returns `dbghelp.dll` has been loaded and `SymInitializeW` has
+/// been called for the current process, so code outside of this crate
+/// shouldn't call `SymInitializeW` or `SymCleanup` itself, but rather use
+/// `cleanup` to tear it down. An error is returned if `dbghelp.dll` couldn't
+/// be loaded.
 pub fn init() -> Result<Init, ()> {
+    let ret = load()?;
+
+    unsafe {
+        // Once we've opened `dbghelp.dll` we need to call some initialization
+        // functions in it, and that's detailed more below. We only do this
+        // once, though, so we've got a global boolean indicating whether we're
+        // done yet or not.
+        if INITIALIZED {
+            return Ok(ret);
+        }
+
+        let orig = DBGHELP.SymGetOptions().unwrap()();
+
+        // Ensure that the `SYMOPT_DEFERRED_LOADS` flag is set, because
+        // according to MSVC's own docs about this: "This is the fastest, most
+        // efficient way to use the symbol handler.", so let's do that!
+        DBGHELP.SymSetOptions().unwrap()(orig | SYMOPT_DEFERRED_LOADS);
+
+        // Actually initialize symbols with MSVC. Note that this can fail, but we
+        // ignore it. There's not a ton of prior art for this per se, but LLVM
+        // internally seems to ignore the return value here and one of the
+        // sanitizer libraries in LLVM prints a scary warning if this fails but
+        // basically ignores it in the long run.
+        //
+        // One case this comes up a lot for Rust is that the standard library and
+        // this crate on crates.io both want to compete for `SymInitializeW`. The
+        // standard library historically wanted to initialize then cleanup most of
+        // the time, but now that it's using this crate it means that someone will
+        // get to initialization first and the other will pick up that
+        // initialization.
+        //
+        // Without a search path dbghelp uses its default one, which directories
+        // set with `set_search_paths` are prepended to.
+        #[cfg(all(feature = "std-base", feature = "symbolize"))]
+        let path = search_path();
+        #[cfg(all(feature = "std-base", feature = "symbolize"))]
+        let path_ptr = path.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+        #[cfg(not(all(feature = "std-base", feature = "symbolize")))]
+        let path_ptr = ptr::null();
+        DBGHELP.SymInitializeW().unwrap()(GetCurrentProcess(), path_ptr, TRUE);
+        INITIALIZED = true;
+        Ok(ret)
+    }
+}
+
+/// Returns the symbol search path made of the directories set with
+/// `set_search_paths` followed by dbghelp's default search path, as a
+/// nul-terminated string, or `None` if no directories are set.
+#[cfg(all(feature = "std-base", feature = "symbolize"))]
+fn search_path() -> Option<std::vec::Vec<u16>> {
+    let dirs = crate::symbolize::search_paths::search_paths();
+    if dirs.is_empty() {
+        None
+    } else {
+        Some(search_path_with(&dirs))
+    }
+}
+
+#[cfg(all(feature = "std-base", feature = "symbolize"))]
+fn search_path_with(dirs: &[std::path::PathBuf]) -> std::vec::Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+
+    let mut path = std::vec::Vec::new();
+    for dir in dirs {
+        path.extend(dir.as_os_str().encode_wide());
+        path.push(u16::from(b';'));
+    }
+    // dbghelp's default search path, which passing one replaces, is made of
+    // these variables and the current directory.
+    for var in ["_NT_SYMBOL_PATH", "_NT_ALTERNATE_SYMBOL_PATH"].iter() {
+        if let Some(value) = std::env::var_os(var) {
+            path.extend(value.encode_wide());
+            path.push(u16::from(b';'));
+        }
+    }
+    path.push(u16::from(b'.'));
+    path.push(0);
+    path
+}
+
+/// Updates the search path of the symbol handler after `set_search_paths`
+/// was called, if it has already been initialized.
+#[cfg(all(feature = "std-base", feature = "symbolize"))]
+pub(crate) fn update_search_path() {
+    let _lock = match lock() {
+        Ok(lock) => lock,
+        Err(()) => return,
+    };
+    unsafe {
+        if INITIALIZED {
+            let path = search_path_with(&crate::symbolize::search_paths::search_paths());
+            DBGHELP.SymSetSearchPathW().unwrap()(GetCurrentProcess(), path.as_ptr());
+        }
+    }
+}
+
+/// Whether `SymInitializeW` has been called for this process by this crate,
+/// without a matching `SymCleanup` since.
+static mut INITIALIZED: bool = false;
+
+/// Tears down what `init` set up, calling `SymCleanup` for the current process
+/// and unloading `dbghelp.dll`.
+///
+/// This releases the memory used by the symbols `dbghelp.dll` has loaded, and
+/// the dll itself, for processes which only occasionally need backtraces.
+/// Nothing else needs to be done before using this crate again afterwards, as
+/// everything is set up again the next time it's needed.
+///
+/// Note that if the standard library has symbolicated a backtrace in this
+/// process (for example to print a panic), it won't know that the symbol
+/// handler has been cleaned up and will be unable to symbolicate until this
+/// crate initializes it again. This must also not be called while an `Init`
+/// returned by `init` is alive on the same thread.
+pub fn cleanup() {
+    let _lock = match lock() {
+        Ok(lock) => lock,
+        Err(()) => return,
+    };
+    unsafe {
+        if INITIALIZED {
+            DBGHELP.SymCleanup().unwrap()(GetCurrentProcess());
+            INITIALIZED = false;
+        }
+        DBGHELP.close();
+    }
+}
+
+/// Loads `dbghelp.dll` if it isn't already, without initializing its symbol
+/// handler, which is enough to walk the stack with functions other than
+/// those from `dbghelp.dll` to find function tables and module bases.
+pub(crate) fn load() -> Result<Init, ()> {
+    let ret = lock()?;
+
+    // Ok, phew! Now that we're all safely synchronized, let's actually start
+    // processing everything. First up we need to ensure that `dbghelp.dll` is
+    // actually loaded in this process. We do this dynamically to avoid a
+    // static dependency. This has historically been done to work around weird
+    // linking issues and is intended at making binaries a bit more portable
+    // since this is largely just a debugging utility.
+    unsafe {
+        DBGHELP.ensure_open()?;
+    }
+    Ok(ret)
+}
+
+/// Acquires the locks guarding all use of `dbghelp.dll`, without loading it.
+fn lock() -> Result<Init, ()> {
     use core::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 
+    #[cfg(feature = "std-base")]
+    let rust_lock = crate::lock::lock();
+
     unsafe {
         // First thing we need to do is to synchronize this function. This can
         // be called concurrently from other threads or recursively within one
@@ -295,49 +484,11 @@ pub fn init() -> Result<Init, ()> {
         let lock = lock as HANDLE;
         let r = WaitForSingleObjectEx(lock, INFINITE, FALSE);
         debug_assert_eq!(r, 0);
-        let ret = Init { lock };
-
-        // Ok, phew! Now that we're all safely synchronized, let's actually
-        // start processing everything. First up we need to ensure that
-        // `dbghelp.dll` is actually loaded in this process. We do this
-        // dynamically to avoid a static dependency. This has historically been
-        // done to work around weird linking issues and is intended at making
-        // binaries a bit more portable since this is largely just a debugging
-        // utility.
-        //
-        // Once we've opened `dbghelp.dll` we need to call some initialization
-        // functions in it, and that's detailed more below. We only do this
-        // once, though, so we've got a global boolean indicating whether we're
-        // done yet or not.
-        DBGHELP.ensure_open()?;
-
-        static mut INITIALIZED: bool = false;
-        if INITIALIZED {
-            return Ok(ret);
-        }
-
-        let orig = DBGHELP.SymGetOptions().unwrap()();
-
-        // Ensure that the `SYMOPT_DEFERRED_LOADS` flag is set, because
-        // according to MSVC's own docs about this: "This is the fastest, most
-        // efficient way to use the symbol handler.", so let's do that!
-        DBGHELP.SymSetOptions().unwrap()(orig | SYMOPT_DEFERRED_LOADS);
-
-        // Actually initialize symbols with MSVC. Note that this can fail, but we
-        // ignore it. There's not a ton of prior art for this per se, but LLVM
-        // internally seems to ignore the return value here and one of the
-        // sanitizer libraries in LLVM prints a scary warning if this fails but
-        // basically ignores it in the long run.
-        //
-        // One case this comes up a lot for Rust is that the standard library and
-        // this crate on crates.io both want to compete for `SymInitializeW`. The
-        // standard library historically wanted to initialize then cleanup most of
-        // the time, but now that it's using this crate it means that someone will
-        // get to initialization first and the other will pick up that
-        // initialization.
-        DBGHELP.SymInitializeW().unwrap()(GetCurrentProcess(), ptr::null_mut(), TRUE);
-        INITIALIZED = true;
-        Ok(ret)
+        Ok(Init {
+            lock,
+            #[cfg(feature = "std-base")]
+            _lock: rust_lock,
+        })
     }
 }
 
@@ -0,0 +1,169 @@
+//! Fetching debug information of stripped libraries from debuginfod servers.
+//!
+//! Production binaries are commonly stripped, with their debug information
+//! published on a [debuginfod] server instead. When this module is enabled,
+//! libraries without DWARF debug information whose separate debug file can't
+//! be found locally are looked up by their build id on the servers listed in
+//! the `DEBUGINFOD_URLS` environment variable, the same servers as gdb and
+//! the elfutils tools use. The environment variable is a space-separated
+//! list of URLs which are tried in order.
+//!
+//! Downloaded files are kept in the same cache as the elfutils client, at
+//! `$DEBUGINFOD_CACHE_PATH`, `$XDG_CACHE_HOME/debuginfod_client` or
+//! `$HOME/.cache/debuginfod_client`, so each file is only downloaded once
+//! and files downloaded by other tools are used as well.
+//!
+//! Only plain `http` URLs can be fetched by this crate itself. Other URLs,
+//! such as `https` ones, are skipped unless a function fetching them is
+//! registered with `set_fetcher`, which can use any HTTP client.
+//!
+//! This only affects platforms using ELF. On Windows, dbghelp already
+//! downloads PDBs from the symbol servers configured in `_NT_SYMBOL_PATH`,
+//! such as `srv*C:\symbols*https://msdl.microsoft.com/download/symbols`.
+//!
+//! Looking up symbols of a stripped library can block on the network the
+//! first time it's resolved, so this is best suited for tools and crash
+//! reporters rather than code which is sensitive to latency.
+//!
+//! [debuginfod]: https://sourceware.org/elfutils/Debuginfod.html
+//!
+//! # Required features
+//!
+//! This module requires the `debuginfod` feature of the `backtrace` crate to
+//! be enabled, along with the `std` feature which is enabled by default.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::prelude::v1::*;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// The function registered through `set_fetcher`, or 0 if there is none.
+static FETCHER: AtomicUsize = AtomicUsize::new(0);
+
+/// How long connecting to a server and each read and write may take.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Registers the function used to download files from debuginfod servers.
+///
+/// `fetch` is called with the full URL of a file, and returns its contents
+/// if the server had it. This replaces the built-in client, which only
+/// supports plain `http` URLs, so that servers which need `https`, a proxy
+/// or authentication can be used.
+///
+/// There is only one fetcher per process, and each call replaces the one
+/// previously registered.
+///
+/// # Required features
+///
+/// This function requires the `debuginfod` feature of the `backtrace` crate
+/// to be enabled, along with the `std` feature which is enabled by default.
+pub fn set_fetcher(fetch: fn(&str) -> Option<Vec<u8>>) {
+    FETCHER.store(fetch as usize, Ordering::Release);
+}
+
+/// Returns the path of the debug file for `build_id`, downloading it into the
+/// cache first if it isn't there yet.
+pub(crate) fn locate(build_id: &[u8]) -> Option<PathBuf> {
+    if build_id.is_empty() {
+        return None;
+    }
+    let hex = build_id
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    let mut dir = cache_path()?;
+    dir.push(&hex);
+    let path = dir.join("debuginfo");
+    if path.is_file() {
+        return Some(path);
+    }
+
+    let urls = env::var("DEBUGINFOD_URLS").ok()?;
+    let contents = urls.split_whitespace().find_map(|url| {
+        fetch(&format!(
+            "{}/buildid/{}/debuginfo",
+            url.trim_end_matches('/'),
+            hex
+        ))
+    })?;
+
+    // Write to a file of our own first so that other processes never see a
+    // partially written file.
+    fs::create_dir_all(&dir).ok()?;
+    let tmp = dir.join(format!("debuginfo.{}.tmp", process::id()));
+    fs::write(&tmp, contents).ok()?;
+    if fs::rename(&tmp, &path).is_err() {
+        let _ = fs::remove_file(&tmp);
+        return None;
+    }
+    Some(path)
+}
+
+fn cache_path() -> Option<PathBuf> {
+    let var = |name| env::var_os(name).filter(|value: &OsString| !value.is_empty());
+    if let Some(path) = var("DEBUGINFOD_CACHE_PATH") {
+        return Some(path.into());
+    }
+    let mut path = match var("XDG_CACHE_HOME") {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(var("HOME")?).join(".cache"),
+    };
+    path.push("debuginfod_client");
+    Some(path)
+}
+
+fn fetch(url: &str) -> Option<Vec<u8>> {
+    let fetcher = FETCHER.load(Ordering::Acquire);
+    if fetcher == 0 {
+        return http_get(url);
+    }
+    // SAFETY: `FETCHER` is only ever set from a function pointer of this
+    // type, in `set_fetcher`.
+    let fetcher: fn(&str) -> Option<Vec<u8>> = unsafe { core::mem::transmute(fetcher) };
+    fetcher(url)
+}
+
+/// A minimal HTTP/1.0 client, which is all that's needed to download a file
+/// from a server which doesn't redirect.
+fn http_get(url: &str) -> Option<Vec<u8>> {
+    let rest = url.strip_prefix("http://")?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    // The port is optional, but IPv6 addresses in brackets contain colons.
+    let host_end = authority.rfind(']').unwrap_or(0);
+    let addr = if authority[host_end..].contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+
+    let mut stream = addr
+        .to_socket_addrs()
+        .ok()?
+        .find_map(|addr| TcpStream::connect_timeout(&addr, TIMEOUT).ok())?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(TIMEOUT)).ok()?;
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: backtrace-rs\r\n\r\n",
+        path, authority
+    )
+    .ok()?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).ok()?;
+    let end = response.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let status_line = response[..end].split(|&b| b == b'\r').next()?;
+    if status_line.split(|&b| b == b' ').nth(1)? != b"200" {
+        return None;
+    }
+    Some(response.split_off(end + 4))
+}
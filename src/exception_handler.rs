@@ -0,0 +1,264 @@
+//! Reporting crashes from a Windows exception handler.
+//!
+//! This is the Windows counterpart of `crash_handler`. `ExceptionHandler`
+//! installs an unhandled exception filter, which is called when an exception
+//! such as an access violation isn't handled by any of the frames on the
+//! stack, just before the process is terminated. The filter walks the stack of
+//! the faulting thread from the `CONTEXT` of the exception, resolves its
+//! symbols, and passes the resulting `Backtrace` to a callback. The filter
+//! which was installed before is then called, so error reporting and
+//! debuggers still see the exception.
+//!
+//! Unlike signal handlers, exception filters may allocate and take locks, so
+//! the backtrace can be resolved from within the filter. The crashing thread
+//! may still hold a lock the callback needs, though, such as that of the
+//! allocator when the heap was corrupted, in which case the callback blocks
+//! and the process hangs rather than terminate.
+//!
+//! # Caveats
+//!
+//! Crashes which terminate the process without raising an exception, such as
+//! `std::process::abort` or a panic with `panic = "abort"`, which use
+//! `__fastfail`, aren't reported.
+//!
+//! The filter runs on the stack of the thread which crashed, so a stack
+//! overflow may not leave it enough stack to report. Calling
+//! `SetThreadStackGuarantee` on threads which may overflow reserves some.
+//!
+//! # Required features
+//!
+//! This module requires the `crash-handler` feature of the `backtrace` crate
+//! to be enabled, along with the `std` feature which is enabled by default.
+
+use crate::windows::*;
+use crate::Backtrace;
+use core::ffi::c_void;
+use core::fmt;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering::SeqCst};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::prelude::v1::*;
+
+const EXCEPTION_CONTINUE_SEARCH: LONG = 0;
+
+const EXCEPTION_ACCESS_VIOLATION: u32 = 0xc000_0005;
+const EXCEPTION_IN_PAGE_ERROR: u32 = 0xc000_0006;
+
+type Callback = Box<dyn Fn(&ExceptionReport<'_>) + Send + Sync>;
+
+// The callback of the last installation, which is leaked once replaced as a
+// filter may still be running it.
+static CALLBACK: AtomicPtr<Callback> = AtomicPtr::new(ptr::null_mut());
+// The filter which was installed before this module's, as a function pointer.
+static PREVIOUS: AtomicUsize = AtomicUsize::new(0);
+static FILTER_INSTALLED: AtomicBool = AtomicBool::new(false);
+static VECTORED_INSTALLED: AtomicBool = AtomicBool::new(false);
+// Whether an exception was reported, so that the vectored handler and the
+// filter don't both report the same one.
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// An exception, as passed to the callback of `ExceptionHandler::install`.
+pub struct ExceptionReport<'a> {
+    record: &'a EXCEPTION_RECORD,
+    backtrace: &'a Backtrace,
+}
+
+impl<'a> ExceptionReport<'a> {
+    /// Returns the code of the exception, such as `0xC0000005` for an access
+    /// violation.
+    pub fn code(&self) -> u32 {
+        self.record.ExceptionCode
+    }
+
+    /// Returns the name of the exception's code, such as
+    /// `EXCEPTION_ACCESS_VIOLATION`, or `None` if it's not one raised by the
+    /// processor or the system.
+    pub fn code_name(&self) -> Option<&'static str> {
+        code_name(self.code())
+    }
+
+    /// Returns the address of the instruction which raised the exception.
+    pub fn address(&self) -> usize {
+        self.record.ExceptionAddress as usize
+    }
+
+    /// Returns the address whose access raised the exception, for access
+    /// violations and in-page errors.
+    pub fn fault_address(&self) -> Option<usize> {
+        match self.code() {
+            EXCEPTION_ACCESS_VIOLATION | EXCEPTION_IN_PAGE_ERROR
+                if self.record.NumberParameters >= 2 =>
+            {
+                Some(self.record.ExceptionInformation[1])
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the resolved backtrace of the thread which raised the
+    /// exception, starting with the frame which raised it.
+    pub fn backtrace(&self) -> &Backtrace {
+        self.backtrace
+    }
+}
+
+impl fmt::Debug for ExceptionReport<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ExceptionReport")
+            .field("code", &format_args!("{:#x}", self.code()))
+            .field("address", &format_args!("{:#x}", self.address()))
+            .field("backtrace", &self.backtrace)
+            .finish()
+    }
+}
+
+/// Prints the exception like `crash_handler` prints signals, followed by the
+/// backtrace.
+impl fmt::Display for ExceptionReport<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "crashed with exception {:#x}", self.code())?;
+        if let Some(name) = self.code_name() {
+            write!(fmt, " ({})", name)?;
+        }
+        write!(fmt, " at {:#x}", self.address())?;
+        if let Some(addr) = self.fault_address() {
+            write!(fmt, " accessing address {:#x}", addr)?;
+        }
+        writeln!(fmt, "\nstack backtrace:")?;
+        fmt::Display::fmt(self.backtrace, fmt)
+    }
+}
+
+/// A builder for installing exception handlers, see the module documentation.
+///
+/// # Examples
+///
+/// ```no_run
+/// use backtrace::exception_handler::ExceptionHandler;
+///
+/// ExceptionHandler::new().install(|report| eprint!("{}", report)).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExceptionHandler {
+    vectored: bool,
+}
+
+impl ExceptionHandler {
+    /// Returns an exception handler which only installs an unhandled
+    /// exception filter.
+    pub fn new() -> ExceptionHandler {
+        ExceptionHandler { vectored: false }
+    }
+
+    /// Sets whether a vectored exception handler is installed as well.
+    ///
+    /// Vectored handlers see exceptions before any frame on the stack does,
+    /// so they still report crashes when another library replaces the
+    /// unhandled exception filter, as some runtimes do. They only report
+    /// exceptions raised by the processor which usually mean the process is
+    /// about to crash, such as access violations, but those may still be
+    /// handled by code further up the stack with `__try`, in which case the
+    /// report is spurious. Only the first exception is reported either way.
+    pub fn vectored(mut self, vectored: bool) -> Self {
+        self.vectored = vectored;
+        self
+    }
+
+    /// Installs the handlers, which pass a report of the exception which
+    /// crashed the process to `callback`.
+    ///
+    /// Installing again replaces the callback of the previous installation.
+    /// If `callback` panics, the panic is caught and the exception reaches
+    /// the previous filter as if it hadn't.
+    pub fn install<F>(self, callback: F) -> io::Result<()>
+    where
+        F: Fn(&ExceptionReport<'_>) + Send + Sync + 'static,
+    {
+        let callback: Box<Callback> = Box::new(Box::new(callback));
+        CALLBACK.store(Box::into_raw(callback), SeqCst);
+
+        let _guard = crate::lock::lock();
+        unsafe {
+            if self.vectored && !VECTORED_INSTALLED.load(SeqCst) {
+                if AddVectoredExceptionHandler(1, Some(vectored_handler)).is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                VECTORED_INSTALLED.store(true, SeqCst);
+            }
+            if !FILTER_INSTALLED.swap(true, SeqCst) {
+                let previous = SetUnhandledExceptionFilter(Some(filter));
+                PREVIOUS.store(previous.map_or(0, |f| f as usize), SeqCst);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ExceptionHandler {
+    fn default() -> ExceptionHandler {
+        ExceptionHandler::new()
+    }
+}
+
+/// Returns the name of the exception code `code`, for those raised by the
+/// processor or the system.
+fn code_name(code: u32) -> Option<&'static str> {
+    Some(match code {
+        EXCEPTION_ACCESS_VIOLATION => "EXCEPTION_ACCESS_VIOLATION",
+        EXCEPTION_IN_PAGE_ERROR => "EXCEPTION_IN_PAGE_ERROR",
+        0x8000_0002 => "EXCEPTION_DATATYPE_MISALIGNMENT",
+        0x8000_0003 => "EXCEPTION_BREAKPOINT",
+        0xc000_001d => "EXCEPTION_ILLEGAL_INSTRUCTION",
+        0xc000_008c => "EXCEPTION_ARRAY_BOUNDS_EXCEEDED",
+        0xc000_008e => "EXCEPTION_FLT_DIVIDE_BY_ZERO",
+        0xc000_0094 => "EXCEPTION_INT_DIVIDE_BY_ZERO",
+        0xc000_0096 => "EXCEPTION_PRIV_INSTRUCTION",
+        0xc000_00fd => "EXCEPTION_STACK_OVERFLOW",
+        0xc000_0374 => "STATUS_HEAP_CORRUPTION",
+        _ => return None,
+    })
+}
+
+/// Returns whether exceptions with `code` are faults which usually crash the
+/// process, and so are reported by the vectored handler.
+fn is_fatal(code: u32) -> bool {
+    code_name(code).is_some() && code != 0x8000_0003
+}
+
+unsafe extern "system" fn vectored_handler(info: *mut EXCEPTION_POINTERS) -> LONG {
+    if is_fatal((*(*info).ExceptionRecord).ExceptionCode) {
+        report(info);
+    }
+    EXCEPTION_CONTINUE_SEARCH
+}
+
+unsafe extern "system" fn filter(info: *mut EXCEPTION_POINTERS) -> LONG {
+    report(info);
+    match PREVIOUS.load(SeqCst) {
+        0 => EXCEPTION_CONTINUE_SEARCH,
+        previous => {
+            let previous: unsafe extern "system" fn(*mut EXCEPTION_POINTERS) -> LONG =
+                mem::transmute(previous);
+            previous(info)
+        }
+    }
+}
+
+unsafe fn report(info: *mut EXCEPTION_POINTERS) {
+    if REPORTED.swap(true, SeqCst) {
+        return;
+    }
+    let callback = CALLBACK.load(SeqCst);
+    if callback.is_null() {
+        return;
+    }
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let backtrace = Backtrace::from_context((*info).ContextRecord as *const c_void);
+        (*callback)(&ExceptionReport {
+            record: &*(*info).ExceptionRecord,
+            backtrace: &backtrace,
+        });
+    }));
+}
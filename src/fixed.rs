@@ -0,0 +1,164 @@
+use super::trace_unsynchronized;
+use core::fmt;
+
+/// A backtrace of at most `N` frames, stored inline without allocating.
+///
+/// This is an alternative to `Backtrace` for code which can't allocate, for
+/// example in a global allocator, an out-of-memory handler or on targets
+/// without a heap. Only the instruction pointer of each frame is recorded,
+/// and frames past the first `N` are counted but not stored.
+///
+/// Symbols are resolved when the backtrace is printed, which with the `std`
+/// feature prints it like a `Backtrace` through `Backtrace::from`. Resolving
+/// symbols does allocate, so a backtrace captured where allocating isn't
+/// possible should be printed later on. Without the `std` feature symbols
+/// can't be resolved safely here, so only the addresses are printed, and
+/// they can be passed to `resolve_unsynchronized` instead. The same goes for
+/// builds without the `symbolize` feature, where they can be symbolicated
+/// elsewhere.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::BacktraceFixed;
+///
+/// # #[cfg(feature = "std-base")] {
+/// let bt = BacktraceFixed::<16>::new();
+/// assert!(bt.ips().len() <= 16);
+/// println!("{:?}", bt);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BacktraceFixed<const N: usize> {
+    ips: [usize; N],
+    len: usize,
+    frames_dropped: usize,
+}
+
+impl<const N: usize> BacktraceFixed<N> {
+    /// Captures a backtrace at the callsite of this function.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    #[cfg(feature = "std-base")]
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub fn new() -> BacktraceFixed<N> {
+        let _guard = super::lock::lock();
+        unsafe { Self::capture(Self::new as *const () as usize) }
+    }
+
+    /// Same as `new`, only unsafe as it's unsynchronized.
+    ///
+    /// This function does not have synchronization guarantees but is available
+    /// when the `std` feature of this crate isn't compiled in. See the
+    /// `trace_unsynchronized` function for more information.
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub unsafe fn new_unsynchronized() -> BacktraceFixed<N> {
+        Self::capture(Self::new_unsynchronized as *const () as usize)
+    }
+
+    unsafe fn capture(ip: usize) -> BacktraceFixed<N> {
+        let mut bt = BacktraceFixed {
+            ips: [0; N],
+            len: 0,
+            frames_dropped: 0,
+        };
+        let mut found = false;
+        trace_unsynchronized(|frame| {
+            if bt.len < N {
+                bt.ips[bt.len] = frame.ip() as usize;
+                bt.len += 1;
+            } else {
+                bt.frames_dropped += 1;
+            }
+            // Everything up to the frame which requested the capture is
+            // internal to this crate, so start over after it. Platforms which
+            // can't tell us where that frame is keep all frames instead.
+            if !found && frame.symbol_address() as usize == ip {
+                found = true;
+                bt.len = 0;
+                bt.frames_dropped = 0;
+            }
+            true
+        });
+        bt
+    }
+
+    /// Returns the instruction pointers of the recorded frames, from the
+    /// innermost to the outermost.
+    pub fn ips(&self) -> &[usize] {
+        &self.ips[..self.len]
+    }
+
+    /// Returns the number of frames which didn't fit into this backtrace.
+    pub fn frames_dropped(&self) -> usize {
+        self.frames_dropped
+    }
+
+    #[cfg(all(
+        not(feature = "std-base"),
+        any(feature = "symbolize", backtrace_in_libstd)
+    ))]
+    fn fmt_pretty(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use super::{BacktraceFmt, BytesOrWideString, PrintFmt};
+
+        let style = if fmt.alternate() {
+            PrintFmt::Full
+        } else {
+            PrintFmt::Short
+        };
+        // Only addresses are printed, so there are never any paths.
+        let mut print_path = |_: &mut fmt::Formatter<'_>, _: BytesOrWideString<'_>| Ok(());
+        let mut f = BacktraceFmt::new(fmt, style, &mut print_path);
+        f.add_context()?;
+        for &ip in self.ips() {
+            f.frame()
+                .print_raw(ip as *mut core::ffi::c_void, None, None, None)?;
+        }
+        f.finish()?;
+        if self.frames_dropped > 0 {
+            writeln!(fmt, "      ... {} frames omitted", self.frames_dropped)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(all(feature = "std-base", feature = "symbolize"))]
+    fn fmt_pretty(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bt = super::Backtrace::from(self);
+        bt.resolve();
+        fmt::Debug::fmt(&bt, fmt)
+    }
+
+    #[cfg(not(any(feature = "symbolize", backtrace_in_libstd)))]
+    fn fmt_pretty(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(fmt, "stack backtrace:")?;
+        for (i, &ip) in self.ips().iter().enumerate() {
+            writeln!(fmt, "{:4}: {:#x}", i, ip)?;
+        }
+        if self.frames_dropped > 0 {
+            writeln!(fmt, "      ... {} frames omitted", self.frames_dropped)?;
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> fmt::Debug for BacktraceFixed<N> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_pretty(fmt)
+    }
+}
+
+impl<const N: usize> fmt::Display for BacktraceFixed<N> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_pretty(fmt)
+    }
+}
+
+#[cfg(feature = "std-base")]
+impl<const N: usize> Default for BacktraceFixed<N> {
+    fn default() -> BacktraceFixed<N> {
+        BacktraceFixed::new()
+    }
+}
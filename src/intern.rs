@@ -0,0 +1,196 @@
+//! Storing many backtraces which are mostly the same.
+//!
+//! Heap profilers capture a backtrace for every allocation, which adds up to
+//! millions of backtraces even though most of them are identical, as they
+//! come from the same few call sites. A `BacktraceInterner` stores each
+//! distinct backtrace once and hands out a `BacktraceId` for it, which is
+//! small and cheap to compare, so that a profiler can record the id with
+//! each allocation instead of a whole backtrace. Frames are shared between
+//! the backtraces they appear in, so each of them is only resolved once.
+//!
+//! # Examples
+//!
+//! ```
+//! use backtrace::intern::BacktraceInterner;
+//! use backtrace::Backtrace;
+//!
+//! let mut interner = BacktraceInterner::new();
+//! let ids = (0..3)
+//!     .map(|_| interner.intern(&Backtrace::new_unresolved()))
+//!     .collect::<Vec<_>>();
+//! // Every capture comes from the same place, so they're all the same.
+//! assert_eq!(interner.len(), 1);
+//! assert_eq!(ids[0], ids[2]);
+//!
+//! interner.resolve();
+//! for frame in interner.frames(ids[0]).unwrap() {
+//!     println!("{:?}", frame.symbols().first().and_then(|s| s.name()));
+//! }
+//! ```
+//!
+//! # Required features
+//!
+//! This module requires the `std` feature of the `backtrace` crate to be
+//! enabled, and the `std` feature is enabled by default.
+
+use crate::{Backtrace, BacktraceFrame};
+use std::collections::HashMap;
+use std::prelude::v1::*;
+use std::slice;
+use std::sync::Arc;
+
+/// A set of distinct backtraces, told apart by the instruction pointers of
+/// their frames.
+///
+/// Every frame is stored once however many backtraces it appears in, and
+/// each backtrace as the indices of its frames.
+#[derive(Clone, Debug, Default)]
+pub struct BacktraceInterner {
+    frames: Vec<BacktraceFrame>,
+    /// The index into `frames` of the frame for each instruction pointer.
+    frame_index: HashMap<usize, usize>,
+    stacks: Vec<Arc<[usize]>>,
+    /// The id of each backtrace in `stacks`, sharing their allocations.
+    stack_index: HashMap<Arc<[usize]>, BacktraceId>,
+    /// Reused by `intern` to look backtraces up without allocating.
+    scratch: Vec<usize>,
+}
+
+/// A handle for a backtrace stored in a `BacktraceInterner`.
+///
+/// Ids are numbered from zero in the order that backtraces were first
+/// interned, so they can also be used to index arrays of per-backtrace data.
+/// Two ids from the same interner are equal exactly when their backtraces
+/// are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BacktraceId(usize);
+
+impl BacktraceId {
+    /// Returns the number of this id, which is how many distinct backtraces
+    /// had been interned before its own.
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl BacktraceInterner {
+    /// Creates an empty interner.
+    pub fn new() -> BacktraceInterner {
+        BacktraceInterner::default()
+    }
+
+    /// Returns the id of `backtrace`, storing it if no backtrace with the
+    /// same frames was interned before.
+    ///
+    /// Frames are compared by instruction pointer alone. If a frame is
+    /// already stored without symbols and `backtrace` has them resolved, the
+    /// stored frame is replaced by the resolved one.
+    pub fn intern(&mut self, backtrace: &Backtrace) -> BacktraceId {
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.clear();
+        for frame in backtrace.frames() {
+            scratch.push(self.intern_frame(frame));
+        }
+        let id = match self.stack_index.get(&scratch[..]) {
+            Some(id) => *id,
+            None => {
+                let id = BacktraceId(self.stacks.len());
+                let stack = Arc::<[usize]>::from(&scratch[..]);
+                self.stacks.push(stack.clone());
+                self.stack_index.insert(stack, id);
+                id
+            }
+        };
+        self.scratch = scratch;
+        id
+    }
+
+    fn intern_frame(&mut self, frame: &BacktraceFrame) -> usize {
+        let frames = &mut self.frames;
+        let i = *self
+            .frame_index
+            .entry(frame.ip() as usize)
+            .or_insert_with(|| {
+                frames.push(frame.clone());
+                frames.len() - 1
+            });
+        // Keep the symbols of whichever backtrace had them resolved.
+        if frames[i].symbols().is_empty() && !frame.symbols().is_empty() {
+            frames[i] = frame.clone();
+        }
+        i
+    }
+
+    /// Returns the frames of the backtrace `id`, or `None` if it isn't from
+    /// this interner.
+    pub fn frames(&self, id: BacktraceId) -> Option<Frames<'_>> {
+        let stack = self.stacks.get(id.0)?;
+        Some(Frames {
+            frames: &self.frames,
+            indices: stack.iter(),
+        })
+    }
+
+    /// Returns a copy of the backtrace `id`, or `None` if it isn't from this
+    /// interner.
+    pub fn get(&self, id: BacktraceId) -> Option<Backtrace> {
+        let frames = self.frames(id)?.cloned().collect::<Vec<_>>();
+        Some(Backtrace::from(frames))
+    }
+
+    /// Resolves the symbols of every stored frame which hasn't been resolved
+    /// yet.
+    ///
+    /// Each distinct frame is only resolved once, however many backtraces it
+    /// appears in, which is much cheaper than resolving the backtraces
+    /// themselves.
+    pub fn resolve(&mut self) {
+        crate::capture::resolve_frames(&mut self.frames);
+    }
+
+    /// Returns the number of distinct backtraces in this interner.
+    pub fn len(&self) -> usize {
+        self.stacks.len()
+    }
+
+    /// Returns whether no backtraces were interned.
+    pub fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+
+    /// Returns the number of distinct frames in this interner, which is how
+    /// many frames `resolve` has to resolve at most.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns an iterator over the ids of every backtrace in this interner,
+    /// in order.
+    pub fn ids(&self) -> impl Iterator<Item = BacktraceId> {
+        (0..self.stacks.len()).map(BacktraceId)
+    }
+}
+
+/// An iterator over the frames of an interned backtrace, returned by
+/// `BacktraceInterner::frames`.
+///
+/// Frames are produced innermost first, like in `Backtrace::frames`.
+#[derive(Clone, Debug)]
+pub struct Frames<'a> {
+    frames: &'a [BacktraceFrame],
+    indices: slice::Iter<'a, usize>,
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = &'a BacktraceFrame;
+
+    fn next(&mut self) -> Option<&'a BacktraceFrame> {
+        self.indices.next().map(|&i| &self.frames[i])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Frames<'_> {}
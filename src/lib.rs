@@ -20,7 +20,7 @@
 //! ```
 //! fn main() {
 //! # // Unsafe here so test passes on no_std.
-//! # #[cfg(feature = "std")] {
+//! # #[cfg(all(feature = "std-base", feature = "trace", feature = "symbolize"))] {
 //!     backtrace::trace(|frame| {
 //!         let ip = frame.ip();
 //!         let symbol_address = frame.symbol_address();
@@ -79,7 +79,27 @@
 //!   libbacktrace on non-Windows platforms for reading debuginfo for
 //!   backtraces. If both crate features are disabled, however, then these
 //!   platforms will generate a backtrace but be unable to generate symbols for
-//!   it.
+//!   it. Likewise without the `symbolize` feature only addresses are captured,
+//!   and without the `trace` feature only symbolication of addresses obtained
+//!   elsewhere is available. Both features are enabled by default.
+//!
+//! * The `trace-frame-pointer` feature replaces unwinding with walking the
+//!   chain of frame pointers on unix platforms for x86, x86_64 and AArch64.
+//!   This is much faster, but is only accurate if all code on the stack was
+//!   compiled with frame pointers (`-Cforce-frame-pointers=yes`), and can
+//!   read arbitrary memory if it wasn't. This feature requires Rust 1.59 or
+//!   later.
+//!
+//! * The `unwind-dwarf` feature replaces libunwind on Linux for x86_64 and
+//!   AArch64 with an unwinder written in Rust which interprets the `.eh_frame`
+//!   unwind tables of loaded objects. This avoids depending on the platform's
+//!   unwinder, for example in statically linked musl binaries, but objects
+//!   without an `.eh_frame_hdr` section can't be unwound through. This
+//!   feature requires Rust 1.59 or later.
+//!
+//! * With the `std` feature, the platform's unwinder stays compiled in next to
+//!   the backends of the two features above, and `set_trace_backend` switches
+//!   between the ones which are compiled in at runtime.
 //!
 //! In most standard workflows for most standard platforms you generally don't
 //! need to worry about these caveats. We'll try to fix ones where we can over
@@ -90,18 +110,24 @@
 #![deny(missing_docs)]
 #![no_std]
 #![cfg_attr(
-    all(feature = "std", target_env = "sgx", target_vendor = "fortanix"),
+    all(feature = "std-base", target_env = "sgx", target_vendor = "fortanix"),
     feature(sgx_platform)
 )]
 #![warn(rust_2018_idioms)]
 // When we're building as part of libstd, silence all warnings since they're
 // irrelevant as this crate is developed out-of-tree.
 #![cfg_attr(backtrace_in_libstd, allow(warnings))]
-#![cfg_attr(not(feature = "std"), allow(dead_code))]
+#![cfg_attr(not(feature = "std-base"), allow(dead_code))]
+// Builds with only one of the `trace` and `symbolize` features leave parts of
+// the other half's support code unused.
+#![cfg_attr(
+    not(any(all(feature = "trace", feature = "symbolize"), backtrace_in_libstd)),
+    allow(dead_code, unused_imports)
+)]
 // We know this is deprecated, it's only here for back-compat reasons.
 #![cfg_attr(feature = "rustc-serialize", allow(deprecated))]
 
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 #[macro_use]
 extern crate std;
 
@@ -110,29 +136,114 @@ extern crate std;
 #[allow(unused_extern_crates)]
 extern crate alloc;
 
-pub use self::backtrace::{trace_unsynchronized, Frame};
+#[cfg(any(feature = "trace", backtrace_in_libstd))]
+pub use self::backtrace::{
+    depth_unsynchronized, stack_bounds, trace_from_context_unsynchronized,
+    trace_raw_unsynchronized, trace_unsynchronized, Backend, Frame,
+};
+#[cfg(any(feature = "trace", backtrace_in_libstd))]
 mod backtrace;
 
+#[cfg(any(all(feature = "trace", feature = "symbolize"), backtrace_in_libstd))]
 pub use self::symbolize::resolve_frame_unsynchronized;
-pub use self::symbolize::{resolve_unsynchronized, Symbol, SymbolName};
+#[cfg(any(feature = "symbolize", backtrace_in_libstd))]
+pub use self::symbolize::{
+    resolve_many_unsynchronized, resolve_precise_unsynchronized, resolve_unsynchronized, Language,
+    Symbol, SymbolName,
+};
+#[cfg(any(feature = "symbolize", backtrace_in_libstd))]
 mod symbolize;
 
 pub use self::types::BytesOrWideString;
 mod types;
 
-#[cfg(feature = "std")]
-pub use self::symbolize::clear_symbol_cache;
-
+#[cfg(any(feature = "symbolize", backtrace_in_libstd))]
 mod print;
-pub use print::{BacktraceFmt, BacktraceFrameFmt, PrintFmt};
+#[cfg(any(feature = "symbolize", backtrace_in_libstd))]
+pub use print::{BacktraceFmt, BacktraceFrameFmt, FrameOrder, PrintFmt};
+
+#[cfg(any(feature = "trace", backtrace_in_libstd))]
+mod fixed;
+#[cfg(any(feature = "trace", backtrace_in_libstd))]
+pub use self::fixed::BacktraceFixed;
 
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub use self::backtrace::{
+    current_thread_id, depth, frames, set_conservative, set_trace_backend, thread_ids, trace,
+    trace_from_context, trace_raw, trace_thread, trace_with_diagnostics, trace_with_limit,
+    StopReason, TraceIter, TraceLimit,
+};
+#[cfg(all(feature = "std-base", feature = "symbolize"))]
+pub use self::print::{set_backtrace_style, set_path_map, set_source_map, BacktraceStyle, PathMap};
+#[cfg(feature = "std-base")]
+pub use self::reentrancy::is_reentrant;
+#[cfg(all(feature = "std-base", feature = "symbolize"))]
+pub use self::symbolize::{
+    clear_symbol_cache, jit, modules, offline, preload, register_image, resolve, resolve_many,
+    resolve_precise, set_demangler, set_search_paths, set_symbolizer, unload, unregister_image,
+    Module, NativeSymbolizer, SymbolizeBackend,
+};
+#[cfg(feature = "std-base")]
+mod reentrancy;
+
+// Everything else with the standard library works with both traces and
+// their symbols.
 cfg_if::cfg_if! {
-    if #[cfg(feature = "std")] {
-        pub use self::backtrace::trace;
-        pub use self::symbolize::{resolve, resolve_frame};
-        pub use self::capture::{Backtrace, BacktraceFrame, BacktraceSymbol};
+    if #[cfg(all(feature = "std-base", feature = "trace", feature = "symbolize"))] {
+        pub use self::symbolize::{resolve_frame, try_resolve, ResolveError, SymbolCache};
+        pub use self::capture::{
+            install_panic_hook, install_panic_hook_with, set_capture_hook, with_config,
+            AnnotatedBacktrace, Backtrace, BacktraceBuffer, BacktraceBuilder, BacktraceFrame,
+            BacktraceSymbol, CaptureConfig, FrameRegisters, LazyBacktrace, ModuleSnapshot,
+            NormalizedBacktrace, NormalizedFrame, NormalizedModule, PanicReport,
+        };
         mod capture;
+        pub use self::print::{set_collapse_recursion, set_frame_order};
+        pub use self::verify::{verify, VerifiedFunction, VerifyReport};
+        mod verify;
+        pub mod intern;
+        pub mod sampled;
+        pub mod tree;
+        #[cfg(feature = "minidump")]
+        pub mod minidump;
+        #[cfg(feature = "remote")]
+        pub mod remote;
+        #[cfg(feature = "debuginfod")]
+        pub mod debuginfod;
+        #[cfg(feature = "capi")]
+        pub mod capi;
+        #[cfg(all(feature = "crash-handler", unix))]
+        pub mod crash_handler;
+        #[cfg(all(feature = "crash-handler", windows))]
+        pub mod exception_handler;
+    }
+}
+
+// IP values from stack frames are typically the instruction *after* the call
+// that's the actual stack trace. Symbolizing this causes the filename/line
+// number to be one ahead and perhaps into the void if it's near the end of
+// the function, so addresses are moved back into the call instruction before
+// they're resolved.
+//
+// Where instructions have a fixed size the address is moved to the start of
+// the call, so that it's a meaningful address to show. Elsewhere subtracting
+// one lands somewhere inside the call, which is just as good for looking it
+// up.
+pub(crate) fn adjust_ip(a: *mut core::ffi::c_void) -> *mut core::ffi::c_void {
+    if a.is_null() {
+        return a;
     }
+    let a = a as usize;
+    let call = if cfg!(target_arch = "aarch64") {
+        (a & !3).wrapping_sub(4)
+    } else if cfg!(target_arch = "arm") {
+        // Thumb calls may be two bytes long, and return addresses into Thumb
+        // code may have their lowest bit set.
+        (a & !1).wrapping_sub(2)
+    } else {
+        a - 1
+    };
+    call as *mut core::ffi::c_void
 }
 
 #[allow(dead_code)]
@@ -150,7 +261,7 @@ impl Drop for Bomb {
 }
 
 #[allow(dead_code)]
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 mod lock {
     use std::boxed::Box;
     use std::cell::Cell;
@@ -173,6 +284,11 @@ mod lock {
         }
     }
 
+    /// Returns whether the current thread holds the lock.
+    pub fn is_held() -> bool {
+        LOCK_HELD.try_with(|l| l.get()).unwrap_or(false)
+    }
+
     pub fn lock() -> LockGuard {
         if LOCK_HELD.with(|l| l.get()) {
             return LockGuard(None);
@@ -188,6 +304,6 @@ mod lock {
 }
 
 #[cfg(all(windows, not(target_vendor = "uwp")))]
-mod dbghelp;
+pub mod dbghelp;
 #[cfg(windows)]
 mod windows;
@@ -0,0 +1,412 @@
+//! Writing minidumps of a thread of the current process.
+//!
+//! Minidumps are the crash dump format of Windows, which is also used by
+//! Breakpad and Crashpad on other platforms. The minidumps written here are
+//! minimal ones for a single thread described by a saved register context,
+//! with the following streams:
+//!
+//! * A system info stream describing the CPU architecture and the operating
+//!   system.
+//! * A thread list stream with the thread's registers and stack.
+//! * A module list stream with every module loaded into the process, along
+//!   with its build id.
+//! * A memory list stream with the thread's stack memory.
+//!
+//! That's enough for tools consuming Breakpad-compatible minidumps, such as
+//! `minidump-stackwalk`, to unwind and symbolicate the thread offline.
+//!
+//! # Required features
+//!
+//! This module requires the `minidump` feature of the `backtrace` crate to be
+//! enabled, along with the `std` feature which is enabled by default.
+
+use core::ffi::c_void;
+use std::prelude::v1::*;
+
+/// Writes a minidump of the thread whose registers are in `context`.
+///
+/// `context` must be a pointer to a `ucontext_t`, such as the third argument
+/// of a signal handler installed with `SA_SIGINFO`. The thread is recorded as
+/// the calling thread, which is the case for synchronous signals like
+/// `SIGSEGV`. Up to 256 KiB of the stack above the stack pointer are
+/// included.
+///
+/// Returns the contents of the minidump, or `None` if the stack couldn't be
+/// found or writing minidumps isn't supported on this platform.
+///
+/// # Caveats
+///
+/// Minidumps can currently only be written on Linux for x86_64 and AArch64.
+///
+/// Writing a minidump allocates memory and reads `/proc/self/maps`, so this
+/// isn't async-signal-safe. Crash handlers should only call this for
+/// synchronous signals, or hand the context to a thread which isn't
+/// interrupted.
+///
+/// # Safety
+///
+/// The `context` pointer must point to a valid, initialized context for a
+/// thread of the current process whose stack remains valid for the duration
+/// of the call.
+pub unsafe fn from_context(context: *const c_void) -> Option<Vec<u8>> {
+    imp::from_context(context)
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod imp {
+    use super::arch;
+    use core::ffi::c_void;
+    use core::ops::Range;
+    use core::slice;
+    use std::ffi::CStr;
+    use std::prelude::v1::*;
+
+    const SIGNATURE: u32 = 0x504d_444d; // "MDMP"
+    const VERSION: u32 = 0xa793;
+
+    const THREAD_LIST_STREAM: u32 = 3;
+    const MODULE_LIST_STREAM: u32 = 4;
+    const MEMORY_LIST_STREAM: u32 = 5;
+    const SYSTEM_INFO_STREAM: u32 = 7;
+
+    const PLATFORM_LINUX: u32 = 0x8201;
+    /// The CodeView record signature Breakpad uses for ELF build ids.
+    const CV_SIGNATURE_ELF: u32 = 0x4270_454c; // "BpEL"
+
+    const MAX_STACK: usize = 256 * 1024;
+
+    pub unsafe fn from_context(context: *const c_void) -> Option<Vec<u8>> {
+        let (context, sp) = arch::context(context as *const libc::ucontext_t);
+        let stack = stack_range(sp)?;
+        let modules = crate::symbolize::modules();
+
+        let mut w = Writer(Vec::new());
+        w.u32(SIGNATURE);
+        w.u32(VERSION);
+        w.u32(4); // number of streams
+        w.u32(32); // directory, right after this header
+        w.u32(0); // checksum
+        w.u32(timestamp());
+        w.u64(0); // flags
+        let directory = w.rva();
+        w.0.resize(w.0.len() + 4 * 12, 0);
+        let mut streams = Vec::new();
+
+        let (version, release) = os_version();
+        let csd_version = w.string(&version);
+        let start = w.start();
+        w.u16(arch::PROCESSOR_ARCHITECTURE);
+        w.u16(0); // processor level
+        w.u16(0); // processor revision
+        let cpus = libc::sysconf(libc::_SC_NPROCESSORS_ONLN);
+        w.0.push(cpus.clamp(0, 255) as u8);
+        w.0.push(0); // product type
+        for &n in release.iter() {
+            w.u32(n);
+        }
+        w.u32(PLATFORM_LINUX);
+        w.u32(csd_version);
+        w.u16(0); // suite mask
+        w.u16(0); // reserved
+        w.0.extend_from_slice(&[0; 24]); // cpu information
+        streams.push((SYSTEM_INFO_STREAM, w.end(start)));
+
+        let context = w.blob(&context);
+        let memory = w.blob(slice::from_raw_parts(
+            stack.start as *const u8,
+            stack.end - stack.start,
+        ));
+        let start = w.start();
+        w.u32(1); // number of threads
+        w.u32(crate::current_thread_id() as u32);
+        w.u32(0); // suspend count
+        w.u32(0); // priority class
+        w.u32(0); // priority
+        w.u64(0); // thread environment block
+        w.u64(stack.start as u64);
+        w.location(memory);
+        w.location(context);
+        streams.push((THREAD_LIST_STREAM, w.end(start)));
+
+        let records = modules
+            .iter()
+            .map(|module| {
+                let name = w.string(&module.path().to_string_lossy());
+                let cv = match module.build_id() {
+                    Some(id) => {
+                        let mut record = CV_SIGNATURE_ELF.to_le_bytes().to_vec();
+                        record.extend_from_slice(id);
+                        w.blob(&record)
+                    }
+                    None => Location::default(),
+                };
+                (name, cv)
+            })
+            .collect::<Vec<_>>();
+        let start = w.start();
+        w.u32(modules.len() as u32);
+        for (module, (name, cv)) in modules.iter().zip(records) {
+            w.u64(module.base_address() as u64);
+            w.u32(module.size() as u32);
+            w.u32(0); // checksum
+            w.u32(0); // timestamp
+            w.u32(name);
+            w.0.extend_from_slice(&[0; 52]); // version info
+            w.location(cv);
+            w.location(Location::default()); // misc record
+            w.u64(0); // reserved
+            w.u64(0); // reserved
+        }
+        streams.push((MODULE_LIST_STREAM, w.end(start)));
+
+        let start = w.start();
+        w.u32(1); // number of memory ranges
+        w.u64(stack.start as u64);
+        w.location(memory);
+        streams.push((MEMORY_LIST_STREAM, w.end(start)));
+
+        for (i, (kind, location)) in streams.into_iter().enumerate() {
+            let at = directory as usize + i * 12;
+            w.0[at..at + 4].copy_from_slice(&kind.to_le_bytes());
+            w.0[at + 4..at + 8].copy_from_slice(&location.size.to_le_bytes());
+            w.0[at + 8..at + 12].copy_from_slice(&location.rva.to_le_bytes());
+        }
+        Some(w.0)
+    }
+
+    /// Finds the part of the stack to record, from a little below `sp` up to
+    /// the end of its mapping.
+    fn stack_range(sp: usize) -> Option<Range<usize>> {
+        let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+        for line in maps.lines() {
+            let mut fields = line.split(' ');
+            let range = fields.next()?;
+            let perms = fields.next()?;
+            let mut bounds = range.split('-');
+            let start = usize::from_str_radix(bounds.next()?, 16).ok()?;
+            let end = usize::from_str_radix(bounds.next()?, 16).ok()?;
+            if start <= sp && sp < end && perms.starts_with('r') {
+                let start = sp.saturating_sub(arch::RED_ZONE).max(start);
+                return Some(start..end.min(start + MAX_STACK));
+            }
+        }
+        None
+    }
+
+    /// Returns a description of the kernel, and its version as the major,
+    /// minor and build number.
+    fn os_version() -> (String, [u32; 3]) {
+        unsafe {
+            let mut name = core::mem::zeroed::<libc::utsname>();
+            if libc::uname(&mut name) != 0 {
+                return (String::new(), [0; 3]);
+            }
+            let field = |f: &[libc::c_char]| CStr::from_ptr(f.as_ptr()).to_string_lossy();
+            let release = field(&name.release);
+            let mut version = [0; 3];
+            let numbers = release.split(|c: char| !c.is_ascii_digit());
+            for (slot, n) in version.iter_mut().zip(numbers) {
+                *slot = n.parse().unwrap_or(0);
+            }
+            // Like Breakpad the kernel's release, version and machine are
+            // recorded, which tools such as `minidump-stackwalk` show as the
+            // version of the operating system.
+            let description = format!(
+                "{} {} {}",
+                release,
+                field(&name.version),
+                field(&name.machine)
+            );
+            (description, version)
+        }
+    }
+
+    fn timestamp() -> u32 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as u32)
+            .unwrap_or(0)
+    }
+
+    /// The position and size of some data in a minidump.
+    #[derive(Clone, Copy, Default)]
+    struct Location {
+        size: u32,
+        rva: u32,
+    }
+
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn rva(&self) -> u32 {
+            self.0.len() as u32
+        }
+
+        fn u16(&mut self, n: u16) {
+            self.0.extend_from_slice(&n.to_le_bytes());
+        }
+
+        fn u32(&mut self, n: u32) {
+            self.0.extend_from_slice(&n.to_le_bytes());
+        }
+
+        fn u64(&mut self, n: u64) {
+            self.0.extend_from_slice(&n.to_le_bytes());
+        }
+
+        fn location(&mut self, location: Location) {
+            self.u32(location.size);
+            self.u32(location.rva);
+        }
+
+        /// Aligns the end of the minidump for the next structure, returning
+        /// where it starts.
+        fn start(&mut self) -> u32 {
+            let len = (self.0.len() + 7) & !7;
+            self.0.resize(len, 0);
+            self.rva()
+        }
+
+        fn end(&self, start: u32) -> Location {
+            Location {
+                size: self.rva() - start,
+                rva: start,
+            }
+        }
+
+        fn blob(&mut self, data: &[u8]) -> Location {
+            let start = self.start();
+            self.0.extend_from_slice(data);
+            self.end(start)
+        }
+
+        /// Writes `s` as a UTF-16 `MINIDUMP_STRING`, returning its position.
+        fn string(&mut self, s: &str) -> u32 {
+            let start = self.start();
+            let units = s.encode_utf16().collect::<Vec<_>>();
+            self.u32(units.len() as u32 * 2);
+            for unit in units {
+                self.u16(unit);
+            }
+            self.u16(0);
+            start
+        }
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+mod imp {
+    use core::ffi::c_void;
+    use std::prelude::v1::*;
+
+    pub unsafe fn from_context(_context: *const c_void) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// Conversion of a `ucontext_t` to the `CONTEXT_AMD64` structure of
+/// minidumps, which has the layout of `CONTEXT` on Windows.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod arch {
+    use std::prelude::v1::*;
+
+    pub const PROCESSOR_ARCHITECTURE: u16 = 9;
+    /// The bytes below the stack pointer which functions may use.
+    pub const RED_ZONE: usize = 128;
+
+    const CONTEXT_AMD64: u32 = 0x0010_0000;
+    const CONTEXT_CONTROL: u32 = 0x1;
+    const CONTEXT_INTEGER: u32 = 0x2;
+    #[cfg(target_env = "gnu")]
+    const CONTEXT_FLOATING_POINT: u32 = 0x8;
+
+    /// Returns the context and the stack pointer of `uc`.
+    pub unsafe fn context(uc: *const libc::ucontext_t) -> (Vec<u8>, usize) {
+        let mut context = vec![0; 1232];
+        let mut put = |offset: usize, bytes: &[u8]| {
+            context[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        let gregs = &(*uc).uc_mcontext.gregs;
+        let reg = |index: libc::c_int| gregs[index as usize] as u64;
+        let mut flags = CONTEXT_AMD64 | CONTEXT_CONTROL | CONTEXT_INTEGER;
+        put(0x38, &(reg(libc::REG_CSGSFS) as u16).to_le_bytes());
+        put(0x44, &(reg(libc::REG_EFL) as u32).to_le_bytes());
+        let registers = [
+            libc::REG_RAX,
+            libc::REG_RCX,
+            libc::REG_RDX,
+            libc::REG_RBX,
+            libc::REG_RSP,
+            libc::REG_RBP,
+            libc::REG_RSI,
+            libc::REG_RDI,
+            libc::REG_R8,
+            libc::REG_R9,
+            libc::REG_R10,
+            libc::REG_R11,
+            libc::REG_R12,
+            libc::REG_R13,
+            libc::REG_R14,
+            libc::REG_R15,
+            libc::REG_RIP,
+        ];
+        for (i, &index) in registers.iter().enumerate() {
+            put(0x78 + i * 8, &reg(index).to_le_bytes());
+        }
+        // The kernel saves the floating point registers in the `FXSAVE`
+        // format, which is also what `CONTEXT` embeds.
+        #[cfg(target_env = "gnu")]
+        {
+            let fpregs = (*uc).uc_mcontext.fpregs;
+            if !fpregs.is_null() {
+                let mut fxsave = [0u8; 512];
+                core::ptr::copy_nonoverlapping(fpregs as *const u8, fxsave.as_mut_ptr(), 512);
+                put(0x34, &(*fpregs).mxcsr.to_le_bytes());
+                put(0x100, &fxsave);
+                flags |= CONTEXT_FLOATING_POINT;
+            }
+        }
+        put(0x30, &flags.to_le_bytes());
+        (context, reg(libc::REG_RSP) as usize)
+    }
+}
+
+/// Conversion of a `ucontext_t` to the `CONTEXT_ARM64` structure of
+/// minidumps, which has the layout of `CONTEXT` on Windows.
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+mod arch {
+    use std::prelude::v1::*;
+
+    pub const PROCESSOR_ARCHITECTURE: u16 = 12;
+    pub const RED_ZONE: usize = 0;
+
+    const CONTEXT_ARM64: u32 = 0x0040_0000;
+    const CONTEXT_CONTROL: u32 = 0x1;
+    const CONTEXT_INTEGER: u32 = 0x2;
+
+    /// Returns the context and the stack pointer of `uc`.
+    pub unsafe fn context(uc: *const libc::ucontext_t) -> (Vec<u8>, usize) {
+        let mut context = vec![0; 912];
+        let mut put = |offset: usize, n: u64| {
+            context[offset..offset + 8].copy_from_slice(&n.to_le_bytes());
+        };
+        let mcontext = &(*uc).uc_mcontext;
+        // x0 to x28 are followed by the frame pointer and link register,
+        // which are x29 and x30.
+        for (i, &reg) in mcontext.regs.iter().enumerate() {
+            put(8 + i * 8, reg as u64);
+        }
+        put(0x100, mcontext.sp as u64);
+        put(0x108, mcontext.pc as u64);
+        let flags = CONTEXT_ARM64 | CONTEXT_CONTROL | CONTEXT_INTEGER;
+        context[0..4].copy_from_slice(&flags.to_le_bytes());
+        context[4..8].copy_from_slice(&(mcontext.pstate as u32).to_le_bytes());
+        (context, mcontext.sp as usize)
+    }
+}
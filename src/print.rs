@@ -1,13 +1,38 @@
-#[cfg(feature = "std")]
+#[cfg(any(feature = "trace", backtrace_in_libstd))]
+use super::Frame;
+#[cfg(all(feature = "std-base", feature = "trace"))]
 use super::{BacktraceFrame, BacktraceSymbol};
-use super::{BytesOrWideString, Frame, SymbolName};
+use super::{BytesOrWideString, SymbolName};
 use core::ffi::c_void;
 use core::fmt;
+#[cfg(all(feature = "std-base", feature = "trace"))]
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std-base")]
+use std::prelude::v1::*;
 
 const HEX_WIDTH: usize = 2 + 2 * core::mem::size_of::<usize>();
 
+// Ends the highlighting of a symbol by `BacktraceStyle`.
+const RESET: &str = "\x1b[0m";
+
 #[cfg(target_os = "fuchsia")]
 mod fuchsia;
+#[cfg(feature = "std-base")]
+mod path_map;
+#[cfg(feature = "std-base")]
+pub(crate) use self::path_map::{current as current_path_map, current_source_map};
+#[cfg(feature = "std-base")]
+pub use self::path_map::{set_path_map, set_source_map, PathMap};
+#[cfg(feature = "std-base")]
+mod style;
+#[cfg(feature = "std-base")]
+pub(crate) use self::style::current as current_style;
+#[cfg(feature = "std-base")]
+pub use self::style::{set_backtrace_style, BacktraceStyle};
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub(crate) mod recursion;
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub use self::recursion::set_collapse_recursion;
 
 /// A formatter for backtraces.
 ///
@@ -18,6 +43,16 @@ pub struct BacktraceFmt<'a, 'b> {
     fmt: &'a mut fmt::Formatter<'b>,
     frame_index: usize,
     format: PrintFmt,
+    order: FrameOrder,
+    frames: usize,
+    source_context: usize,
+    #[cfg(feature = "std-base")]
+    path_map: Option<&'a PathMap>,
+    #[cfg(feature = "std-base")]
+    style: Option<&'a BacktraceStyle>,
+    // The crate of the last symbol printed, for `BacktraceStyle::group_crates`.
+    #[cfg(feature = "std-base")]
+    last_crate: Option<Option<String>>,
     print_path:
         &'a mut (dyn FnMut(&mut fmt::Formatter<'_>, BytesOrWideString<'_>) -> fmt::Result + 'b),
 }
@@ -33,6 +68,60 @@ pub enum PrintFmt {
     __Nonexhaustive,
 }
 
+/// The orders in which the frames of a backtrace can be printed.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum FrameOrder {
+    /// Prints the innermost frame, where the backtrace was captured, first, and
+    /// the callers after it. This is what the standard library and most native
+    /// debuggers print.
+    InnermostFirst,
+    /// Prints the outermost frame first and the innermost frame last, so the
+    /// most recent call is last, as Python and Node.js do.
+    OutermostFirst,
+    #[doc(hidden)]
+    __Nonexhaustive,
+}
+
+// Whether `Backtrace` is printed with `FrameOrder::OutermostFirst`.
+#[cfg(all(feature = "std-base", feature = "trace"))]
+static OUTERMOST_FIRST: AtomicBool = AtomicBool::new(false);
+
+/// Sets the order in which the frames of a `Backtrace` are printed.
+///
+/// This applies to the `Debug` and `Display` output of `Backtrace`. Frames
+/// keep the numbers they have by default, so the innermost frame is number 0
+/// either way, and symbols of inlined functions are reordered along with the
+/// frames. Frames are printed `FrameOrder::InnermostFirst` by default.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::FrameOrder;
+///
+/// backtrace::set_frame_order(FrameOrder::OutermostFirst);
+/// println!("{:?}", backtrace::Backtrace::new());
+/// backtrace::set_frame_order(FrameOrder::InnermostFirst);
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` and `trace` features of the `backtrace`
+/// crate to be enabled, and both are enabled by default.
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub fn set_frame_order(order: FrameOrder) {
+    OUTERMOST_FIRST.store(order == FrameOrder::OutermostFirst, Ordering::Relaxed);
+}
+
+/// Returns the order that `set_frame_order` asked for.
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub(crate) fn frame_order() -> FrameOrder {
+    if OUTERMOST_FIRST.load(Ordering::Relaxed) {
+        FrameOrder::OutermostFirst
+    } else {
+        FrameOrder::InnermostFirst
+    }
+}
+
 impl<'a, 'b> BacktraceFmt<'a, 'b> {
     /// Create a new `BacktraceFmt` which will write output to the provided
     /// `fmt`.
@@ -51,10 +140,90 @@ impl<'a, 'b> BacktraceFmt<'a, 'b> {
             fmt,
             frame_index: 0,
             format,
+            order: FrameOrder::InnermostFirst,
+            frames: 0,
+            source_context: 0,
+            #[cfg(feature = "std-base")]
+            path_map: None,
+            #[cfg(feature = "std-base")]
+            style: None,
+            #[cfg(feature = "std-base")]
+            last_crate: None,
             print_path,
         }
     }
 
+    /// Numbers the frames added afterwards as the `frames` frames of a
+    /// backtrace printed in `order`.
+    ///
+    /// Frames are numbered by how deep they are, so the innermost frame is
+    /// number 0 either way. With `FrameOrder::OutermostFirst` frames have to
+    /// be added outermost first, and are numbered from `frames - 1` down to 0,
+    /// while the symbols of each frame are printed outermost first as well.
+    /// Nothing else is reordered, as frames are printed as they're added.
+    pub fn frame_order(&mut self, order: FrameOrder, frames: usize) {
+        self.order = order;
+        self.frames = frames;
+    }
+
+    /// Returns whether frames are being added outermost first.
+    fn outermost_first(&self) -> bool {
+        self.order == FrameOrder::OutermostFirst
+    }
+
+    /// Returns the number to print for the `printed`th frame added.
+    fn frame_number(&self, printed: usize) -> usize {
+        if self.outermost_first() {
+            self.frames.saturating_sub(printed + 1)
+        } else {
+            printed
+        }
+    }
+
+    /// Prints `lines` lines of source code before and after the line of each
+    /// symbol which has a filename and line number, if the source file can be
+    /// read.
+    ///
+    /// The line being executed is marked with a `>`. Zero lines are printed by
+    /// default, and no source code is printed at all then.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    #[cfg(feature = "std-base")]
+    pub fn source_context(&mut self, lines: usize) {
+        self.source_context = lines;
+    }
+
+    /// Rewrites the filenames of symbols with `map` before they're passed to
+    /// the `print_path` callback.
+    ///
+    /// Backtraces whose symbols were resolved after `set_path_map` have
+    /// their filenames rewritten already, so this is for symbols which
+    /// weren't, such as those passed to `resolve`'s closure.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    #[cfg(feature = "std-base")]
+    pub fn path_map(&mut self, map: &'a PathMap) {
+        self.path_map = Some(map);
+    }
+
+    /// Highlights symbols with `style`, such as with the colors that
+    /// `set_backtrace_style` applies to `Backtrace`.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    #[cfg(feature = "std-base")]
+    pub fn style(&mut self, style: &'a BacktraceStyle) {
+        self.style = Some(style);
+    }
+
     /// Prints a preamble for the backtrace about to be printed.
     ///
     /// This is required on some platforms for backtraces to be fully
@@ -78,6 +247,31 @@ impl<'a, 'b> BacktraceFmt<'a, 'b> {
         }
     }
 
+    /// Adds a line in place of the next `frames` frames, which are `times`
+    /// more repetitions of a cycle of frames that was just printed, such as
+    /// `[frames 10..500 repeat 49 times]`.
+    ///
+    /// The frames are counted as if they had been printed, so frames added
+    /// afterwards are numbered as they would be otherwise.
+    pub fn repeated(&mut self, frames: usize, times: usize) -> fmt::Result {
+        let end = self.frame_index + frames;
+        let (low, high) = if self.outermost_first() {
+            (
+                self.frames.saturating_sub(end),
+                self.frames.saturating_sub(self.frame_index),
+            )
+        } else {
+            (self.frame_index, end)
+        };
+        writeln!(
+            self.fmt,
+            "      [frames {}..{} repeat {} times]",
+            low, high, times
+        )?;
+        self.frame_index = end;
+        Ok(())
+    }
+
     /// Completes the backtrace output.
     ///
     /// This is currently a no-op but is added for future compatibility with
@@ -104,13 +298,19 @@ impl BacktraceFrameFmt<'_, '_, '_> {
     ///
     /// # Required features
     ///
-    /// This function requires the `std` feature of the `backtrace` crate to be
-    /// enabled, and the `std` feature is enabled by default.
-    #[cfg(feature = "std")]
+    /// This function requires the `std` and `trace` features of the
+    /// `backtrace` crate to be enabled, and both are enabled by default.
+    #[cfg(all(feature = "std-base", feature = "trace"))]
     pub fn backtrace_frame(&mut self, frame: &BacktraceFrame) -> fmt::Result {
         let symbols = frame.symbols();
-        for symbol in symbols {
-            self.backtrace_symbol(frame, symbol)?;
+        if self.fmt.outermost_first() {
+            for symbol in symbols.iter().rev() {
+                self.backtrace_symbol(frame, symbol)?;
+            }
+        } else {
+            for symbol in symbols {
+                self.backtrace_symbol(frame, symbol)?;
+            }
         }
         if symbols.is_empty() {
             self.print_raw(frame.ip(), None, None, None)?;
@@ -120,17 +320,20 @@ impl BacktraceFrameFmt<'_, '_, '_> {
 
     /// Prints a `BacktraceSymbol` within a `BacktraceFrame`.
     ///
+    /// Symbols for which `BacktraceSymbol::is_inline` is true are marked as
+    /// `[inlined]` after their name.
+    ///
     /// # Required features
     ///
-    /// This function requires the `std` feature of the `backtrace` crate to be
-    /// enabled, and the `std` feature is enabled by default.
-    #[cfg(feature = "std")]
+    /// This function requires the `std` and `trace` features of the
+    /// `backtrace` crate to be enabled, and both are enabled by default.
+    #[cfg(all(feature = "std-base", feature = "trace"))]
     pub fn backtrace_symbol(
         &mut self,
         frame: &BacktraceFrame,
         symbol: &BacktraceSymbol,
     ) -> fmt::Result {
-        self.print_raw_with_column(
+        self.print_raw_inlined(
             frame.ip(),
             symbol.name(),
             // TODO: this isn't great that we don't end up printing anything
@@ -141,12 +344,14 @@ impl BacktraceFrameFmt<'_, '_, '_> {
                 .and_then(|p| Some(BytesOrWideString::Bytes(p.to_str()?.as_bytes()))),
             symbol.lineno(),
             symbol.colno(),
+            symbol.is_inline(),
         )?;
         Ok(())
     }
 
     /// Prints a raw traced `Frame` and `Symbol`, typically from within the raw
     /// callbacks of this crate.
+    #[cfg(any(feature = "trace", backtrace_in_libstd))]
     pub fn symbol(&mut self, frame: &Frame, symbol: &super::Symbol) -> fmt::Result {
         self.print_raw_with_column(
             frame.ip(),
@@ -185,6 +390,18 @@ impl BacktraceFrameFmt<'_, '_, '_> {
         filename: Option<BytesOrWideString<'_>>,
         lineno: Option<u32>,
         colno: Option<u32>,
+    ) -> fmt::Result {
+        self.print_raw_inlined(frame_ip, symbol_name, filename, lineno, colno, false)
+    }
+
+    fn print_raw_inlined(
+        &mut self,
+        frame_ip: *mut c_void,
+        symbol_name: Option<SymbolName<'_>>,
+        filename: Option<BytesOrWideString<'_>>,
+        lineno: Option<u32>,
+        colno: Option<u32>,
+        inlined: bool,
     ) -> fmt::Result {
         // Fuchsia is unable to symbolize within a process so it has a special
         // format which can be used to symbolize later. Print that instead of
@@ -192,7 +409,7 @@ impl BacktraceFrameFmt<'_, '_, '_> {
         if cfg!(target_os = "fuchsia") {
             self.print_raw_fuchsia(frame_ip)?;
         } else {
-            self.print_raw_generic(frame_ip, symbol_name, filename, lineno, colno)?;
+            self.print_raw_generic(frame_ip, symbol_name, filename, lineno, colno, inlined)?;
         }
         self.symbol_index += 1;
         Ok(())
@@ -206,6 +423,7 @@ impl BacktraceFrameFmt<'_, '_, '_> {
         filename: Option<BytesOrWideString<'_>>,
         lineno: Option<u32>,
         colno: Option<u32>,
+        inlined: bool,
     ) -> fmt::Result {
         // No need to print "null" frames, it basically just means that the
         // system backtrace was a bit eager to trace back super far.
@@ -218,17 +436,21 @@ impl BacktraceFrameFmt<'_, '_, '_> {
         // To reduce TCB size in Sgx enclave, we do not want to implement symbol
         // resolution functionality.  Rather, we can print the offset of the
         // address here, which could be later mapped to correct function.
-        #[cfg(all(feature = "std", target_env = "sgx", target_vendor = "fortanix"))]
+        #[cfg(all(feature = "std-base", target_env = "sgx", target_vendor = "fortanix"))]
         {
             let image_base = std::os::fortanix_sgx::mem::image_base();
             frame_ip = usize::wrapping_sub(frame_ip as usize, image_base as _) as _;
         }
 
+        #[cfg(feature = "std-base")]
+        let krate = self.start_crate(symbol_name.as_ref())?;
+
         // Print the index of the frame as well as the optional instruction
         // pointer of the frame. If we're beyond the first symbol of this frame
         // though we just print appropriate whitespace.
         if self.symbol_index == 0 {
-            write!(self.fmt.fmt, "{:4}: ", self.fmt.frame_index)?;
+            let number = self.fmt.frame_number(self.fmt.frame_index);
+            write!(self.fmt.fmt, "{:4}: ", number)?;
             if let PrintFmt::Full = self.fmt.format {
                 write!(self.fmt.fmt, "{:1$?} - ", frame_ip, HEX_WIDTH)?;
             }
@@ -239,39 +461,129 @@ impl BacktraceFrameFmt<'_, '_, '_> {
             }
         }
 
+        #[cfg(feature = "std-base")]
+        let highlight = self.fmt.style.and_then(|style| style.highlight(filename));
+        #[cfg(not(feature = "std-base"))]
+        let highlight = None;
+
         // Next up write out the symbol name, using the alternate formatting for
         // more information if we're a full backtrace. Here we also handle
         // symbols which don't have a name,
+        if let Some(highlight) = highlight {
+            self.fmt.fmt.write_str(highlight)?;
+        }
         match (symbol_name, &self.fmt.format) {
             (Some(name), PrintFmt::Short) => write!(self.fmt.fmt, "{:#}", name)?,
             (Some(name), PrintFmt::Full) => write!(self.fmt.fmt, "{}", name)?,
             (None, _) | (_, PrintFmt::__Nonexhaustive) => write!(self.fmt.fmt, "<unknown>")?,
         }
+        // All symbols of a frame share its index, so mark the ones which were
+        // inlined to tell them apart from the function the frame is in.
+        if inlined {
+            self.fmt.fmt.write_str(" [inlined]")?;
+        }
+        if highlight.is_some() {
+            self.fmt.fmt.write_str(RESET)?;
+        }
+        #[cfg(feature = "std-base")]
+        {
+            if let Some(krate) = krate {
+                self.fmt.fmt.write_str(" (")?;
+                self.print_crate(&krate)?;
+                self.fmt.fmt.write_str(")")?;
+            }
+        }
         self.fmt.fmt.write_str("\n")?;
 
         // And last up, print out the filename/line number if they're available.
         if let (Some(file), Some(line)) = (filename, lineno) {
-            self.print_fileline(file, line, colno)?;
+            self.print_fileline(file, line, colno, highlight)?;
         }
 
         Ok(())
     }
 
+    /// Prints the line heading a new run of symbols if the style groups
+    /// symbols by crate and the crate of `symbol_name` isn't the one of the
+    /// last symbol, returning the crate to print after the name, if any.
+    #[cfg(feature = "std-base")]
+    fn start_crate(
+        &mut self,
+        symbol_name: Option<&SymbolName<'_>>,
+    ) -> Result<Option<String>, fmt::Error> {
+        let style = match self.fmt.style {
+            Some(style) if style.annotates_crates() || style.groups_crates() => style,
+            _ => return Ok(None),
+        };
+        let krate = symbol_name.and_then(style::crate_name);
+        if style.groups_crates() && self.fmt.last_crate.as_ref() != Some(&krate) {
+            self.fmt.fmt.write_str("      -- ")?;
+            match &krate {
+                Some(krate) => self.print_crate(krate)?,
+                None => self.fmt.fmt.write_str("<unknown>")?,
+            }
+            self.fmt.fmt.write_str(" --\n")?;
+            self.fmt.last_crate = Some(krate.clone());
+        }
+        Ok(if style.annotates_crates() {
+            krate
+        } else {
+            None
+        })
+    }
+
+    /// Prints the name of a crate, in its color if the style has colors.
+    #[cfg(feature = "std-base")]
+    fn print_crate(&mut self, krate: &str) -> fmt::Result {
+        let color = self.fmt.style.and_then(|style| style.crate_color(krate));
+        if let Some(color) = color {
+            self.fmt.fmt.write_str(color)?;
+        }
+        self.fmt.fmt.write_str(krate)?;
+        if color.is_some() {
+            self.fmt.fmt.write_str(RESET)?;
+        }
+        Ok(())
+    }
+
     fn print_fileline(
         &mut self,
         file: BytesOrWideString<'_>,
         line: u32,
         colno: Option<u32>,
+        highlight: Option<&str>,
     ) -> fmt::Result {
         // Filename/line are printed on lines under the symbol name, so print
         // some appropriate whitespace to sort of right-align ourselves.
         if let PrintFmt::Full = self.fmt.format {
             write!(self.fmt.fmt, "{:1$}", "", HEX_WIDTH)?;
         }
+        if let Some(highlight) = highlight {
+            self.fmt.fmt.write_str(highlight)?;
+        }
         write!(self.fmt.fmt, "             at ")?;
 
+        #[cfg(feature = "std-base")]
+        let path = if self.fmt.source_context > 0 {
+            Some(file.into_path_buf())
+        } else {
+            None
+        };
+
         // Delegate to our internal callback to print the filename and then
         // print out the line number.
+        #[cfg(feature = "std-base")]
+        {
+            if let Some(map) = self.fmt.path_map {
+                let fmt = &mut *self.fmt;
+                path_map::with_raw(&map.apply(&file.into_path_buf()), |file| {
+                    (fmt.print_path)(fmt.fmt, file)
+                })?;
+            } else {
+                (self.fmt.print_path)(self.fmt.fmt, file)?;
+            }
+        }
+        #[cfg(not(feature = "std-base"))]
         (self.fmt.print_path)(self.fmt.fmt, file)?;
         write!(self.fmt.fmt, ":{}", line)?;
 
@@ -279,8 +591,48 @@ impl BacktraceFrameFmt<'_, '_, '_> {
         if let Some(colno) = colno {
             write!(self.fmt.fmt, ":{}", colno)?;
         }
+        if highlight.is_some() {
+            self.fmt.fmt.write_str(RESET)?;
+        }
 
         write!(self.fmt.fmt, "\n")?;
+
+        #[cfg(feature = "std-base")]
+        {
+            if let Some(path) = path {
+                self.print_source(&path, line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the lines of `path` around `line`, if it can be read.
+    #[cfg(feature = "std-base")]
+    fn print_source(&mut self, path: &std::path::Path, line: u32) -> fmt::Result {
+        let source = match std::fs::read(path) {
+            Ok(source) => source,
+            Err(_) => return Ok(()),
+        };
+        let line = line as usize;
+        let first = line.saturating_sub(self.fmt.source_context).max(1);
+        let last = line.saturating_add(self.fmt.source_context);
+        let width = last.to_string().len();
+        let lines = source.split(|&b| b == b'\n').enumerate();
+        for (i, text) in lines.skip(first - 1).take(last + 1 - first) {
+            let text = text.strip_suffix(b"\r").unwrap_or(text);
+            if let PrintFmt::Full = self.fmt.format {
+                write!(self.fmt.fmt, "{:1$}", "", HEX_WIDTH)?;
+            }
+            let marker = if i + 1 == line { '>' } else { ' ' };
+            writeln!(
+                self.fmt.fmt,
+                "           {} {:>3$} | {}",
+                marker,
+                i + 1,
+                String::from_utf8_lossy(text),
+                width,
+            )?;
+        }
         Ok(())
     }
 
@@ -288,7 +640,8 @@ impl BacktraceFrameFmt<'_, '_, '_> {
         // We only care about the first symbol of a frame
         if self.symbol_index == 0 {
             self.fmt.fmt.write_str("{{{bt:")?;
-            write!(self.fmt.fmt, "{}:{:?}", self.fmt.frame_index, frame_ip)?;
+            let number = self.fmt.frame_number(self.fmt.frame_index);
+            write!(self.fmt.fmt, "{}:{:?}", number, frame_ip)?;
             self.fmt.fmt.write_str("}}}\n")?;
         }
         Ok(())
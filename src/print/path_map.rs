@@ -0,0 +1,260 @@
+//! Rewriting the source paths of symbols, with `PathMap`.
+
+use crate::BytesOrWideString;
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::prelude::v1::*;
+use std::sync::Arc;
+
+/// Rules for rewriting the prefixes of source file paths, like the
+/// `-fdebug-prefix-map` and `--remap-path-prefix` options of compilers.
+///
+/// Binaries built in CI record the absolute paths of the sources they were
+/// built from, like `/build/agent/work/src/main.rs`, which makes backtraces
+/// noisy and reveals details of the build machine. A `PathMap` replaces such
+/// prefixes, either with a path of their own or with nothing at all to make
+/// the paths relative. It can be applied to the symbols of every backtrace
+/// with `set_path_map`, or to a single backtrace as it's printed with
+/// `BacktraceFmt::path_map`.
+///
+/// The other way around, `set_source_map` uses a `PathMap` to find the
+/// sources of binaries whose paths were remapped when they were built, such
+/// as with `--remap-path-prefix` or `-Ztrim-paths`.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::PathMap;
+/// use std::path::Path;
+///
+/// let map = PathMap::new()
+///     .map("/build/agent/work", "")
+///     .map("/home/ci/.cargo/registry/src", "$CARGO");
+/// assert_eq!(map.apply(Path::new("/build/agent/work/src/main.rs")), Path::new("src/main.rs"));
+/// assert_eq!(map.apply(Path::new("/usr/src/lib.rs")), Path::new("/usr/src/lib.rs"));
+/// ```
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct PathMap {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+enum Rule {
+    Prefix(PathBuf, PathBuf),
+    RustSrc(PathBuf),
+}
+
+impl PathMap {
+    /// Returns a map without any rules, which leaves every path as it is.
+    pub fn new() -> PathMap {
+        PathMap::default()
+    }
+
+    /// Adds a rule replacing the prefix `from` of paths with `to`.
+    ///
+    /// Prefixes are matched by whole path components, so `/build` doesn't
+    /// match `/builds/main.rs`. If `to` is empty the rest of the path is left
+    /// as a relative path. When several rules match a path the one added last
+    /// is used, like with `-fdebug-prefix-map`.
+    pub fn map(mut self, from: impl Into<PathBuf>, to: impl Into<PathBuf>) -> Self {
+        self.rules.push(Rule::Prefix(from.into(), to.into()));
+        self
+    }
+
+    /// Adds a rule replacing the `/rustc/<commit hash>` prefix of the paths of
+    /// the standard library's sources with `dir`.
+    ///
+    /// The standard library is built with its sources remapped to a
+    /// directory named after the commit of the compiler, such as
+    /// `/rustc/90b35a6239c3d8bdabc530a6a0816f7ff89a0aaf/library/std/src/rt.rs`,
+    /// which matches any hash. The `rust-src` component of rustup installs
+    /// those sources to `lib/rustlib/src/rust` in the sysroot, so `dir` is
+    /// usually that directory of the output of `rustc --print sysroot`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use backtrace::PathMap;
+    /// use std::path::Path;
+    ///
+    /// let map = PathMap::new().map_rust_src("/opt/rust/lib/rustlib/src/rust");
+    /// let path = "/rustc/90b35a6239c3d8bdabc530a6a0816f7ff89a0aaf/library/std/src/rt.rs";
+    /// assert_eq!(
+    ///     map.apply(Path::new(path)),
+    ///     Path::new("/opt/rust/lib/rustlib/src/rust/library/std/src/rt.rs"),
+    /// );
+    /// ```
+    pub fn map_rust_src(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.rules.push(Rule::RustSrc(dir.into()));
+        self
+    }
+
+    /// Returns whether this map has no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Rewrites `path` with the last rule whose prefix it starts with,
+    /// returning it unchanged if there's none.
+    pub fn apply<'p>(&self, path: &'p Path) -> Cow<'p, Path> {
+        for rule in self.rules.iter().rev() {
+            let (rest, to) = match rule {
+                Rule::Prefix(from, to) => (path.strip_prefix(from).ok(), to),
+                Rule::RustSrc(to) => (strip_rustc_prefix(path), to),
+            };
+            if let Some(rest) = rest {
+                return Cow::Owned(to.join(rest));
+            }
+        }
+        Cow::Borrowed(path)
+    }
+}
+
+/// Strips the `/rustc/<commit hash>` prefix which the sources of the standard
+/// library are remapped to off `path`.
+pub(super) fn strip_rustc_prefix(path: &Path) -> Option<&Path> {
+    let mut components = path.components();
+    if components.next()? != Component::RootDir
+        || components.next()? != Component::Normal("rustc".as_ref())
+    {
+        return None;
+    }
+    match components.next()? {
+        Component::Normal(hash)
+            if hash.len() == 40 && hash.to_str()?.bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            Some(components.as_path())
+        }
+        _ => None,
+    }
+}
+
+// The map registered through `set_path_map`, if any, guarded by the global
+// lock in `crate::lock`.
+static mut PATH_MAP: Option<Arc<PathMap>> = None;
+
+/// Rewrites the source paths of all symbols resolved afterwards with `map`.
+///
+/// This applies to the filenames of the symbols in `Backtrace`, and so to how
+/// it's printed and serialized, but not to those that `Symbol::filename`
+/// returns, which are the paths found in the debug information as rewritten
+/// by `set_source_map`. Each call replaces the previous map, and passing an
+/// empty `PathMap` stops rewriting paths.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::PathMap;
+///
+/// backtrace::set_path_map(PathMap::new().map(env!("CARGO_MANIFEST_DIR"), ""));
+/// # #[cfg(feature = "trace")]
+/// println!("{:?}", backtrace::Backtrace::new());
+/// backtrace::set_path_map(PathMap::new());
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` and `symbolize` features of the
+/// `backtrace` crate to be enabled, and both are enabled by default.
+pub fn set_path_map(map: PathMap) {
+    let _guard = crate::lock::lock();
+    let map = if map.is_empty() {
+        None
+    } else {
+        Some(Arc::new(map))
+    };
+    unsafe {
+        PATH_MAP = map;
+    }
+}
+
+/// Returns the map registered through `set_path_map`, if any.
+pub(crate) fn current() -> Option<Arc<PathMap>> {
+    let _guard = crate::lock::lock();
+    unsafe { (*core::ptr::addr_of!(PATH_MAP)).clone() }
+}
+
+// The map registered through `set_source_map`, if any, guarded by the global
+// lock in `crate::lock`.
+static mut SOURCE_MAP: Option<Arc<PathMap>> = None;
+
+/// Rewrites the source paths that `Symbol::filename` returns for symbols
+/// resolved afterwards with `map`, to find the sources of binaries whose
+/// paths were remapped when they were built.
+///
+/// Options such as `--remap-path-prefix` and `-Ztrim-paths` of rustc, or
+/// `-fdebug-prefix-map` of C compilers, replace the paths recorded in debug
+/// information, so they no longer exist on the machine the binary runs on
+/// and editors and debuggers can't open them. `map` reverses that, mapping
+/// the remapped prefixes to where the sources are. `PathMap::map_rust_src`
+/// does so for the standard library, which is always built with its paths
+/// remapped.
+///
+/// `Symbol::filename_raw` still returns the path as recorded, and
+/// `set_path_map` is applied after this one. Each call replaces the previous
+/// map, and passing an empty `PathMap` stops rewriting paths. Only the gimli
+/// and dbghelp backends rewrite paths.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::PathMap;
+///
+/// // The binary was built with `--remap-path-prefix=/build/agent/work=/app`,
+/// // and its sources are checked out in the working directory.
+/// backtrace::set_source_map(
+///     PathMap::new()
+///         .map("/app", std::env::current_dir().unwrap())
+///         .map_rust_src("/opt/rust/lib/rustlib/src/rust"),
+/// );
+/// # #[cfg(feature = "trace")]
+/// println!("{:?}", backtrace::Backtrace::new());
+/// backtrace::set_source_map(PathMap::new());
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` and `symbolize` features of the
+/// `backtrace` crate to be enabled, and both are enabled by default.
+pub fn set_source_map(map: PathMap) {
+    let _guard = crate::lock::lock();
+    let map = if map.is_empty() {
+        None
+    } else {
+        Some(Arc::new(map))
+    };
+    unsafe {
+        SOURCE_MAP = map;
+    }
+}
+
+/// Returns the map registered through `set_source_map`, if any.
+pub(crate) fn current_source_map() -> Option<Arc<PathMap>> {
+    let _guard = crate::lock::lock();
+    unsafe { (*core::ptr::addr_of!(SOURCE_MAP)).clone() }
+}
+
+/// Calls `f` with `path` in the representation that `BacktraceFmt` prints.
+pub(crate) fn with_raw<R>(path: &Path, f: impl FnOnce(BytesOrWideString<'_>) -> R) -> R {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        f(BytesOrWideString::Bytes(path.as_os_str().as_bytes()))
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        let wide = path.as_os_str().encode_wide().collect::<Vec<_>>();
+        f(BytesOrWideString::Wide(&wide))
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let s = path.to_string_lossy();
+        f(BytesOrWideString::Bytes(s.as_bytes()))
+    }
+}
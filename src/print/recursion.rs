@@ -0,0 +1,148 @@
+//! Collapsing repeated cycles of frames, with `set_collapse_recursion`.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::prelude::v1::*;
+
+/// The longest cycle of frames that's looked for. Mutual recursion rarely
+/// goes through more functions than this, and looking for longer cycles
+/// makes finding them proportionally slower.
+const MAX_PERIOD: usize = 16;
+
+// The number of times a cycle must repeat to be collapsed, or zero if cycles
+// aren't collapsed.
+static MIN_REPEATS: AtomicUsize = AtomicUsize::new(0);
+
+/// Collapses cycles of frames which repeat at least `min_repeats` times in a
+/// row when backtraces are printed, or stops collapsing them with `None`.
+///
+/// Deeply recursive code produces backtraces with thousands of frames, most of
+/// which are the same few functions calling each other, and which bury the
+/// interesting frames around them. With this set the first occurrence of such
+/// a cycle is printed and the rest is replaced by a line such as
+/// `[frames 10..500 repeat 49 times]`, for both the `Debug` and `Display`
+/// output of `Backtrace` and for `Backtrace::to_json`. Backtraces themselves
+/// keep all of their frames, as do the formats which are read back, such as
+/// `Backtrace::to_bytes`.
+///
+/// Frames are compared by instruction pointer: direct recursion calls itself
+/// from the same place every time, and mutual recursion produces a cycle of
+/// instruction pointers, of up to 16 frames. A `min_repeats` of less than 2
+/// is treated as 2. Nothing is collapsed by default.
+///
+/// # Examples
+///
+/// ```
+/// backtrace::set_collapse_recursion(Some(3));
+/// println!("{:?}", backtrace::Backtrace::new());
+/// backtrace::set_collapse_recursion(None);
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` and `trace` features of the `backtrace`
+/// crate to be enabled, and both are enabled by default.
+pub fn set_collapse_recursion(min_repeats: Option<usize>) {
+    let min_repeats = min_repeats.map_or(0, |n| n.max(2));
+    MIN_REPEATS.store(min_repeats, Ordering::Relaxed);
+}
+
+/// A cycle of `period` frames starting at `start`, which occurs `times` times
+/// in a row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Repeat {
+    pub start: usize,
+    pub period: usize,
+    pub times: usize,
+}
+
+impl Repeat {
+    /// Returns the index of the first frame after the first occurrence of the
+    /// cycle, which is where collapsing starts.
+    pub fn collapsed_start(&self) -> usize {
+        self.start + self.period
+    }
+
+    /// Returns the index of the first frame after the cycle.
+    pub fn end(&self) -> usize {
+        self.start + self.period * self.times
+    }
+}
+
+/// Returns the cycles in `ips` to collapse, in order, if
+/// `set_collapse_recursion` asked for that.
+pub(crate) fn find(ips: &[usize]) -> Vec<Repeat> {
+    match MIN_REPEATS.load(Ordering::Relaxed) {
+        0 => Vec::new(),
+        min_repeats => find_repeats(ips, min_repeats),
+    }
+}
+
+/// Returns the cycles in `ips` which repeat at least `min_repeats` times.
+///
+/// This scans from the innermost frame, taking at each position the cycle
+/// which covers the most frames and continuing after it, so cycles never
+/// overlap.
+fn find_repeats(ips: &[usize], min_repeats: usize) -> Vec<Repeat> {
+    let mut repeats = Vec::new();
+    let mut start = 0;
+    while start < ips.len() {
+        let mut best: Option<Repeat> = None;
+        for period in 1..=MAX_PERIOD {
+            if start + period * min_repeats > ips.len() {
+                break;
+            }
+            let cycle = &ips[start..start + period];
+            let times = 1 + ips[start + period..]
+                .chunks_exact(period)
+                .take_while(|chunk| *chunk == cycle)
+                .count();
+            let repeat = Repeat {
+                start,
+                period,
+                times,
+            };
+            if times >= min_repeats && best.filter(|b| b.end() >= repeat.end()).is_none() {
+                best = Some(repeat);
+            }
+        }
+        match best {
+            Some(repeat) => {
+                start = repeat.end();
+                repeats.push(repeat);
+            }
+            None => start += 1,
+        }
+    }
+    repeats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeat(start: usize, period: usize, times: usize) -> Repeat {
+        Repeat {
+            start,
+            period,
+            times,
+        }
+    }
+
+    #[test]
+    fn test_find_repeats() {
+        assert_eq!(find_repeats(&[1, 2, 3], 2), []);
+        assert_eq!(find_repeats(&[1, 2, 2, 2, 3], 3), [repeat(1, 1, 3)]);
+        assert_eq!(find_repeats(&[1, 2, 2, 3], 3), []);
+        // Mutual recursion, with a partial cycle left over.
+        assert_eq!(
+            find_repeats(&[1, 2, 3, 2, 3, 2, 3, 2, 4], 2),
+            [repeat(1, 2, 3)]
+        );
+        // A cycle of one frame is preferred over one of several.
+        assert_eq!(find_repeats(&[5, 5, 5, 5], 2), [repeat(0, 1, 4)]);
+        assert_eq!(
+            find_repeats(&[1, 1, 2, 3, 2, 3, 4], 2),
+            [repeat(0, 1, 2), repeat(2, 2, 2)]
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Colors for printed backtraces, with `BacktraceStyle`.
+
+use super::path_map::strip_rustc_prefix;
+use crate::{BytesOrWideString, SymbolName};
+use std::path::{Path, PathBuf};
+use std::prelude::v1::*;
+use std::sync::Arc;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+// The colors that crate names are printed in, which skip red and white so
+// they don't look like errors or plain text.
+const CRATE_COLORS: &[&str] = &["\x1b[32m", "\x1b[33m", "\x1b[34m", "\x1b[35m", "\x1b[36m"];
+
+/// Options for how the symbols of printed backtraces look, which can be
+/// applied to every `Backtrace` with `set_backtrace_style`, or to a single
+/// backtrace as it's printed with `BacktraceFmt::style`.
+///
+/// With colors enabled, the names and locations of symbols in user code are
+/// printed bold and those in the runtime are dimmed, using ANSI escape codes,
+/// so that the frames to look at stand out in a long backtrace. A symbol is in
+/// the runtime if it has no source location, like most symbols of the C
+/// library, or if it's in the sources of the standard library. Which code is
+/// user code is up to the `user_code` prefixes, so nothing is bold without
+/// any.
+///
+/// The crate that each symbol is in can be printed after its name with
+/// `crate_names`, or before each run of symbols from the same crate with
+/// `group_crates`, which makes long stacks of async code and of
+/// combinators much easier to scan. Crates are inferred from the first path
+/// segment of the demangled name, so `<tokio::task::JoinHandle<T> as
+/// core::future::Future>::poll` is in `tokio`, and symbols whose names
+/// aren't paths, such as those of C functions, aren't in a crate. With
+/// colors each crate name is also printed in a color of its own.
+///
+/// Escape codes are only meant for terminals, and this crate doesn't check
+/// where output goes, so it's up to the caller to only enable colors when
+/// they're wanted, for example unless the `NO_COLOR` environment variable is
+/// set. Colors are disabled by default, which prints backtraces the same as
+/// without a style. The same goes for crate names.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::BacktraceStyle;
+/// use std::path::Path;
+///
+/// let style = BacktraceStyle::new()
+///     .color(std::env::var_os("NO_COLOR").is_none())
+///     .user_code("/home/me/project");
+/// assert!(style.is_user_code(Path::new("/home/me/project/src/main.rs")));
+/// assert!(!style.is_user_code(Path::new("/home/me/projects/main.rs")));
+/// ```
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct BacktraceStyle {
+    color: bool,
+    user_code: Vec<PathBuf>,
+    crate_names: bool,
+    group_crates: bool,
+}
+
+impl BacktraceStyle {
+    /// Returns a style without colors or user code.
+    pub fn new() -> BacktraceStyle {
+        BacktraceStyle::default()
+    }
+
+    /// Sets whether symbols are highlighted with ANSI escape codes.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Adds `prefix` to the paths of the sources of user code, such as the
+    /// directory of a workspace.
+    ///
+    /// Prefixes are matched by whole path components against the filenames
+    /// of symbols, which `set_path_map` has already rewritten for a
+    /// `Backtrace`.
+    pub fn user_code(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.user_code.push(prefix.into());
+        self
+    }
+
+    /// Sets whether the crate of each symbol is printed after its name, like
+    /// `tokio::task::spawn (tokio)`.
+    pub fn crate_names(mut self, crate_names: bool) -> Self {
+        self.crate_names = crate_names;
+        self
+    }
+
+    /// Sets whether a line naming the crate, like `-- tokio --`, is printed
+    /// before each run of symbols from the same crate.
+    ///
+    /// Runs of symbols which aren't in a crate start with `-- <unknown> --`.
+    pub fn group_crates(mut self, group_crates: bool) -> Self {
+        self.group_crates = group_crates;
+        self
+    }
+
+    /// Returns whether this style prints backtraces the same as without one.
+    fn is_plain(&self) -> bool {
+        !self.color && !self.crate_names && !self.group_crates
+    }
+
+    /// Returns whether crate names are printed after the names of symbols.
+    pub(crate) fn annotates_crates(&self) -> bool {
+        self.crate_names
+    }
+
+    /// Returns whether runs of symbols are headed by the name of their crate.
+    pub(crate) fn groups_crates(&self) -> bool {
+        self.group_crates
+    }
+
+    /// Returns the escape code to print the name of `krate` in, if colors
+    /// are enabled.
+    ///
+    /// The color is picked by hashing the name, so that a crate is always
+    /// printed in the same color.
+    pub(crate) fn crate_color(&self, krate: &str) -> Option<&'static str> {
+        if !self.color {
+            return None;
+        }
+        let hash = krate.bytes().fold(0usize, |hash, b| {
+            hash.wrapping_mul(31).wrapping_add(b as usize)
+        });
+        Some(CRATE_COLORS[hash % CRATE_COLORS.len()])
+    }
+
+    /// Returns whether `path` is in the sources of user code, according to
+    /// the prefixes added with `user_code`.
+    pub fn is_user_code(&self, path: &Path) -> bool {
+        self.user_code.iter().any(|prefix| path.starts_with(prefix))
+    }
+
+    /// Returns the escape code to print a symbol with the source file `file`
+    /// in, if it's highlighted at all.
+    pub(crate) fn highlight(&self, file: Option<BytesOrWideString<'_>>) -> Option<&'static str> {
+        if !self.color {
+            return None;
+        }
+        let path = match file {
+            Some(file) => file.into_path_buf(),
+            None => return Some(DIM),
+        };
+        if self.is_user_code(&path) {
+            Some(BOLD)
+        } else if is_std_source(&path) {
+            Some(DIM)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the crate that the symbol `name` is in, which is the first segment
+/// of its demangled path.
+pub(crate) fn crate_name(name: &SymbolName<'_>) -> Option<String> {
+    let name = format!("{:#}", name);
+    // The paths of trait implementations start with the type they're for,
+    // such as `<&mut F as FnOnce>::call_once`.
+    let path = name.trim_start_matches(&['<', '&', '*'][..]);
+    let path = ["mut ", "const ", "dyn "]
+        .iter()
+        .fold(path, |path, prefix| {
+            path.strip_prefix(prefix).unwrap_or(path)
+        });
+    let krate = &path[..path.find("::")?];
+    if krate.is_empty() || !krate.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(krate.to_string())
+}
+
+/// Returns whether `path` is in the sources of the standard library, either
+/// as they were remapped when it was built or as installed by the `rust-src`
+/// component.
+fn is_std_source(path: &Path) -> bool {
+    if strip_rustc_prefix(path).is_some() {
+        return true;
+    }
+    let rust_src: &Path = "lib/rustlib/src/rust".as_ref();
+    path.ancestors().skip(1).any(|dir| dir.ends_with(rust_src))
+}
+
+// The style registered through `set_backtrace_style`, if any, guarded by the
+// global lock in `crate::lock`.
+static mut STYLE: Option<Arc<BacktraceStyle>> = None;
+
+/// Prints the symbols of every `Backtrace` with `style` afterwards.
+///
+/// This applies to the `Debug` and `Display` output of `Backtrace`, and so to
+/// the backtraces printed by `install_panic_hook`, but not to
+/// `Backtrace::write_to`, which avoids the allocations it needs. Each call
+/// replaces the previous style, and passing `BacktraceStyle::new()` stops
+/// highlighting symbols and printing crates.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::BacktraceStyle;
+///
+/// backtrace::set_backtrace_style(
+///     BacktraceStyle::new()
+///         .color(true)
+///         .user_code(env!("CARGO_MANIFEST_DIR")),
+/// );
+/// # #[cfg(feature = "trace")]
+/// println!("{:?}", backtrace::Backtrace::new());
+/// backtrace::set_backtrace_style(BacktraceStyle::new());
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` and `symbolize` features of the
+/// `backtrace` crate to be enabled, and both are enabled by default.
+pub fn set_backtrace_style(style: BacktraceStyle) {
+    let _guard = crate::lock::lock();
+    let style = if style.is_plain() {
+        None
+    } else {
+        Some(Arc::new(style))
+    };
+    unsafe {
+        STYLE = style;
+    }
+}
+
+/// Returns the style registered through `set_backtrace_style`, if any.
+pub(crate) fn current() -> Option<Arc<BacktraceStyle>> {
+    let _guard = crate::lock::lock();
+    unsafe { (*core::ptr::addr_of!(STYLE)).clone() }
+}
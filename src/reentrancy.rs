@@ -0,0 +1,114 @@
+//! Detecting when a thread enters this crate again while it's already walking
+//! its stack or resolving symbols.
+//!
+//! This happens when a signal handler captures a backtrace while the thread
+//! it interrupted was doing so, or when the closure given to `trace` or
+//! `resolve` captures one itself, for example by panicking into a panic hook
+//! which does. The walk or resolution that was interrupted may hold locks
+//! inside the unwinder or the system's symbolication library, and the gimli
+//! backend's caches are borrowed for as long as it's resolving, so the nested
+//! call can't do anything safely. Walks and resolutions are therefore tracked
+//! per thread, and nested ones yield nothing instead of deadlocking.
+//!
+//! Walking and resolving are tracked separately, as resolving the frames of a
+//! backtrace from within the closure given to `trace` is common, and is fine.
+
+use std::cell::Cell;
+use std::thread::LocalKey;
+
+/// What a thread can be doing in this crate which mustn't be nested.
+#[derive(Clone, Copy)]
+pub(crate) enum Activity {
+    Walking,
+    Resolving,
+}
+
+thread_local! {
+    static WALKING: Cell<bool> = Cell::new(false);
+    static RESOLVING: Cell<bool> = Cell::new(false);
+}
+
+impl Activity {
+    fn flag(self) -> &'static LocalKey<Cell<bool>> {
+        match self {
+            Activity::Walking => &WALKING,
+            Activity::Resolving => &RESOLVING,
+        }
+    }
+
+    /// Returns whether this thread is doing this already.
+    fn is_active(self) -> bool {
+        self.flag().try_with(Cell::get).unwrap_or(false)
+    }
+}
+
+/// Marks the current thread as doing an activity until it's dropped.
+pub(crate) struct Guard(Option<Activity>);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if let Some(activity) = self.0 {
+            let _ = activity.flag().try_with(|flag| flag.set(false));
+        }
+    }
+}
+
+/// Marks the current thread as doing `activity`, or returns `None` if it's
+/// doing it already.
+///
+/// If the thread's flags are gone because it's exiting, nesting can't be
+/// detected and a guard which does nothing is returned.
+pub(crate) fn enter(activity: Activity) -> Option<Guard> {
+    match activity.flag().try_with(|flag| flag.replace(true)) {
+        Ok(true) => None,
+        Ok(false) => Some(Guard(Some(activity))),
+        Err(_) => Some(Guard(None)),
+    }
+}
+
+/// Returns whether the calling thread is in the middle of walking its stack or
+/// resolving symbols with this crate.
+///
+/// Stack walks and symbol resolution which start while one of the same is in
+/// progress on the same thread, from a signal handler which interrupted it or
+/// from the closure given to `trace` or `resolve`, could deadlock on locks
+/// held by the one in progress. Instead, such nested walks yield no frames and
+/// nested resolutions yield no symbols, so a backtrace captured then is empty,
+/// which `Backtrace::is_reentrant` reports, and its frames are left
+/// unresolved. Resolving symbols from within the closure given to `trace` is
+/// fine, and isn't counted as nested.
+///
+/// This is useful for crash handlers and panic hooks, which can check it to
+/// fall back to something other than a backtrace rather than capture an
+/// empty one.
+///
+/// # Examples
+///
+/// ```
+/// assert!(!backtrace::is_reentrant());
+/// # #[cfg(feature = "trace")]
+/// backtrace::trace(|_| {
+///     assert!(backtrace::is_reentrant());
+///     false
+/// });
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn is_reentrant() -> bool {
+    Activity::Walking.is_active() || Activity::Resolving.is_active()
+}
+
+/// Returns whether frames walked on this thread right now would be nested in
+/// another walk, and so wouldn't be yielded.
+pub(crate) fn is_walking() -> bool {
+    Activity::Walking.is_active()
+}
+
+/// Returns whether symbols resolved on this thread right now would be nested
+/// in another resolution, and so wouldn't be yielded.
+pub(crate) fn is_resolving() -> bool {
+    Activity::Resolving.is_active()
+}
@@ -0,0 +1,710 @@
+//! Capturing backtraces of threads of other processes.
+//!
+//! A `Process` walks the stacks of the threads of another process from
+//! outside of it, the way a debugger does, and resolves the symbols of their
+//! frames by loading the object files the process has mapped. The threads
+//! are stopped with `ptrace` just long enough to read their registers and
+//! stack, so the process can keep running afterwards.
+//!
+//! Stacks are unwound with the `.eh_frame` unwind tables of the objects on
+//! disk, falling back to frame pointers for code without any, such as code
+//! generated at runtime. Only the subset of CFI needed for compiler-generated
+//! code is evaluated, as with the `unwind-dwarf` feature, so frames described
+//! by DWARF expressions end the trace.
+//!
+//! Tracing another process needs the same permissions as attaching a debugger
+//! to it: usually that it's a child of the calling process or that both run
+//! as the same user, depending on `/proc/sys/kernel/yama/ptrace_scope`.
+//!
+//! A `CoreDump` traces the threads of a process which already exited, from
+//! the core file written when it crashed, with the same unwinding and
+//! symbolization, so a crash can be analyzed without a debugger.
+//!
+//! # Caveats
+//!
+//! Other processes can currently only be traced on Linux for x86_64 and
+//! AArch64, and only core dumps of Linux processes of the same architecture
+//! can be read. Elsewhere `Process::new` and `CoreDump::open` return an error.
+//!
+//! # Required features
+//!
+//! This module requires the `remote` feature of the `backtrace` crate to be
+//! enabled, along with the `std` feature which is enabled by default.
+
+use crate::offline::Resolver;
+use crate::Symbol;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::prelude::v1::*;
+
+mod core_dump;
+
+pub use self::core_dump::CoreDump;
+
+/// The maximum number of frames recorded for a thread.
+const MAX_FRAMES: usize = 256;
+
+/// Another process whose threads can be traced.
+///
+/// The objects the process has mapped are listed, and their unwind tables
+/// read, when it's created, so libraries it loads afterwards aren't known.
+/// Create a new `Process` to pick them up. The symbols of an object file are
+/// only loaded once a frame in it is resolved, and kept for as long as the
+/// `Process` is.
+pub struct Process {
+    pid: u32,
+    modules: Vec<Module>,
+}
+
+struct Module {
+    path: PathBuf,
+    /// The address ranges the module is mapped at, along with the stated
+    /// address each is mapped from.
+    ranges: Vec<(usize, usize, usize)>,
+    /// The stated address module-relative addresses are relative to.
+    image_base: usize,
+    #[allow(dead_code)] // only read on platforms which can unwind
+    unwind: Option<imp::UnwindInfo>,
+    resolver: Option<Option<Resolver>>,
+}
+
+/// A file-backed mapping, as its start, end, file offset and path.
+type Mapping = (usize, usize, usize, PathBuf);
+
+/// A frame of a thread of another process, as returned by
+/// `Process::trace_thread`.
+#[derive(Clone, Debug)]
+pub struct RemoteFrame {
+    ip: usize,
+    sp: usize,
+    module: Option<usize>,
+    module_base: Option<usize>,
+}
+
+impl RemoteFrame {
+    /// Returns the instruction pointer of this frame, an address in the
+    /// other process.
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    /// Returns the stack pointer of this frame, an address in the other
+    /// process.
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    /// Returns the address the module containing this frame is loaded at in
+    /// the other process, if the frame is in a module.
+    ///
+    /// Like with `Frame::module`, `ip() - module_base()` is the address which
+    /// `offline::Resolver::resolve_addr` takes.
+    pub fn module_base(&self) -> Option<usize> {
+        self.module_base
+    }
+}
+
+impl Process {
+    /// Prepares to trace the threads of the process `pid`, listing the
+    /// objects it has mapped.
+    ///
+    /// This doesn't stop or attach to the process yet. Returns an error if
+    /// the process doesn't exist or can't be inspected, or if tracing other
+    /// processes isn't supported on this platform.
+    pub fn new(pid: u32) -> io::Result<Process> {
+        let modules = imp::modules(pid)?;
+        Ok(Process { pid, modules })
+    }
+
+    /// Returns the process identifier of the process.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Returns the identifiers of the threads of the process.
+    pub fn threads(&self) -> io::Result<Vec<u32>> {
+        imp::threads(self.pid)
+    }
+
+    /// Walks the stack of the thread `tid` of the process, returning its
+    /// frames innermost first.
+    ///
+    /// The thread is attached to with `PTRACE_SEIZE` and stopped with
+    /// `PTRACE_INTERRUPT` while its stack is read, and is detached from
+    /// before returning. The first frame is the one the thread was executing
+    /// when it was stopped. At most 256 frames are returned.
+    ///
+    /// Returns an error if the thread can't be attached to, which is the case
+    /// when another debugger is tracing it, or if it exits meanwhile.
+    pub fn trace_thread(&mut self, tid: u32) -> io::Result<Vec<RemoteFrame>> {
+        let _attached = imp::Attached::new(tid)?;
+        self.trace_stopped_thread(tid)
+    }
+
+    /// Walks the stack of the thread `tid` of the process, which the calling
+    /// thread is already tracing and which is stopped.
+    ///
+    /// This is for debuggers, which have attached to the thread themselves.
+    /// Returns an error if its registers can't be read.
+    pub fn trace_stopped_thread(&mut self, tid: u32) -> io::Result<Vec<RemoteFrame>> {
+        let regs = imp::registers(tid)?;
+        let pid = self.pid;
+        Ok(trace(&self.modules, &|addr| imp::read(pid, addr), regs))
+    }
+
+    /// Resolves the symbols of `frame`, a frame of this process, passing them
+    /// to `cb`.
+    ///
+    /// Like with `backtrace::resolve`, several symbols are yielded where
+    /// functions were inlined, innermost first. The object file of the frame
+    /// is loaded with `offline::Resolver`, along with its separate debug
+    /// information.
+    pub fn resolve<F: FnMut(&Symbol)>(&mut self, frame: &RemoteFrame, cb: F) {
+        resolve(&mut self.modules, frame, cb)
+    }
+
+    /// Returns the path of the object file of the module containing `frame`,
+    /// if it's in one.
+    pub fn module_path(&self, frame: &RemoteFrame) -> Option<&Path> {
+        Some(&self.modules.get(frame.module?)?.path)
+    }
+}
+
+/// Walks a stack from the registers `regs`, reading the memory of the thread
+/// with `read`, and looks up the module of each frame in `modules`.
+fn trace(
+    modules: &[Module],
+    read: &dyn Fn(usize) -> Option<usize>,
+    regs: imp::Regs,
+) -> Vec<RemoteFrame> {
+    let mut frames = Vec::new();
+    imp::walk(modules, read, regs, &mut |ip, sp| {
+        frames.push((ip, sp));
+        frames.len() < MAX_FRAMES
+    });
+    frames
+        .into_iter()
+        .map(|(ip, sp)| {
+            let module = modules.iter().position(|m| m.svma(ip).is_some());
+            let module_base = module.and_then(|i| {
+                let svma = modules[i].svma(ip)?;
+                Some(ip.wrapping_sub(svma.wrapping_sub(modules[i].image_base)))
+            });
+            RemoteFrame {
+                ip,
+                sp,
+                module,
+                module_base,
+            }
+        })
+        .collect()
+}
+
+/// Resolves the symbols of `frame`, loading the object file of its module in
+/// `modules` if it wasn't yet.
+fn resolve<F: FnMut(&Symbol)>(modules: &mut [Module], frame: &RemoteFrame, mut cb: F) {
+    let (index, base) = match (frame.module, frame.module_base) {
+        (Some(index), Some(base)) => (index, base),
+        _ => return,
+    };
+    let module = match modules.get_mut(index) {
+        Some(module) => module,
+        None => return,
+    };
+    let path = &module.path;
+    let resolver = module.resolver.get_or_insert_with(|| Resolver::new(path));
+    if let Some(resolver) = resolver {
+        resolver.resolve_addr(frame.ip.wrapping_sub(base), &mut cb);
+    }
+}
+
+impl Module {
+    /// Translates `addr` in the other process to the stated address in its
+    /// object file, if it's in this module.
+    fn svma(&self, addr: usize) -> Option<usize> {
+        self.ranges
+            .iter()
+            .find(|&&(start, end, _)| start <= addr && addr < end)
+            .map(|&(start, _, stated)| addr - start + stated)
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod imp {
+    use super::{Mapping, Module};
+    use addr2line::gimli::{
+        BaseAddresses, CfaRule, EhFrame, EhFrameHdr, EndianSlice, NativeEndian, RegisterRule,
+        UnwindContext, UnwindSection, UnwindTableRow,
+    };
+    use core::mem::{self, MaybeUninit};
+    use core::ptr;
+    use object::{Object, ObjectSection, ObjectSegment};
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+    use std::prelude::v1::*;
+    use std::str;
+
+    const NT_PRSTATUS: libc::c_int = 1;
+
+    /// Number of register slots tracked, indexed by DWARF register number,
+    /// with the pc in the last one on AArch64 where it has no number.
+    const NREGS: usize = 33;
+
+    #[cfg(target_arch = "x86_64")]
+    mod arch {
+        pub const SP: usize = 7;
+        pub const FP: usize = 6;
+        pub const RA: usize = 16;
+        pub const IP: usize = 16;
+
+        /// `user_regs_struct`.
+        pub type UserRegs = [u64; 27];
+
+        /// The index in `user_regs_struct` of each DWARF register from `rax`
+        /// to `rip`.
+        const INDICES: [usize; 17] = [10, 12, 11, 5, 13, 14, 4, 19, 9, 8, 7, 6, 3, 2, 1, 0, 16];
+
+        pub fn dwarf_regs(user: &UserRegs) -> super::Regs {
+            let mut regs = [None; super::NREGS];
+            for (dwarf, &index) in INDICES.iter().enumerate() {
+                regs[dwarf] = Some(user[index] as usize);
+            }
+            regs
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    mod arch {
+        pub const SP: usize = 31;
+        pub const FP: usize = 29;
+        pub const RA: usize = 30;
+        pub const IP: usize = 32;
+
+        /// `user_pt_regs`, `x0` to `x30` followed by `sp`, `pc` and `pstate`.
+        pub type UserRegs = [u64; 34];
+
+        pub fn dwarf_regs(user: &UserRegs) -> super::Regs {
+            let mut regs = [None; super::NREGS];
+            for (slot, &value) in regs.iter_mut().zip(user[..33].iter()) {
+                *slot = Some(value as usize);
+            }
+            regs
+        }
+    }
+
+    pub type Regs = [Option<usize>; NREGS];
+    type Slice<'a> = EndianSlice<'a, NativeEndian>;
+
+    /// The unwind tables of an object file, along with their stated
+    /// addresses.
+    pub struct UnwindInfo {
+        eh_frame: Option<(Vec<u8>, u64)>,
+        eh_frame_hdr: Option<(Vec<u8>, u64)>,
+    }
+
+    pub fn modules(pid: u32) -> io::Result<Vec<Module>> {
+        let maps = fs::read(format!("/proc/{}/maps", pid))?;
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        Ok(load_modules(
+            maps.split(|&b| b == b'\n').filter_map(parse_maps_line),
+            page_size,
+        ))
+    }
+
+    /// Groups `mappings` into modules, reading the object file of each.
+    pub fn load_modules(mappings: impl Iterator<Item = Mapping>, page_size: u64) -> Vec<Module> {
+        let mut modules: Vec<Module> = Vec::new();
+        for (start, end, offset, path) in mappings {
+            let index = match modules.iter().position(|m| m.path == path) {
+                Some(index) => index,
+                None => {
+                    modules.push(Module {
+                        path,
+                        ranges: Vec::new(),
+                        image_base: 0,
+                        unwind: None,
+                        resolver: None,
+                    });
+                    modules.len() - 1
+                }
+            };
+            modules[index].ranges.push((start, end, offset));
+        }
+
+        // The ranges were recorded with file offsets, which are translated to
+        // stated addresses with the program headers of each object file.
+        // Files which can't be read, or aren't object files, are dropped.
+        modules
+            .into_iter()
+            .filter_map(|module| load(module, page_size))
+            .collect()
+    }
+
+    /// Reads the object file of `module`, whose ranges have file offsets,
+    /// translating them to stated addresses.
+    fn load(mut module: Module, page_size: u64) -> Option<Module> {
+        let data = fs::read(&module.path).ok()?;
+        let file = object::File::parse(&*data).ok()?;
+        let segments = file
+            .segments()
+            .map(|s| (s.file_range(), s.address()))
+            .filter(|&((_, size), _)| size > 0)
+            .collect::<Vec<_>>();
+        module.image_base = segments.iter().map(|&(_, address)| address).min()? as usize;
+        let page_mask = !(page_size - 1);
+        let mut ranges = Vec::new();
+        for &(start, end, offset) in module.ranges.iter() {
+            // Segments are mapped from the page containing their start, which
+            // may also hold the end of the previous segment, so this mapping
+            // is of the last segment starting in or before its first page.
+            let offset = offset as u64;
+            let segment = segments
+                .iter()
+                .filter(|&&((seg_offset, size), _)| {
+                    seg_offset & page_mask <= offset && offset < seg_offset + size
+                })
+                .max_by_key(|&&((seg_offset, _), _)| seg_offset);
+            if let Some(&((seg_offset, _), address)) = segment {
+                ranges.push((start, end, (offset + address - seg_offset) as usize));
+            }
+        }
+        if ranges.is_empty() {
+            return None;
+        }
+        module.ranges = ranges;
+
+        let section = |name: &str| {
+            let section = file.section_by_name(name)?;
+            Some((section.data().ok()?.to_vec(), section.address()))
+        };
+        module.unwind = Some(UnwindInfo {
+            eh_frame: section(".eh_frame"),
+            eh_frame_hdr: section(".eh_frame_hdr"),
+        });
+        Some(module)
+    }
+
+    /// Parses a line of `/proc/<pid>/maps` of a file-backed executable
+    /// mapping into its start, end, file offset and path.
+    fn parse_maps_line(line: &[u8]) -> Option<Mapping> {
+        let mut fields = line.splitn(6, |&b| b == b' ');
+        let range = str::from_utf8(fields.next()?).ok()?;
+        let perms = fields.next()?;
+        let offset = str::from_utf8(fields.next()?).ok()?;
+        let _dev = fields.next()?;
+        let _inode = fields.next()?;
+        // The path is padded to line up in a column.
+        let path = fields.next()?;
+        let path = &path[path.iter().position(|&b| b != b' ')?..];
+        if !perms.contains(&b'x') || !path.starts_with(b"/") {
+            return None;
+        }
+        let (start, end) = range.split_once('-')?;
+        Some((
+            usize::from_str_radix(start, 16).ok()?,
+            usize::from_str_radix(end, 16).ok()?,
+            usize::from_str_radix(offset, 16).ok()?,
+            PathBuf::from(OsStr::from_bytes(path)),
+        ))
+    }
+
+    pub fn threads(pid: u32) -> io::Result<Vec<u32>> {
+        let mut ret = Vec::new();
+        for entry in fs::read_dir(format!("/proc/{}/task", pid))? {
+            if let Some(tid) = entry?.file_name().to_str().and_then(|s| s.parse().ok()) {
+                ret.push(tid);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// A thread attached to with `PTRACE_SEIZE` and stopped, which is
+    /// detached from when this is dropped.
+    pub struct Attached(libc::pid_t);
+
+    impl Attached {
+        pub fn new(tid: u32) -> io::Result<Attached> {
+            let tid = tid as libc::pid_t;
+            unsafe {
+                if ptrace(libc::PTRACE_SEIZE, tid, ptr::null_mut()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let attached = Attached(tid);
+                if ptrace(libc::PTRACE_INTERRUPT, tid, ptr::null_mut()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let mut status = 0;
+                if libc::waitpid(tid, &mut status, libc::__WALL) != tid {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(attached)
+            }
+        }
+    }
+
+    impl Drop for Attached {
+        fn drop(&mut self) {
+            unsafe {
+                ptrace(libc::PTRACE_DETACH, self.0, ptr::null_mut());
+            }
+        }
+    }
+
+    #[cfg(target_env = "gnu")]
+    unsafe fn ptrace(request: libc::c_uint, tid: libc::pid_t, data: *mut libc::c_void) -> i64 {
+        libc::ptrace(request, tid, ptr::null_mut::<libc::c_void>(), data) as i64
+    }
+
+    #[cfg(not(target_env = "gnu"))]
+    unsafe fn ptrace(request: libc::c_int, tid: libc::pid_t, data: *mut libc::c_void) -> i64 {
+        libc::ptrace(request, tid, ptr::null_mut::<libc::c_void>(), data) as i64
+    }
+
+    pub fn check_core_dumps_supported() -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn registers(tid: u32) -> io::Result<Regs> {
+        let mut user = MaybeUninit::<arch::UserRegs>::zeroed();
+        let mut iov = libc::iovec {
+            iov_base: user.as_mut_ptr() as *mut libc::c_void,
+            iov_len: mem::size_of::<arch::UserRegs>(),
+        };
+        unsafe {
+            let r = libc::ptrace(
+                libc::PTRACE_GETREGSET,
+                tid as libc::pid_t,
+                NT_PRSTATUS as usize as *mut libc::c_void,
+                &mut iov as *mut libc::iovec as *mut libc::c_void,
+            );
+            if r != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(arch::dwarf_regs(&user.assume_init()))
+        }
+    }
+
+    /// Reads the registers of a thread from the `NT_PRSTATUS` note of a core
+    /// dump, whose `pr_reg` has the layout `PTRACE_GETREGSET` reads.
+    pub fn prstatus_registers(prstatus: &[u8]) -> Option<Regs> {
+        // `pr_reg` follows the signal information, pending and held signals,
+        // process identifiers and times in `struct elf_prstatus`.
+        const PR_REG: usize = 112;
+        let bytes = prstatus.get(PR_REG..PR_REG + mem::size_of::<arch::UserRegs>())?;
+        let user = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const arch::UserRegs) };
+        Some(arch::dwarf_regs(&user))
+    }
+
+    /// Reads a word at `addr` from the memory of the process `pid`.
+    pub fn read(pid: u32, addr: usize) -> Option<usize> {
+        let mut value = 0usize;
+        let local = libc::iovec {
+            iov_base: &mut value as *mut usize as *mut libc::c_void,
+            iov_len: mem::size_of::<usize>(),
+        };
+        let remote = libc::iovec {
+            iov_base: addr as *mut libc::c_void,
+            iov_len: mem::size_of::<usize>(),
+        };
+        let n = unsafe { libc::process_vm_readv(pid as libc::pid_t, &local, 1, &remote, 1, 0) };
+        if n == mem::size_of::<usize>() as isize {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Walks the stack of a thread from its registers `regs`, reading its
+    /// memory with `read`, and passes the instruction and stack pointers of
+    /// each frame to `cb`.
+    pub fn walk(
+        modules: &[Module],
+        read: &dyn Fn(usize) -> Option<usize>,
+        mut regs: Regs,
+        cb: &mut dyn FnMut(usize, usize) -> bool,
+    ) {
+        let mut is_return_address = false;
+        loop {
+            let ip = match regs[arch::IP] {
+                Some(0) | None => return,
+                Some(ip) => ip,
+            };
+            let sp = regs[arch::SP].unwrap_or(0);
+            if !cb(ip, sp) {
+                return;
+            }
+            let pc = if is_return_address { ip - 1 } else { ip };
+            let next = step(modules, read, &regs, pc).or_else(|| frame_pointer(read, &regs));
+            // As when unwinding locally, callers must have their stack above
+            // that of their callees.
+            regs = match next {
+                Some(next) if matches!(next[arch::SP], Some(next_sp) if next_sp > sp) => next,
+                _ => return,
+            };
+            is_return_address = true;
+        }
+    }
+
+    /// Computes the caller's registers with the unwind tables of the object
+    /// containing `pc`.
+    fn step(
+        modules: &[Module],
+        read: &dyn Fn(usize) -> Option<usize>,
+        regs: &Regs,
+        pc: usize,
+    ) -> Option<Regs> {
+        let module = modules.iter().find(|m| m.svma(pc).is_some())?;
+        let svma = module.svma(pc)? as u64;
+        let info = module.unwind.as_ref()?;
+        let (eh_frame_data, eh_frame_address) = info.eh_frame.as_ref()?;
+        let eh_frame = EhFrame::new(eh_frame_data, NativeEndian);
+        let mut bases = BaseAddresses::default().set_eh_frame(*eh_frame_address);
+        let fde = match &info.eh_frame_hdr {
+            Some((data, address)) => {
+                bases = bases.set_eh_frame_hdr(*address);
+                EhFrameHdr::new(data, NativeEndian)
+                    .parse(&bases, mem::size_of::<usize>() as u8)
+                    .ok()
+                    .and_then(|hdr| {
+                        hdr.table()?
+                            .fde_for_address(&eh_frame, &bases, svma, EhFrame::cie_from_offset)
+                            .ok()
+                    })
+            }
+            None => eh_frame
+                .fde_for_address(&bases, svma, EhFrame::cie_from_offset)
+                .ok(),
+        }?;
+        let mut ctx = UnwindContext::new();
+        let row = fde
+            .unwind_info_for_address(&eh_frame, &bases, &mut ctx, svma)
+            .ok()?;
+        apply(read, row, regs)
+    }
+
+    /// Evaluates an unwind table row against the callee's registers, reading
+    /// the saved ones from the stack with `read`.
+    fn apply(
+        read: &dyn Fn(usize) -> Option<usize>,
+        row: &UnwindTableRow<Slice<'_>>,
+        regs: &Regs,
+    ) -> Option<Regs> {
+        let cfa = match *row.cfa() {
+            CfaRule::RegisterAndOffset { register, offset } => {
+                let base = (*regs.get(register.0 as usize)?)?;
+                (base as i64).wrapping_add(offset) as usize
+            }
+            CfaRule::Expression(_) => return None,
+        };
+        let mut next = *regs;
+        for &(register, ref rule) in row.registers() {
+            let slot = match next.get_mut(register.0 as usize) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            *slot = match *rule {
+                RegisterRule::SameValue => regs[register.0 as usize],
+                RegisterRule::Offset(offset) => read((cfa as i64).wrapping_add(offset) as usize),
+                RegisterRule::ValOffset(offset) => Some((cfa as i64).wrapping_add(offset) as usize),
+                RegisterRule::Register(other) => regs.get(other.0 as usize).copied().flatten(),
+                _ => None,
+            };
+        }
+        // See `apply` in the `unwind-dwarf` backend for how the outermost
+        // frame is recognized.
+        if cfg!(target_arch = "x86_64") && !row.registers().any(|&(r, _)| r.0 as usize == arch::RA)
+        {
+            next[arch::RA] = None;
+        }
+        next[arch::SP] = Some(cfa);
+        next[arch::IP] = next[arch::RA];
+        Some(next)
+    }
+
+    /// Computes the caller's registers by following the frame pointer, for
+    /// code without unwind tables.
+    fn frame_pointer(read: &dyn Fn(usize) -> Option<usize>, regs: &Regs) -> Option<Regs> {
+        let fp = regs[arch::FP]?;
+        if fp <= regs[arch::SP]? || fp % mem::size_of::<usize>() != 0 {
+            return None;
+        }
+        let mut next = [None; NREGS];
+        next[arch::FP] = Some(read(fp)?);
+        next[arch::IP] = Some(read(fp + mem::size_of::<usize>())?);
+        next[arch::SP] = Some(fp + 2 * mem::size_of::<usize>());
+        Some(next)
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+mod imp {
+    use super::{Mapping, Module};
+    use std::io;
+    use std::prelude::v1::*;
+
+    pub struct UnwindInfo;
+    #[derive(Clone, Copy)]
+    pub struct Regs;
+    pub struct Attached;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Other,
+            "tracing other processes isn't supported on this platform",
+        )
+    }
+
+    pub fn modules(_pid: u32) -> io::Result<Vec<Module>> {
+        Err(unsupported())
+    }
+
+    pub fn load_modules(_mappings: impl Iterator<Item = Mapping>, _page_size: u64) -> Vec<Module> {
+        Vec::new()
+    }
+
+    pub fn check_core_dumps_supported() -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "reading core dumps isn't supported on this platform",
+        ))
+    }
+
+    pub fn threads(_pid: u32) -> io::Result<Vec<u32>> {
+        Err(unsupported())
+    }
+
+    impl Attached {
+        pub fn new(_tid: u32) -> io::Result<Attached> {
+            Err(unsupported())
+        }
+    }
+
+    pub fn registers(_tid: u32) -> io::Result<Regs> {
+        Err(unsupported())
+    }
+
+    pub fn prstatus_registers(_prstatus: &[u8]) -> Option<Regs> {
+        None
+    }
+
+    pub fn read(_pid: u32, _addr: usize) -> Option<usize> {
+        None
+    }
+
+    pub fn walk(
+        _modules: &[Module],
+        _read: &dyn Fn(usize) -> Option<usize>,
+        _regs: Regs,
+        _cb: &mut dyn FnMut(usize, usize) -> bool,
+    ) {
+    }
+}
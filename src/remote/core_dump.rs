@@ -0,0 +1,312 @@
+use super::{imp, resolve, trace, Mapping, Module, RemoteFrame};
+use crate::Symbol;
+use object::elf::{FileHeader64, ELFMAG, ET_CORE, NT_FILE, NT_PRSTATUS, PT_LOAD, PT_NOTE};
+use object::read::elf::{FileHeader, ProgramHeader};
+use object::NativeEndian;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::prelude::v1::*;
+
+/// A core dump of a process, whose threads can be traced after it exited.
+///
+/// This is the offline counterpart of `Process`. The registers of each thread
+/// and the memory of the process are read from an ELF core file, such as the
+/// kernel writes when a process crashes or `gcore` writes, and the stacks are
+/// unwound and their symbols resolved with the object files the core lists as
+/// mapped by the process. Core files usually only hold the memory the process
+/// wrote to, which includes its stacks but not its code, so the unwind tables
+/// are read from those object files.
+///
+/// The object files must be the ones the process ran, so when analyzing a
+/// core on another machine than the one it was written on, use `open_with` to
+/// find copies of them. Object files which can't be read are skipped, and
+/// frames in them aren't resolved and may end the trace.
+///
+/// Like with `Process`, this is only supported on Linux for x86_64 and
+/// AArch64, and only for cores written on the same architecture.
+pub struct CoreDump {
+    file: File,
+    /// The memory saved in the core, as the address, file offset and size of
+    /// each segment, sorted by address.
+    segments: Vec<(usize, u64, usize)>,
+    threads: Vec<Thread>,
+    modules: Vec<Module>,
+}
+
+struct Thread {
+    tid: u32,
+    signal: i32,
+    regs: imp::Regs,
+}
+
+impl CoreDump {
+    /// Reads the core dump at `path`, along with the object files at the
+    /// paths it lists.
+    ///
+    /// Returns an error if the file can't be read or isn't an ELF core file,
+    /// or if reading core dumps isn't supported on this platform.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<CoreDump> {
+        CoreDump::open_with(path, |path| path.to_path_buf())
+    }
+
+    /// Same as `open`, but reads each object file the core lists from the
+    /// path `locate` returns for it.
+    ///
+    /// `locate` is called with the path the process had mapped the object
+    /// file from. This allows reading the object files from a sysroot or a
+    /// directory of binaries collected along with the core.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use backtrace::remote::CoreDump;
+    /// use std::path::Path;
+    ///
+    /// let sysroot = Path::new("/srv/sysroots/crashed-host");
+    /// let core = CoreDump::open_with("core", |path| {
+    ///     sysroot.join(path.strip_prefix("/").unwrap_or(path))
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn open_with<P, F>(path: P, mut locate: F) -> io::Result<CoreDump>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Path) -> PathBuf,
+    {
+        imp::check_core_dumps_supported()?;
+        let file = File::open(path)?;
+        let endian = NativeEndian;
+        let mut head = vec![0; mem::size_of::<FileHeader64<NativeEndian>>()];
+        read_at(&file, &mut head, 0)?;
+        let header = FileHeader64::<NativeEndian>::parse(&*head).map_err(|_| invalid())?;
+        if header.e_type(endian) != ET_CORE {
+            return Err(invalid());
+        }
+        // Read the program headers along with the file header, so that they
+        // can be parsed from the same buffer.
+        let end = header.e_phoff(endian) as usize
+            + header.e_phnum(endian) as usize * header.e_phentsize(endian) as usize;
+        head.resize(end, 0);
+        read_at(&file, &mut head, 0)?;
+        let header = FileHeader64::<NativeEndian>::parse(&*head).map_err(|_| invalid())?;
+        let program_headers = header
+            .program_headers(endian, &*head)
+            .map_err(|_| invalid())?;
+
+        let mut segments = Vec::new();
+        let mut threads = Vec::new();
+        let mut mappings = Vec::new();
+        let mut page_size = 0;
+        for ph in program_headers {
+            match ph.p_type(endian) {
+                PT_LOAD => segments.push((
+                    ph.p_vaddr(endian) as usize,
+                    ph.p_offset(endian),
+                    ph.p_filesz(endian) as usize,
+                )),
+                PT_NOTE => {
+                    let mut data = vec![0; ph.p_filesz(endian) as usize];
+                    read_at(&file, &mut data, ph.p_offset(endian))?;
+                    let mut notes = &data[..];
+                    while !notes.is_empty() {
+                        let (name, n_type, desc) = next_note(&mut notes).ok_or_else(invalid)?;
+                        if name != b"CORE\0" {
+                            continue;
+                        }
+                        match n_type {
+                            NT_PRSTATUS => threads.extend(parse_prstatus(desc)),
+                            NT_FILE => {
+                                let (size, files) = parse_nt_file(desc).ok_or_else(invalid)?;
+                                page_size = size;
+                                mappings = files;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        segments.sort_by_key(|&(address, _, _)| address);
+
+        let mappings = mappings
+            .into_iter()
+            .map(|(start, end, offset, path)| (start, end, offset, locate(&path)))
+            .filter(|(_, _, _, path)| is_elf(path));
+        let modules = imp::load_modules(mappings, page_size);
+        Ok(CoreDump {
+            file,
+            segments,
+            threads,
+            modules,
+        })
+    }
+
+    /// Returns the identifiers of the threads in the core, starting with the
+    /// one which received the signal the core was written for.
+    pub fn threads(&self) -> Vec<u32> {
+        self.threads.iter().map(|t| t.tid).collect()
+    }
+
+    /// Returns the signal the core was written for, such as `SIGSEGV` when
+    /// the process crashed, if there was one.
+    pub fn signal(&self) -> Option<i32> {
+        match self.threads.first()?.signal {
+            0 => None,
+            signal => Some(signal),
+        }
+    }
+
+    /// Walks the stack of the thread `tid` as it was when the core was
+    /// written, returning its frames innermost first.
+    ///
+    /// The first frame is the one the thread was executing, which for the
+    /// thread which crashed is the one which received the signal. At most 256
+    /// frames are returned. Returns an error if there's no thread `tid` in
+    /// the core.
+    pub fn trace_thread(&self, tid: u32) -> io::Result<Vec<RemoteFrame>> {
+        let thread = match self.threads.iter().find(|t| t.tid == tid) {
+            Some(thread) => thread,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no such thread in the core dump",
+                ))
+            }
+        };
+        Ok(trace(&self.modules, &|addr| self.read(addr), thread.regs))
+    }
+
+    /// Resolves the symbols of `frame`, a frame of a thread of this core,
+    /// passing them to `cb`.
+    ///
+    /// This is the same as `Process::resolve`.
+    pub fn resolve<F: FnMut(&Symbol)>(&mut self, frame: &RemoteFrame, cb: F) {
+        resolve(&mut self.modules, frame, cb)
+    }
+
+    /// Returns the path the object file of the module containing `frame` was
+    /// read from, if it's in one.
+    pub fn module_path(&self, frame: &RemoteFrame) -> Option<&Path> {
+        Some(&self.modules.get(frame.module?)?.path)
+    }
+
+    /// Reads a word at `addr` from the memory saved in the core.
+    fn read(&self, addr: usize) -> Option<usize> {
+        let index = self
+            .segments
+            .partition_point(|&(address, _, _)| address <= addr)
+            .checked_sub(1)?;
+        let (address, offset, size) = self.segments[index];
+        if addr - address + mem::size_of::<usize>() > size {
+            return None;
+        }
+        let mut word = [0; mem::size_of::<usize>()];
+        read_at(&self.file, &mut word, offset + (addr - address) as u64).ok()?;
+        Some(usize::from_ne_bytes(word))
+    }
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "not an ELF core file")
+}
+
+/// Splits the next note off `notes`, returning its name, type and
+/// description.
+fn next_note<'a>(notes: &mut &'a [u8]) -> Option<(&'a [u8], u32, &'a [u8])> {
+    let field = |i: usize| {
+        Some(u32::from_ne_bytes(
+            notes.get(i * 4..i * 4 + 4)?.try_into().ok()?,
+        ))
+    };
+    let name_size = field(0)? as usize;
+    let desc_size = field(1)? as usize;
+    let n_type = field(2)?;
+    // The name and the description are each padded to 4 bytes in cores.
+    let pad = |size: usize| (size + 3) & !3;
+    let name = notes.get(12..12 + name_size)?;
+    let desc_start = 12 + pad(name_size);
+    let desc = notes.get(desc_start..desc_start + desc_size)?;
+    *notes = notes.get(desc_start + pad(desc_size)..).unwrap_or(&[]);
+    Some((name, n_type, desc))
+}
+
+/// Parses the thread identifier, signal and registers out of a
+/// `NT_PRSTATUS` note.
+fn parse_prstatus(desc: &[u8]) -> Option<Thread> {
+    // The offsets of `pr_cursig` and `pr_pid` in `struct elf_prstatus`.
+    let signal = i16::from_ne_bytes(desc.get(12..14)?.try_into().ok()?);
+    let tid = u32::from_ne_bytes(desc.get(32..36)?.try_into().ok()?);
+    Some(Thread {
+        tid,
+        signal: signal.into(),
+        regs: imp::prstatus_registers(desc)?,
+    })
+}
+
+/// Parses the page size and the file-backed mappings, as start, end, file
+/// offset and path, out of a `NT_FILE` note.
+fn parse_nt_file(desc: &[u8]) -> Option<(u64, Vec<Mapping>)> {
+    const WORD: usize = mem::size_of::<usize>();
+    let word = |i: usize| {
+        let bytes = desc.get(i * WORD..(i + 1) * WORD)?;
+        Some(usize::from_ne_bytes(bytes.try_into().ok()?))
+    };
+    let count = word(0)?;
+    let page_size = word(1)?;
+    // The start, end and offset in pages of each mapping are followed by
+    // their paths, each terminated by a nul byte.
+    let mut paths = desc
+        .get(2 * WORD + count.checked_mul(3 * WORD)?..)?
+        .split(|&b| b == 0);
+    let mut mappings = Vec::with_capacity(count);
+    for i in 0..count {
+        let entry = 2 + 3 * i;
+        mappings.push((
+            word(entry)?,
+            word(entry + 1)?,
+            word(entry + 2)?.checked_mul(page_size)?,
+            path_from_bytes(paths.next()?),
+        ));
+    }
+    Some((page_size as u64, mappings))
+}
+
+/// Returns whether `path` starts like an ELF object file, so that other files
+/// the process mapped, which may be far larger, aren't read whole.
+fn is_elf(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    match File::open(path) {
+        Ok(file) => read_at(&file, &mut magic, 0).is_ok() && magic == ELFMAG,
+        Err(_) => false,
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(not(unix))]
+fn read_at(_file: &File, _buf: &mut [u8], _offset: u64) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "reading core dumps isn't supported on this platform",
+    ))
+}
@@ -0,0 +1,196 @@
+//! Capturing backtraces for only some of many events.
+//!
+//! Attaching a backtrace to every error is invaluable until errors happen by
+//! the thousands, at which point capturing and resolving them can take more
+//! time than anything else the program does. The helpers in this module
+//! capture a backtrace only for a sample of calls, returning `None` for the
+//! rest, so that a library can attach backtraces to its errors at a bounded
+//! cost:
+//!
+//! * `maybe_capture` captures with a fixed probability, which keeps the cost
+//!   proportional to the number of events.
+//! * `RateLimiter` captures at most a number of backtraces per second, after
+//!   an initial burst, which bounds the cost however many events there are.
+//!
+//! Backtraces are captured like `Backtrace::new` does, so they can be
+//! configured with `with_config`, for example to not resolve symbols right
+//! away.
+//!
+//! # Examples
+//!
+//! ```
+//! use backtrace::sampled::RateLimiter;
+//! use backtrace::Backtrace;
+//!
+//! struct Error {
+//!     backtrace: Option<Backtrace>,
+//! }
+//!
+//! fn fail(limiter: &RateLimiter) -> Error {
+//!     Error {
+//!         backtrace: limiter.capture(),
+//!     }
+//! }
+//!
+//! let limiter = RateLimiter::new(10.0, 2);
+//! let errors = (0..100).map(|_| fail(&limiter)).collect::<Vec<_>>();
+//! assert!(errors.iter().filter(|e| e.backtrace.is_some()).count() < 100);
+//! ```
+//!
+//! # Required features
+//!
+//! This module requires the `std` feature of the `backtrace` crate to be
+//! enabled, and the `std` feature is enabled by default.
+
+use crate::Backtrace;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Captures a backtrace with probability `rate`, returning `None` otherwise.
+///
+/// A `rate` of `1.0` or more always captures, and one of `0.0` or less never
+/// does. Each call decides independently, with a random number generator
+/// local to the thread which is cheap enough to call on every event.
+///
+/// # Examples
+///
+/// ```
+/// // Capture a backtrace for roughly one in a hundred events.
+/// let backtrace = backtrace::sampled::maybe_capture(0.01);
+/// # drop(backtrace);
+/// assert!(backtrace::sampled::maybe_capture(1.0).is_some());
+/// assert!(backtrace::sampled::maybe_capture(0.0).is_none());
+/// ```
+#[inline(never)] // want to make sure there's a frame here to remove
+pub fn maybe_capture(rate: f64) -> Option<Backtrace> {
+    if sample(rate) {
+        Some(Backtrace::new_from(maybe_capture as usize))
+    } else {
+        None
+    }
+}
+
+/// Returns `true` with probability `rate`.
+fn sample(rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    if rate.is_nan() || rate <= 0.0 {
+        return false;
+    }
+    // The top 53 bits of a random number are uniformly distributed over the
+    // values that an `f64` in `0.0..1.0` can take.
+    let x = (random() >> 11) as f64 / (1u64 << 53) as f64;
+    x < rate
+}
+
+/// Returns a random number from a xorshift generator local to the calling
+/// thread, seeded from the randomness of the standard library's hash maps.
+fn random() -> u64 {
+    thread_local!(static STATE: Cell<u64> = Cell::new(seed()));
+
+    fn seed() -> u64 {
+        // Zero is the only state xorshift can't leave.
+        RandomState::new().build_hasher().finish() | 1
+    }
+
+    // The generator may be gone while the thread is exiting, in which case a
+    // fresh one is used.
+    let step = |state: &Cell<u64>| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    };
+    STATE
+        .try_with(step)
+        .unwrap_or_else(|_| step(&Cell::new(seed())))
+}
+
+/// A token bucket limiting how many backtraces are captured over time.
+///
+/// The bucket holds up to `burst` tokens and starts out full. Each capture
+/// takes one token, and tokens are added back at `per_second` per second, so
+/// that after an initial burst backtraces are captured at that rate at most.
+/// Calls made while the bucket is empty return `None` without capturing.
+///
+/// A limiter can be shared between threads, and typically one is shared by
+/// all errors of a library so that they're limited together.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::sampled::RateLimiter;
+///
+/// // Allow bursts of five backtraces, and one per second after that.
+/// let limiter = RateLimiter::new(1.0, 5);
+/// let captured = (0..10).filter_map(|_| limiter.capture()).count();
+/// assert!(captured >= 5 && captured < 10);
+/// ```
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a limiter which allows bursts of up to `burst` backtraces and
+    /// `per_second` backtraces per second after that.
+    pub fn new(per_second: f64, burst: u32) -> RateLimiter {
+        RateLimiter {
+            per_second: per_second.max(0.0),
+            burst: f64::from(burst),
+            state: Mutex::new(Bucket {
+                tokens: f64::from(burst),
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token from the bucket if there is one, returning whether there
+    /// was.
+    ///
+    /// This allows the limit to be applied to something other than capturing
+    /// a backtrace, such as capturing one with `Backtrace::builder`.
+    pub fn try_acquire(&self) -> bool {
+        // The bucket is always left consistent, so it's still usable if a
+        // thread panicked while holding the lock.
+        let mut bucket = match self.state.lock() {
+            Ok(bucket) => bucket,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.per_second).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Captures a backtrace if the limit allows it, returning `None`
+    /// otherwise.
+    #[inline(never)] // want to make sure there's a frame here to remove
+    pub fn capture(&self) -> Option<Backtrace> {
+        if self.try_acquire() {
+            Some(Backtrace::new_from(Self::capture as usize))
+        } else {
+            None
+        }
+    }
+}
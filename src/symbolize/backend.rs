@@ -0,0 +1,277 @@
+//! Replacing how addresses are symbolicated with `set_symbolizer`.
+
+use super::{BytesOrWideString, ResolveWhat, Symbol, SymbolImp, SymbolName};
+use core::ffi::c_void;
+use std::path::{Path, PathBuf};
+use std::prelude::v1::*;
+use std::sync::Arc;
+
+/// A way of finding the symbols for addresses, which can replace the one built
+/// into this crate through `set_symbolizer`.
+///
+/// Applications which already know the symbols of their code, for example
+/// from symbol maps parsed ahead of time, can implement this to have
+/// `resolve`, `Backtrace` and everything else in this crate use those
+/// instead of reading the debug information of each module.
+/// Implementations can fall back to `NativeSymbolizer`, which is how this
+/// crate symbolicates addresses otherwise, for addresses they don't know.
+///
+/// # Required features
+///
+/// This trait requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub trait SymbolizeBackend: Send + Sync {
+    /// Finds the symbols of `addr`, calling `cb` with each of them.
+    ///
+    /// This has the same contract as `backtrace::resolve`: `addr` is usually
+    /// the instruction pointer of a frame and is treated as a return address,
+    /// so what should be looked up is the instruction before it. If the
+    /// address is in inlined code `cb` is called for each inlined function,
+    /// innermost first, and it isn't called at all if nothing is found.
+    /// Symbols to pass to `cb` can be created with `Symbol::new`.
+    ///
+    /// This is called with this crate's lock held, so it must not capture or
+    /// resolve backtraces on other threads and wait for them.
+    fn resolve(&self, addr: *mut c_void, cb: &mut dyn FnMut(&Symbol));
+}
+
+/// The symbolication built into this crate, which is used unless
+/// `set_symbolizer` replaces it.
+///
+/// Addresses are looked up in the code registered with `backtrace::jit`, and
+/// then in the debug information or symbol tables of the loaded modules with
+/// the platform's backend, such as gimli or dbghelp.
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NativeSymbolizer;
+
+impl SymbolizeBackend for NativeSymbolizer {
+    fn resolve(&self, addr: *mut c_void, cb: &mut dyn FnMut(&Symbol)) {
+        let _guard = crate::lock::lock();
+        unsafe { super::resolve_native(ResolveWhat::Address(addr), cb) }
+    }
+}
+
+// The backend registered through `set_symbolizer`, if any. This is guarded
+// by the global lock in `crate::lock`, and reference counted so that a
+// backend can be replaced from inside its own `resolve`.
+static mut SYMBOLIZER: Option<Arc<dyn SymbolizeBackend>> = None;
+
+/// Replaces how this crate finds the symbols of addresses with `backend`.
+///
+/// Every function which resolves symbols, including `resolve`,
+/// `resolve_frame` and `Backtrace::resolve`, calls `backend` instead of
+/// `NativeSymbolizer` afterwards. There's one backend per process, and each
+/// call replaces the previous one; passing `NativeSymbolizer` restores the
+/// default.
+///
+/// Note that frames are resolved by their address alone with a custom
+/// backend, so some information which `resolve_frame` can provide natively,
+/// such as inlined functions with dbghelp, may be missing.
+///
+/// # Examples
+///
+/// ```
+/// use backtrace::{NativeSymbolizer, Symbol, SymbolizeBackend};
+/// use std::ffi::c_void;
+///
+/// struct Maps;
+///
+/// impl SymbolizeBackend for Maps {
+///     fn resolve(&self, addr: *mut c_void, cb: &mut dyn FnMut(&Symbol)) {
+///         // Look `addr` up in the application's symbol maps here, and
+///         // otherwise use what this crate would do.
+///         if addr.is_null() {
+///             return cb(&Symbol::new(b"null").with_lineno(1));
+///         }
+///         NativeSymbolizer.resolve(addr, cb)
+///     }
+/// }
+///
+/// backtrace::set_symbolizer(Box::new(Maps));
+/// backtrace::resolve(std::ptr::null_mut(), |symbol| {
+///     assert_eq!(symbol.name().unwrap().as_bytes(), b"null");
+/// });
+/// backtrace::set_symbolizer(Box::new(NativeSymbolizer));
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn set_symbolizer(backend: Box<dyn SymbolizeBackend>) {
+    let _guard = crate::lock::lock();
+    unsafe {
+        SYMBOLIZER = Some(Arc::from(backend));
+    }
+}
+
+/// Returns the backend registered through `set_symbolizer`, if any.
+///
+/// This is required to be externally synchronized, like the native backends.
+pub(super) unsafe fn current() -> Option<Arc<dyn SymbolizeBackend>> {
+    (*core::ptr::addr_of!(SYMBOLIZER)).clone()
+}
+
+/// A symbol created by a `SymbolizeBackend`, which is what `Symbol` wraps
+/// for those.
+pub(super) struct Owned {
+    pub(super) name: Vec<u8>,
+    pub(super) addr: Option<usize>,
+    pub(super) size: Option<usize>,
+    pub(super) filename: Option<PathBuf>,
+    pub(super) lineno: Option<u32>,
+    pub(super) colno: Option<u32>,
+}
+
+impl Owned {
+    pub fn name(&self) -> Option<SymbolName<'_>> {
+        Some(SymbolName::new(&self.name))
+    }
+
+    pub fn addr(&self) -> Option<*mut c_void> {
+        self.addr.map(|addr| addr as *mut c_void)
+    }
+
+    pub fn offset_in_module(&self) -> Option<usize> {
+        None
+    }
+
+    pub fn module_build_id(&self) -> Option<&[u8]> {
+        None
+    }
+
+    pub fn offset_in_symbol(&self) -> Option<usize> {
+        None
+    }
+
+    pub fn function_start(&self) -> Option<*mut c_void> {
+        self.addr()
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        self.size
+    }
+
+    pub fn filename_raw(&self) -> Option<BytesOrWideString<'_>> {
+        let file = self.filename.as_ref()?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            Some(BytesOrWideString::Bytes(file.as_os_str().as_bytes()))
+        }
+        #[cfg(not(unix))]
+        {
+            file.to_str()
+                .map(|s| BytesOrWideString::Bytes(s.as_bytes()))
+        }
+    }
+
+    pub fn filename(&self) -> Option<&Path> {
+        self.filename.as_deref()
+    }
+
+    pub fn lineno(&self) -> Option<u32> {
+        self.lineno
+    }
+
+    pub fn colno(&self) -> Option<u32> {
+        self.colno
+    }
+}
+
+impl Symbol {
+    /// Creates a symbol for the function `name`, without any other
+    /// information, for a `SymbolizeBackend` to pass to its callback.
+    ///
+    /// The name is demangled like that of any other symbol.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn new(name: &[u8]) -> Symbol {
+        Symbol {
+            inner: SymbolImp::Owned(Owned {
+                name: name.to_vec(),
+                addr: None,
+                size: None,
+                filename: None,
+                lineno: None,
+                colno: None,
+            }),
+        }
+    }
+
+    /// Sets the starting address of the function, as returned by `addr`.
+    ///
+    /// This, like the other `with_*` methods, only has an effect on symbols
+    /// created with `Symbol::new`.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn with_addr(mut self, addr: *mut c_void) -> Symbol {
+        if let SymbolImp::Owned(ref mut s) = self.inner {
+            s.addr = Some(addr as usize);
+        }
+        self
+    }
+
+    /// Sets the size of the function in bytes, as returned by `size`.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn with_size(mut self, size: usize) -> Symbol {
+        if let SymbolImp::Owned(ref mut s) = self.inner {
+            s.size = Some(size);
+        }
+        self
+    }
+
+    /// Sets the file the function was defined in, as returned by `filename`.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn with_filename(mut self, filename: &Path) -> Symbol {
+        if let SymbolImp::Owned(ref mut s) = self.inner {
+            s.filename = Some(filename.to_path_buf());
+        }
+        self
+    }
+
+    /// Sets the line number, as returned by `lineno`.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn with_lineno(mut self, lineno: u32) -> Symbol {
+        if let SymbolImp::Owned(ref mut s) = self.inner {
+            s.lineno = Some(lineno);
+        }
+        self
+    }
+
+    /// Sets the column number, as returned by `colno`.
+    ///
+    /// # Required features
+    ///
+    /// This function requires the `std` feature of the `backtrace` crate to be
+    /// enabled, and the `std` feature is enabled by default.
+    pub fn with_colno(mut self, colno: u32) -> Symbol {
+        if let SymbolImp::Owned(ref mut s) = self.inner {
+            s.colno = Some(colno);
+        }
+        self
+    }
+}
@@ -0,0 +1,107 @@
+use crate::BacktraceSymbol;
+use core::ffi::c_void;
+use std::collections::HashMap;
+use std::prelude::v1::*;
+
+/// A cache of resolved symbols, keyed by address.
+///
+/// Resolving the same addresses over and over, for example when capturing
+/// backtraces at the same call sites repeatedly, does the same work each
+/// time. This type remembers the symbols that addresses resolved to, so that
+/// only the first lookup of each address has to consult debug information.
+///
+/// The cache holds a bounded number of addresses and evicts the least
+/// recently used one when it's full. Resolution is based on the code which is
+/// loaded at the time, so if libraries are unloaded the cache should be
+/// cleared with `clear` as their addresses may be reused.
+///
+/// # Examples
+///
+/// ```
+/// let mut cache = backtrace::SymbolCache::new(1024);
+/// backtrace::trace(|frame| {
+///     for symbol in cache.resolve(frame.ip()) {
+///         println!("{:?}", symbol.name());
+///     }
+///     true
+/// });
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Debug)]
+pub struct SymbolCache {
+    capacity: usize,
+    // Each address's symbols together with when they were last used, which is
+    // a counter incremented on every lookup.
+    entries: HashMap<usize, (u64, Box<[BacktraceSymbol]>)>,
+    clock: u64,
+}
+
+impl SymbolCache {
+    /// Creates an empty cache which holds the symbols of at most `capacity`
+    /// addresses, or of one address if `capacity` is zero.
+    pub fn new(capacity: usize) -> SymbolCache {
+        SymbolCache {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Returns the symbols that `addr` resolves to, the same as the symbols
+    /// passed to the closure of `resolve`.
+    ///
+    /// If `addr` is in the cache its symbols are returned without resolving
+    /// it again. Otherwise it's resolved and added to the cache, which evicts
+    /// the least recently used address if the cache is full.
+    pub fn resolve(&mut self, addr: *mut c_void) -> &[BacktraceSymbol] {
+        self.clock += 1;
+        let key = addr as usize;
+        if !self.entries.contains_key(&key) {
+            if self.entries.len() >= self.capacity {
+                self.evict();
+            }
+            let mut symbols = Vec::new();
+            crate::resolve(addr, |symbol| symbols.push(BacktraceSymbol::new(symbol)));
+            crate::capture::set_inline_depths(&mut symbols);
+            self.entries.insert(key, (0, symbols.into_boxed_slice()));
+        }
+        let entry = self.entries.get_mut(&key).unwrap();
+        entry.0 = self.clock;
+        &entry.1
+    }
+
+    /// Returns the number of addresses in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the cache holds no addresses.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes every address from the cache.
+    ///
+    /// This should be called after libraries are unloaded, as the cached
+    /// symbols of their addresses are no longer valid.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // This scans the whole cache, but it's only done before resolving an
+    // address which is far more expensive anyway.
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, (used, _))| *used)
+            .map(|(key, _)| *key);
+        if let Some(key) = oldest {
+            self.entries.remove(&key);
+        }
+    }
+}
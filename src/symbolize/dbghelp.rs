@@ -14,10 +14,15 @@
 //! converting back and forth between Windows types and Rust types. For example
 //! symbols come to us as wide strings which we then convert to utf-8 strings if
 //! we can.
+//!
+//! With the `pdb` feature the PDBs of modules are read directly instead, see
+//! `pdb.rs`, and dbghelp is only used for modules whose PDB isn't found.
 
 #![allow(bad_style)]
 
-use super::super::{backtrace::StackFrame, dbghelp, windows::*};
+#[cfg(any(feature = "trace", backtrace_in_libstd))]
+use super::super::backtrace::StackFrame;
+use super::super::{dbghelp, windows::*};
 use super::{BytesOrWideString, ResolveWhat, SymbolName};
 use core::char;
 use core::ffi::c_void;
@@ -25,15 +30,22 @@ use core::marker;
 use core::mem;
 use core::slice;
 
+#[cfg(feature = "pdb")]
+mod pdb;
+
 // Store an OsString on std so we can provide the symbol name and filename.
 pub struct Symbol<'a> {
     name: *const [u8],
     addr: *mut c_void,
+    offset_in_module: usize,
+    offset_in_symbol: usize,
+    size: Option<usize>,
+    module_base: usize,
     line: Option<u32>,
     filename: Option<*const [u16]>,
-    #[cfg(feature = "std")]
+    #[cfg(feature = "std-base")]
     _filename_cache: Option<::std::ffi::OsString>,
-    #[cfg(not(feature = "std"))]
+    #[cfg(not(feature = "std-base"))]
     _filename_cache: (),
     _marker: marker::PhantomData<&'a i32>,
 }
@@ -47,6 +59,26 @@ impl Symbol<'_> {
         Some(self.addr as *mut _)
     }
 
+    pub fn offset_in_module(&self) -> Option<usize> {
+        Some(self.offset_in_module)
+    }
+
+    pub fn module_build_id(&self) -> Option<&[u8]> {
+        unsafe { loaded_image_build_id(self.module_base) }
+    }
+
+    pub fn offset_in_symbol(&self) -> Option<usize> {
+        Some(self.offset_in_symbol)
+    }
+
+    pub fn function_start(&self) -> Option<*mut c_void> {
+        Some(self.addr as *mut _)
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        self.size
+    }
+
     pub fn filename_raw(&self) -> Option<BytesOrWideString<'_>> {
         self.filename
             .map(|slice| unsafe { BytesOrWideString::Wide(&*slice) })
@@ -60,7 +92,7 @@ impl Symbol<'_> {
         self.line
     }
 
-    #[cfg(feature = "std")]
+    #[cfg(feature = "std-base")]
     pub fn filename(&self) -> Option<&::std::path::Path> {
         use std::path::Path;
 
@@ -72,6 +104,15 @@ impl Symbol<'_> {
 struct Aligned8<T>(T);
 
 pub unsafe fn resolve(what: ResolveWhat<'_>, cb: &mut dyn FnMut(&super::Symbol)) {
+    // Modules whose PDB is found are symbolicated without ever loading
+    // dbghelp, which is only initialized for the others.
+    #[cfg(feature = "pdb")]
+    {
+        if pdb::resolve(what.address_or_ip(), cb) {
+            return;
+        }
+    }
+
     // Ensure this process's symbols are initialized
     let dbghelp = match dbghelp::init() {
         Ok(dbghelp) => dbghelp,
@@ -79,11 +120,16 @@ pub unsafe fn resolve(what: ResolveWhat<'_>, cb: &mut dyn FnMut(&super::Symbol))
     };
 
     match what {
-        ResolveWhat::Address(_) => resolve_without_inline(&dbghelp, what.address_or_ip(), cb),
+        ResolveWhat::Address(_) | ResolveWhat::Precise(_) => {
+            resolve_without_inline(&dbghelp, what.address_or_ip(), cb)
+        }
+        #[cfg(any(feature = "trace", backtrace_in_libstd))]
         ResolveWhat::Frame(frame) => match &frame.inner.stack_frame {
             StackFrame::New(frame) => resolve_with_inline(&dbghelp, frame, cb),
             StackFrame::Old(_) => resolve_without_inline(&dbghelp, frame.ip(), cb),
         },
+        #[cfg(not(any(feature = "trace", backtrace_in_libstd)))]
+        ResolveWhat::Never(_, never) => match never {},
     }
 }
 
@@ -92,11 +138,13 @@ unsafe fn resolve_with_inline(
     frame: &STACKFRAME_EX,
     cb: &mut dyn FnMut(&super::Symbol),
 ) {
+    let addr = super::super::adjust_ip(frame.AddrPC.Offset as *mut _) as u64;
     do_resolve(
+        addr,
         |info| {
             dbghelp.SymFromInlineContextW()(
                 GetCurrentProcess(),
-                super::adjust_ip(frame.AddrPC.Offset as *mut _) as u64,
+                addr,
                 frame.InlineFrameContext,
                 &mut 0,
                 info,
@@ -105,7 +153,7 @@ unsafe fn resolve_with_inline(
         |line| {
             dbghelp.SymGetLineFromInlineContextW()(
                 GetCurrentProcess(),
-                super::adjust_ip(frame.AddrPC.Offset as *mut _) as u64,
+                addr,
                 frame.InlineFrameContext,
                 0,
                 &mut 0,
@@ -122,6 +170,7 @@ unsafe fn resolve_without_inline(
     cb: &mut dyn FnMut(&super::Symbol),
 ) {
     do_resolve(
+        addr as DWORD64,
         |info| dbghelp.SymFromAddrW()(GetCurrentProcess(), addr as DWORD64, &mut 0, info),
         |line| dbghelp.SymGetLineFromAddrW64()(GetCurrentProcess(), addr as DWORD64, &mut 0, line),
         cb,
@@ -129,6 +178,7 @@ unsafe fn resolve_without_inline(
 }
 
 unsafe fn do_resolve(
+    addr: DWORD64,
     sym_from_addr: impl FnOnce(*mut SYMBOL_INFOW) -> BOOL,
     get_line_from_addr: impl FnOnce(&mut IMAGEHLP_LINEW64) -> BOOL,
     cb: &mut dyn FnMut(&super::Symbol),
@@ -195,24 +245,216 @@ unsafe fn do_resolve(
     }
 
     cb(&super::Symbol {
-        inner: Symbol {
+        inner: super::SymbolImp::Native(Symbol {
             name,
             addr: info.Address as *mut _,
+            offset_in_module: addr.wrapping_sub(info.ModBase) as usize,
+            offset_in_symbol: addr.wrapping_sub(info.Address) as usize,
+            // dbghelp reports a size of 0 if it doesn't know it.
+            size: match info.Size {
+                0 => None,
+                size => Some(size as usize),
+            },
+            module_base: info.ModBase as usize,
             line: lineno,
             filename,
             _filename_cache: cache(filename),
             _marker: marker::PhantomData,
-        },
+        }),
     })
 }
 
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 unsafe fn cache(filename: Option<*const [u16]>) -> Option<::std::ffi::OsString> {
     use std::os::windows::ffi::OsStringExt;
-    filename.map(|f| ::std::ffi::OsString::from_wide(&*f))
+    filename.map(|f| {
+        let file = ::std::ffi::OsString::from_wide(&*f);
+        match crate::print::current_source_map() {
+            Some(map) => map
+                .apply(::std::path::Path::new(&file))
+                .into_owned()
+                .into_os_string(),
+            None => file,
+        }
+    })
 }
 
-#[cfg(not(feature = "std"))]
+#[cfg(not(feature = "std-base"))]
 unsafe fn cache(_filename: Option<*const [u16]>) {}
 
-pub unsafe fn clear_symbol_cache() {}
+pub unsafe fn clear_symbol_cache() {
+    #[cfg(feature = "pdb")]
+    pdb::clear();
+}
+
+pub unsafe fn preload() {
+    drop(dbghelp::init());
+}
+
+pub unsafe fn unload() {
+    #[cfg(feature = "pdb")]
+    pdb::clear();
+    dbghelp::cleanup();
+}
+
+pub unsafe fn batch(f: &mut dyn FnMut()) {
+    f()
+}
+
+/// Offline symbolication isn't supported by this backend, so a resolver can
+/// never be created.
+#[cfg(feature = "std-base")]
+pub enum OfflineResolver {}
+
+#[cfg(feature = "std-base")]
+impl OfflineResolver {
+    pub fn new(_path: &::std::path::Path) -> Option<OfflineResolver> {
+        None
+    }
+
+    pub fn resolve(&mut self, _addr: usize, _cb: &mut dyn FnMut(&super::Symbol)) {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "std-base")]
+pub unsafe fn module_for_address(addr: *mut c_void) -> Option<super::Module> {
+    let mut ret = None;
+    each_module(|me| {
+        let base = me.modBaseAddr as usize;
+        if (base..base + me.modBaseSize as usize).contains(&(addr as usize)) {
+            ret = Some(module(me));
+            false
+        } else {
+            true
+        }
+    });
+    ret
+}
+
+// dbghelp doesn't report why it couldn't find a symbol, so only whether the
+// address is in a module at all can be told apart.
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub unsafe fn diagnose(addr: *mut c_void) -> super::ResolveError {
+    match module_for_address(addr) {
+        Some(_) => super::ResolveError::NoSymbol,
+        None => super::ResolveError::NoModule,
+    }
+}
+
+#[cfg(feature = "std-base")]
+pub unsafe fn loaded_modules() -> std::vec::Vec<super::Module> {
+    let mut ret = std::vec::Vec::new();
+    each_module(|me| {
+        ret.push(module(me));
+        true
+    });
+    ret
+}
+
+/// Calls `f` with each module of the current process until it returns
+/// `false`.
+#[cfg(feature = "std-base")]
+unsafe fn each_module(mut f: impl FnMut(&MODULEENTRY32W) -> bool) {
+    let snap = CreateToolhelp32Snapshot(TH32CS_SNAPMODULE, 0);
+    if snap == INVALID_HANDLE_VALUE {
+        return;
+    }
+
+    let mut me = mem::MaybeUninit::<MODULEENTRY32W>::zeroed().assume_init();
+    me.dwSize = mem::size_of_val(&me) as DWORD;
+    if Module32FirstW(snap, &mut me) == TRUE {
+        while f(&me) && Module32NextW(snap, &mut me) == TRUE {}
+    }
+
+    CloseHandle(snap);
+}
+
+#[cfg(feature = "std-base")]
+unsafe fn module(me: &MODULEENTRY32W) -> super::Module {
+    use std::os::windows::prelude::*;
+
+    let base = me.modBaseAddr as usize;
+    let size = me.modBaseSize as usize;
+    let pos = me
+        .szExePath
+        .iter()
+        .position(|i| *i == 0)
+        .unwrap_or(me.szExePath.len());
+    super::Module {
+        path: ::std::ffi::OsString::from_wide(&me.szExePath[..pos]).into(),
+        base_address: base,
+        // Tools working with PDBs expect addresses relative to the load
+        // address (RVAs) rather than as stated in the image, which assumes
+        // it's loaded at its preferred base.
+        bias: base,
+        size,
+        build_id: image_build_id(slice::from_raw_parts(base as *const u8, size))
+            .map(<[u8]>::to_vec),
+    }
+}
+
+#[cfg(target_pointer_width = "32")]
+type Pe = object::pe::ImageNtHeaders32;
+#[cfg(target_pointer_width = "64")]
+type Pe = object::pe::ImageNtHeaders64;
+
+/// Reads the CodeView GUID and age of the image loaded at `base`, which is
+/// sized by its own headers.
+unsafe fn loaded_image_build_id<'a>(base: usize) -> Option<&'a [u8]> {
+    use object::pe::{ImageDataDirectory, ImageDosHeader, IMAGE_NUMBEROF_DIRECTORY_ENTRIES};
+    use object::read::pe::{ImageNtHeaders, ImageOptionalHeader};
+
+    if base == 0 {
+        return None;
+    }
+    // The headers are always mapped along with the image, and the data
+    // directories are the last thing in them that `parse` reads.
+    let dos_size = mem::size_of::<ImageDosHeader>();
+    let dos_header =
+        ImageDosHeader::parse(slice::from_raw_parts(base as *const u8, dos_size)).ok()?;
+    let mut offset = dos_header.nt_headers_offset().into();
+    let headers_size = offset as usize
+        + mem::size_of::<Pe>()
+        + IMAGE_NUMBEROF_DIRECTORY_ENTRIES * mem::size_of::<ImageDataDirectory>();
+    let headers = slice::from_raw_parts(base as *const u8, headers_size);
+    let (nt_headers, _) = Pe::parse(headers, &mut offset).ok()?;
+    let size = nt_headers.optional_header().size_of_image() as usize;
+    image_build_id(slice::from_raw_parts(base as *const u8, size))
+}
+
+/// Reads the CodeView GUID and age out of an image loaded into memory.
+fn image_build_id(image: &[u8]) -> Option<&[u8]> {
+    codeview_record(image).map(|record| &record[4..24])
+}
+
+/// Finds the CodeView record of an image loaded into memory, which is the
+/// `RSDS` signature followed by the GUID, the age and the nul-terminated path
+/// of the PDB.
+///
+/// A loaded image is laid out by RVA rather than by file offset, so the debug
+/// directory and the CodeView record it points at can be read straight out of
+/// `image` without consulting the section table.
+fn codeview_record(image: &[u8]) -> Option<&[u8]> {
+    use object::pe::{self, ImageDebugDirectory, ImageDosHeader};
+    use object::read::pe::ImageNtHeaders;
+    use object::LittleEndian as LE;
+
+    let dos_header = ImageDosHeader::parse(image).ok()?;
+    let mut offset = dos_header.nt_headers_offset().into();
+    let (_, data_directories) = Pe::parse(image, &mut offset).ok()?;
+    let dir = data_directories.get(pe::IMAGE_DIRECTORY_ENTRY_DEBUG)?;
+    let (start, size) = dir.address_range();
+    let data = image.get(start as usize..)?.get(..size as usize)?;
+    let count = data.len() / mem::size_of::<ImageDebugDirectory>();
+    let (entries, _) = object::pod::slice_from_bytes::<ImageDebugDirectory>(data, count).ok()?;
+    entries
+        .iter()
+        .filter(|entry| entry.typ.get(LE) == pe::IMAGE_DEBUG_TYPE_CODEVIEW)
+        .filter_map(|entry| {
+            let start = entry.address_of_raw_data.get(LE) as usize;
+            let size = entry.size_of_data.get(LE) as usize;
+            image.get(start..)?.get(..size)
+        })
+        .find(|record| record.len() >= 24 && record.starts_with(b"RSDS"))
+}
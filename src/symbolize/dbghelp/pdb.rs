@@ -0,0 +1,158 @@
+//! Symbolication reading PDBs with `pdb-addr2line`, used by the `pdb` feature
+//! before falling back to dbghelp.
+//!
+//! A module's PDB is found with the CodeView record of its debug directory,
+//! the record `Module::build_id` is read from. The path the linker wrote into
+//! it is tried first, followed by a file of the same name next to the module
+//! and then in each directory set with `set_search_paths`. A PDB is only used
+//! if its GUID and age match those of the record, so that one left behind by
+//! an earlier build isn't mistaken for the module's.
+
+use super::super::search_paths::search_paths;
+use core::ffi::c_void;
+use core::{marker, mem, ptr, str};
+use pdb_addr2line::pdb::PDB;
+use pdb_addr2line::{Context, ContextPdbData, TypeFormatterFlags};
+use std::boxed::Box;
+use std::fs::File;
+use std::path::Path;
+use std::vec::Vec;
+
+struct Mapping {
+    // 'static lifetime is a lie to hack around lack of support for
+    // self-referential structs, `cx` borrows from `_data`.
+    cx: Context<'static, 'static>,
+    _data: Box<ContextPdbData<'static, 'static, File>>,
+}
+
+/// The base address and size of each module looked up so far, along with its
+/// PDB or `None` if it has none which could be found. Guarded by the global
+/// lock in `crate::lock`.
+static mut MODULES: Vec<(usize, usize, Option<Mapping>)> = Vec::new();
+
+/// Resolves `addr` with the PDB of the module it's in, returning `false`
+/// without calling `cb` if there's no such PDB or it has no symbol for `addr`.
+///
+/// Like with dbghelp's inline frame support, `cb` is called with the
+/// innermost inlined function first, and the function they're all inlined
+/// into last.
+pub unsafe fn resolve(addr: *mut c_void, cb: &mut dyn FnMut(&super::super::Symbol)) -> bool {
+    let addr = addr as usize;
+    let modules = &mut *ptr::addr_of_mut!(MODULES);
+    let pos = modules
+        .iter()
+        .position(|(base, size, _)| (*base..*base + *size).contains(&addr));
+    let pos = match pos {
+        Some(pos) => pos,
+        None => {
+            let mut found = None;
+            super::each_module(|me| {
+                let base = me.modBaseAddr as usize;
+                let size = me.modBaseSize as usize;
+                if (base..base + size).contains(&addr) {
+                    found = Some((base, size, load(me)));
+                    false
+                } else {
+                    true
+                }
+            });
+            match found {
+                Some(module) => modules.push(module),
+                None => return false,
+            }
+            modules.len() - 1
+        }
+    };
+    let (base, _, mapping) = &modules[pos];
+    let mapping = match mapping {
+        Some(mapping) => mapping,
+        None => return false,
+    };
+    let function = match mapping.cx.find_frames((addr - base) as u32) {
+        Ok(Some(function)) => function,
+        _ => return false,
+    };
+
+    // Only the extent of the outermost function is known, which is used for
+    // the inlined ones too.
+    let start = base + function.start_rva as usize;
+    let size = function
+        .end_rva
+        .map(|end| end.wrapping_sub(function.start_rva) as usize);
+    for frame in function.frames.iter() {
+        let name = frame.function.as_deref().unwrap_or("");
+        let file = frame
+            .file
+            .as_ref()
+            .map(|file| file.encode_utf16().collect::<Vec<u16>>());
+        let filename = file.as_ref().map(|file| &file[..] as *const [u16]);
+        cb(&super::super::Symbol {
+            inner: super::super::SymbolImp::Native(super::Symbol {
+                name: name.as_bytes(),
+                addr: start as *mut c_void,
+                offset_in_module: addr - base,
+                offset_in_symbol: addr - start,
+                size,
+                module_base: *base,
+                line: frame.line,
+                filename,
+                _filename_cache: super::cache(filename),
+                _marker: marker::PhantomData,
+            }),
+        });
+    }
+    true
+}
+
+/// Forgets the PDBs read so far, closing their files.
+pub unsafe fn clear() {
+    (*ptr::addr_of_mut!(MODULES)).clear();
+}
+
+/// Finds and reads the PDB of the module `me`.
+unsafe fn load(me: &super::MODULEENTRY32W) -> Option<Mapping> {
+    let image = core::slice::from_raw_parts(me.modBaseAddr as *const u8, me.modBaseSize as usize);
+    let record = super::codeview_record(image)?;
+    let id = &record[4..24];
+    let path = &record[24..];
+    let path = &path[..path.iter().position(|b| *b == 0).unwrap_or(path.len())];
+    let path = Path::new(str::from_utf8(path).ok()?);
+    let name = path.file_name()?;
+
+    if let Some(mapping) = open(path, id) {
+        return Some(mapping);
+    }
+    let module = super::module(me).path;
+    if let Some(mapping) = module.parent().and_then(|dir| open(&dir.join(name), id)) {
+        return Some(mapping);
+    }
+    search_paths()
+        .iter()
+        .find_map(|dir| open(&dir.join(name), id))
+}
+
+/// Reads the PDB at `path` if it matches the CodeView GUID and age `id`.
+fn open(path: &Path, id: &[u8]) -> Option<Mapping> {
+    let mut pdb = PDB::open(File::open(path).ok()?).ok()?;
+    let info = pdb.pdb_information().ok()?;
+    // The age of the DBI stream is the one matching the image, as the
+    // information stream's can be greater once the PDB has been rewritten, so
+    // it's preferred like debuggers do.
+    let age = pdb
+        .debug_information()
+        .ok()
+        .and_then(|dbi| dbi.age())
+        .unwrap_or(info.age);
+    if id[..16] != info.guid.to_bytes_le() || id[16..] != age.to_le_bytes() {
+        return None;
+    }
+
+    let data = Box::new(ContextPdbData::try_from_pdb(pdb).ok()?);
+    // Names are reported without their argument types, like dbghelp does.
+    let flags = TypeFormatterFlags::default() | TypeFormatterFlags::NO_ARGUMENTS;
+    let cx = data.make_context_with_formatter_flags(flags).ok()?;
+    // Safety: `cx` only borrows from the heap allocation of `data`, which
+    // outlives it in the `Mapping` and is never moved out of.
+    let cx = unsafe { mem::transmute::<Context<'_, 'static>, Context<'static, 'static>>(cx) };
+    Some(Mapping { cx, _data: data })
+}
@@ -0,0 +1,101 @@
+//! Resolving addresses with `try_resolve`, which explains failures.
+
+use super::{backend, imp, resolve_imp, ResolveWhat};
+use crate::BacktraceSymbol;
+use core::ffi::c_void;
+use std::fmt;
+use std::prelude::v1::*;
+
+/// Why `try_resolve` couldn't find any symbols for an address.
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The address isn't in any module loaded into the process, nor in code
+    /// registered with `backtrace::jit`.
+    NoModule,
+    /// The address is in a module, but the module has neither debug
+    /// information nor a symbol table that could be read, for example because
+    /// it was stripped or its file can't be opened anymore.
+    NoDebugInfo,
+    /// The module the address is in has debug information, but it couldn't be
+    /// parsed.
+    ParseError,
+    /// The module the address is in could be read, but nothing in it covers
+    /// the address, such as for padding between functions.
+    NoSymbol,
+    /// Symbols can't be resolved on this platform at all, as it has no
+    /// symbolication backend.
+    BackendUnavailable,
+
+    #[doc(hidden)]
+    __Nonexhaustive,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ResolveError::NoModule => "address is not in any loaded module",
+            ResolveError::NoDebugInfo => "module has no debug information",
+            ResolveError::ParseError => "failed to parse debug information",
+            ResolveError::NoSymbol => "no symbol covers the address",
+            ResolveError::BackendUnavailable => "no symbolication backend is available",
+            ResolveError::__Nonexhaustive => "unknown error",
+        })
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Same as `resolve`, but returns the symbols of `addr` or why there aren't
+/// any.
+///
+/// Symbols are returned innermost first, so there's more than one if `addr`
+/// is in inlined code. When nothing is found the cause is investigated, which
+/// reads the module `addr` is in again, so this is meant for reporting why a
+/// backtrace is missing symbols rather than for resolving every frame.
+///
+/// The cause can't always be told: with a `SymbolizeBackend` registered
+/// through `set_symbolizer`, or on platforms whose backend doesn't say why it
+/// failed such as Windows, `ResolveError::NoSymbol` is returned for any
+/// address which is in a module.
+///
+/// # Examples
+///
+/// ```
+/// match backtrace::try_resolve(std::ptr::null_mut()) {
+///     Ok(symbols) => println!("{:?}", symbols),
+///     Err(e) => println!("can't resolve null: {}", e),
+/// }
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn try_resolve(addr: *mut c_void) -> Result<Vec<BacktraceSymbol>, ResolveError> {
+    let _guard = crate::lock::lock();
+    let mut symbols = Vec::new();
+    unsafe {
+        resolve_imp(ResolveWhat::Address(addr), &mut |symbol| {
+            symbols.push(BacktraceSymbol::new(symbol))
+        });
+    }
+    if !symbols.is_empty() {
+        crate::capture::set_inline_depths(&mut symbols);
+        return Ok(symbols);
+    }
+    let addr = ResolveWhat::Address(addr).address_or_ip();
+    unsafe {
+        if backend::current().is_some() {
+            return Err(match imp::module_for_address(addr) {
+                Some(_) => ResolveError::NoSymbol,
+                None => ResolveError::NoModule,
+            });
+        }
+        Err(imp::diagnose(addr))
+    }
+}
@@ -10,6 +10,7 @@ use super::BytesOrWideString;
 use super::ResolveWhat;
 use super::SymbolName;
 use addr2line::gimli;
+use alloc::sync::Arc;
 use core::convert::TryInto;
 use core::mem;
 use core::u32;
@@ -52,12 +53,18 @@ cfg_if::cfg_if! {
 
 mod stash;
 
+#[cfg(all(feature = "perf-map", target_os = "linux"))]
+mod perf_map;
+
 const MAPPINGS_CACHE_SIZE: usize = 4;
 
 struct Mapping {
     // 'static lifetime is a lie to hack around lack of support for self-referential structs.
     cx: Context<'static>,
-    _map: Mmap,
+    // `None` for images symbolized from the parts of them loaded into memory.
+    _map: Option<Mmap>,
+    // The data of images registered with `register_image`.
+    _image: Option<Arc<[u8]>>,
     stash: Stash,
 }
 
@@ -96,10 +103,30 @@ impl Mapping {
             // Convert to 'static lifetimes since the symbols should
             // only borrow `map` and `stash` and we're preserving them below.
             cx: unsafe { core::mem::transmute::<Context<'_>, Context<'static>>(cx) },
-            _map: data,
+            _map: Some(data),
+            _image: None,
             stash: stash,
         })
     }
+
+    /// Creates a `Mapping` for an image registered with `register_image`,
+    /// like `mk` does for a file.
+    #[cfg(feature = "std-base")]
+    fn mk_image<F>(image: Arc<[u8]>, mk: F) -> Option<Mapping>
+    where
+        F: for<'a> FnOnce(&'a [u8], &'a Stash) -> Option<Context<'a>>,
+    {
+        let stash = Stash::new();
+        let cx = mk(&image, &stash)?;
+        Some(Mapping {
+            // The image is kept alive, and never moves, as long as the
+            // mapping is, like a file's `Mmap`.
+            cx: unsafe { core::mem::transmute::<Context<'_>, Context<'static>>(cx) },
+            _map: None,
+            _image: Some(image),
+            stash,
+        })
+    }
 }
 
 struct Context<'a> {
@@ -172,6 +199,54 @@ impl<'data> Context<'data> {
             l = continuation.resume(handle_split_dwarf(self.package.as_ref(), stash, load));
         }
     }
+
+    /// Returns the start and size of the function containing `probe`, from
+    /// the symbol table, or from the DWARF entry of the function for objects
+    /// whose symbol table doesn't cover it.
+    fn function_bounds(&self, stash: &'data Stash, probe: u64) -> Option<(u64, u64)> {
+        use addr2line::{LookupContinuation, LookupResult};
+
+        if let Some(bounds) = self.object.symbol_bounds(probe) {
+            return Some(bounds);
+        }
+        let mut l = self.dwarf.find_dwarf_and_unit(probe);
+        let (dwarf, unit) = loop {
+            let (load, continuation) = match l {
+                LookupResult::Output(output) => break output?,
+                LookupResult::Load { load, continuation } => (load, continuation),
+            };
+            l = continuation.resume(handle_split_dwarf(self.package.as_ref(), stash, load));
+        };
+        // addr2line doesn't expose the ranges of the functions it finds, so
+        // the unit is searched for the outermost function containing `probe`,
+        // skipping those inlined into it.
+        let mut entries = unit.entries();
+        while let Ok(Some((_, entry))) = entries.next_dfs() {
+            if entry.tag() != gimli::DW_TAG_subprogram {
+                continue;
+            }
+            let mut ranges = match dwarf.die_ranges(unit, entry) {
+                Ok(ranges) => ranges,
+                Err(_) => continue,
+            };
+            while let Ok(Some(range)) = ranges.next() {
+                if range.begin <= probe && probe < range.end {
+                    return Some((range.begin, range.end - range.begin));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Returns the directories set with `set_search_paths`, which are searched for
+/// separate debug information before the default locations.
+#[allow(dead_code)] // not used on all platforms
+fn search_paths() -> Vec<mystd::path::PathBuf> {
+    #[cfg(feature = "std-base")]
+    return super::search_paths::search_paths();
+    #[cfg(not(feature = "std-base"))]
+    return Vec::new();
 }
 
 fn mmap(path: &Path) -> Option<Mmap> {
@@ -193,6 +268,7 @@ cfg_if::cfg_if! {
         mod macho;
         use self::macho::{handle_split_dwarf, Object};
     } else {
+        mod zip;
         mod elf;
         use self::elf::{handle_split_dwarf, Object};
     }
@@ -224,7 +300,12 @@ cfg_if::cfg_if! {
         not(target_env = "uclibc"),
     ))] {
         mod libs_dl_iterate_phdr;
-        use libs_dl_iterate_phdr::native_libraries;
+        use libs_dl_iterate_phdr::{libraries_generation, native_libraries};
+        #[path = "gimli/parse_running_mmaps_unix.rs"]
+        mod parse_running_mmaps;
+    } else if #[cfg(target_os = "android")] {
+        mod libs_android;
+        use libs_android::native_libraries;
         #[path = "gimli/parse_running_mmaps_unix.rs"]
         mod parse_running_mmaps;
     } else if #[cfg(target_env = "libnx")] {
@@ -241,6 +322,23 @@ cfg_if::cfg_if! {
     }
 }
 
+// Platforms other than those using `dl_iterate_phdr` have no cheap way to tell
+// whether libraries have been loaded or unloaded, so there the list of
+// libraries is instead rebuilt when an address isn't found in any of them.
+#[cfg(not(all(
+    any(
+        target_os = "linux",
+        target_os = "fuchsia",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        all(target_os = "android", feature = "dl_iterate_phdr"),
+    ),
+    not(target_env = "uclibc"),
+)))]
+fn libraries_generation() -> Option<u64> {
+    None
+}
+
 #[derive(Default)]
 struct Cache {
     /// All known shared libraries that have been loaded.
@@ -256,6 +354,25 @@ struct Cache {
     /// Note that this is basically an LRU cache and we'll be shifting things
     /// around in here as we symbolize addresses.
     mappings: Vec<(usize, Mapping)>,
+
+    /// The value of `libraries_generation` when `libraries` was built.
+    generation: Option<u64>,
+
+    /// Whether `libraries` was checked to be up to date during the current
+    /// `batch`.
+    checked: bool,
+
+    /// The data of the images registered with `register_image`, which are
+    /// the first libraries of `libraries`, in the same order.
+    images: Vec<Arc<[u8]>>,
+
+    /// The value of `images::generation` when `libraries` was built.
+    #[cfg(feature = "std-base")]
+    images_generation: usize,
+
+    /// Symbols of JIT-compiled code, for addresses outside of all libraries.
+    #[cfg(all(feature = "perf-map", target_os = "linux"))]
+    perf_map: perf_map::PerfMap,
 }
 
 struct Library {
@@ -268,6 +385,11 @@ struct Library {
     /// this bias is subtracted from real virtual memory addresses to index into
     /// debuginfo and the symbol table.
     bias: usize,
+    /// The build-id, UUID or CodeView signature identifying this library, if
+    /// it has one. This is only used to describe the library through
+    /// `Frame::module` and `Symbol::module_build_id` and is not consulted
+    /// when loading debuginfo.
+    build_id: Option<Vec<u8>>,
 }
 
 struct LibrarySegment {
@@ -279,33 +401,160 @@ struct LibrarySegment {
     len: usize,
 }
 
+// A very small, very simple LRU cache for debug info mappings.
+//
+// The hit rate should be very high, since the typical stack doesn't cross
+// between many shared libraries.
+//
+// The `addr2line::Context` structures are pretty expensive to create. Its
+// cost is expected to be amortized by subsequent `locate` queries, which
+// leverage the structures built when constructing `addr2line::Context`s to
+// get nice speedups. If we didn't have this cache, that amortization would
+// never happen, and symbolicating backtraces would be ssssllllooooowwww.
+static mut MAPPINGS_CACHE: Option<Cache> = None;
+
+// Whether a `batch` is in progress.
+static mut IN_BATCH: bool = false;
+
+// unsafe because this is required to be externally synchronized
+pub unsafe fn batch(f: &mut dyn FnMut()) {
+    struct Reset(bool);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            unsafe { IN_BATCH = self.0 }
+        }
+    }
+
+    let _reset = Reset(IN_BATCH);
+    if !IN_BATCH {
+        if let Some(cache) = (*core::ptr::addr_of_mut!(MAPPINGS_CACHE)).as_mut() {
+            cache.checked = false;
+        }
+        IN_BATCH = true;
+    }
+    f()
+}
+
+// unsafe because this is required to be externally synchronized
+pub unsafe fn preload() {
+    Cache::with_global(|cache| {
+        // Parse the debug info of the module this crate is in, which is
+        // usually the executable, so that it's ready for the first backtrace.
+        let addr = preload as unsafe fn() as *const u8;
+        if let Some((lib, _)) = cache.find_library(addr) {
+            cache.mapping_for_lib(lib);
+        }
+    });
+}
+
+// unsafe because this is required to be externally synchronized
+pub unsafe fn unload() {
+    MAPPINGS_CACHE = None;
+}
+
 // unsafe because this is required to be externally synchronized
 pub unsafe fn clear_symbol_cache() {
-    Cache::with_global(|cache| cache.mappings.clear());
+    Cache::with_global(|cache| {
+        cache.mappings.clear();
+        cache.reload_libraries();
+        #[cfg(all(feature = "perf-map", target_os = "linux"))]
+        {
+            cache.perf_map = Default::default();
+        }
+    });
 }
 
 impl Cache {
     fn new() -> Cache {
-        Cache {
+        let mut cache = Cache {
             mappings: Vec::with_capacity(MAPPINGS_CACHE_SIZE),
-            libraries: native_libraries(),
+            generation: libraries_generation(),
+            ..Default::default()
+        };
+        cache.libraries = cache.list_libraries();
+        cache
+    }
+
+    /// Lists the libraries of the images registered with `register_image`,
+    /// which take precedence, followed by those of the platform, updating
+    /// `images` to match.
+    fn list_libraries(&mut self) -> Vec<Library> {
+        let mut libraries = Vec::new();
+        self.images.clear();
+        #[cfg(feature = "std-base")]
+        {
+            self.images_generation = super::images::generation();
+            for image in unsafe { super::images::registered() } {
+                if let Some(lib) = image_library(image) {
+                    libraries.push(lib);
+                    self.images.push(image.data.clone());
+                }
+            }
+        }
+        libraries.extend(native_libraries());
+        libraries
+    }
+
+    /// Rebuilds the list of libraries, keeping the mappings of those which are
+    /// still loaded at the same address.
+    fn reload_libraries(&mut self) {
+        self.generation = libraries_generation();
+        let old_images = mem::take(&mut self.images);
+        let libraries = self.list_libraries();
+        let old = mem::replace(&mut self.libraries, libraries);
+        let mappings = mem::replace(&mut self.mappings, Vec::with_capacity(MAPPINGS_CACHE_SIZE));
+        for (lib, mapping) in mappings {
+            // Images are told apart by their data rather than their name, as
+            // another image may have been registered at the same address.
+            let lib = match old_images.get(lib) {
+                Some(old) => self.images.iter().position(|new| Arc::ptr_eq(new, old)),
+                None => {
+                    let old = &old[lib];
+                    let images = self.images.len();
+                    self.libraries[images..]
+                        .iter()
+                        .position(|new| new.name == old.name && new.bias == old.bias)
+                        .map(|i| images + i)
+                }
+            };
+            if let Some(lib) = lib {
+                self.mappings.push((lib, mapping));
+            }
+        }
+    }
+
+    /// Finds the library containing `addr`, the same as `avma_to_svma`, but
+    /// first making sure that the list of libraries is up to date.
+    ///
+    /// Reading the generation of the libraries takes the dynamic loader's
+    /// lock, so during a `batch` it's only done for the first address.
+    fn find_library(&mut self, addr: *const u8) -> Option<(usize, *const u8)> {
+        if !(unsafe { IN_BATCH } && self.checked) {
+            #[cfg(feature = "std-base")]
+            {
+                if self.images_generation != super::images::generation() {
+                    self.reload_libraries();
+                }
+            }
+            if let Some(generation) = libraries_generation() {
+                if self.generation != Some(generation) {
+                    self.reload_libraries();
+                }
+            }
+            self.checked = true;
+        }
+        match self.generation {
+            Some(_) => self.avma_to_svma(addr),
+            None => self.avma_to_svma(addr).or_else(|| {
+                self.reload_libraries();
+                self.avma_to_svma(addr)
+            }),
         }
     }
 
     // unsafe because this is required to be externally synchronized
     unsafe fn with_global(f: impl FnOnce(&mut Self)) {
-        // A very small, very simple LRU cache for debug info mappings.
-        //
-        // The hit rate should be very high, since the typical stack doesn't cross
-        // between many shared libraries.
-        //
-        // The `addr2line::Context` structures are pretty expensive to create. Its
-        // cost is expected to be amortized by subsequent `locate` queries, which
-        // leverage the structures built when constructing `addr2line::Context`s to
-        // get nice speedups. If we didn't have this cache, that amortization would
-        // never happen, and symbolicating backtraces would be ssssllllooooowwww.
-        static mut MAPPINGS_CACHE: Option<Cache> = None;
-
         f(MAPPINGS_CACHE.get_or_insert_with(|| Cache::new()))
     }
 
@@ -360,7 +609,21 @@ impl Cache {
             // insert it into the front of the cache, and evict the oldest cache
             // entry if necessary.
             let name = &self.libraries[lib].name;
-            let mapping = Mapping::new(name.as_ref())?;
+            let mapping = match self.images.get(lib) {
+                #[cfg(feature = "std-base")]
+                Some(image) => Mapping::new_image(image.clone()),
+                _ => Mapping::new(name.as_ref()),
+            };
+            // Images in the dyld shared cache have no file of their own, so
+            // fall back to the symbol table which dyld has loaded.
+            #[cfg(any(
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "tvos",
+                target_os = "watchos",
+            ))]
+            let mapping = mapping.or_else(|| Mapping::new_loaded(name.as_ref()));
+            let mapping = mapping?;
 
             if self.mappings.len() == MAPPINGS_CACHE_SIZE {
                 self.mappings.pop();
@@ -381,6 +644,165 @@ impl Cache {
     }
 }
 
+#[cfg(feature = "std-base")]
+pub unsafe fn module_for_address(addr: *mut c_void) -> Option<super::Module> {
+    let mut ret = None;
+    Cache::with_global(|cache| {
+        if let Some((lib, _)) = cache.find_library(addr as *const u8) {
+            ret = module(&cache.libraries[lib]);
+        }
+    });
+    ret
+}
+
+#[cfg(feature = "std-base")]
+pub unsafe fn loaded_modules() -> Vec<super::Module> {
+    let mut ret = Vec::new();
+    Cache::with_global(|cache| {
+        if cache.generation != libraries_generation()
+            || cache.images_generation != super::images::generation()
+        {
+            cache.reload_libraries();
+        }
+        ret = cache.libraries.iter().filter_map(module).collect();
+    });
+    ret
+}
+
+#[cfg(feature = "std-base")]
+fn module(lib: &Library) -> Option<super::Module> {
+    // Segments which don't occupy any memory (such as `PT_GNU_STACK` on
+    // ELF) don't have a meaningful address, so they're ignored when
+    // computing the extent of the library's image.
+    let segments = lib.segments.iter().filter(|s| s.len > 0);
+    let start = segments
+        .clone()
+        .map(|s| s.stated_virtual_memory_address)
+        .min()?;
+    let end = segments
+        .map(|s| s.stated_virtual_memory_address.wrapping_add(s.len))
+        .max()
+        .unwrap_or(start);
+    Some(super::Module {
+        path: lib.name.clone().into(),
+        base_address: start.wrapping_add(lib.bias),
+        bias: lib.bias,
+        size: end.wrapping_sub(start),
+        build_id: lib.build_id.clone(),
+    })
+}
+
+/// A mapping of an object file on disk which is not necessarily loaded into
+/// this process, used to implement `offline::Resolver`.
+#[cfg(feature = "std-base")]
+pub struct OfflineResolver {
+    mapping: Mapping,
+    /// The stated address that module-relative addresses are relative to.
+    base: usize,
+    build_id: Option<Vec<u8>>,
+}
+
+#[cfg(feature = "std-base")]
+impl OfflineResolver {
+    pub fn new(path: &Path) -> Option<OfflineResolver> {
+        let data = mmap(path)?;
+        let base = image_base(&data)?;
+        let build_id = object::File::parse(&*data)
+            .ok()
+            .and_then(|file| build_id(&file));
+        Some(OfflineResolver {
+            mapping: Mapping::new(path)?,
+            base,
+            build_id,
+        })
+    }
+
+    pub fn resolve(&mut self, addr: usize, cb: &mut dyn FnMut(&super::Symbol)) {
+        let addr = super::super::adjust_ip(addr as *mut c_void) as usize;
+        let cx: &mut Context<'static> = &mut self.mapping.cx;
+        // As in `Cache::mapping_for_lib`, don't leak the `'static` lifetime.
+        let cx = unsafe { mem::transmute::<&mut Context<'static>, &mut Context<'_>>(cx) };
+        lookup(
+            cx,
+            &self.mapping.stash,
+            self.base.wrapping_add(addr) as *const u8,
+            // Addresses are relative to the base of the module.
+            0usize.wrapping_sub(self.base),
+            self.build_id.as_deref(),
+            &mut |sym: Symbol<'_>| {
+                // See `resolve` below for why this is extended to `'static`.
+                let sym = unsafe { mem::transmute::<Symbol<'_>, Symbol<'static>>(sym) };
+                cb(&super::Symbol {
+                    inner: super::SymbolImp::Native(sym),
+                })
+            },
+        );
+    }
+}
+
+/// Describes an image registered with `register_image` as a library.
+#[cfg(feature = "std-base")]
+fn image_library(image: &super::images::Image) -> Option<Library> {
+    use object::{Object as _, ObjectSegment as _};
+
+    let file = object::File::parse(&*image.data).ok()?;
+    let base = image_base(&image.data)?;
+    let segments = file
+        .segments()
+        // This leaves out `__PAGEZERO` on Mach-O, which lies below the image.
+        .filter(|s| s.address() >= base as u64 && s.size() > 0)
+        .map(|s| LibrarySegment {
+            stated_virtual_memory_address: s.address() as usize,
+            len: s.size() as usize,
+        })
+        .collect();
+    Some(Library {
+        name: format!("<memory image at {:#x}>", image.base).into(),
+        segments,
+        bias: image.base.wrapping_sub(base),
+        build_id: build_id(&file),
+    })
+}
+
+/// Returns the build-id, UUID or CodeView GUID and age of the object `file`,
+/// in the same form as `Module::build_id`.
+#[cfg(feature = "std-base")]
+fn build_id(file: &object::File<'_>) -> Option<Vec<u8>> {
+    use object::Object as _;
+
+    match file.build_id() {
+        Ok(Some(id)) => Some(id.to_vec()),
+        _ => match (file.mach_uuid(), file.pdb_info()) {
+            (Ok(Some(uuid)), _) => Some(uuid.to_vec()),
+            (_, Ok(Some(info))) => {
+                let mut id = info.guid().to_vec();
+                id.extend_from_slice(&info.age().to_le_bytes());
+                Some(id)
+            }
+            _ => None,
+        },
+    }
+}
+
+/// Returns the stated address of the start of the image described by the
+/// object file `data`, matching what `Module::base_address` reports once the
+/// object is loaded.
+#[cfg(feature = "std-base")]
+fn image_base(data: &[u8]) -> Option<usize> {
+    use object::{BinaryFormat, Object as _, ObjectSegment as _};
+
+    let file = object::File::parse(data).ok()?;
+    let base = if file.format() == BinaryFormat::Pe {
+        file.relative_address_base()
+    } else {
+        file.segments()
+            .filter(|s| s.file_range().1 > 0)
+            .map(|s| s.address())
+            .min()?
+    };
+    base.try_into().ok()
+}
+
 pub unsafe fn resolve(what: ResolveWhat<'_>, cb: &mut dyn FnMut(&super::Symbol)) {
     let addr = what.address_or_ip();
     let mut call = |sym: Symbol<'_>| {
@@ -388,59 +810,145 @@ pub unsafe fn resolve(what: ResolveWhat<'_>, cb: &mut dyn FnMut(&super::Symbol))
         // required to here, but it's only ever going out as a reference so no
         // reference to it should be persisted beyond this frame anyway.
         let sym = mem::transmute::<Symbol<'_>, Symbol<'static>>(sym);
-        (cb)(&super::Symbol { inner: sym });
+        (cb)(&super::Symbol {
+            inner: super::SymbolImp::Native(sym),
+        });
     };
 
     Cache::with_global(|cache| {
-        let (lib, addr) = match cache.avma_to_svma(addr as *const u8) {
+        let (lib, addr) = match cache.find_library(addr as *const u8) {
             Some(pair) => pair,
-            None => return,
+            None => {
+                #[cfg(all(feature = "perf-map", target_os = "linux"))]
+                {
+                    if let Some((name, start, size)) = cache.perf_map.lookup(addr as usize) {
+                        call(Symbol::PerfMap {
+                            name,
+                            function: (start, size),
+                        });
+                    }
+                }
+                return;
+            }
         };
 
+        // Copied out as the mapping below borrows the whole cache.
+        let build_id = cache.libraries[lib].build_id.clone();
+        let bias = cache.libraries[lib].bias;
+
         // Finally, get a cached mapping or create a new mapping for this file, and
         // evaluate the DWARF info to find the file/line/name for this address.
         let (cx, stash) = match cache.mapping_for_lib(lib) {
             Some((cx, stash)) => (cx, stash),
             None => return,
         };
-        let mut any_frames = false;
-        if let Ok(mut frames) = cx.find_frames(stash, addr as u64) {
-            while let Ok(Some(frame)) = frames.next() {
-                any_frames = true;
-                let name = match frame.function {
-                    Some(f) => Some(f.name.slice()),
-                    None => cx.object.search_symtab(addr as u64),
+        lookup(cx, stash, addr, bias, build_id.as_deref(), &mut call);
+    });
+}
+
+// unsafe because this is required to be externally synchronized
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub unsafe fn diagnose(addr: *mut c_void) -> super::ResolveError {
+    use super::ResolveError;
+
+    let mut ret = ResolveError::NoModule;
+    Cache::with_global(|cache| {
+        let (lib, addr) = match cache.find_library(addr as *const u8) {
+            Some(pair) => pair,
+            None => return,
+        };
+        // A mapping is only missing if the object file couldn't be read or
+        // its debug information parsed, which is told apart by reading it.
+        let readable = match cache.images.get(lib) {
+            Some(_) => true,
+            None => mmap(cache.libraries[lib].name.as_ref()).is_some(),
+        };
+        let (cx, stash) = match cache.mapping_for_lib(lib) {
+            Some(pair) => pair,
+            None => {
+                ret = if readable {
+                    ResolveError::ParseError
+                } else {
+                    ResolveError::NoDebugInfo
                 };
-                call(Symbol::Frame {
-                    addr: addr as *mut c_void,
-                    location: frame.location,
-                    name,
-                });
+                return;
             }
+        };
+        let has_dwarf = !cx
+            .object
+            .section(stash, ".debug_info")
+            .unwrap_or(&[])
+            .is_empty();
+        ret = if !has_dwarf && cx.object.search_symtab(addr as u64).is_none() {
+            ResolveError::NoDebugInfo
+        } else if cx.find_frames(stash, addr as u64).is_err() {
+            ResolveError::ParseError
+        } else {
+            ResolveError::NoSymbol
+        };
+    });
+    ret
+}
+
+/// Invokes `call` for every symbol found at `addr`, which is an address as
+/// stated in the object file that `cx` was loaded from, whose identifier is
+/// `build_id`. Adding `bias` to a stated address gives the address that
+/// symbols report for it.
+fn lookup<'a>(
+    cx: &'a mut Context<'a>,
+    stash: &'a Stash,
+    addr: *const u8,
+    bias: usize,
+    build_id: Option<&'a [u8]>,
+    call: &mut dyn FnMut(Symbol<'_>),
+) {
+    let function = cx
+        .function_bounds(stash, addr as u64)
+        .map(|(start, size)| ((start as usize).wrapping_add(bias), size as usize));
+    let mut any_frames = false;
+    if let Ok(mut frames) = cx.find_frames(stash, addr as u64) {
+        while let Ok(Some(frame)) = frames.next() {
+            any_frames = true;
+            let name = match frame.function {
+                Some(f) => Some(f.name.slice()),
+                None => cx.object.search_symtab(addr as u64).map(|(name, _)| name),
+            };
+            call(Symbol::frame(
+                addr,
+                frame.location,
+                name,
+                function,
+                build_id,
+            ));
         }
-        if !any_frames {
-            if let Some((object_cx, object_addr)) = cx.object.search_object_map(addr as u64) {
-                if let Ok(mut frames) = object_cx.find_frames(stash, object_addr) {
-                    while let Ok(Some(frame)) = frames.next() {
-                        any_frames = true;
-                        call(Symbol::Frame {
-                            addr: addr as *mut c_void,
-                            location: frame.location,
-                            name: frame.function.map(|f| f.name.slice()),
-                        });
-                    }
+    }
+    if !any_frames {
+        if let Some((object_cx, object_addr)) = cx.object.search_object_map(addr as u64) {
+            if let Ok(mut frames) = object_cx.find_frames(stash, object_addr) {
+                while let Ok(Some(frame)) = frames.next() {
+                    any_frames = true;
+                    call(Symbol::frame(
+                        addr,
+                        frame.location,
+                        frame.function.map(|f| f.name.slice()),
+                        function,
+                        build_id,
+                    ));
                 }
             }
         }
-        if !any_frames {
-            if let Some(name) = cx.object.search_symtab(addr as u64) {
-                call(Symbol::Symtab {
-                    addr: addr as *mut c_void,
-                    name,
-                });
-            }
+    }
+    if !any_frames {
+        if let Some((name, start)) = cx.object.search_symtab(addr as u64) {
+            call(Symbol::Symtab {
+                addr: addr as *mut c_void,
+                offset: (addr as u64).wrapping_sub(start) as usize,
+                name,
+                function,
+                build_id,
+            });
         }
-    });
+    }
 }
 
 pub enum Symbol<'a> {
@@ -450,10 +958,111 @@ pub enum Symbol<'a> {
         addr: *mut c_void,
         location: Option<addr2line::Location<'a>>,
         name: Option<&'a [u8]>,
+        /// The file of `location` with `.` and `..` components removed and
+        /// rewritten by `set_source_map`, if that changed it.
+        normalized: Option<mystd::path::PathBuf>,
+        /// The start and size of the function containing `addr`, which for
+        /// inlined functions is the one they were inlined into.
+        function: Option<(usize, usize)>,
+        build_id: Option<&'a [u8]>,
     },
     /// Couldn't find debug information, but we found it in the symbol table of
-    /// the elf executable.
-    Symtab { addr: *mut c_void, name: &'a [u8] },
+    /// the elf executable, `offset` bytes past the start of the symbol.
+    Symtab {
+        addr: *mut c_void,
+        offset: usize,
+        name: &'a [u8],
+        function: Option<(usize, usize)>,
+        build_id: Option<&'a [u8]>,
+    },
+    /// Found in the perf map of JIT-compiled code, which isn't part of any
+    /// library.
+    #[cfg(all(feature = "perf-map", target_os = "linux"))]
+    PerfMap {
+        name: &'a [u8],
+        function: (usize, usize),
+    },
+}
+
+impl<'a> Symbol<'a> {
+    fn frame(
+        addr: *const u8,
+        location: Option<addr2line::Location<'a>>,
+        name: Option<&'a [u8]>,
+        function: Option<(usize, usize)>,
+        build_id: Option<&'a [u8]>,
+    ) -> Symbol<'a> {
+        let file = location.as_ref().and_then(|l| l.file);
+        let normalized = file.and_then(normalize_path);
+        let normalized = match file {
+            Some(file) => remap_source(normalized.as_deref().unwrap_or_else(|| Path::new(file)))
+                .or(normalized),
+            None => None,
+        };
+        Symbol::Frame {
+            addr: addr as *mut c_void,
+            location,
+            name,
+            normalized,
+            function,
+            build_id,
+        }
+    }
+}
+
+/// Rewrites `path` with the map registered through `set_source_map`, returning
+/// `None` if it's left unchanged.
+fn remap_source(path: &Path) -> Option<mystd::path::PathBuf> {
+    #[cfg(feature = "std-base")]
+    return match crate::print::current_source_map()?.apply(path) {
+        mystd::borrow::Cow::Owned(path) => Some(path),
+        mystd::borrow::Cow::Borrowed(_) => None,
+    };
+    #[cfg(not(feature = "std-base"))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Removes the `.` and `..` components of `path`, returning `None` if it
+/// doesn't have any.
+///
+/// The file names in line tables are the compilation directory, an include
+/// directory and a file name joined together, each of which may be relative
+/// to the one before, so paths like `/build/src/../include/./util.h` are
+/// common. `..` is resolved lexically, the same as debuggers do, as the
+/// directories usually don't exist on the machine the binary runs on.
+fn normalize_path(path: &str) -> Option<mystd::path::PathBuf> {
+    use mystd::path::{Component, PathBuf};
+
+    let mut normalized = PathBuf::new();
+    // The number of normal components at the end of `normalized`, which are
+    // the ones a `..` can remove.
+    let mut depth = 0;
+    for component in Path::new(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => {
+                normalized.pop();
+                depth -= 1;
+            }
+            // There's nothing above the root.
+            Component::ParentDir if normalized.has_root() => {}
+            Component::Normal(name) => {
+                normalized.push(name);
+                depth += 1;
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    // Every component that was dropped shortened the path, as did repeated
+    // and trailing separators, which `components` skips as well.
+    if normalized.as_os_str().len() != path.len() && !normalized.as_os_str().is_empty() {
+        Some(normalized)
+    } else {
+        None
+    }
 }
 
 impl Symbol<'_> {
@@ -464,6 +1073,8 @@ impl Symbol<'_> {
                 Some(SymbolName::new(name))
             }
             Symbol::Symtab { name, .. } => Some(SymbolName::new(name)),
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { name, .. } => Some(SymbolName::new(name)),
         }
     }
 
@@ -471,6 +1082,53 @@ impl Symbol<'_> {
         match self {
             Symbol::Frame { addr, .. } => Some(*addr),
             Symbol::Symtab { .. } => None,
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { .. } => None,
+        }
+    }
+
+    pub fn offset_in_module(&self) -> Option<usize> {
+        match self {
+            Symbol::Frame { addr, .. } | Symbol::Symtab { addr, .. } => Some(*addr as usize),
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { .. } => None,
+        }
+    }
+
+    pub fn module_build_id(&self) -> Option<&[u8]> {
+        match self {
+            Symbol::Frame { build_id, .. } | Symbol::Symtab { build_id, .. } => *build_id,
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { .. } => None,
+        }
+    }
+
+    pub fn offset_in_symbol(&self) -> Option<usize> {
+        match self {
+            Symbol::Frame { .. } => None,
+            Symbol::Symtab { offset, .. } => Some(*offset),
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { .. } => None,
+        }
+    }
+
+    pub fn function_start(&self) -> Option<*mut c_void> {
+        match self {
+            Symbol::Frame { function, .. } | Symbol::Symtab { function, .. } => {
+                function.map(|(start, _)| start as *mut c_void)
+            }
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { function, .. } => Some(function.0 as *mut c_void),
+        }
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            Symbol::Frame { function, .. } | Symbol::Symtab { function, .. } => {
+                function.map(|(_, size)| size)
+            }
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { function, .. } => Some(function.1),
         }
     }
 
@@ -481,16 +1139,24 @@ impl Symbol<'_> {
                 Some(BytesOrWideString::Bytes(file.as_bytes()))
             }
             Symbol::Symtab { .. } => None,
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { .. } => None,
         }
     }
 
     pub fn filename(&self) -> Option<&Path> {
         match self {
+            Symbol::Frame {
+                normalized: Some(file),
+                ..
+            } => Some(file),
             Symbol::Frame { location, .. } => {
                 let file = location.as_ref()?.file?;
                 Some(Path::new(file))
             }
             Symbol::Symtab { .. } => None,
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { .. } => None,
         }
     }
 
@@ -498,6 +1164,8 @@ impl Symbol<'_> {
         match self {
             Symbol::Frame { location, .. } => location.as_ref()?.line,
             Symbol::Symtab { .. } => None,
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { .. } => None,
         }
     }
 
@@ -505,6 +1173,27 @@ impl Symbol<'_> {
         match self {
             Symbol::Frame { location, .. } => location.as_ref()?.column,
             Symbol::Symtab { .. } => None,
+            #[cfg(all(feature = "perf-map", target_os = "linux"))]
+            Symbol::PerfMap { .. } => None,
         }
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        let normalized = |p| normalize_path(p).map(|p| p.to_str().unwrap().to_owned());
+        assert_eq!(normalized("/build/src/main.rs"), None);
+        assert_eq!(
+            normalized("/build/src/../include/./util.h").as_deref(),
+            Some("/build/include/util.h")
+        );
+        assert_eq!(normalized("/../a/b").as_deref(), Some("/a/b"));
+        assert_eq!(normalized("../a/./b").as_deref(), Some("../a/b"));
+        assert_eq!(normalized("a/../../b").as_deref(), Some("../b"));
+        assert_eq!(normalized("./"), None);
+    }
+}
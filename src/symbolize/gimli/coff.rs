@@ -18,6 +18,14 @@ impl Mapping {
             Context::new(stash, Object::parse(data)?, None, None)
         })
     }
+
+    /// Creates a mapping for an image registered with `register_image`.
+    #[cfg(feature = "std-base")]
+    pub fn new_image(image: Arc<[u8]>) -> Option<Mapping> {
+        Mapping::mk_image(image, |data, stash| {
+            Context::new(stash, Object::parse(data)?, None, None)
+        })
+    }
 }
 
 pub struct Object<'a> {
@@ -34,6 +42,19 @@ pub fn get_image_base(data: &[u8]) -> Option<usize> {
     usize::try_from(nt_headers.optional_header().image_base()).ok()
 }
 
+/// Returns the CodeView GUID followed by the little-endian age, which together
+/// identify the PDB matching this image.
+pub fn get_build_id(data: &[u8]) -> Option<Vec<u8>> {
+    use object::read::pe::PeFile;
+    use object::read::Object as _;
+
+    let file = PeFile::<Pe>::parse(data).ok()?;
+    let info = file.pdb_info().ok()??;
+    let mut id = info.guid().to_vec();
+    id.extend_from_slice(&info.age().to_le_bytes());
+    Some(id)
+}
+
 impl<'a> Object<'a> {
     fn parse(data: &'a [u8]) -> Option<Object<'a>> {
         let dos_header = ImageDosHeader::parse(data).ok()?;
@@ -85,7 +106,7 @@ impl<'a> Object<'a> {
         )
     }
 
-    pub fn search_symtab<'b>(&'b self, addr: u64) -> Option<&'b [u8]> {
+    pub fn search_symtab<'b>(&'b self, addr: u64) -> Option<(&'b [u8], u64)> {
         // Note that unlike other formats COFF doesn't embed the size of
         // each symbol. As a last ditch effort search for the *closest*
         // symbol to a particular address and return that one. This gets
@@ -100,7 +121,24 @@ impl<'a> Object<'a> {
             // greatest less than `addr`
             Err(i) => i.checked_sub(1)?,
         };
-        self.symbols[i].1.name(self.strings).ok()
+        let (address, sym) = &self.symbols[i];
+        Some((sym.name(self.strings).ok()?, *address as u64))
+    }
+
+    /// Returns the start and size of the symbol containing `addr`, which
+    /// like in `search_symtab` is taken to extend up to the next symbol.
+    pub fn symbol_bounds(&self, addr: u64) -> Option<(u64, u64)> {
+        let addr = usize::try_from(addr).ok()?;
+        let i = match self.symbols.binary_search_by_key(&addr, |p| p.0) {
+            Ok(i) => i,
+            Err(i) => i.checked_sub(1)?,
+        };
+        let start = self.symbols[i].0;
+        let end = self.symbols[i + 1..]
+            .iter()
+            .map(|p| p.0)
+            .find(|&a| a > start)?;
+        Some((start as u64, (end - start) as u64))
     }
 
     pub(super) fn search_object_map(&self, _addr: u64) -> Option<(&Context<'_>, u64)> {
@@ -9,9 +9,12 @@ use core::convert::{TryFrom, TryInto};
 use core::str;
 use object::elf::{ELFCOMPRESS_ZLIB, ELF_NOTE_GNU, NT_GNU_BUILD_ID, SHF_COMPRESSED};
 use object::read::elf::{CompressionHeader, FileHeader, SectionHeader, SectionTable, Sym};
-use object::read::StringTable;
 use object::{BigEndian, Bytes, NativeEndian};
 
+/// Not yet defined by `object`.
+#[cfg(feature = "zstd")]
+const ELFCOMPRESS_ZSTD: u32 = 2;
+
 #[cfg(target_pointer_width = "32")]
 type Elf = object::elf::FileHeader32<NativeEndian>;
 #[cfg(target_pointer_width = "64")]
@@ -19,7 +22,10 @@ type Elf = object::elf::FileHeader64<NativeEndian>;
 
 impl Mapping {
     pub fn new(path: &Path) -> Option<Mapping> {
-        let map = super::mmap(path)?;
+        let map = match super::mmap(path) {
+            Some(map) => map,
+            None => return Mapping::new_in_apk(path),
+        };
         Mapping::mk_or_other(map, |map, stash| {
             let object = Object::parse(&map)?;
 
@@ -37,12 +43,59 @@ impl Mapping {
                 }
             }
 
+            // Stripped libraries may have their debug file on a debuginfod
+            // server instead.
+            #[cfg(feature = "debuginfod")]
+            if object.section(stash, ".debug_info").is_none() {
+                if let Some(path_debug) = object.build_id().and_then(crate::debuginfod::locate) {
+                    if let Some(mapping) = Mapping::new_debug(path, path_debug, None) {
+                        return Some(Either::A(mapping));
+                    }
+                }
+            }
+
+            // Distributions which strip their binaries may leave a compressed
+            // symbol table behind for them, which has more symbols than
+            // `.dynsym` does.
+            #[cfg(feature = "gnu-debugdata")]
+            let object = object.with_gnu_debugdata(stash);
+
             let dwp = Mapping::load_dwarf_package(path, stash);
 
             Context::new(stash, object, None, dwp).map(Either::B)
         })
     }
 
+    /// Creates a mapping for an image registered with `register_image`.
+    ///
+    /// Such images have no path for a debug link or split DWARF to be
+    /// relative to, but a separate debug file is still looked for by their
+    /// build ID.
+    #[cfg(feature = "std-base")]
+    pub fn new_image(image: Arc<[u8]>) -> Option<Mapping> {
+        if let Some(path_debug) = Object::parse(&image)?.build_id().and_then(locate_build_id) {
+            if let Some(mapping) = Mapping::new_debug(&path_debug, path_debug.clone(), None) {
+                return Some(mapping);
+            }
+        }
+        Mapping::mk_image(image, |data, stash| {
+            Context::new(stash, Object::parse(data)?, None, None)
+        })
+    }
+
+    /// Load debuginfo from a library stored in an Android APK, which the
+    /// dynamic loader names like `base.apk!/lib/arm64-v8a/libfoo.so`.
+    fn new_in_apk(path: &Path) -> Option<Mapping> {
+        let path = path.as_os_str().as_bytes();
+        let split = path.windows(2).position(|w| w == b"!/")?;
+        let (apk, entry) = (&path[..split], &path[split + 2..]);
+        let map = super::mmap(Path::new(OsStr::from_bytes(apk)))?;
+        Mapping::mk(map, |map, stash| {
+            let object = Object::parse(super::zip::find(map, entry)?)?;
+            Context::new(stash, object, None, None)
+        })
+    }
+
     /// Load debuginfo from an external debug file.
     fn new_debug(original_path: &Path, path: PathBuf, crc: Option<u32>) -> Option<Mapping> {
         let map = super::mmap(&path)?;
@@ -95,10 +148,10 @@ impl Mapping {
     }
 }
 
-struct ParsedSym {
+struct ParsedSym<'a> {
     address: u64,
     size: u64,
-    name: u32,
+    name: &'a [u8],
 }
 
 pub struct Object<'a> {
@@ -109,9 +162,8 @@ pub struct Object<'a> {
     /// The entire file data.
     data: &'a [u8],
     sections: SectionTable<'a, Elf>,
-    strings: StringTable<'a>,
     /// List of pre-parsed and sorted symbols by base address.
-    syms: Vec<ParsedSym>,
+    syms: Vec<ParsedSym<'a>>,
 }
 
 impl<'a> Object<'a> {
@@ -119,48 +171,51 @@ impl<'a> Object<'a> {
         let elf = Elf::parse(data).ok()?;
         let endian = elf.endian().ok()?;
         let sections = elf.sections(endian, data).ok()?;
-        let mut syms = sections
-            .symbols(endian, data, object::elf::SHT_SYMTAB)
-            .ok()?;
-        if syms.is_empty() {
-            syms = sections
-                .symbols(endian, data, object::elf::SHT_DYNSYM)
-                .ok()?;
-        }
-        let strings = syms.strings();
 
-        let mut syms = syms
-            .iter()
-            // Only look at function/object symbols. This mirrors what
-            // libbacktrace does and in general we're only symbolicating
-            // function addresses in theory. Object symbols correspond
-            // to data, and maybe someone's crazy enough to have a
-            // function go into static data?
-            .filter(|sym| {
-                let st_type = sym.st_type();
-                st_type == object::elf::STT_FUNC || st_type == object::elf::STT_OBJECT
-            })
-            // skip anything that's in an undefined section header,
-            // since it means it's an imported function and we're only
-            // symbolicating with locally defined functions.
-            .filter(|sym| sym.st_shndx(endian) != object::elf::SHN_UNDEF)
-            .map(|sym| {
-                let address = sym.st_value(endian).into();
-                let size = sym.st_size(endian).into();
-                let name = sym.st_name(endian);
-                ParsedSym {
-                    address,
-                    size,
-                    name,
-                }
-            })
-            .collect::<Vec<_>>();
-        syms.sort_unstable_by_key(|s| s.address);
+        // Binaries without debug information are usually still symbolicated
+        // through their symbol tables. `strip` removes `.symtab` but has to
+        // keep `.dynsym` for the dynamic linker, and some tools leave a
+        // reduced `.symtab` behind, so the symbols of both are combined.
+        let mut syms = Vec::new();
+        for &sh_type in &[object::elf::SHT_SYMTAB, object::elf::SHT_DYNSYM] {
+            let table = sections.symbols(endian, data, sh_type).ok()?;
+            let strings = table.strings();
+            syms.extend(
+                table
+                    .iter()
+                    // Only look at function/object symbols. This mirrors what
+                    // libbacktrace does and in general we're only symbolicating
+                    // function addresses in theory. Object symbols correspond
+                    // to data, and maybe someone's crazy enough to have a
+                    // function go into static data?
+                    .filter(|sym| {
+                        let st_type = sym.st_type();
+                        st_type == object::elf::STT_FUNC || st_type == object::elf::STT_OBJECT
+                    })
+                    // skip anything that's in an undefined section header,
+                    // since it means it's an imported function and we're only
+                    // symbolicating with locally defined functions.
+                    .filter(|sym| sym.st_shndx(endian) != object::elf::SHN_UNDEF)
+                    .filter_map(|sym| {
+                        let address = sym.st_value(endian).into();
+                        let size = sym.st_size(endian).into();
+                        let name = sym.name(endian, strings).ok()?;
+                        Some(ParsedSym {
+                            address,
+                            size,
+                            name,
+                        })
+                    }),
+            );
+        }
+        // The sort is stable so for symbols found in both tables the one from
+        // `.symtab` comes first and is the one that's kept.
+        syms.sort_by_key(|s| s.address);
+        syms.dedup_by(|b, a| a.address == b.address && a.name == b.name);
         Some(Object {
             endian,
             data,
             sections,
-            strings,
             syms,
         })
     }
@@ -178,13 +233,16 @@ impl<'a> Object<'a> {
             }
 
             let header = data.read::<<Elf as FileHeader>::CompressionHeader>().ok()?;
-            if header.ch_type(self.endian) != ELFCOMPRESS_ZLIB {
-                // Zlib compression is the only known type.
-                return None;
-            }
+            let decompress = match header.ch_type(self.endian) {
+                ELFCOMPRESS_ZLIB => decompress_zlib,
+                #[cfg(feature = "zstd")]
+                ELFCOMPRESS_ZSTD => decompress_zstd,
+                // Zlib and zstd compression are the only known types.
+                _ => return None,
+            };
             let size = usize::try_from(header.ch_size(self.endian)).ok()?;
             let buf = stash.allocate(size);
-            decompress_zlib(data.0, buf)?;
+            decompress(data.0, buf)?;
             return Some(buf);
         }
 
@@ -218,21 +276,79 @@ impl<'a> Object<'a> {
         Some(buf)
     }
 
+    /// Returns this object with the symbols of the MiniDebugInfo in its
+    /// `.gnu_debugdata` section, if it has one, added to its own.
+    ///
+    /// MiniDebugInfo is an xz-compressed ELF file holding just a `.symtab`
+    /// with the function symbols that stripping removed, at the same
+    /// addresses as in this object. The format is documented at:
+    /// https://sourceware.org/gdb/onlinedocs/gdb/MiniDebugInfo.html
+    #[cfg(feature = "gnu-debugdata")]
+    fn with_gnu_debugdata(mut self, stash: &'a Stash) -> Object<'a> {
+        let data = match self.section(stash, ".gnu_debugdata") {
+            Some(data) => data,
+            None => return self,
+        };
+        let mut decompressed = Vec::new();
+        if lzma_rs::xz_decompress(&mut &data[..], &mut decompressed).is_err() {
+            return self;
+        }
+        let buf = stash.allocate(decompressed.len());
+        buf.copy_from_slice(&decompressed);
+        let mini = match Object::parse(buf) {
+            Some(mini) => mini,
+            None => return self,
+        };
+        self.syms.extend(mini.syms);
+        self.syms.sort_by_key(|s| s.address);
+        self.syms
+            .dedup_by(|b, a| a.address == b.address && a.name == b.name);
+        self
+    }
+
     fn section_header(&self, name: &str) -> Option<&<Elf as FileHeader>::SectionHeader> {
         self.sections
             .section_by_name(self.endian, name.as_bytes())
             .map(|(_index, section)| section)
     }
 
-    pub fn search_symtab<'b>(&'b self, addr: u64) -> Option<&'b [u8]> {
+    pub fn search_symtab<'b>(&'b self, addr: u64) -> Option<(&'b [u8], u64)> {
         // Same sort of binary search as Windows above
         let i = match self.syms.binary_search_by_key(&addr, |sym| sym.address) {
             Ok(i) => i,
             Err(i) => i.checked_sub(1)?,
         };
         let sym = self.syms.get(i)?;
-        if sym.address <= addr && addr <= sym.address + sym.size {
-            self.strings.get(sym.name).ok()
+        // Symbols defined in assembly often don't have their size set, so
+        // those are taken to extend up to the next symbol.
+        if sym.address <= addr && (sym.size == 0 || addr <= sym.address + sym.size) {
+            Some((sym.name, sym.address))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the start and size of the symbol containing `addr`.
+    pub fn symbol_bounds(&self, addr: u64) -> Option<(u64, u64)> {
+        let i = match self.syms.binary_search_by_key(&addr, |sym| sym.address) {
+            Ok(i) => i,
+            Err(i) => i.checked_sub(1)?,
+        };
+        let sym = self.syms.get(i)?;
+        // As in `search_symtab`, symbols without a size extend up to the next
+        // symbol.
+        let size = match sym.size {
+            0 => {
+                self.syms[i + 1..]
+                    .iter()
+                    .map(|s| s.address)
+                    .find(|&a| a > sym.address)?
+                    - sym.address
+            }
+            size => size,
+        };
+        if sym.address <= addr && addr - sym.address < size {
+            Some((sym.address, size))
         } else {
             None
         }
@@ -304,6 +420,18 @@ fn decompress_zlib(input: &[u8], output: &mut [u8]) -> Option<()> {
     }
 }
 
+#[cfg(feature = "zstd")]
+fn decompress_zstd(input: &[u8], output: &mut [u8]) -> Option<()> {
+    use ruzstd::frame_decoder::FrameDecoder;
+
+    let out_read = FrameDecoder::new().decode_all(input, output).ok()?;
+    if out_read == output.len() {
+        Some(())
+    } else {
+        None
+    }
+}
+
 const DEBUG_PATH: &[u8] = b"/usr/lib/debug";
 
 fn debug_path_exists() -> bool {
@@ -328,25 +456,41 @@ fn debug_path_exists() -> bool {
     }
 }
 
-/// Locate a debug file based on its build ID.
+/// Locate a debug file based on its build ID, in the directories set with
+/// `set_search_paths` and then in `DEBUG_PATH`.
 ///
 /// The format of build id paths is documented at:
 /// https://sourceware.org/gdb/onlinedocs/gdb/Separate-Debug-Files.html
 fn locate_build_id(build_id: &[u8]) -> Option<PathBuf> {
-    const BUILD_ID_PATH: &[u8] = b"/usr/lib/debug/.build-id/";
-    const BUILD_ID_SUFFIX: &[u8] = b".debug";
-
     if build_id.len() < 2 {
         return None;
     }
 
+    for dir in super::search_paths() {
+        let path = build_id_path(dir.as_os_str().as_bytes(), build_id);
+        if path.is_file() {
+            return Some(path);
+        }
+    }
+
     if !debug_path_exists() {
         return None;
     }
 
-    let mut path =
-        Vec::with_capacity(BUILD_ID_PATH.len() + BUILD_ID_SUFFIX.len() + build_id.len() * 2 + 1);
-    path.extend(BUILD_ID_PATH);
+    Some(build_id_path(DEBUG_PATH, build_id))
+}
+
+/// Returns the path of the debug file with `build_id` under the debug
+/// directory `dir`.
+fn build_id_path(dir: &[u8], build_id: &[u8]) -> PathBuf {
+    const BUILD_ID_DIR: &[u8] = b"/.build-id/";
+    const BUILD_ID_SUFFIX: &[u8] = b".debug";
+
+    let mut path = Vec::with_capacity(
+        dir.len() + BUILD_ID_DIR.len() + BUILD_ID_SUFFIX.len() + build_id.len() * 2 + 1,
+    );
+    path.extend(dir);
+    path.extend(BUILD_ID_DIR);
     path.push(hex(build_id[0] >> 4));
     path.push(hex(build_id[0] & 0xf));
     path.push(b'/');
@@ -355,7 +499,7 @@ fn locate_build_id(build_id: &[u8]) -> Option<PathBuf> {
         path.push(hex(byte & 0xf));
     }
     path.extend(BUILD_ID_SUFFIX);
-    Some(PathBuf::from(OsString::from_vec(path)))
+    PathBuf::from(OsString::from_vec(path))
 }
 
 fn hex(byte: u8) -> u8 {
@@ -374,9 +518,12 @@ fn hex(byte: u8) -> u8 {
 /// Search order is based on gdb, documented at:
 /// https://sourceware.org/gdb/onlinedocs/gdb/Separate-Debug-Files.html
 ///
-/// gdb also allows the user to customize the debug search path, but we don't.
+/// The directories set with `set_search_paths` are searched first, like the
+/// directories of gdb's `debug-file-directory` setting, also looking for the
+/// file directly in them.
 ///
-/// gdb also supports debuginfod, but we don't yet.
+/// Debug files on debuginfod servers are found by build ID instead, see
+/// `crate::debuginfod`.
 fn locate_debuglink(path: &Path, filename: &[u8]) -> Option<PathBuf> {
     let path = fs::canonicalize(path).ok()?;
     let parent = path.parent()?;
@@ -385,6 +532,17 @@ fn locate_debuglink(path: &Path, filename: &[u8]) -> Option<PathBuf> {
     ));
     let filename = Path::new(OsStr::from_bytes(filename));
 
+    // Try "/dir/filename" and "/dir/parent/filename" for each search path
+    for dir in super::search_paths() {
+        let candidates = [
+            dir.join(filename),
+            dir.join(parent.strip_prefix("/").unwrap()).join(filename),
+        ];
+        if let Some(f) = candidates.iter().find(|f| f.is_file()) {
+            return Some(f.clone());
+        }
+    }
+
     // Try "/parent/filename" if it differs from "path"
     f.push(parent);
     f.push(filename);
@@ -426,11 +584,10 @@ fn locate_debuglink(path: &Path, filename: &[u8]) -> Option<PathBuf> {
 ///
 /// Search order is based on gdb:
 /// - filename, which is either absolute or relative to `path`
-/// - the build ID path under `BUILD_ID_PATH`
+/// - the build ID path under the search paths and `DEBUG_PATH`
 ///
-/// gdb also allows the user to customize the debug search path, but we don't.
-///
-/// gdb also supports debuginfod, but we don't yet.
+/// gdb also supports looking up supplementary files on debuginfod servers,
+/// but we don't yet.
 fn locate_debugaltlink(path: &Path, filename: &[u8], build_id: &[u8]) -> Option<PathBuf> {
     let filename = Path::new(OsStr::from_bytes(filename));
     if filename.is_absolute() {
@@ -473,7 +630,15 @@ pub(super) fn handle_split_dwarf<'data>(
 
     path.push(convert_path(load.path.as_ref()?).ok()?);
 
-    if let Some(map_dwo) = super::mmap(&path) {
+    // The `.dwo` file may have been moved from where it was written, in which
+    // case it's looked up by name in the search paths.
+    let map_dwo = super::mmap(&path).or_else(|| {
+        let name = path.file_name()?;
+        super::search_paths()
+            .iter()
+            .find_map(|dir| super::mmap(&dir.join(name)))
+    });
+    if let Some(map_dwo) = map_dwo {
         let map_dwo = stash.cache_mmap(map_dwo);
         if let Some(dwo) = Object::parse(map_dwo) {
             return gimli::Dwarf::load(|id| -> Result<_, ()> {
@@ -0,0 +1,81 @@
+// Android only gained `dl_iterate_phdr` in API level 21, so on older versions
+// the loaded libraries are instead found through `/proc/self/maps`. Each
+// object's ELF header is mapped at the start of its first segment, which is
+// where its program headers are read from.
+
+use super::mystd::os::unix::prelude::*;
+use super::parse_running_mmaps::{self, MapsEntry};
+use super::{Library, LibrarySegment, OsString, Vec};
+use core::slice;
+use object::read::elf::{FileHeader, ProgramHeader};
+use object::NativeEndian;
+
+#[cfg(target_pointer_width = "32")]
+type Elf = object::elf::FileHeader32<NativeEndian>;
+#[cfg(target_pointer_width = "64")]
+type Elf = object::elf::FileHeader64<NativeEndian>;
+
+pub(super) fn native_libraries() -> Vec<Library> {
+    let entries = match parse_running_mmaps::parse_maps() {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries.iter().filter_map(library).collect()
+}
+
+fn library(entry: &MapsEntry) -> Option<Library> {
+    let name = entry.pathname().as_bytes();
+    if !entry.is_readable() || name.is_empty() || name[0] == b'[' {
+        return None;
+    }
+    let (start, end) = entry.address();
+    let data = unsafe { slice::from_raw_parts(start as *const u8, end - start) };
+    if !data.starts_with(b"\x7fELF") {
+        return None;
+    }
+    let elf = Elf::parse(data).ok()?;
+    let endian = elf.endian().ok()?;
+    let headers = elf.program_headers(endian, data).ok()?;
+
+    // The segment at the start of the file is the one mapped here, which
+    // determines where the object was loaded.
+    let vaddr = |h: &<Elf as FileHeader>::ProgramHeader| -> u64 { h.p_vaddr(endian).into() };
+    let first = headers.iter().find(|h| {
+        let offset: u64 = h.p_offset(endian).into();
+        h.p_type(endian) == object::elf::PT_LOAD && offset == 0
+    })?;
+    let bias = start.wrapping_sub(vaddr(first) as usize);
+
+    // Objects mapped from the middle of a file are libraries loaded directly
+    // out of an APK, which are named after their entry in the archive.
+    let name = if entry.offset() == 0 {
+        entry.pathname().clone()
+    } else {
+        apk_library_name(entry.pathname(), entry.offset())?
+    };
+
+    Some(Library {
+        name,
+        segments: headers
+            .iter()
+            .map(|header| {
+                let len: u64 = header.p_memsz(endian).into();
+                LibrarySegment {
+                    len: len as usize,
+                    stated_virtual_memory_address: vaddr(header) as usize,
+                }
+            })
+            .collect(),
+        bias,
+        build_id: None,
+    })
+}
+
+fn apk_library_name(apk: &OsString, offset: usize) -> Option<OsString> {
+    let map = super::mmap(apk.as_ref())?;
+    let entry = super::zip::entries(&map)?.find(|entry| entry.offset == offset)?;
+    let mut name = apk.as_bytes().to_vec();
+    name.extend_from_slice(b"!/");
+    name.extend_from_slice(entry.name);
+    Some(OsString::from_vec(name))
+}
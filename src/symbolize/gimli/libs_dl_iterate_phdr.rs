@@ -7,13 +7,26 @@ use super::mystd::env;
 use super::mystd::ffi::{CStr, OsStr};
 use super::mystd::os::unix::prelude::*;
 use super::{Library, LibrarySegment, OsString, Vec};
+use core::convert::TryInto;
 use core::slice;
 
+#[cfg(target_pointer_width = "32")]
+type Phdr = libc::Elf32_Phdr;
+#[cfg(target_pointer_width = "64")]
+type Phdr = libc::Elf64_Phdr;
+
 pub(super) fn native_libraries() -> Vec<Library> {
     let mut ret = Vec::new();
     unsafe {
         libc::dl_iterate_phdr(Some(callback), &mut ret as *mut Vec<_> as *mut _);
     }
+    // Statically linked programs may not report themselves through
+    // `dl_iterate_phdr`, in which case the program headers which the kernel
+    // passed to the executable are used instead.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if ret.is_empty() {
+        ret.extend(unsafe { main_program() });
+    }
     return ret;
 }
 
@@ -28,7 +41,67 @@ fn infer_current_exe(base_addr: usize) -> OsString {
             return path;
         }
     }
-    env::current_exe().map(|e| e.into()).unwrap_or_default()
+    if let Ok(path) = env::current_exe() {
+        return path.into();
+    }
+    // Without `/proc`, such as in some containers and chroots, fall back to
+    // the path the executable was started from. This is relative to the
+    // working directory at the time, which usually hasn't changed.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    unsafe {
+        let execfn = libc::getauxval(libc::AT_EXECFN) as *const libc::c_char;
+        if !execfn.is_null() {
+            return OsStr::from_bytes(CStr::from_ptr(execfn).to_bytes()).to_owned();
+        }
+    }
+    OsString::new()
+}
+
+// Describes the executable from its own program headers, which the kernel
+// passes to it in the auxiliary vector.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn main_program() -> Option<Library> {
+    use super::mystd::path::Path;
+    use object::elf::{PT_LOAD, PT_PHDR};
+    use object::read::elf::FileHeader;
+    use object::NativeEndian;
+
+    #[cfg(target_pointer_width = "32")]
+    type Elf = object::elf::FileHeader32<NativeEndian>;
+    #[cfg(target_pointer_width = "64")]
+    type Elf = object::elf::FileHeader64<NativeEndian>;
+
+    let phdr = libc::getauxval(libc::AT_PHDR) as usize;
+    let phnum = libc::getauxval(libc::AT_PHNUM) as usize;
+    let phent = libc::getauxval(libc::AT_PHENT) as usize;
+    if phdr == 0 || phent != core::mem::size_of::<Phdr>() {
+        return None;
+    }
+    let headers = slice::from_raw_parts(phdr as *const Phdr, phnum);
+    let name = infer_current_exe(phdr);
+
+    // Comparing where the program headers were loaded with where they were
+    // linked tells how far the executable was relocated. That's stated by
+    // `PT_PHDR` if there is one, and otherwise follows from their offset in
+    // the file, which is read from `/proc/self/exe` if it can be opened.
+    let vaddr = match headers.iter().find(|h| h.p_type == PT_PHDR) {
+        Some(header) => header.p_vaddr as usize,
+        None => {
+            let map = super::mmap(Path::new("/proc/self/exe"))
+                .or_else(|| super::mmap(Path::new(&name)))?;
+            let phoff = Elf::parse(&*map).ok()?.e_phoff(NativeEndian) as usize;
+            headers
+                .iter()
+                .filter(|h| h.p_type == PT_LOAD)
+                .find(|h| {
+                    let offset = h.p_offset as usize;
+                    offset <= phoff && phoff - offset < h.p_filesz as usize
+                })
+                .map(|h| (h.p_vaddr as usize).wrapping_add(phoff - h.p_offset as usize))?
+        }
+    };
+    let bias = phdr.wrapping_sub(vaddr);
+    Some(library(name, bias, headers))
 }
 
 // `info` should be a valid pointers.
@@ -40,7 +113,11 @@ unsafe extern "C" fn callback(
 ) -> libc::c_int {
     let info = &*info;
     let libs = &mut *(vec as *mut Vec<Library>);
-    let is_main_prog = info.dlpi_name.is_null() || *info.dlpi_name == 0;
+    // Statically linked musl programs call themselves `/proc/self/exe`, which
+    // can't be opened when `/proc` isn't mounted.
+    let is_main_prog = info.dlpi_name.is_null()
+        || *info.dlpi_name == 0
+        || CStr::from_ptr(info.dlpi_name).to_bytes() == b"/proc/self/exe";
     let name = if is_main_prog {
         // The man page for dl_iterate_phdr says that the first object visited by
         // callback is the main program; so the first time we encounter a
@@ -56,16 +133,93 @@ unsafe extern "C" fn callback(
         OsStr::from_bytes(bytes).to_owned()
     };
     let headers = slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize);
-    libs.push(Library {
+    libs.push(library(name, info.dlpi_addr as usize, headers));
+    0
+}
+
+// `headers` should be the program headers of an object loaded at `bias`.
+unsafe fn library(name: OsString, bias: usize, headers: &[Phdr]) -> Library {
+    Library {
         name,
         segments: headers
             .iter()
             .map(|header| LibrarySegment {
-                len: (*header).p_memsz as usize,
-                stated_virtual_memory_address: (*header).p_vaddr as usize,
+                len: header.p_memsz as usize,
+                stated_virtual_memory_address: header.p_vaddr as usize,
             })
             .collect(),
-        bias: info.dlpi_addr as usize,
-    });
-    0
+        bias,
+        build_id: build_id(bias, headers),
+    }
+}
+
+// Searches the `PT_NOTE` segments of a loaded object for its GNU build-id.
+// The notes are part of a loaded segment so they can be read directly out of
+// memory rather than reopening the file.
+unsafe fn build_id(bias: usize, headers: &[Phdr]) -> Option<Vec<u8>> {
+    use object::elf::{ELF_NOTE_GNU, NT_GNU_BUILD_ID, PT_NOTE};
+
+    for header in headers.iter().filter(|h| h.p_type == PT_NOTE) {
+        let align = if header.p_align == 8 { 8 } else { 4 };
+        let mut data = slice::from_raw_parts(
+            bias.wrapping_add(header.p_vaddr as usize) as *const u8,
+            header.p_memsz as usize,
+        );
+        // Each note is a `namesz`, `descsz` and `type` header followed by the
+        // name and descriptor, each padded to the segment's alignment.
+        while data.len() >= 12 {
+            let word = |i: usize| u32::from_ne_bytes(data[i..i + 4].try_into().unwrap()) as usize;
+            let (namesz, descsz, ty) = (word(0), word(4), word(8));
+            let desc_start = (12 + namesz + align - 1) & !(align - 1);
+            let desc_end = desc_start + descsz;
+            if desc_end > data.len() {
+                break;
+            }
+            let name = &data[12..12 + namesz];
+            if ty == NT_GNU_BUILD_ID as usize && name.strip_suffix(&[0]) == Some(ELF_NOTE_GNU) {
+                return Some(data[desc_start..desc_end].to_vec());
+            }
+            data = &data[((desc_end + align - 1) & !(align - 1)).min(data.len())..];
+        }
+    }
+    None
+}
+
+/// Returns a value which changes whenever a library is loaded or unloaded, so
+/// the list of libraries only needs to be rebuilt when it's out of date.
+///
+/// This is built from the `dlpi_adds` and `dlpi_subs` counters, which only
+/// need to be read from the first object. They're a later addition to
+/// `dl_phdr_info` though, so `None` is returned if the loader doesn't provide
+/// them.
+#[cfg(not(target_os = "openbsd"))]
+pub(super) fn libraries_generation() -> Option<u64> {
+    unsafe extern "C" fn callback(
+        info: *mut libc::dl_phdr_info,
+        size: libc::size_t,
+        generation: *mut libc::c_void,
+    ) -> libc::c_int {
+        let info = &*info;
+        let end = (&info.dlpi_subs as *const _ as usize) - (info as *const _ as usize)
+            + core::mem::size_of_val(&info.dlpi_subs);
+        if size >= end {
+            *(generation as *mut Option<u64>) =
+                Some(info.dlpi_adds.wrapping_add(info.dlpi_subs << 32));
+        }
+        1
+    }
+
+    let mut generation = None;
+    unsafe {
+        libc::dl_iterate_phdr(
+            Some(callback),
+            &mut generation as *mut Option<u64> as *mut _,
+        );
+    }
+    generation
+}
+
+#[cfg(target_os = "openbsd")]
+pub(super) fn libraries_generation() -> Option<u64> {
+    None
 }
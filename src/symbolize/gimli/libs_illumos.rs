@@ -90,6 +90,7 @@ pub(super) fn native_libraries() -> Vec<Library> {
                 // Other addresses are relative to the object base.
                 addr as usize
             },
+            build_id: None,
         });
 
         l = unsafe { (*l).l_next };
@@ -21,6 +21,7 @@ pub(super) fn native_libraries() -> Vec<Library> {
         name: path.into(),
         segments,
         bias,
+        build_id: None,
     });
 
     ret
@@ -68,8 +68,17 @@ fn native_library(i: u32) -> Option<Library> {
     let mut segments = Vec::new();
     let mut first_text = 0;
     let mut text_fileoff_zero = false;
+    let mut build_id = None;
     while let Some(cmd) = load_commands.next().ok()? {
+        if let Some(uuid) = cmd.uuid().ok()? {
+            build_id = Some(uuid.uuid.to_vec());
+        }
         if let Some((seg, _)) = cmd.segment_32().ok()? {
+            // `__PAGEZERO` only reserves the address space below the image,
+            // nothing is ever loaded there.
+            if seg.name() == b"__PAGEZERO" {
+                continue;
+            }
             if seg.name() == b"__TEXT" {
                 first_text = segments.len();
                 if seg.fileoff(endian) == 0 && seg.filesize(endian) > 0 {
@@ -82,6 +91,9 @@ fn native_library(i: u32) -> Option<Library> {
             });
         }
         if let Some((seg, _)) = cmd.segment_64().ok()? {
+            if seg.name() == b"__PAGEZERO" {
+                continue;
+            }
             if seg.name() == b"__TEXT" {
                 first_text = segments.len();
                 if seg.fileoff(endian) == 0 && seg.filesize(endian) > 0 {
@@ -142,5 +154,6 @@ fn native_library(i: u32) -> Option<Library> {
         name: OsStr::from_bytes(name.to_bytes()).to_owned(),
         segments,
         bias: slide,
+        build_id,
     })
 }
@@ -77,9 +77,11 @@ unsafe fn load_library(me: &MODULEENTRY32W) -> Option<Library> {
     // segment per library, using `modBaseSize` as the whole size.
     let mmap = mmap(name.as_ref())?;
     let image_base = coff::get_image_base(&mmap)?;
+    let build_id = coff::get_build_id(&mmap);
     let base_addr = me.modBaseAddr as usize;
     Some(Library {
         name,
+        build_id,
         bias: base_addr.wrapping_sub(image_base),
         segments: vec![LibrarySegment {
             stated_virtual_memory_address: image_base,
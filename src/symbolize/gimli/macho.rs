@@ -1,9 +1,12 @@
+use super::mystd::ffi::CStr;
+use super::mystd::os::unix::prelude::*;
 use super::{gimli, Box, Context, Endian, EndianSlice, Mapping, Path, Stash, Vec};
 use alloc::sync::Arc;
 use core::convert::TryInto;
+use core::{mem, slice};
 use object::macho;
-use object::read::macho::{MachHeader, Nlist, Section, Segment as _};
-use object::{Bytes, NativeEndian};
+use object::read::macho::{MachHeader, Nlist, Section, Segment as _, SymbolTable};
+use object::{Bytes, NativeEndian, U32};
 
 #[cfg(target_pointer_width = "32")]
 type Mach = object::macho::MachHeader32<NativeEndian>;
@@ -24,19 +27,8 @@ impl Mapping {
         let (macho, data) = find_header(&map)?;
         let endian = macho.endian().ok()?;
         let uuid = macho.uuid(endian, data, 0).ok()?;
-
-        // Next we need to look for a `*.dSYM` file. For now we just probe the
-        // containing directory and look around for something that matches
-        // `*.dSYM`. Once it's found we root through the dwarf resources that it
-        // contains and try to find a macho file which has a matching UUID as
-        // the one of our own file. If we find a match that's the dwarf file we
-        // want to return.
-        if let Some(uuid) = uuid {
-            if let Some(parent) = path.parent() {
-                if let Some(mapping) = Mapping::load_dsym(parent, uuid) {
-                    return Some(mapping);
-                }
-            }
+        if let Some(mapping) = uuid.and_then(|uuid| Mapping::find_dsym(path, uuid)) {
+            return Some(mapping);
         }
 
         // Looks like nothing matched our UUID, so let's at least return our own
@@ -50,6 +42,138 @@ impl Mapping {
         })
     }
 
+    /// Creates a mapping for an image registered with `register_image`.
+    #[cfg(feature = "std-base")]
+    pub fn new_image(image: Arc<[u8]>) -> Option<Mapping> {
+        Mapping::mk_image(image, |data, stash| {
+            let (macho, data) = find_header(data)?;
+            let endian = macho.endian().ok()?;
+            let obj = Object::parse(macho, endian, data)?;
+            Context::new(stash, obj, None, None)
+        })
+    }
+
+    /// Creates a mapping for an image which has no file that can be read,
+    /// such as the system libraries which are only part of the dyld shared
+    /// cache, from the parts of it which are loaded into memory.
+    ///
+    /// Unless a dSYM is found for it, all that's available then is the symbol
+    /// table in the `__LINKEDIT` segment, which lacks the local symbols of
+    /// stripped images but still names their exported functions.
+    pub fn new_loaded(path: &Path) -> Option<Mapping> {
+        let (header, slide) = find_loaded_image(path)?;
+        let endian = NativeEndian;
+        let data = unsafe {
+            slice::from_raw_parts(
+                header as *const Mach as *const u8,
+                mem::size_of::<Mach>() + header.sizeofcmds(endian) as usize,
+            )
+        };
+        if let Some(uuid) = header.uuid(endian, data, 0).ok()? {
+            if let Some(mapping) = Mapping::find_dsym(path, uuid) {
+                return Some(mapping);
+            }
+        }
+
+        let mut commands = header.load_commands(endian, data, 0).ok()?;
+        let mut linkedit = None;
+        let mut text_adjust = 0;
+        let mut symtab = None;
+        while let Ok(Some(command)) = commands.next() {
+            if let Some((segment, _)) = MachSegment::from_command(command).ok()? {
+                if segment.name() == b"__LINKEDIT" {
+                    linkedit = Some(segment);
+                }
+                // Symbol addresses must be made relative to the library's
+                // bias the same way `native_libraries` computes it.
+                if segment.name() == b"__TEXT" && segment.fileoff(endian) != 0 {
+                    text_adjust = segment.vmaddr(endian).into();
+                }
+            } else if let Some(command) = command.symtab().ok()? {
+                symtab = Some(*command);
+            }
+        }
+
+        // The offsets in the symbol table command are file offsets, so they
+        // are rebased onto where `__LINKEDIT` has been loaded.
+        let linkedit = linkedit?;
+        let mut symtab = symtab?;
+        let fileoff: u64 = linkedit.fileoff(endian).into();
+        let rebase = |offset: U32<NativeEndian>| -> Option<U32<NativeEndian>> {
+            let offset = u64::from(offset.get(endian)).checked_sub(fileoff)?;
+            Some(U32::new(endian, offset.try_into().ok()?))
+        };
+        symtab.symoff = rebase(symtab.symoff)?;
+        symtab.stroff = rebase(symtab.stroff)?;
+        let vmaddr: u64 = linkedit.vmaddr(endian).into();
+        let vmsize: u64 = linkedit.vmsize(endian).into();
+        let data = unsafe {
+            slice::from_raw_parts(
+                (vmaddr as usize).wrapping_add(slide) as *const u8,
+                vmsize as usize,
+            )
+        };
+        let symbols = symtab.symbols::<Mach, _>(endian, data).ok()?;
+        let mut syms = defined_symbols(&symbols, endian);
+        for (_, addr) in syms.iter_mut() {
+            *addr = addr.wrapping_sub(text_adjust);
+        }
+        syms.sort_unstable_by_key(|(_, addr)| *addr);
+
+        let stash = Stash::new();
+        let obj = Object {
+            endian,
+            data,
+            dwarf: None,
+            syms,
+            syms_sort_by_name: false,
+            object_map: None,
+            object_mappings: Box::new([]),
+        };
+        let cx = Context::new(&stash, obj, None, None)?;
+        Some(Mapping {
+            // The context only borrows loaded memory, which lives as long as
+            // the image stays loaded, and the stash which is kept alongside.
+            cx: unsafe { core::mem::transmute::<Context<'_>, Context<'static>>(cx) },
+            _map: None,
+            _image: None,
+            stash,
+        })
+    }
+
+    /// Looks for the `*.dSYM` of the image at `path` with the UUID `uuid`.
+    fn find_dsym(path: &Path, uuid: [u8; 16]) -> Option<Mapping> {
+        // For now we just probe the containing directory and look around for
+        // something that matches `*.dSYM`. Once it's found we root through the
+        // dwarf resources that it contains and try to find a macho file which
+        // has a matching UUID as the one of our own file. If we find a match
+        // that's the dwarf file we want to return.
+        //
+        // Executables inside of a bundle (e.g. `Foo.app/Contents/MacOS/Foo`)
+        // have their dSYM placed next to the bundle itself by Xcode, as
+        // `Foo.app.dSYM`, so the directories containing any enclosing
+        // `.app`/`.framework`/etc bundles are probed as well.
+        //
+        // The directories set with `set_search_paths` are probed first.
+        for dir in super::search_paths() {
+            if let Some(mapping) = Mapping::load_dsym(&dir, uuid) {
+                return Some(mapping);
+            }
+        }
+        let parent = path.parent()?;
+        if let Some(mapping) = Mapping::load_dsym(parent, uuid) {
+            return Some(mapping);
+        }
+        for bundle in parent.ancestors().filter(|p| is_bundle(p)) {
+            if let Some(dir) = bundle.parent() {
+                if let Some(mapping) = Mapping::load_dsym(dir, uuid) {
+                    return Some(mapping);
+                }
+            }
+        }
+        None
+    }
+
     fn load_dsym(dir: &Path, uuid: [u8; 16]) -> Option<Mapping> {
         for entry in dir.read_dir().ok()? {
             let entry = entry.ok()?;
@@ -94,6 +218,38 @@ impl Mapping {
     }
 }
 
+/// Finds the image loaded from `path`, returning its header and slide.
+fn find_loaded_image(path: &Path) -> Option<(&'static Mach, usize)> {
+    let path = path.as_os_str().as_bytes();
+    unsafe {
+        for i in 0..libc::_dyld_image_count() {
+            let name = libc::_dyld_get_image_name(i);
+            if name.is_null() || CStr::from_ptr(name).to_bytes() != path {
+                continue;
+            }
+            let header = libc::_dyld_get_image_header(i) as *const Mach;
+            let magic = if cfg!(target_pointer_width = "64") {
+                macho::MH_MAGIC_64
+            } else {
+                macho::MH_MAGIC
+            };
+            if header.is_null() || (*header).magic() != magic {
+                return None;
+            }
+            let slide = libc::_dyld_get_image_vmaddr_slide(i) as usize;
+            return Some((&*header, slide));
+        }
+    }
+    None
+}
+
+fn is_bundle(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => ["app", "framework", "bundle", "appex", "xpc"].contains(&ext),
+        None => false,
+    }
+}
+
 fn find_header(data: &'_ [u8]) -> Option<(&'_ Mach, &'_ [u8])> {
     use object::endian::BigEndian;
 
@@ -186,17 +342,7 @@ impl<'a> Object<'a> {
                 }
             } else if let Some(symtab) = command.symtab().ok()? {
                 let symbols = symtab.symbols::<Mach, _>(endian, data).ok()?;
-                syms = symbols
-                    .iter()
-                    .filter_map(|nlist: &MachNlist| {
-                        let name = nlist.name(endian, symbols.strings()).ok()?;
-                        if name.len() > 0 && nlist.is_definition() {
-                            Some((name, u64::from(nlist.n_value(endian))))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
+                syms = defined_symbols(&symbols, endian);
                 if is_object {
                     // We never search object file symbols by address.
                     // Instead, we already know the symbol name from the executable, and we
@@ -237,14 +383,31 @@ impl<'a> Object<'a> {
         Some(section.data(self.endian, self.data).ok()?)
     }
 
-    pub fn search_symtab<'b>(&'b self, addr: u64) -> Option<&'b [u8]> {
+    pub fn search_symtab<'b>(&'b self, addr: u64) -> Option<(&'b [u8], u64)> {
         debug_assert!(!self.syms_sort_by_name);
         let i = match self.syms.binary_search_by_key(&addr, |(_, addr)| *addr) {
             Ok(i) => i,
             Err(i) => i.checked_sub(1)?,
         };
-        let (sym, _addr) = self.syms.get(i)?;
-        Some(sym)
+        let (sym, addr) = self.syms.get(i)?;
+        Some((sym, *addr))
+    }
+
+    /// Returns the start and size of the symbol containing `addr`, which
+    /// Mach-O doesn't record, so each symbol is taken to extend up to the
+    /// next one.
+    pub fn symbol_bounds(&self, addr: u64) -> Option<(u64, u64)> {
+        debug_assert!(!self.syms_sort_by_name);
+        let i = match self.syms.binary_search_by_key(&addr, |(_, addr)| *addr) {
+            Ok(i) => i,
+            Err(i) => i.checked_sub(1)?,
+        };
+        let start = self.syms.get(i)?.1;
+        let end = self.syms[i + 1..]
+            .iter()
+            .map(|(_, addr)| *addr)
+            .find(|&a| a > start)?;
+        Some((start, end - start))
     }
 
     /// Try to load a context for an object file.
@@ -281,6 +444,24 @@ impl<'a> Object<'a> {
     }
 }
 
+/// Returns the names and addresses of the symbols defined in `symbols`.
+fn defined_symbols<'a>(
+    symbols: &SymbolTable<'a, Mach>,
+    endian: NativeEndian,
+) -> Vec<(&'a [u8], u64)> {
+    symbols
+        .iter()
+        .filter_map(|nlist: &MachNlist| {
+            let name = nlist.name(endian, symbols.strings()).ok()?;
+            if name.len() > 0 && nlist.is_definition() {
+                Some((name, u64::from(nlist.n_value(endian))))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
 fn object_mapping(path: &[u8]) -> Option<Mapping> {
     use super::mystd::ffi::OsStr;
     use super::mystd::os::unix::prelude::*;
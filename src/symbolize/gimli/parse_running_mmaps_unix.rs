@@ -1,6 +1,7 @@
 // Note: This file is only currently used on targets that call out to the code
-// in `mod libs_dl_iterate_phdr` (e.g. linux, freebsd, ...); it may be more
-// general purpose, but it hasn't been tested elsewhere.
+// in `mod libs_dl_iterate_phdr` (e.g. linux, freebsd, ...) and on Android
+// without `dl_iterate_phdr`; it may be more general purpose, but it hasn't been
+// tested elsewhere.
 
 use super::mystd::fs::File;
 use super::mystd::io::Read;
@@ -73,6 +74,21 @@ impl MapsEntry {
         &self.pathname
     }
 
+    #[cfg(target_os = "android")]
+    pub(super) fn address(&self) -> (usize, usize) {
+        self.address
+    }
+
+    #[cfg(target_os = "android")]
+    pub(super) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    #[cfg(target_os = "android")]
+    pub(super) fn is_readable(&self) -> bool {
+        self.perms[0] == 'r'
+    }
+
     pub(super) fn ip_matches(&self, ip: usize) -> bool {
         self.address.0 <= ip && ip < self.address.1
     }
@@ -0,0 +1,77 @@
+//! Reading the symbols of JIT-compiled code from the `/tmp/perf-<pid>.map`
+//! file which runtimes such as Node, the JVM and wasmtime can write for
+//! `perf`.
+//!
+//! Each line of the file describes one function as `START SIZE name`, with the
+//! start address and size in hexadecimal. Runtimes only ever append to the
+//! file as they generate code, so it's read again whenever an address isn't
+//! found in it and it has grown since it was last read.
+
+use super::mystd::fs;
+use super::mystd::prelude::v1::*;
+use super::mystd::process;
+
+#[derive(Default)]
+pub struct PerfMap {
+    /// The length of the file when it was last read.
+    len: u64,
+    /// The start address, end address and name of every function in the
+    /// file, sorted by start address. When entries overlap, because a runtime
+    /// reused memory for new code, the one later in the file comes later here.
+    symbols: Vec<(usize, usize, Vec<u8>)>,
+}
+
+impl PerfMap {
+    /// Returns the name, start and size of the function containing `addr`.
+    pub fn lookup(&mut self, addr: usize) -> Option<(&[u8], usize, usize)> {
+        if self.find(addr).is_none() {
+            self.reload();
+        }
+        let (start, end, ref name) = self.symbols[self.find(addr)?];
+        Some((name, start, end - start))
+    }
+
+    fn find(&self, addr: usize) -> Option<usize> {
+        let i = self
+            .symbols
+            .partition_point(|&(start, _, _)| start <= addr)
+            .checked_sub(1)?;
+        if addr < self.symbols[i].1 {
+            Some(i)
+        } else {
+            None
+        }
+    }
+
+    fn reload(&mut self) {
+        let path = format!("/tmp/perf-{}.map", process::id());
+        let len = match fs::metadata(&path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return,
+        };
+        if len == self.len {
+            return;
+        }
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+        self.len = len;
+        self.symbols = data.split(|&b| b == b'\n').filter_map(parse_line).collect();
+        self.symbols.sort_by_key(|&(start, _, _)| start);
+    }
+}
+
+fn parse_line(line: &[u8]) -> Option<(usize, usize, Vec<u8>)> {
+    let mut parts = line.splitn(3, |&b| b == b' ');
+    let start = parse_hex(parts.next()?)?;
+    let size = parse_hex(parts.next()?)?;
+    let name = parts.next()?;
+    let name = name.strip_suffix(b"\r").unwrap_or(name);
+    Some((start, start.checked_add(size)?, name.to_vec()))
+}
+
+fn parse_hex(s: &[u8]) -> Option<usize> {
+    let s = s.strip_prefix(b"0x").unwrap_or(s);
+    usize::from_str_radix(core::str::from_utf8(s).ok()?, 16).ok()
+}
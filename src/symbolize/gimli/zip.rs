@@ -0,0 +1,129 @@
+//! Just enough of the zip format to find libraries stored in Android APKs.
+//!
+//! Android can load shared libraries directly out of an APK, without
+//! extracting them, as long as they're stored uncompressed and page-aligned in
+//! the archive. Such libraries are named `path/to/base.apk!/lib/abi/libfoo.so`
+//! by the dynamic loader, so to read their debuginfo we look up the entry in
+//! the archive's central directory and use its data in place.
+
+use core::convert::TryInto;
+
+const END_OF_CENTRAL_DIRECTORY: &[u8] = b"PK\x05\x06";
+const CENTRAL_DIRECTORY_HEADER: &[u8] = b"PK\x01\x02";
+const LOCAL_FILE_HEADER: &[u8] = b"PK\x03\x04";
+
+/// A file stored in a zip archive.
+pub(super) struct Entry<'a> {
+    pub(super) name: &'a [u8],
+    /// The offset of the entry's data within the archive.
+    #[cfg_attr(not(target_os = "android"), allow(dead_code))]
+    pub(super) offset: usize,
+    /// The entry's data, or `None` if it's compressed.
+    pub(super) data: Option<&'a [u8]>,
+}
+
+/// Returns the data of the file `name` in the zip archive `zip`, if the file
+/// is stored without compression.
+pub(super) fn find<'a>(zip: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    entries(zip)?.find(|entry| entry.name == name)?.data
+}
+
+/// Iterates over the files in the zip archive `zip`.
+pub(super) fn entries(zip: &[u8]) -> Option<impl Iterator<Item = Entry<'_>>> {
+    // The end of central directory record is at the very end of the archive,
+    // except for a trailing comment of at most 64KiB.
+    let last = zip.len().checked_sub(22)?;
+    let end = (last.saturating_sub(0xffff)..=last)
+        .rev()
+        .find(|&i| &zip[i..i + 4] == END_OF_CENTRAL_DIRECTORY)?;
+    let count = u16_at(zip, end + 10)?;
+    let mut pos = u32_at(zip, end + 16)?;
+
+    let entries = (0..count).map(move |_| {
+        let header = zip.get(pos..)?;
+        if header.get(..4)? != CENTRAL_DIRECTORY_HEADER {
+            return None;
+        }
+        let compression = u16_at(header, 10)?;
+        let size = u32_at(header, 20)?;
+        let name_len = u16_at(header, 28)?;
+        let extra_len = u16_at(header, 30)?;
+        let comment_len = u16_at(header, 32)?;
+        let local = u32_at(header, 42)?;
+        let name = header.get(46..46 + name_len)?;
+        pos += 46 + name_len + extra_len + comment_len;
+
+        // The data follows the local file header, whose variable-length
+        // fields may differ from those in the central directory.
+        let local_header = zip.get(local..)?;
+        if local_header.get(..4)? != LOCAL_FILE_HEADER {
+            return None;
+        }
+        let offset = local + 30 + u16_at(local_header, 26)? + u16_at(local_header, 28)?;
+        let data = match compression {
+            0 => Some(zip.get(offset..offset.checked_add(size)?)?),
+            _ => None,
+        };
+        Some(Entry { name, offset, data })
+    });
+    // Stop at the first malformed header, as nothing after it can be found.
+    Some(entries.take_while(|entry| entry.is_some()).flatten())
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes(bytes.try_into().unwrap()) as usize)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+}
+
+#[test]
+fn find_stored_entry() {
+    fn push(zip: &mut alloc::vec::Vec<u8>, fields: &[(u32, usize)]) {
+        for &(value, size) in fields {
+            zip.extend_from_slice(&value.to_le_bytes()[..size]);
+        }
+    }
+
+    let mut zip = alloc::vec::Vec::new();
+    let files: &[(&[u8], &[u8], u32)] = &[
+        (b"assets/compressed", b"xxxx", 8),
+        (b"lib/x86_64/libfoo.so", b"\x7fELF", 0),
+    ];
+    let mut locals = alloc::vec::Vec::new();
+    for &(name, data, compression) in files {
+        locals.push(zip.len() as u32);
+        zip.extend_from_slice(LOCAL_FILE_HEADER);
+        push(
+            &mut zip,
+            &[(20, 2), (0, 2), (compression, 2), (0, 4), (0, 4)],
+        );
+        push(&mut zip, &[(data.len() as u32, 4), (data.len() as u32, 4)]);
+        push(&mut zip, &[(name.len() as u32, 2), (0, 2)]);
+        zip.extend_from_slice(name);
+        zip.extend_from_slice(data);
+    }
+    let directory = zip.len() as u32;
+    for (&(name, data, compression), &local) in files.iter().zip(&locals) {
+        zip.extend_from_slice(CENTRAL_DIRECTORY_HEADER);
+        push(&mut zip, &[(20, 2), (20, 2), (0, 2), (compression, 2)]);
+        push(&mut zip, &[(0, 4), (0, 4), (data.len() as u32, 4)]);
+        push(&mut zip, &[(data.len() as u32, 4), (name.len() as u32, 2)]);
+        push(
+            &mut zip,
+            &[(0, 2), (0, 2), (0, 2), (0, 2), (0, 4), (local, 4)],
+        );
+        zip.extend_from_slice(name);
+    }
+    let size = zip.len() as u32 - directory;
+    zip.extend_from_slice(END_OF_CENTRAL_DIRECTORY);
+    push(&mut zip, &[(0, 2), (0, 2), (2, 2), (2, 2), (size, 4)]);
+    push(&mut zip, &[(directory, 4), (0, 2)]);
+
+    assert_eq!(find(&zip, b"lib/x86_64/libfoo.so"), Some(&b"\x7fELF"[..]));
+    assert_eq!(find(&zip, b"assets/compressed"), None);
+    assert_eq!(find(&zip, b"missing"), None);
+}
@@ -0,0 +1,97 @@
+//! Symbolication of object files which were loaded from memory.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::prelude::v1::*;
+use std::sync::Arc;
+
+/// An object file registered with `register_image`.
+pub(crate) struct Image {
+    pub(crate) base: usize,
+    pub(crate) data: Arc<[u8]>,
+}
+
+// All registered images, guarded by the global lock in `crate::lock`.
+static mut IMAGES: Vec<Image> = Vec::new();
+
+// Incremented whenever `IMAGES` changes, so that the symbolication backend
+// knows when to update its list of modules.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers `image` as the contents of the object file loaded at `base`.
+///
+/// Code loaded from memory rather than from a file, for example from a
+/// `memfd` or with `NSCreateObjectFileImageFromMemory`, has no path the
+/// object file can be read from, so its frames would otherwise resolve to
+/// nothing. Addresses in the segments of a registered image are instead
+/// looked up in its symbol table and debug information, as if it had been
+/// loaded from a file.
+///
+/// `base` is where the lowest segment of the image is loaded, which is what
+/// `Module::base_address` reports for modules, and `image` the whole object
+/// file in the format of the platform. The modules of registered images are
+/// named after their base address. Registering another image at the same
+/// `base` replaces the previous one.
+///
+/// # Caveats
+///
+/// Registered images are only consulted by the gimli backend (e.g. unix
+/// platforms and MinGW), elsewhere they're ignored.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let (base, bytes) = (0, Vec::new());
+/// // `bytes` was read into a memfd, which the plugin was then loaded from,
+/// // and `base` is where the loader mapped it.
+/// backtrace::register_image(base, bytes);
+/// // ... backtraces through the plugin now have its symbols ...
+/// backtrace::unregister_image(base);
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn register_image(base: usize, image: Vec<u8>) {
+    let _guard = crate::lock::lock();
+    let images = unsafe { &mut *core::ptr::addr_of_mut!(IMAGES) };
+    images.retain(|i| i.base != base);
+    images.push(Image {
+        base,
+        data: image.into(),
+    });
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Unregisters the image which was registered by `register_image` at `base`,
+/// for example because it has been unloaded.
+///
+/// Returns whether such an image was registered.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn unregister_image(base: usize) -> bool {
+    let _guard = crate::lock::lock();
+    let images = unsafe { &mut *core::ptr::addr_of_mut!(IMAGES) };
+    let len = images.len();
+    images.retain(|i| i.base != base);
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+    images.len() != len
+}
+
+/// Returns all registered images.
+///
+/// This is required to be externally synchronized, like the native backends.
+#[allow(dead_code)]
+pub(crate) unsafe fn registered() -> &'static [Image] {
+    &*core::ptr::addr_of!(IMAGES)
+}
+
+/// Returns a number which changes whenever an image is registered or
+/// unregistered.
+#[allow(dead_code)]
+pub(crate) fn generation() -> usize {
+    GENERATION.load(Ordering::Relaxed)
+}
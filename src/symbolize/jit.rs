@@ -0,0 +1,277 @@
+//! Symbolication of machine code generated at runtime.
+//!
+//! Code emitted by a JIT compiler isn't part of any object file on disk, so
+//! the native symbolication backends can't find anything for addresses inside
+//! it and frames in JIT-compiled code are printed as `<unknown>`. Runtimes can
+//! instead describe their code to this crate: regions passed to `register` are
+//! consulted by `resolve` and friends before the native backends are.
+//!
+//! On Linux and Android, runtimes which already describe their code to
+//! debuggers through GDB's JIT interface can alternatively have this crate
+//! read the objects registered there, see `set_gdb_jit_interface`.
+//!
+//! # Required features
+//!
+//! This module requires the `std` feature of the `backtrace` crate to be
+//! enabled, and the `std` feature is enabled by default.
+
+use super::{BytesOrWideString, SymbolName};
+use core::ffi::c_void;
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::prelude::v1::*;
+use std::sync::Arc;
+
+struct Region {
+    range: Range<usize>,
+    name: Vec<u8>,
+    file: Option<PathBuf>,
+    line: Option<u32>,
+}
+
+// All registered regions sorted by their start address, none of them
+// overlapping. This is guarded by the global lock in `crate::lock`. Regions
+// are reference counted so that a symbol handed to a callback stays valid even
+// if the callback registers or unregisters code.
+static mut REGIONS: Vec<Arc<Region>> = Vec::new();
+
+static GDB_JIT_INTERFACE: AtomicBool = AtomicBool::new(false);
+
+/// Registers the code in `range` as belonging to the function `name`.
+///
+/// Addresses inside `range` are afterwards resolved to a symbol with this
+/// name, with `range.start` as its address and `file` and `line` as its
+/// location, if given. The name is demangled like that of any other symbol, so
+/// if it isn't a Rust or C++ name a demangler registered with `set_demangler`
+/// is consulted.
+///
+/// Regions previously registered which overlap `range` are unregistered, as
+/// the memory they described is presumably being reused for new code.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+///
+/// let code = [0u8; 64]; // pretend that this is machine code
+/// let start = code.as_ptr() as usize;
+/// backtrace::jit::register(
+///     start..start + code.len(),
+///     "my_script::main",
+///     Some(Path::new("main.script")),
+///     Some(3),
+/// );
+///
+/// backtrace::resolve((start + 8) as *mut _, |symbol| {
+///     assert_eq!(symbol.name().unwrap().to_string(), "my_script::main");
+///     assert_eq!(symbol.lineno(), Some(3));
+/// });
+/// backtrace::jit::unregister(start);
+/// ```
+pub fn register(range: Range<usize>, name: &str, file: Option<&Path>, line: Option<u32>) {
+    let _guard = crate::lock::lock();
+    let regions = unsafe { &mut *core::ptr::addr_of_mut!(REGIONS) };
+    regions.retain(|r| r.range.end <= range.start || range.end <= r.range.start);
+    if range.start >= range.end {
+        return;
+    }
+    let i = regions.partition_point(|r| r.range.start < range.start);
+    regions.insert(
+        i,
+        Arc::new(Region {
+            range,
+            name: name.as_bytes().to_vec(),
+            file: file.map(Path::to_path_buf),
+            line,
+        }),
+    );
+}
+
+/// Unregisters the region which was registered by `register` starting at
+/// `start`, for example because its code has been freed.
+///
+/// Returns whether such a region was registered.
+pub fn unregister(start: usize) -> bool {
+    let _guard = crate::lock::lock();
+    let regions = unsafe { &mut *core::ptr::addr_of_mut!(REGIONS) };
+    match regions.binary_search_by_key(&start, |r| r.range.start) {
+        Ok(i) => {
+            regions.remove(i);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Configures whether symbols are looked up in the objects registered through
+/// GDB's JIT interface.
+///
+/// Runtimes which support debugging their generated code with GDB, such as
+/// LLVM's JIT compilers, register an in-memory object file for it with the
+/// `__jit_debug_register_code` function and link these objects from the
+/// `__jit_debug_descriptor` symbol. When this is enabled, addresses which
+/// aren't in a region passed to `register` are looked up in the symbol tables
+/// of those objects, if the descriptor is exported from the process. Only
+/// function names are read from these objects, not file or line information.
+///
+/// This is disabled by default, and has no effect on platforms other than
+/// Linux and Android.
+pub fn set_gdb_jit_interface(enabled: bool) {
+    GDB_JIT_INTERFACE.store(enabled, Ordering::Relaxed);
+}
+
+/// A symbol for JIT-compiled code, which is what `Symbol` wraps for addresses
+/// resolved by this module.
+pub(crate) struct Symbol {
+    region: Arc<Region>,
+}
+
+impl Symbol {
+    pub fn name(&self) -> Option<SymbolName<'_>> {
+        Some(SymbolName::new(&self.region.name))
+    }
+
+    pub fn addr(&self) -> Option<*mut c_void> {
+        Some(self.region.range.start as *mut c_void)
+    }
+
+    pub fn offset_in_module(&self) -> Option<usize> {
+        None
+    }
+
+    pub fn module_build_id(&self) -> Option<&[u8]> {
+        None
+    }
+
+    pub fn offset_in_symbol(&self) -> Option<usize> {
+        None
+    }
+
+    pub fn function_start(&self) -> Option<*mut c_void> {
+        Some(self.region.range.start as *mut c_void)
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        Some(self.region.range.len())
+    }
+
+    pub fn filename_raw(&self) -> Option<BytesOrWideString<'_>> {
+        let file = self.region.file.as_ref()?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            Some(BytesOrWideString::Bytes(file.as_os_str().as_bytes()))
+        }
+        #[cfg(not(unix))]
+        {
+            file.to_str()
+                .map(|s| BytesOrWideString::Bytes(s.as_bytes()))
+        }
+    }
+
+    pub fn filename(&self) -> Option<&Path> {
+        self.region.file.as_deref()
+    }
+
+    pub fn lineno(&self) -> Option<u32> {
+        self.region.line
+    }
+
+    pub fn colno(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Returns the symbol of the JIT-compiled code containing `addr`, if any.
+///
+/// This is required to be externally synchronized, like the native backends.
+pub(crate) unsafe fn lookup(addr: *mut c_void) -> Option<Symbol> {
+    let addr = addr as usize;
+    let regions = &*core::ptr::addr_of!(REGIONS);
+    let i = regions.partition_point(|r| r.range.start <= addr);
+    if let Some(region) = i.checked_sub(1).map(|i| &regions[i]) {
+        if addr < region.range.end {
+            return Some(Symbol {
+                region: region.clone(),
+            });
+        }
+    }
+    if GDB_JIT_INTERFACE.load(Ordering::Relaxed) {
+        return gdb::lookup(addr).map(|region| Symbol {
+            region: Arc::new(region),
+        });
+    }
+    None
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod gdb {
+    use super::Region;
+    use core::slice;
+    use object::{Object as _, ObjectSymbol as _, SymbolKind};
+    use std::prelude::v1::*;
+
+    // These are the structures of GDB's JIT interface, see "JIT Compilation
+    // Interface" in the GDB manual.
+    #[repr(C)]
+    struct JitCodeEntry {
+        next_entry: *const JitCodeEntry,
+        prev_entry: *const JitCodeEntry,
+        symfile_addr: *const u8,
+        symfile_size: u64,
+    }
+
+    #[repr(C)]
+    struct JitDescriptor {
+        version: u32,
+        action_flag: u32,
+        relevant_entry: *const JitCodeEntry,
+        first_entry: *const JitCodeEntry,
+    }
+
+    pub unsafe fn lookup(addr: usize) -> Option<Region> {
+        let descriptor = libc::dlsym(
+            libc::RTLD_DEFAULT,
+            "__jit_debug_descriptor\0".as_ptr() as *const libc::c_char,
+        ) as *const JitDescriptor;
+        if descriptor.is_null() || (*descriptor).version != 1 {
+            return None;
+        }
+        let mut entry = (*descriptor).first_entry;
+        while !entry.is_null() {
+            let data = slice::from_raw_parts((*entry).symfile_addr, (*entry).symfile_size as usize);
+            if let Some(region) = lookup_object(data, addr) {
+                return Some(region);
+            }
+            entry = (*entry).next_entry;
+        }
+        None
+    }
+
+    // The runtime is required to have relocated the objects it registers to
+    // where their code is loaded, so symbol addresses are used as they are.
+    fn lookup_object(data: &[u8], addr: usize) -> Option<Region> {
+        let file = object::File::parse(data).ok()?;
+        let addr = addr as u64;
+        let symbol = file.symbols().find(|s| {
+            s.kind() == SymbolKind::Text && s.address() <= addr && addr - s.address() < s.size()
+        })?;
+        let start = symbol.address() as usize;
+        Some(Region {
+            range: start..start + symbol.size() as usize,
+            name: symbol.name_bytes().ok()?.to_vec(),
+            file: None,
+            line: None,
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+mod gdb {
+    use super::Region;
+
+    pub unsafe fn lookup(_addr: usize) -> Option<Region> {
+        None
+    }
+}
@@ -7,7 +7,7 @@ use super::{ResolveWhat, SymbolName};
 
 pub unsafe fn resolve(what: ResolveWhat<'_>, cb: &mut dyn FnMut(&super::Symbol)) {
     let sym = match what {
-        ResolveWhat::Address(addr) => Symbol {
+        ResolveWhat::Address(addr) | ResolveWhat::Precise(addr) => Symbol {
             inner: resolve_addr(addr),
             _unused: PhantomData,
         },
@@ -16,7 +16,9 @@ pub unsafe fn resolve(what: ResolveWhat<'_>, cb: &mut dyn FnMut(&super::Symbol))
             _unused: PhantomData,
         },
     };
-    cb(&super::Symbol { inner: sym })
+    cb(&super::Symbol {
+        inner: super::SymbolImp::Native(sym),
+    })
 }
 
 pub struct Symbol<'a> {
@@ -33,6 +35,26 @@ impl<'a> Symbol<'a> {
         Some(self.inner.addr)
     }
 
+    pub fn offset_in_module(&self) -> Option<usize> {
+        None
+    }
+
+    pub fn module_build_id(&self) -> Option<&[u8]> {
+        None
+    }
+
+    pub fn offset_in_symbol(&self) -> Option<usize> {
+        None
+    }
+
+    pub fn function_start(&self) -> Option<*mut c_void> {
+        None
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        None
+    }
+
     pub fn filename_raw(&self) -> Option<BytesOrWideString<'_>> {
         Some(BytesOrWideString::Bytes(&self.inner.inner.filename))
     }
@@ -45,7 +67,7 @@ impl<'a> Symbol<'a> {
         Some(self.inner.inner.colno)
     }
 
-    #[cfg(feature = "std")]
+    #[cfg(feature = "std-base")]
     pub fn filename(&self) -> Option<&std::path::Path> {
         Some(std::path::Path::new(
             core::str::from_utf8(&self.inner.inner.filename).unwrap(),
@@ -54,3 +76,43 @@ impl<'a> Symbol<'a> {
 }
 
 pub unsafe fn clear_symbol_cache() {}
+
+pub unsafe fn preload() {}
+
+pub unsafe fn unload() {}
+
+pub unsafe fn batch(f: &mut dyn FnMut()) {
+    f()
+}
+
+/// Offline symbolication isn't supported by this backend, so a resolver can
+/// never be created.
+#[cfg(feature = "std-base")]
+pub enum OfflineResolver {}
+
+#[cfg(feature = "std-base")]
+impl OfflineResolver {
+    pub fn new(_path: &::std::path::Path) -> Option<OfflineResolver> {
+        None
+    }
+
+    pub fn resolve(&mut self, _addr: usize, _cb: &mut dyn FnMut(&super::Symbol)) {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "std-base")]
+pub unsafe fn module_for_address(_addr: *mut c_void) -> Option<super::Module> {
+    None
+}
+
+#[cfg(feature = "std-base")]
+pub unsafe fn loaded_modules() -> std::vec::Vec<super::Module> {
+    std::vec::Vec::new()
+}
+
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub unsafe fn diagnose(_addr: *mut c_void) -> super::ResolveError {
+    // Miri resolves every address it handed out, so there's nothing to tell.
+    super::ResolveError::NoSymbol
+}
@@ -1,15 +1,21 @@
 use core::{fmt, str};
 
 cfg_if::cfg_if! {
-    if #[cfg(feature = "std")] {
-        use std::path::Path;
+    if #[cfg(feature = "std-base")] {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use std::path::{Path, PathBuf};
         use std::prelude::v1::*;
     }
 }
 
+#[cfg(any(feature = "trace", backtrace_in_libstd))]
 use super::backtrace::Frame;
 use super::types::BytesOrWideString;
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::ffi::c_void;
+use core::fmt::Write;
 use rustc_demangle::{try_demangle, Demangle};
 
 /// Resolve an address to a symbol, passing the symbol to the specified
@@ -23,15 +29,18 @@ use rustc_demangle::{try_demangle, Demangle};
 /// also may be called more than once in the case of inlined functions.
 ///
 /// Symbols yielded represent the execution at the specified `addr`, returning
-/// file/line pairs for that address (if available).
+/// file/line pairs for that address (if available). The address is taken to
+/// be a return address, like the instruction pointers of most frames, so it's
+/// the call instruction before it which is looked up. Use `resolve_precise`
+/// for addresses of the instruction of interest itself.
 ///
 /// Note that if you have a `Frame` then it's recommended to use the
 /// `resolve_frame` function instead of this one.
 ///
 /// # Required features
 ///
-/// This function requires the `std` feature of the `backtrace` crate to be
-/// enabled, and the `std` feature is enabled by default.
+/// This function requires the `std` and `symbolize` features of the
+/// `backtrace` crate to be enabled, and both are enabled by default.
 ///
 /// # Panics
 ///
@@ -46,6 +55,7 @@ use rustc_demangle::{try_demangle, Demangle};
 /// extern crate backtrace;
 ///
 /// fn main() {
+/// # #[cfg(feature = "trace")] {
 ///     backtrace::trace(|frame| {
 ///         let ip = frame.ip();
 ///
@@ -55,9 +65,10 @@ use rustc_demangle::{try_demangle, Demangle};
 ///
 ///         false // only look at the top frame
 ///     });
+/// # }
 /// }
 /// ```
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 pub fn resolve<F: FnMut(&Symbol)>(addr: *mut c_void, cb: F) {
     let _guard = crate::lock::lock();
     unsafe { resolve_unsynchronized(addr, cb) }
@@ -72,10 +83,14 @@ pub fn resolve<F: FnMut(&Symbol)>(addr: *mut c_void, cb: F) {
 /// or information about inline frames for example. It's recommended to use this
 /// if you can.
 ///
+/// The address looked up is that of `Frame::ip_for_symbolication`, so unlike
+/// with `resolve` the instruction pointers of frames interrupted by a signal
+/// are looked up as they are.
+///
 /// # Required features
 ///
-/// This function requires the `std` feature of the `backtrace` crate to be
-/// enabled, and the `std` feature is enabled by default.
+/// This function requires the `std`, `trace` and `symbolize` features of the
+/// `backtrace` crate to be enabled, and all of them are enabled by default.
 ///
 /// # Panics
 ///
@@ -99,53 +114,109 @@ pub fn resolve<F: FnMut(&Symbol)>(addr: *mut c_void, cb: F) {
 ///     });
 /// }
 /// ```
-#[cfg(feature = "std")]
+#[cfg(all(feature = "std-base", feature = "trace"))]
 pub fn resolve_frame<F: FnMut(&Symbol)>(frame: &Frame, cb: F) {
     let _guard = crate::lock::lock();
     unsafe { resolve_frame_unsynchronized(frame, cb) }
 }
 
+/// Same as `resolve`, but looks up `addr` itself rather than the instruction
+/// before it.
+///
+/// This is meant for addresses which aren't return addresses, such as the
+/// instruction pointers sampled by a profiler from interrupted threads or
+/// performance counters, which point at the instruction that was executing.
+/// Resolving those with `resolve` can attribute them to the wrong line, or
+/// even to the wrong function if the instruction is the first of one.
+///
+/// # Required features
+///
+/// This function requires the `std` and `symbolize` features of the
+/// `backtrace` crate to be enabled, and both are enabled by default.
+///
+/// # Panics
+///
+/// See information on `resolve` for caveats on `cb` panicking.
+#[cfg(feature = "std-base")]
+pub fn resolve_precise<F: FnMut(&Symbol)>(addr: *mut c_void, cb: F) {
+    let _guard = crate::lock::lock();
+    unsafe { resolve_precise_unsynchronized(addr, cb) }
+}
+
+/// Resolve a list of addresses to symbols, passing each symbol to the
+/// specified closure along with the index of the address it belongs to.
+///
+/// This function is the same as calling `resolve` for each address in `addrs`,
+/// but it's more efficient for large numbers of addresses. Synchronization is
+/// only performed once and addresses are resolved in sorted order rather than
+/// in the order given, so the addresses belonging to one module are processed
+/// together and its debug information only needs to be loaded once.
+///
+/// The closure is invoked with the index into `addrs` of the address being
+/// resolved. As with `resolve` it may not be called for an address that could
+/// not be resolved, and it may be called multiple times for an address in the
+/// case of inlined functions.
+///
+/// # Required features
+///
+/// This function requires the `std` and `symbolize` features of the
+/// `backtrace` crate to be enabled, and both are enabled by default.
+///
+/// # Panics
+///
+/// See information on `resolve` for caveats on `cb` panicking.
+///
+/// # Example
+///
+/// ```
+/// extern crate backtrace;
+///
+/// fn main() {
+/// # #[cfg(feature = "trace")] {
+///     let mut ips = Vec::new();
+///     backtrace::trace(|frame| {
+///         ips.push(frame.ip());
+///         true
+///     });
+///
+///     backtrace::resolve_many(&ips, |i, symbol| {
+///         println!("{:?}: {:?}", ips[i], symbol.name());
+///     });
+/// # }
+/// }
+/// ```
+#[cfg(feature = "std-base")]
+pub fn resolve_many<F: FnMut(usize, &Symbol)>(addrs: &[*mut c_void], cb: F) {
+    let _guard = crate::lock::lock();
+    unsafe { resolve_many_unsynchronized(addrs, cb) }
+}
+
 pub enum ResolveWhat<'a> {
     Address(*mut c_void),
+    // An address which is looked up as it is, from `resolve_precise`.
+    Precise(*mut c_void),
+    #[cfg(any(feature = "trace", backtrace_in_libstd))]
     Frame(&'a Frame),
+    // Keeps the lifetime used without frames to borrow.
+    #[cfg(not(any(feature = "trace", backtrace_in_libstd)))]
+    #[allow(dead_code)]
+    Never(core::marker::PhantomData<&'a ()>, core::convert::Infallible),
 }
 
 impl<'a> ResolveWhat<'a> {
     #[allow(dead_code)]
     fn address_or_ip(&self) -> *mut c_void {
         match self {
-            ResolveWhat::Address(a) => adjust_ip(*a),
-            ResolveWhat::Frame(f) => adjust_ip(f.ip()),
+            ResolveWhat::Address(a) => super::adjust_ip(*a),
+            ResolveWhat::Precise(a) => *a,
+            #[cfg(any(feature = "trace", backtrace_in_libstd))]
+            ResolveWhat::Frame(f) => f.ip_for_symbolication(),
+            #[cfg(not(any(feature = "trace", backtrace_in_libstd)))]
+            ResolveWhat::Never(_, never) => match *never {},
         }
     }
 }
 
-// IP values from stack frames are typically (always?) the instruction
-// *after* the call that's the actual stack trace. Symbolizing this on
-// causes the filename/line number to be one ahead and perhaps into
-// the void if it's near the end of the function.
-//
-// This appears to basically always be the case on all platforms, so we always
-// subtract one from a resolved ip to resolve it to the previous call
-// instruction instead of the instruction being returned to.
-//
-// Ideally we would not do this. Ideally we would require callers of the
-// `resolve` APIs here to manually do the -1 and account that they want location
-// information for the *previous* instruction, not the current. Ideally we'd
-// also expose on `Frame` if we are indeed the address of the next instruction
-// or the current.
-//
-// For now though this is a pretty niche concern so we just internally always
-// subtract one. Consumers should keep working and getting pretty good results,
-// so we should be good enough.
-fn adjust_ip(a: *mut c_void) -> *mut c_void {
-    if a.is_null() {
-        a
-    } else {
-        (a as usize - 1) as *mut c_void
-    }
-}
-
 /// Same as `resolve`, only unsafe as it's unsynchronized.
 ///
 /// This function does not have synchronization guarantees but is available when
@@ -159,7 +230,52 @@ pub unsafe fn resolve_unsynchronized<F>(addr: *mut c_void, mut cb: F)
 where
     F: FnMut(&Symbol),
 {
-    imp::resolve(ResolveWhat::Address(addr), &mut cb)
+    resolve_imp(ResolveWhat::Address(addr), &mut cb)
+}
+
+/// Same as `resolve_precise`, only unsafe as it's unsynchronized.
+///
+/// This function does not have synchronization guarantees but is available
+/// when the `std` feature of this crate isn't compiled in. See the
+/// `resolve_precise` function for more documentation.
+///
+/// # Panics
+///
+/// See information on `resolve` for caveats on `cb` panicking.
+pub unsafe fn resolve_precise_unsynchronized<F>(addr: *mut c_void, mut cb: F)
+where
+    F: FnMut(&Symbol),
+{
+    resolve_imp(ResolveWhat::Precise(addr), &mut cb)
+}
+
+/// Same as `resolve_many`, only unsafe as it's unsynchronized.
+///
+/// This function does not have synchronization guarantees but is available
+/// when the `std` feature of this crate isn't compiled in. See the
+/// `resolve_many` function for more documentation and examples.
+///
+/// # Panics
+///
+/// See information on `resolve` for caveats on `cb` panicking.
+pub unsafe fn resolve_many_unsynchronized<F>(addrs: &[*mut c_void], mut cb: F)
+where
+    F: FnMut(usize, &Symbol),
+{
+    let mut order = (0..addrs.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| addrs[i] as usize);
+    batch(&mut || {
+        for &i in &order {
+            resolve_imp(ResolveWhat::Address(addrs[i]), &mut |sym| cb(i, sym))
+        }
+    })
+}
+
+/// Resolves everything `f` does as a single batch, for which the native
+/// backend only checks once whether libraries were loaded or unloaded rather
+/// than for every address.
+pub(crate) unsafe fn batch(f: &mut dyn FnMut()) {
+    imp::batch(f)
 }
 
 /// Same as `resolve_frame`, only unsafe as it's unsynchronized.
@@ -171,11 +287,62 @@ where
 /// # Panics
 ///
 /// See information on `resolve_frame` for caveats on `cb` panicking.
+#[cfg(any(feature = "trace", backtrace_in_libstd))]
 pub unsafe fn resolve_frame_unsynchronized<F>(frame: &Frame, mut cb: F)
 where
     F: FnMut(&Symbol),
 {
-    imp::resolve(ResolveWhat::Frame(frame), &mut cb)
+    resolve_imp(ResolveWhat::Frame(frame), &mut cb)
+}
+
+/// Resolves `what` with the backend registered through `set_symbolizer`, or
+/// if there's none then like `NativeSymbolizer` does.
+unsafe fn resolve_imp(what: ResolveWhat<'_>, cb: &mut dyn FnMut(&Symbol)) {
+    #[cfg(feature = "std-base")]
+    {
+        if let Some(backend) = backend::current() {
+            // Backends look up the instruction before the address they're
+            // given, so addresses which are to be looked up as they are are
+            // moved past it.
+            let addr = match what {
+                ResolveWhat::Address(addr) => addr,
+                ResolveWhat::Precise(addr) => (addr as usize).wrapping_add(1) as *mut c_void,
+                #[cfg(any(feature = "trace", backtrace_in_libstd))]
+                ResolveWhat::Frame(frame) if frame.is_signal_frame() => {
+                    (frame.ip() as usize).wrapping_add(1) as *mut c_void
+                }
+                #[cfg(any(feature = "trace", backtrace_in_libstd))]
+                ResolveWhat::Frame(frame) => frame.ip(),
+                #[cfg(not(any(feature = "trace", backtrace_in_libstd)))]
+                ResolveWhat::Never(_, never) => match never {},
+            };
+            return backend.resolve(addr, cb);
+        }
+    }
+    resolve_native(what, cb)
+}
+
+/// Resolves `what` with the JIT code registry, or if it's not in any
+/// registered code then with the native backend.
+///
+/// Nothing is resolved if this thread is already resolving symbols natively,
+/// from the closure given to `resolve` for example, as the backend's caches
+/// and locks are in use by the resolution in progress.
+unsafe fn resolve_native(what: ResolveWhat<'_>, cb: &mut dyn FnMut(&Symbol)) {
+    #[cfg(feature = "std-base")]
+    let _guard = match crate::reentrancy::enter(crate::reentrancy::Activity::Resolving) {
+        Some(guard) => guard,
+        None => return,
+    };
+    #[cfg(feature = "std-base")]
+    {
+        if let Some(sym) = jit::lookup(what.address_or_ip()) {
+            return cb(&Symbol {
+                inner: SymbolImp::Jit(sym),
+            });
+        }
+    }
+    imp::resolve(what, cb)
 }
 
 /// A trait representing the resolution of a symbol in a file.
@@ -187,11 +354,35 @@ where
 /// A symbol can give contextual information about a function, for example the
 /// name, filename, line number, precise address, etc. Not all information is
 /// always available in a symbol, however, so all methods return an `Option`.
+///
+/// Symbols are only created by this crate, except for those that a
+/// `SymbolizeBackend` creates with `Symbol::new` to hand to its callback.
 pub struct Symbol {
     // TODO: this lifetime bound needs to be persisted eventually to `Symbol`,
     // but that's currently a breaking change. For now this is safe since
     // `Symbol` is only ever handed out by reference and can't be cloned.
-    inner: imp::Symbol<'static>,
+    inner: SymbolImp,
+}
+
+enum SymbolImp {
+    Native(imp::Symbol<'static>),
+    #[cfg(feature = "std-base")]
+    Jit(jit::Symbol),
+    #[cfg(feature = "std-base")]
+    Owned(backend::Owned),
+}
+
+// Forwards to the implementation of whichever kind of symbol this is.
+macro_rules! forward {
+    ($sym:expr, $s:ident => $e:expr) => {
+        match &$sym.inner {
+            SymbolImp::Native($s) => $e,
+            #[cfg(feature = "std-base")]
+            SymbolImp::Jit($s) => $e,
+            #[cfg(feature = "std-base")]
+            SymbolImp::Owned($s) => $e,
+        }
+    };
 }
 
 impl Symbol {
@@ -205,18 +396,97 @@ impl Symbol {
     ///   utf-8).
     /// * The raw bytes for the symbol name can be accessed.
     pub fn name(&self) -> Option<SymbolName<'_>> {
-        self.inner.name()
+        forward!(self, s => s.name())
+    }
+
+    /// Returns the language this function is most likely written in, judging
+    /// by how its name is mangled, or `None` if it has no name.
+    ///
+    /// See `SymbolName::language` for how this is determined.
+    pub fn language(&self) -> Option<Language> {
+        self.name().map(|name| name.language())
     }
 
     /// Returns the starting address of this function.
     pub fn addr(&self) -> Option<*mut c_void> {
-        self.inner.addr().map(|p| p as *mut _)
+        forward!(self, s => s.addr()).map(|p| p as *mut _)
+    }
+
+    /// Returns the address that was looked up for this symbol relative to
+    /// the load bias of the module containing it, see `Frame::module_base`.
+    ///
+    /// This is the address to look up in the module's object file on disk to
+    /// symbolicate it offline, for example with `addr2line`. As explained for
+    /// `resolve`, for instruction pointers of stack frames the address looked
+    /// up is the one before the instruction pointer. `None` is returned if
+    /// the symbol doesn't belong to a module, such as for JIT-compiled code,
+    /// or if the platform doesn't support this.
+    pub fn offset_in_module(&self) -> Option<usize> {
+        forward!(self, s => s.offset_in_module())
+    }
+
+    /// Returns the unique identifier of the build of the module containing
+    /// this symbol, the same as `Module::build_id` of that module.
+    ///
+    /// Together with `offset_in_module` this identifies the exact binary and
+    /// location a symbol came from, which crash reporters can use to group
+    /// reports by the build they came from or to fetch the matching debug
+    /// information. `None` is returned if the module carries no identifier,
+    /// if the symbol doesn't belong to a module, or if the platform doesn't
+    /// support this.
+    pub fn module_build_id(&self) -> Option<&[u8]> {
+        forward!(self, s => s.module_build_id())
+    }
+
+    /// Returns how far past the start of the symbol the address that was
+    /// looked up is, such as the `0x1a` in `main+0x1a`.
+    ///
+    /// This is mostly useful when no debug information is available so
+    /// only the name of the function could be found in the symbol table of
+    /// the module, without a filename or line number. `None` is returned if
+    /// the start of the symbol isn't known.
+    pub fn offset_in_symbol(&self) -> Option<usize> {
+        forward!(self, s => s.offset_in_symbol())
+    }
+
+    /// Returns the address at which the function containing the address that
+    /// was looked up starts.
+    ///
+    /// Together with `size` this gives the bounds of the function, so that
+    /// profilers can attribute arbitrary addresses, such as sampled
+    /// instruction pointers, to the functions they're in without resolving
+    /// each of them. For functions inlined into others this is the function
+    /// they were inlined into, which is what occupies the memory.
+    ///
+    /// Unlike `addr`, this is always an address in memory, or for symbols
+    /// from `offline::Resolver` an offset like the one that was looked up.
+    /// With gimli this is read from the symbol table of the module, or from
+    /// its DWARF debug information if the symbol table doesn't cover the
+    /// address. `None` is returned if the bounds of the function aren't
+    /// known.
+    pub fn function_start(&self) -> Option<*mut c_void> {
+        forward!(self, s => s.function_start())
+    }
+
+    /// Returns the size in bytes of the function starting at
+    /// `function_start`.
+    ///
+    /// Symbols in symbol tables which don't record sizes, such as those of
+    /// Mach-O and COFF objects, are taken to extend up to the next symbol.
+    /// `None` is returned if the size of the function isn't known.
+    pub fn size(&self) -> Option<usize> {
+        forward!(self, s => s.size())
     }
 
     /// Returns the raw filename as a slice. This is mainly useful for `no_std`
     /// environments.
+    ///
+    /// This is the path as recorded in the debug information, which for DWARF
+    /// is the compilation directory, the include directory and the file name
+    /// joined together, and so may contain `.` and `..` components that
+    /// `filename` removes.
     pub fn filename_raw(&self) -> Option<BytesOrWideString<'_>> {
-        self.inner.filename_raw()
+        forward!(self, s => s.filename_raw())
     }
 
     /// Returns the column number for where this symbol is currently executing.
@@ -224,7 +494,7 @@ impl Symbol {
     /// Only gimli currently provides a value here and even then only if `filename`
     /// returns `Some`, and so it is then consequently subject to similar caveats.
     pub fn colno(&self) -> Option<u32> {
-        self.inner.colno()
+        forward!(self, s => s.colno())
     }
 
     /// Returns the line number for where this symbol is currently executing.
@@ -232,7 +502,7 @@ impl Symbol {
     /// This return value is typically `Some` if `filename` returns `Some`, and
     /// is consequently subject to similar caveats.
     pub fn lineno(&self) -> Option<u32> {
-        self.inner.lineno()
+        forward!(self, s => s.lineno())
     }
 
     /// Returns the file name where this function was defined.
@@ -242,14 +512,19 @@ impl Symbol {
     /// debuginfo. If neither of these conditions is met then this will likely
     /// return `None`.
     ///
+    /// With gimli the path is normalized, so `/build/src/../include/util.h`
+    /// is returned as `/build/include/util.h`. The map registered with
+    /// `set_source_map` is applied as well. Use `filename_raw` for the path
+    /// exactly as recorded.
+    ///
     /// # Required features
     ///
     /// This function requires the `std` feature of the `backtrace` crate to be
     /// enabled, and the `std` feature is enabled by default.
-    #[cfg(feature = "std")]
+    #[cfg(feature = "std-base")]
     #[allow(unreachable_code)]
     pub fn filename(&self) -> Option<&Path> {
-        self.inner.filename()
+        forward!(self, s => s.filename())
     }
 }
 
@@ -263,7 +538,7 @@ impl fmt::Debug for Symbol {
             d.field("addr", &addr);
         }
 
-        #[cfg(feature = "std")]
+        #[cfg(feature = "std-base")]
         {
             if let Some(filename) = self.filename() {
                 d.field("filename", &filename);
@@ -291,6 +566,15 @@ cfg_if::cfg_if! {
             fn none() -> OptionCppSymbol<'a> {
                 OptionCppSymbol(None)
             }
+
+            fn demangle(&self) -> Option<String> {
+                // Like for `Debug` below, printing may fail if the parsed
+                // symbol isn't actually valid.
+                let cpp = self.0.as_ref()?;
+                let mut s = String::new();
+                write!(s, "{}", cpp).ok()?;
+                Some(s)
+            }
         }
     } else {
         use core::marker::PhantomData;
@@ -307,12 +591,26 @@ cfg_if::cfg_if! {
             fn none() -> OptionCppSymbol<'a> {
                 OptionCppSymbol(PhantomData)
             }
+
+            fn demangle(&self) -> Option<String> {
+                None
+            }
         }
     }
 }
 
 /// A wrapper around a symbol name to provide ergonomic accessors to the
 /// demangled name, the raw bytes, the raw string, etc.
+///
+/// The `Display` implementation prints the demangled name if the symbol could
+/// be demangled. Like for `rustc_demangle`, the alternate format (`{:#}`)
+/// omits the hash at the end of Rust symbol names.
+///
+/// ```
+/// let name = backtrace::SymbolName::new(b"_ZN3foo3bar17h05af221e174051e9E");
+/// assert_eq!(name.to_string(), "foo::bar::h05af221e174051e9");
+/// assert_eq!(format!("{:#}", name), "foo::bar");
+/// ```
 // Allow dead code for when the `cpp_demangle` feature is not enabled.
 #[allow(dead_code)]
 pub struct SymbolName<'a> {
@@ -354,6 +652,110 @@ impl<'a> SymbolName<'a> {
     pub fn as_bytes(&self) -> &'a [u8] {
         self.bytes
     }
+
+    /// Returns the raw symbol name as a `str` if it's valid utf-8.
+    ///
+    /// This is exactly the name found in the object file, which is what
+    /// external tools such as `nm` or a demangler expect.
+    pub fn as_raw_str(&self) -> Option<&'a str> {
+        str::from_utf8(self.bytes).ok()
+    }
+
+    /// Returns the demangled symbol name, or `None` if the name isn't
+    /// mangled in a way this crate knows about.
+    ///
+    /// Names are demangled as Rust symbols, as C++ symbols with the
+    /// `cpp_demangle` feature, and with the function registered with
+    /// `set_demangler`. Unlike the `Display` implementation, this doesn't
+    /// fall back to the raw name, so it can be used to tell whether a name
+    /// was demangled at all.
+    ///
+    /// ```
+    /// use backtrace::SymbolName;
+    ///
+    /// let name = SymbolName::new(b"_ZN3foo3bar17h05af221e174051e9E");
+    /// assert_eq!(name.demangled().unwrap(), "foo::bar::h05af221e174051e9");
+    /// assert!(SymbolName::new(b"main").demangled().is_none());
+    /// ```
+    pub fn demangled(&self) -> Option<Cow<'a, str>> {
+        if let Some(ref s) = self.demangled {
+            let mut name = String::new();
+            write!(name, "{}", s).ok()?;
+            return Some(Cow::Owned(name));
+        }
+        if let Some(name) = self.cpp_demangled.demangle() {
+            return Some(Cow::Owned(name));
+        }
+        #[cfg(feature = "std-base")]
+        {
+            if let Some(name) = custom_demangle(self.bytes) {
+                return Some(Cow::Owned(name));
+            }
+        }
+        None
+    }
+
+    /// Returns the language this symbol is most likely from, judging by how
+    /// its name is mangled.
+    ///
+    /// Rust names are recognized in both the legacy and the v0 mangling
+    /// schemes, and C++ names in both the Itanium and the MSVC schemes. Legacy
+    /// Rust names are Itanium-mangled too, so they're told apart by the hash
+    /// that rustc ends them with. Names which aren't mangled at all are taken
+    /// to be C, even though they may come from anything else which doesn't
+    /// mangle names, such as assembly or `#[no_mangle]` Rust functions.
+    ///
+    /// ```
+    /// use backtrace::{Language, SymbolName};
+    ///
+    /// let language = |name: &[u8]| SymbolName::new(name).language();
+    /// assert_eq!(language(b"_ZN3foo3bar17h05af221e174051e9E"), Language::Rust);
+    /// assert_eq!(language(b"_RNvCs1234_7mycrate3foo"), Language::Rust);
+    /// assert_eq!(language(b"_ZN3foo3barEv"), Language::Cpp);
+    /// assert_eq!(language(b"?bar@foo@@YAXXZ"), Language::Cpp);
+    /// assert_eq!(language(b"memcpy"), Language::C);
+    /// ```
+    pub fn language(&self) -> Language {
+        // Mach-O prepends an underscore to every name.
+        let bytes = match self.bytes {
+            [b'_', rest @ ..] if rest.starts_with(b"_Z") || rest.starts_with(b"_R") => rest,
+            bytes => bytes,
+        };
+        if self.demangled.is_some() && (bytes.starts_with(b"_R") || has_rust_hash(bytes)) {
+            Language::Rust
+        } else if bytes.starts_with(b"_Z") || bytes.starts_with(b"?") {
+            Language::Cpp
+        } else {
+            Language::C
+        }
+    }
+}
+
+/// Whether a legacy-mangled name ends with the hash rustc adds to all of them,
+/// such as the `17h05af221e174051e9` in `_ZN3foo17h05af221e174051e9E`.
+fn has_rust_hash(bytes: &[u8]) -> bool {
+    // LLVM may add suffixes like `.llvm.1234` to names.
+    let end = bytes.iter().position(|&b| b == b'.').unwrap_or(bytes.len());
+    match bytes[..end].strip_suffix(b"E") {
+        Some(name) if name.len() >= 19 => {
+            let hash = &name[name.len() - 19..];
+            hash.starts_with(b"17h") && hash[3..].iter().all(|b| b.is_ascii_hexdigit())
+        }
+        _ => false,
+    }
+}
+
+/// The source language of a symbol, as returned by `SymbolName::language`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Language {
+    /// A Rust function, with a legacy or v0 mangled name.
+    Rust,
+    /// A C++ function, with an Itanium or MSVC mangled name.
+    Cpp,
+    /// A function whose name isn't mangled, which is typically C.
+    C,
+    #[doc(hidden)]
+    __Nonexhaustive,
 }
 
 fn format_symbol_name(
@@ -361,6 +763,13 @@ fn format_symbol_name(
     mut bytes: &[u8],
     f: &mut fmt::Formatter<'_>,
 ) -> fmt::Result {
+    #[cfg(feature = "std-base")]
+    {
+        if let Some(name) = custom_demangle(bytes) {
+            return fmt(&name, f);
+        }
+    }
+
     while bytes.len() > 0 {
         match str::from_utf8(bytes) {
             Ok(name) => {
@@ -380,6 +789,60 @@ fn format_symbol_name(
     Ok(())
 }
 
+/// The function registered through `set_demangler`, or 0 if there is none.
+#[cfg(feature = "std-base")]
+static DEMANGLER: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers a function used to demangle symbol names which this crate
+/// doesn't know how to demangle itself.
+///
+/// The `Display` and `Debug` implementations of `SymbolName` first try the
+/// Rust demangler and, with the `cpp_demangle` feature, the C++ demangler. If
+/// neither recognizes a name then `demangler` is called with its raw bytes,
+/// and the returned name is printed instead, if any. This allows applications
+/// to prettify names from their own mangling schemes, for example symbols of
+/// JIT-compiled code.
+///
+/// There is only one demangler per process, and each call replaces the one
+/// previously registered.
+///
+/// # Examples
+///
+/// ```
+/// fn demangle(name: &[u8]) -> Option<String> {
+///     let name = std::str::from_utf8(name).ok()?;
+///     if !name.starts_with("jit$") {
+///         return None;
+///     }
+///     Some(name[4..].replace('$', "::"))
+/// }
+///
+/// backtrace::set_demangler(demangle);
+/// let name = backtrace::SymbolName::new(b"jit$my_module$my_function");
+/// assert_eq!(name.to_string(), "my_module::my_function");
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[cfg(feature = "std-base")]
+pub fn set_demangler(demangler: fn(&[u8]) -> Option<String>) {
+    DEMANGLER.store(demangler as usize, Ordering::Release);
+}
+
+#[cfg(feature = "std-base")]
+fn custom_demangle(bytes: &[u8]) -> Option<String> {
+    let demangler = DEMANGLER.load(Ordering::Acquire);
+    if demangler == 0 {
+        return None;
+    }
+    // SAFETY: `DEMANGLER` is only ever set from a function pointer of this
+    // type, in `set_demangler`.
+    let demangler: fn(&[u8]) -> Option<String> = unsafe { core::mem::transmute(demangler) };
+    demangler(bytes)
+}
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "cpp_demangle")] {
         impl<'a> fmt::Display for SymbolName<'a> {
@@ -407,7 +870,7 @@ cfg_if::cfg_if! {
 }
 
 cfg_if::cfg_if! {
-    if #[cfg(all(feature = "std", feature = "cpp_demangle"))] {
+    if #[cfg(all(feature = "std-base", feature = "cpp_demangle"))] {
         impl<'a> fmt::Debug for SymbolName<'a> {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 use std::fmt::Write;
@@ -442,12 +905,153 @@ cfg_if::cfg_if! {
     }
 }
 
+/// Information about a module, such as the main executable or a shared
+/// library, which is loaded into the current process.
+///
+/// This is returned from `Frame::module` to describe the object file that
+/// contains a frame's instruction pointer, and `modules` lists every module
+/// of the process. Together with `base_address` and `build_id` this is
+/// typically enough to symbolicate the frame offline, for example on a
+/// machine which has access to the debug information that was stripped from
+/// the deployed binary.
+///
+/// Modules compare equal if they're the same file loaded at the same
+/// address, which can be used to tell whether a module is still loaded.
+///
+/// # Required features
+///
+/// This type requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[cfg(feature = "std-base")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Module {
+    path: PathBuf,
+    base_address: usize,
+    bias: usize,
+    size: usize,
+    build_id: Option<Vec<u8>>,
+}
+
+#[cfg(feature = "std-base")]
+impl Module {
+    /// Returns the path this module was loaded from.
+    ///
+    /// The path is whatever the platform's loader reports, so it may be
+    /// relative or, for some anonymous mappings, empty.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the address at which this module is loaded into memory.
+    ///
+    /// Subtracting this from an instruction pointer yields an offset relative
+    /// to the start of the module's image.
+    pub fn base_address(&self) -> *mut c_void {
+        self.base_address as *mut c_void
+    }
+
+    /// Returns the size, in bytes, of this module's image in memory.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the unique identifier of the build this module came from.
+    ///
+    /// This is the contents of the GNU build-id note on ELF platforms, the
+    /// `LC_UUID` load command on macOS and the CodeView GUID followed by the
+    /// little-endian age on Windows. `None` is returned if the module carries
+    /// no such identifier.
+    pub fn build_id(&self) -> Option<&[u8]> {
+        self.build_id.as_deref()
+    }
+
+    /// Returns the load bias of this module, as returned by
+    /// `Frame::module_base`.
+    pub(crate) fn bias(&self) -> usize {
+        self.bias
+    }
+}
+
+#[cfg(feature = "std-base")]
+mod backend;
+#[cfg(feature = "std-base")]
+pub use self::backend::{set_symbolizer, NativeSymbolizer, SymbolizeBackend};
+// These return the owned symbols of `Backtrace`.
+#[cfg(all(feature = "std-base", feature = "trace"))]
+mod fallible;
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub use self::fallible::{try_resolve, ResolveError};
+#[cfg(feature = "std-base")]
+pub(crate) mod images;
+#[cfg(feature = "std-base")]
+pub use self::images::{register_image, unregister_image};
+#[cfg(feature = "std-base")]
+pub mod jit;
+#[cfg(feature = "std-base")]
+pub(crate) mod search_paths;
+#[cfg(feature = "std-base")]
+pub use self::search_paths::set_search_paths;
+#[cfg(feature = "std-base")]
+pub mod offline;
+
+#[cfg(all(feature = "std-base", feature = "trace"))]
+mod cache;
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub use self::cache::SymbolCache;
+
+/// Looks up the module containing the instruction pointer of `frame`.
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub(crate) fn module_for_frame(frame: &Frame) -> Option<Module> {
+    module_for_address(ResolveWhat::Frame(frame).address_or_ip())
+}
+
+/// Looks up the module containing `addr`.
+#[cfg(feature = "std-base")]
+pub(crate) fn module_for_address(addr: *mut c_void) -> Option<Module> {
+    let _guard = crate::lock::lock();
+    unsafe { imp::module_for_address(addr) }
+}
+
+/// Returns every module loaded into the current process.
+///
+/// This includes the main executable and each shared library, in the order
+/// the platform's loader reports them. Crash reporters can send this list
+/// along with the raw addresses of a backtrace, so that it can be
+/// symbolicated elsewhere with the debug information of each module.
+///
+/// An empty list is returned on platforms where modules can't be
+/// enumerated.
+///
+/// # Examples
+///
+/// ```
+/// for module in backtrace::modules() {
+///     println!("{:?} at {:?}", module.path(), module.base_address());
+/// }
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[cfg(feature = "std-base")]
+pub fn modules() -> Vec<Module> {
+    let _guard = crate::lock::lock();
+    unsafe { imp::loaded_modules() }
+}
+
 /// Attempt to reclaim that cached memory used to symbolicate addresses.
 ///
 /// This method will attempt to release any global data structures that have
 /// otherwise been cached globally or in the thread which typically represent
 /// parsed DWARF information or similar.
 ///
+/// With the `gimli-symbolize` backend this also rebuilds the list of loaded
+/// libraries. Libraries loaded or unloaded through `dlopen` and `dlclose` are
+/// normally detected automatically where the platform's `dl_iterate_phdr`
+/// reports it, and elsewhere the list is rebuilt when an address isn't found
+/// in any known library, so calling this isn't required for correctness.
+///
 /// # Caveats
 ///
 /// While this function is always available it doesn't actually do anything on
@@ -455,7 +1059,7 @@ cfg_if::cfg_if! {
 /// facilities to deallocate state and manage the allocated memory. For now the
 /// `gimli-symbolize` feature of this crate is the only feature where this
 /// function has any effect.
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 pub fn clear_symbol_cache() {
     let _guard = crate::lock::lock();
     unsafe {
@@ -463,8 +1067,67 @@ pub fn clear_symbol_cache() {
     }
 }
 
+/// Eagerly initializes the symbolication backend, so that the first backtrace
+/// to be symbolicated doesn't pay for it.
+///
+/// Nothing is initialized until it's needed, so backtraces can be captured
+/// and symbolicated without calling this. Instead it's for programs which
+/// would rather pay the cost up front, for example at startup, than when
+/// reporting an error:
+///
+/// * With dbghelp on Windows this loads `dbghelp.dll` and initializes its
+///   symbol handler for the process.
+/// * With the `gimli-symbolize` backend this lists the loaded libraries and
+///   parses the debug information of the module containing this crate,
+///   which is usually the executable.
+///
+/// `unload` releases everything set up here.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[cfg(feature = "std-base")]
+pub fn preload() {
+    let _guard = crate::lock::lock();
+    unsafe {
+        imp::preload();
+    }
+}
+
+/// Releases everything the symbolication backend has set up, for processes
+/// which only need backtraces occasionally and want to keep their memory use
+/// and loaded libraries down in between.
+///
+/// With dbghelp on Windows this calls `SymCleanup` and unloads
+/// `dbghelp.dll`, which is otherwise kept loaded once a backtrace has been
+/// captured. With the `gimli-symbolize` backend all cached debug information
+/// and the list of loaded libraries are dropped. Either way it's all set up
+/// again the next time it's needed, so this crate may be used as normal
+/// afterwards.
+///
+/// # Caveats
+///
+/// On Windows the symbol handler is shared by the whole process, including
+/// the copy of this crate in the standard library, which won't be able to
+/// symbolicate backtraces (such as those of panics) after this has been
+/// called if it had already done so itself, until a backtrace is symbolicated
+/// through this crate again. See `backtrace::dbghelp::cleanup` for more.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[cfg(feature = "std-base")]
+pub fn unload() {
+    let _guard = crate::lock::lock();
+    unsafe {
+        imp::unload();
+    }
+}
+
 cfg_if::cfg_if! {
-    if #[cfg(miri)] {
+    if #[cfg(all(miri, any(feature = "trace", backtrace_in_libstd)))] {
         mod miri;
         use miri as imp;
     } else if #[cfg(all(windows, target_env = "msvc", not(target_vendor = "uwp")))] {
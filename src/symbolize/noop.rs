@@ -20,11 +20,31 @@ impl Symbol<'_> {
         None
     }
 
+    pub fn offset_in_module(&self) -> Option<usize> {
+        None
+    }
+
+    pub fn module_build_id(&self) -> Option<&[u8]> {
+        None
+    }
+
+    pub fn offset_in_symbol(&self) -> Option<usize> {
+        None
+    }
+
+    pub fn function_start(&self) -> Option<*mut c_void> {
+        None
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        None
+    }
+
     pub fn filename_raw(&self) -> Option<BytesOrWideString<'_>> {
         None
     }
 
-    #[cfg(feature = "std")]
+    #[cfg(feature = "std-base")]
     pub fn filename(&self) -> Option<&::std::path::Path> {
         None
     }
@@ -39,3 +59,42 @@ impl Symbol<'_> {
 }
 
 pub unsafe fn clear_symbol_cache() {}
+
+pub unsafe fn preload() {}
+
+pub unsafe fn unload() {}
+
+pub unsafe fn batch(f: &mut dyn FnMut()) {
+    f()
+}
+
+/// Offline symbolication isn't supported by this backend, so a resolver can
+/// never be created.
+#[cfg(feature = "std-base")]
+pub enum OfflineResolver {}
+
+#[cfg(feature = "std-base")]
+impl OfflineResolver {
+    pub fn new(_path: &::std::path::Path) -> Option<OfflineResolver> {
+        None
+    }
+
+    pub fn resolve(&mut self, _addr: usize, _cb: &mut dyn FnMut(&super::Symbol)) {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "std-base")]
+pub unsafe fn module_for_address(_addr: *mut c_void) -> Option<super::Module> {
+    None
+}
+
+#[cfg(feature = "std-base")]
+pub unsafe fn loaded_modules() -> std::vec::Vec<super::Module> {
+    std::vec::Vec::new()
+}
+
+#[cfg(all(feature = "std-base", feature = "trace"))]
+pub unsafe fn diagnose(_addr: *mut c_void) -> super::ResolveError {
+    super::ResolveError::BackendUnavailable
+}
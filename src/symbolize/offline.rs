@@ -0,0 +1,73 @@
+//! Symbolication of addresses in object files that aren't necessarily loaded
+//! into the current process.
+//!
+//! This is useful when a minimal crash handler records raw instruction
+//! pointers and leaves the (comparatively expensive) work of symbolicating
+//! them to a separate process, or even to a separate machine. The crash
+//! handler records, for each frame, the module it belongs to (see
+//! `Frame::module`) and the instruction pointer relative to that module's
+//! base address. A `Resolver` for the module's object file can then turn
+//! those offsets back into symbols.
+//!
+//! # Required features
+//!
+//! This module requires the `std` feature of the `backtrace` crate to be
+//! enabled, and the `std` feature is enabled by default.
+
+use super::Symbol;
+use std::path::Path;
+
+/// A symbolicator for a single object file on disk.
+///
+/// # Caveats
+///
+/// Offline symbolication is only supported by the gimli backend (e.g. unix
+/// platforms and MinGW), elsewhere `Resolver::new` always returns `None`.
+pub struct Resolver {
+    inner: super::imp::OfflineResolver,
+}
+
+impl Resolver {
+    /// Loads the object file at `path`, along with any separate debug
+    /// information that can be located for it.
+    ///
+    /// Returns `None` if the file couldn't be read or parsed, or if offline
+    /// symbolication isn't supported on this platform.
+    pub fn new<P: AsRef<Path>>(path: P) -> Option<Resolver> {
+        super::imp::OfflineResolver::new(path.as_ref()).map(|inner| Resolver { inner })
+    }
+
+    /// Resolves an address relative to the base address of the module this
+    /// object file was loaded as, passing the symbols found to the specified
+    /// closure.
+    ///
+    /// Like `backtrace::resolve` the address is assumed to be an instruction
+    /// pointer taken from a stack frame, so the instruction before it is what
+    /// is actually looked up. Multiple symbols may be yielded for inlined
+    /// functions.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "trace")] {
+    /// let mut frame = None;
+    /// backtrace::trace(|f| {
+    ///     frame = Some(f.clone());
+    ///     false
+    /// });
+    /// let frame = frame.unwrap();
+    ///
+    /// if let Some(module) = frame.module() {
+    ///     let offset = frame.ip() as usize - module.base_address() as usize;
+    ///     if let Some(mut resolver) = backtrace::offline::Resolver::new(module.path()) {
+    ///         resolver.resolve_addr(offset, |symbol| {
+    ///             println!("{:?}", symbol.name());
+    ///         });
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn resolve_addr<F: FnMut(&Symbol)>(&mut self, addr: usize, mut cb: F) {
+        self.inner.resolve(addr, &mut cb)
+    }
+}
@@ -0,0 +1,61 @@
+//! Directories searched for separate debug information.
+
+use std::path::PathBuf;
+use std::prelude::v1::*;
+
+// The directories set with `set_search_paths`, guarded by the global lock in
+// `crate::lock`.
+static mut SEARCH_PATHS: Vec<PathBuf> = Vec::new();
+
+/// Sets the directories which are searched for the separate debug information
+/// of modules, before the platform's default locations.
+///
+/// Debuggers let this be configured, as debug information is often kept apart
+/// from the binaries it describes, for example in a symbol store, in a
+/// directory shipped along with an application, or in a sysroot. Each
+/// backend looks for what it uses in `paths`, in order:
+///
+/// * With the `gimli-symbolize` backend on ELF platforms, each directory is
+///   treated like `/usr/lib/debug`, as gdb treats its `debug-file-directory`:
+///   files named by build ID are looked up in its `.build-id` subdirectory,
+///   and files named by `.gnu_debuglink` sections in it directly and under
+///   the path of the module's directory. Split DWARF `.dwo` files which
+///   aren't at the path they were written to are looked up in it by name.
+/// * On macOS and iOS, the `*.dSYM` bundles in each directory are searched
+///   for one whose UUID matches the module's.
+/// * With dbghelp on Windows, the directories come first in the symbol search
+///   path used to find PDBs, followed by those of `_NT_SYMBOL_PATH` and
+///   `_NT_ALTERNATE_SYMBOL_PATH`, which dbghelp would otherwise use.
+///
+/// Each call replaces the directories of the previous one, and an empty slice
+/// restores the defaults. Symbols which were already loaded for a module
+/// aren't looked up again, except with the `gimli-symbolize` backend where
+/// this clears the symbol cache like `clear_symbol_cache` does.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+///
+/// backtrace::set_search_paths(&[PathBuf::from("/opt/myapp/debug")]);
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn set_search_paths(paths: &[PathBuf]) {
+    let _guard = crate::lock::lock();
+    unsafe {
+        *core::ptr::addr_of_mut!(SEARCH_PATHS) = paths.to_vec();
+        super::imp::clear_symbol_cache();
+    }
+    #[cfg(all(windows, target_env = "msvc", not(target_vendor = "uwp")))]
+    crate::dbghelp::update_search_path();
+}
+
+/// Returns the directories set with `set_search_paths`.
+pub(crate) fn search_paths() -> Vec<PathBuf> {
+    let _guard = crate::lock::lock();
+    unsafe { (*core::ptr::addr_of!(SEARCH_PATHS)).clone() }
+}
@@ -0,0 +1,273 @@
+//! Aggregating many backtraces into a call tree.
+//!
+//! Heap and leak profilers capture a backtrace for every allocation and are
+//! interested in where the bulk of them come from rather than in individual
+//! backtraces. A `CallTree` merges backtraces by their frames, starting from
+//! the outermost one, so that each node stands for a call path and carries
+//! the combined weight, such as the number of bytes allocated, of every
+//! backtrace passing through it. This is the same aggregation that
+//! flamegraphs are drawn from.
+//!
+//! Backtraces are best captured with `Backtrace::new_unresolved`, which is
+//! much cheaper, and the tree then resolved once with `CallTree::resolve`,
+//! which only looks up each distinct frame once.
+//!
+//! # Examples
+//!
+//! ```
+//! use backtrace::tree::CallTree;
+//! use backtrace::Backtrace;
+//!
+//! let mut tree = CallTree::new();
+//! for size in [16, 32, 64].iter() {
+//!     tree.insert(&Backtrace::new_unresolved(), *size);
+//! }
+//! tree.resolve();
+//! assert_eq!(tree.weight(), 112);
+//!
+//! for node in tree.iter() {
+//!     let indent = node.depth() * 2;
+//!     match node.symbols().first().and_then(|s| s.name()) {
+//!         Some(name) => println!("{:1$}{2} {3}", "", indent, name, node.weight()),
+//!         None => println!("{:1$}{2:?} {3}", "", indent, node.address(), node.weight()),
+//!     }
+//! }
+//! ```
+//!
+//! # Required features
+//!
+//! This module requires the `std` feature of the `backtrace` crate to be
+//! enabled, and the `std` feature is enabled by default.
+
+use crate::capture::folded;
+use crate::{Backtrace, BacktraceFrame, BacktraceSymbol};
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::prelude::v1::*;
+use std::slice;
+
+/// Backtraces merged into a tree of call paths, each with a weight.
+///
+/// The roots of the tree are the outermost frames of the backtraces inserted,
+/// typically the entry points of threads, and the children of each node are
+/// the functions it called. Frames are told apart by their instruction
+/// pointer, so calls from different places in a function are separate nodes.
+#[derive(Clone, Debug, Default)]
+pub struct CallTree {
+    children: Children,
+    weight: u64,
+}
+
+/// A call path in a `CallTree`, along with the weight of the backtraces
+/// which took it.
+#[derive(Clone, Debug)]
+pub struct Node {
+    frame: BacktraceFrame,
+    depth: usize,
+    weight: u64,
+    self_weight: u64,
+    children: Children,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Children {
+    nodes: Vec<Node>,
+    /// The index into `nodes` of the node for each instruction pointer.
+    index: HashMap<usize, usize>,
+}
+
+impl Children {
+    fn get_or_insert(&mut self, frame: &BacktraceFrame, depth: usize) -> &mut Node {
+        let nodes = &mut self.nodes;
+        let i = *self.index.entry(frame.ip() as usize).or_insert_with(|| {
+            nodes.push(Node {
+                frame: frame.clone(),
+                depth,
+                weight: 0,
+                self_weight: 0,
+                children: Children::default(),
+            });
+            nodes.len() - 1
+        });
+        let node = &mut nodes[i];
+        // Keep the symbols of whichever backtrace had them resolved.
+        if node.frame.symbols().is_empty() && !frame.symbols().is_empty() {
+            node.frame = frame.clone();
+        }
+        node
+    }
+
+    fn merge(&mut self, other: &Children) {
+        for other in other.nodes.iter() {
+            let node = self.get_or_insert(&other.frame, other.depth);
+            node.weight += other.weight;
+            node.self_weight += other.self_weight;
+            node.children.merge(&other.children);
+        }
+    }
+}
+
+impl CallTree {
+    /// Creates an empty call tree.
+    pub fn new() -> CallTree {
+        CallTree::default()
+    }
+
+    /// Adds the call path of `backtrace` to this tree with `weight`.
+    ///
+    /// The weight is added to every node along the path, and to the self
+    /// weight of the node for the innermost frame.
+    pub fn insert(&mut self, backtrace: &Backtrace, weight: u64) {
+        self.weight += weight;
+        let frames = backtrace.frames();
+        let mut children = &mut self.children;
+        for (depth, frame) in frames.iter().rev().enumerate() {
+            let node = children.get_or_insert(frame, depth);
+            node.weight += weight;
+            if depth + 1 == frames.len() {
+                node.self_weight += weight;
+            }
+            children = &mut node.children;
+        }
+    }
+
+    /// Adds every call path of `other` to this tree with its weight, as if
+    /// the backtraces inserted into `other` were inserted here.
+    ///
+    /// This allows trees to be built separately, for example per thread,
+    /// and combined afterwards.
+    pub fn merge(&mut self, other: &CallTree) {
+        self.weight += other.weight;
+        self.children.merge(&other.children);
+    }
+
+    /// Returns the combined weight of all backtraces in this tree.
+    pub fn weight(&self) -> u64 {
+        self.weight
+    }
+
+    /// Returns the nodes for the outermost frames of the backtraces in this
+    /// tree.
+    pub fn roots(&self) -> &[Node] {
+        &self.children.nodes
+    }
+
+    /// Returns an iterator over every node of this tree.
+    ///
+    /// Nodes are visited depth-first with each node before its children, so
+    /// `Node::depth` is enough to print the tree indented.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            stack: vec![self.children.nodes.iter()],
+        }
+    }
+
+    /// Returns this tree in the folded stack format read by flamegraph tools
+    /// such as `flamegraph.pl` and inferno.
+    ///
+    /// There's a line for each node with a self weight, with the call path to
+    /// it and that weight, formatted like `Backtrace::to_folded`. Symbols are
+    /// resolved as needed, without changing this tree.
+    pub fn to_folded(&self) -> String {
+        fn walk(out: &mut String, path: &str, nodes: &[Node]) {
+            for node in nodes {
+                let mut path = path.to_string();
+                folded::push_frame(&mut path, &node.frame);
+                if node.self_weight > 0 {
+                    folded::push_line(out, &path, node.self_weight);
+                }
+                walk(out, &path, node.children());
+            }
+        }
+
+        let mut out = String::new();
+        walk(&mut out, "", self.roots());
+        out
+    }
+
+    /// Resolves the symbols of every node whose frame hasn't been resolved
+    /// yet.
+    pub fn resolve(&mut self) {
+        let mut stack = vec![&mut self.children.nodes];
+        while let Some(nodes) = stack.pop() {
+            for node in nodes.iter_mut() {
+                node.frame.resolve();
+                stack.push(&mut node.children.nodes);
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a CallTree {
+    type Item = &'a Node;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl Node {
+    /// Returns the instruction pointer of this node's frame.
+    pub fn address(&self) -> *mut c_void {
+        self.frame.ip()
+    }
+
+    /// Returns this node's frame, as it was in the first backtrace with
+    /// resolved symbols to pass through it, or the first one to do so at all.
+    pub fn frame(&self) -> &BacktraceFrame {
+        &self.frame
+    }
+
+    /// Returns the symbols of this node's frame, which is empty if it hasn't
+    /// been resolved.
+    pub fn symbols(&self) -> &[BacktraceSymbol] {
+        self.frame.symbols()
+    }
+
+    /// Returns how many frames this node is away from the root of its call
+    /// path, which is zero for the roots of the tree.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the combined weight of all backtraces passing through this
+    /// node.
+    pub fn weight(&self) -> u64 {
+        self.weight
+    }
+
+    /// Returns the combined weight of the backtraces whose innermost frame is
+    /// this node's, which leaves out the weight of its children.
+    pub fn self_weight(&self) -> u64 {
+        self.self_weight
+    }
+
+    /// Returns the nodes for the functions called from this one.
+    pub fn children(&self) -> &[Node] {
+        &self.children.nodes
+    }
+}
+
+/// An iterator over the nodes of a `CallTree`, returned by `CallTree::iter`.
+pub struct Iter<'a> {
+    stack: Vec<slice::Iter<'a, Node>>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(node) => {
+                    self.stack.push(node.children.nodes.iter());
+                    return Some(node);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 //! Platform dependent types.
 
 cfg_if::cfg_if! {
-    if #[cfg(feature = "std")] {
+    if #[cfg(feature = "std-base")] {
         use std::borrow::Cow;
         use std::fmt;
         use std::path::PathBuf;
@@ -13,7 +13,7 @@ cfg_if::cfg_if! {
 /// A platform independent representation of a string. When working with `std`
 /// enabled it is recommended to the convenience methods for providing
 /// conversions to `std` types.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum BytesOrWideString<'a> {
     /// A slice, typically provided on Unix platforms.
     Bytes(&'a [u8]),
@@ -21,7 +21,7 @@ pub enum BytesOrWideString<'a> {
     Wide(&'a [u16]),
 }
 
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 impl<'a> BytesOrWideString<'a> {
     /// Lossy converts to a `Cow<str>`, will allocate if `Bytes` is not valid
     /// UTF-8 or if `BytesOrWideString` is `Wide`.
@@ -75,7 +75,7 @@ impl<'a> BytesOrWideString<'a> {
     }
 }
 
-#[cfg(feature = "std")]
+#[cfg(feature = "std-base")]
 impl<'a> fmt::Display for BytesOrWideString<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.to_str_lossy().fmt(f)
@@ -0,0 +1,211 @@
+use crate::{resolve_frame, trace, Frame};
+use core::ptr;
+use std::fmt;
+use std::prelude::v1::*;
+
+/// Checks whether backtraces work in the current build configuration.
+///
+/// Whether a backtrace can be captured and resolved depends on much more than
+/// this crate, for example optimizations such as LTO, `-Cpanic=abort`
+/// omitting unwind tables, stripped debug information or custom linkers. This
+/// function calls through a known chain of functions which can't be inlined,
+/// captures a backtrace at the end of it, and reports which of the functions
+/// could be found in the backtrace and how well they were resolved. It's
+/// meant to be called from a test of the configuration that's shipped.
+///
+/// # Examples
+///
+/// ```
+/// let report = backtrace::verify();
+/// if !report.is_ok() {
+///     println!("{}", report);
+/// }
+/// ```
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+pub fn verify() -> VerifyReport {
+    let mut frames = Vec::new();
+    outer(&mut frames);
+
+    // The functions of the call chain, from the innermost to the outermost.
+    let functions = [
+        ("verify::inner", inner as *const () as usize),
+        ("verify::middle", middle as *const () as usize),
+        ("verify::outer", outer as *const () as usize),
+    ];
+    let mut report = VerifyReport {
+        frames: frames.len(),
+        functions: functions
+            .iter()
+            .map(|&(name, addr)| VerifiedFunction {
+                name,
+                // Not every unwinder knows where functions start, so frames
+                // are also matched by name once they're resolved below.
+                frame: frames
+                    .iter()
+                    .position(|f| f.symbol_address() as usize == addr),
+                resolved: false,
+                has_line_info: false,
+            })
+            .collect(),
+    };
+    for (i, frame) in frames.iter().enumerate() {
+        resolve_frame(frame, |symbol| {
+            let name = match symbol.name() {
+                Some(name) => format!("{:#}", name),
+                None => return,
+            };
+            let function = report
+                .functions
+                .iter_mut()
+                .find(|f| !f.resolved && f.frame.unwrap_or(i) == i && name.contains(f.name));
+            if let Some(function) = function {
+                function.frame = Some(i);
+                function.resolved = true;
+                function.has_line_info = symbol.lineno().is_some();
+            }
+        });
+    }
+    report
+}
+
+// Each of these functions does some work after its call, so that the call
+// can't be turned into a tail call which would remove the caller's frame.
+
+#[inline(never)]
+fn outer(frames: &mut Vec<Frame>) {
+    middle(frames);
+    unsafe { ptr::read_volatile(&0u8) };
+}
+
+#[inline(never)]
+fn middle(frames: &mut Vec<Frame>) {
+    inner(frames);
+    unsafe { ptr::read_volatile(&0u8) };
+}
+
+#[inline(never)]
+fn inner(frames: &mut Vec<Frame>) {
+    trace(|frame| {
+        frames.push(frame.clone());
+        true
+    });
+    unsafe { ptr::read_volatile(&0u8) };
+}
+
+/// The results of `verify`.
+///
+/// The `Display` implementation prints a summary of what was checked, which
+/// is suitable for a test failure message.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Debug)]
+pub struct VerifyReport {
+    frames: usize,
+    functions: Vec<VerifiedFunction>,
+}
+
+impl VerifyReport {
+    /// Returns whether every function of the call chain was found in the
+    /// backtrace with its name, in the right order.
+    ///
+    /// Line information isn't required, as it's commonly unavailable in
+    /// release builds.
+    pub fn is_ok(&self) -> bool {
+        self.functions.iter().all(|f| f.resolved) && self.in_order()
+    }
+
+    /// Returns the number of frames that were captured.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Returns what was found for each function of the call chain, from the
+    /// innermost to the outermost.
+    pub fn functions(&self) -> &[VerifiedFunction] {
+        &self.functions
+    }
+
+    /// Returns whether the functions which were found are in the order in
+    /// which they called each other.
+    pub fn in_order(&self) -> bool {
+        let frames = self.functions.iter().filter_map(|f| f.frame);
+        frames.clone().zip(frames.skip(1)).all(|(a, b)| a < b)
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.is_ok() { "succeeded" } else { "failed" };
+        writeln!(
+            f,
+            "backtrace verification {}, {} frames captured",
+            status, self.frames
+        )?;
+        for function in &self.functions {
+            write!(f, "  {}: ", function.name)?;
+            match function.frame {
+                Some(i) => write!(f, "frame {}", i)?,
+                None => f.write_str("not found")?,
+            }
+            if function.frame.is_some() && !function.resolved {
+                f.write_str(", not resolved")?;
+            }
+            if function.resolved && !function.has_line_info {
+                f.write_str(", no line information")?;
+            }
+            f.write_str("\n")?;
+        }
+        if !self.in_order() {
+            f.write_str("  functions are out of order\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// What `verify` found for one function of its call chain.
+///
+/// # Required features
+///
+/// This function requires the `std` feature of the `backtrace` crate to be
+/// enabled, and the `std` feature is enabled by default.
+#[derive(Clone, Debug)]
+pub struct VerifiedFunction {
+    name: &'static str,
+    frame: Option<usize>,
+    resolved: bool,
+    has_line_info: bool,
+}
+
+impl VerifiedFunction {
+    /// Returns the end of the path of the function, such as
+    /// `verify::inner`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the index of the frame that belongs to this function, if it
+    /// was found.
+    ///
+    /// A frame may be found through the start address of its function even
+    /// if it couldn't be resolved.
+    pub fn frame(&self) -> Option<usize> {
+        self.frame
+    }
+
+    /// Returns whether a frame was resolved to this function's name.
+    pub fn resolved(&self) -> bool {
+        self.resolved
+    }
+
+    /// Returns whether the symbol of this function had a line number.
+    pub fn has_line_info(&self) -> bool {
+        self.has_line_info
+    }
+}
@@ -25,6 +25,7 @@ cfg_if::cfg_if! {
             pub use winapi::shared::basetsd::*;
             pub use winapi::shared::minwindef::*;
             pub use winapi::um::dbghelp::*;
+            pub use winapi::um::errhandlingapi::*;
             pub use winapi::um::fileapi::*;
             pub use winapi::um::handleapi::*;
             pub use winapi::um::libloaderapi::*;
@@ -326,6 +327,33 @@ ffi! {
         pub szExePath: [WCHAR; MAX_PATH],
     }
 
+    #[repr(C)]
+    pub struct THREADENTRY32 {
+        pub dwSize: DWORD,
+        pub cntUsage: DWORD,
+        pub th32ThreadID: DWORD,
+        pub th32OwnerProcessID: DWORD,
+        pub tpBasePri: LONG,
+        pub tpDeltaPri: LONG,
+        pub dwFlags: DWORD,
+    }
+
+    #[repr(C)]
+    pub struct EXCEPTION_RECORD {
+        pub ExceptionCode: DWORD,
+        pub ExceptionFlags: DWORD,
+        pub ExceptionRecord: PEXCEPTION_RECORD,
+        pub ExceptionAddress: PVOID,
+        pub NumberParameters: DWORD,
+        pub ExceptionInformation: [ULONG_PTR; EXCEPTION_MAXIMUM_PARAMETERS],
+    }
+
+    #[repr(C)]
+    pub struct EXCEPTION_POINTERS {
+        pub ExceptionRecord: PEXCEPTION_RECORD,
+        pub ContextRecord: PCONTEXT,
+    }
+
     pub const MAX_SYM_NAME: usize = 2000;
     pub const AddrModeFlat: ADDRESS_MODE = 3;
     pub const TRUE: BOOL = 1;
@@ -342,10 +370,15 @@ ffi! {
     pub const INFINITE: DWORD = !0;
     pub const PAGE_READONLY: DWORD = 2;
     pub const FILE_MAP_READ: DWORD = 4;
+    pub const TH32CS_SNAPTHREAD: DWORD = 0x00000004;
     pub const TH32CS_SNAPMODULE: DWORD = 0x00000008;
+    pub const THREAD_SUSPEND_RESUME: DWORD = 0x0002;
+    pub const THREAD_GET_CONTEXT: DWORD = 0x0008;
+    pub const THREAD_QUERY_INFORMATION: DWORD = 0x0040;
     pub const INVALID_HANDLE_VALUE: HANDLE = -1isize as HANDLE;
     pub const MAX_MODULE_NAME32: usize = 255;
     pub const MAX_PATH: usize = 260;
+    pub const EXCEPTION_MAXIMUM_PARAMETERS: usize = 15;
 
     pub type DWORD = u32;
     pub type PDWORD = *mut u32;
@@ -370,6 +403,15 @@ ffi! {
     pub type LPVOID = *mut c_void;
     pub type LPCVOID = *const c_void;
     pub type LPMODULEENTRY32W = *mut MODULEENTRY32W;
+    pub type LPTHREADENTRY32 = *mut THREADENTRY32;
+    pub type LONG = i32;
+    pub type ULONG_PTR = usize;
+    pub type PEXCEPTION_RECORD = *mut EXCEPTION_RECORD;
+    pub type PEXCEPTION_POINTERS = *mut EXCEPTION_POINTERS;
+    pub type PVECTORED_EXCEPTION_HANDLER =
+        Option<unsafe extern "system" fn(ExceptionInfo: *mut EXCEPTION_POINTERS) -> LONG>;
+    pub type LPTOP_LEVEL_EXCEPTION_FILTER =
+        Option<unsafe extern "system" fn(ExceptionInfo: *mut EXCEPTION_POINTERS) -> LONG>;
 
     #[link(name = "kernel32")]
     extern "system" {
@@ -377,6 +419,7 @@ ffi! {
         pub fn GetCurrentThread() -> HANDLE;
         pub fn RtlCaptureContext(ContextRecord: PCONTEXT) -> ();
         pub fn LoadLibraryA(a: *const i8) -> HMODULE;
+        pub fn FreeLibrary(h: HMODULE) -> BOOL;
         pub fn GetProcAddress(h: HMODULE, name: *const i8) -> FARPROC;
         pub fn GetModuleHandleA(name: *const i8) -> HMODULE;
         pub fn OpenProcess(
@@ -434,6 +477,30 @@ ffi! {
             hSnapshot: HANDLE,
             lpme: LPMODULEENTRY32W,
         ) -> BOOL;
+        pub fn Thread32First(
+            hSnapshot: HANDLE,
+            lpte: LPTHREADENTRY32,
+        ) -> BOOL;
+        pub fn Thread32Next(
+            hSnapshot: HANDLE,
+            lpte: LPTHREADENTRY32,
+        ) -> BOOL;
+        pub fn GetCurrentThreadId() -> DWORD;
+        pub fn OpenThread(
+            dwDesiredAccess: DWORD,
+            bInheritHandle: BOOL,
+            dwThreadId: DWORD,
+        ) -> HANDLE;
+        pub fn SuspendThread(hThread: HANDLE) -> DWORD;
+        pub fn ResumeThread(hThread: HANDLE) -> DWORD;
+        pub fn GetThreadContext(hThread: HANDLE, lpContext: PCONTEXT) -> BOOL;
+        pub fn AddVectoredExceptionHandler(
+            First: ULONG,
+            Handler: PVECTORED_EXCEPTION_HANDLER,
+        ) -> PVOID;
+        pub fn SetUnhandledExceptionFilter(
+            lpTopLevelExceptionFilter: LPTOP_LEVEL_EXCEPTION_FILTER,
+        ) -> LPTOP_LEVEL_EXCEPTION_FILTER;
     }
 }
 
@@ -451,6 +518,8 @@ ffi! {
 
 #[cfg(target_arch = "aarch64")]
 ffi! {
+    pub const CONTEXT_FULL: DWORD = 0x00400007;
+
     #[repr(C, align(16))]
     pub struct CONTEXT {
         pub ContextFlags: DWORD,
@@ -524,6 +593,8 @@ ffi! {
 
 #[cfg(target_arch = "x86")]
 ffi! {
+    pub const CONTEXT_FULL: DWORD = 0x00010007;
+
     #[repr(C)]
     pub struct CONTEXT {
         pub ContextFlags: DWORD,
@@ -569,6 +640,8 @@ ffi! {
 
 #[cfg(target_arch = "x86_64")]
 ffi! {
+    pub const CONTEXT_FULL: DWORD = 0x0010000B;
+
     #[repr(C, align(8))]
     pub struct CONTEXT {
         pub P1Home: DWORDLONG,
@@ -643,6 +716,8 @@ pub struct FLOATING_SAVE_AREA {
 
 #[cfg(target_arch = "arm")]
 ffi! {
+    pub const CONTEXT_FULL: DWORD = 0x00200007;
+
     // #[repr(C)]
     // pub struct NEON128 {
     //     pub Low: ULONG64,
@@ -0,0 +1,98 @@
+use backtrace::capi::*;
+use std::ffi::{c_void, CStr};
+use std::ptr;
+
+const FRAMES: usize = 64;
+
+#[inline(never)]
+fn capture(skip: usize) -> Vec<backtrace_frame_t> {
+    let mut frames = vec![
+        backtrace_frame_t {
+            ip: ptr::null_mut(),
+            symbol_address: ptr::null_mut(),
+            module_base: ptr::null_mut(),
+        };
+        FRAMES
+    ];
+    let n = unsafe { backtrace_capture(frames.as_mut_ptr(), frames.len(), skip) };
+    assert!(n <= FRAMES);
+    frames.truncate(n);
+    frames
+}
+
+fn names(ip: *mut c_void) -> Vec<String> {
+    unsafe extern "C" fn push(symbol: *const backtrace_symbol_t, data: *mut c_void) {
+        let names = &mut *(data as *mut Vec<String>);
+        let symbol = &*symbol;
+        if !symbol.name.is_null() {
+            names.push(CStr::from_ptr(symbol.name).to_string_lossy().into_owned());
+        }
+    }
+
+    let mut names = Vec::new();
+    let n = unsafe { backtrace_resolve(ip, push, &mut names as *mut Vec<String> as *mut c_void) };
+    assert!(n >= names.len());
+    names
+}
+
+#[test]
+fn capture_and_resolve() {
+    let frames = capture(0);
+    assert!(!frames.is_empty());
+    // The first frame is the one calling `backtrace_capture`.
+    let first = names(frames[0].ip);
+    assert!(first.iter().any(|n| n.ends_with("capture")), "{:?}", first);
+    // Skipping `capture` leaves this function's frame first, called from the
+    // same place as before.
+    let skipped = capture(1);
+    assert!(frames.len() > 2);
+    assert_eq!(skipped[1].ip, frames[2].ip);
+    let mut none = [];
+    assert_eq!(unsafe { backtrace_capture(none.as_mut_ptr(), 0, 0) }, 0);
+}
+
+#[test]
+fn format_like_snprintf() {
+    let frames = capture(0);
+    let len = unsafe { backtrace_format(frames.as_ptr(), frames.len(), ptr::null_mut(), 0) };
+    assert!(len > 0);
+
+    let mut buf = vec![0xffu8; len + 1];
+    let written = unsafe {
+        backtrace_format(
+            frames.as_ptr(),
+            frames.len(),
+            buf.as_mut_ptr() as *mut _,
+            buf.len(),
+        )
+    };
+    assert_eq!(written, len);
+    let text = CStr::from_bytes_with_nul(&buf).unwrap().to_str().unwrap();
+    assert!(text.contains("format_like_snprintf"), "{}", text);
+
+    // The output doesn't follow the settings for printing `Backtrace`.
+    backtrace::set_frame_order(backtrace::FrameOrder::OutermostFirst);
+    let mut reordered = vec![0xffu8; len + 1];
+    unsafe {
+        backtrace_format(
+            frames.as_ptr(),
+            frames.len(),
+            reordered.as_mut_ptr() as *mut _,
+            reordered.len(),
+        )
+    };
+    backtrace::set_frame_order(backtrace::FrameOrder::InnermostFirst);
+    assert_eq!(reordered, buf);
+
+    let mut short = [0xffu8; 8];
+    unsafe {
+        backtrace_format(
+            frames.as_ptr(),
+            frames.len(),
+            short.as_mut_ptr() as *mut _,
+            8,
+        )
+    };
+    assert_eq!(&short[..7], &text.as_bytes()[..7]);
+    assert_eq!(short[7], 0);
+}
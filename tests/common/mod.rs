@@ -9,6 +9,8 @@ pub fn cannot_reexec_the_test() -> bool {
     cfg!(unix)
         && (cfg!(target_arch = "arm")
             || cfg!(target_arch = "aarch64")
+            || cfg!(target_arch = "mips64")
+            || cfg!(target_arch = "riscv64")
             || cfg!(target_arch = "s390x"))
         || cfg!(miri)
 }
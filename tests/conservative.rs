@@ -0,0 +1,131 @@
+// This is its own test binary as conservative tracing and the trace backend
+// are process-wide settings, which would change how other tests are traced.
+// The tests here change them too, so they take turns.
+
+use backtrace::{Backend, Backtrace};
+use std::sync::{Mutex, MutexGuard, Once};
+
+// Held by each test while it changes the settings. `Mutex::new` isn't a
+// `const fn` on the oldest Rust supported, so it's created on first use.
+fn settings() -> MutexGuard<'static, ()> {
+    static INIT: Once = Once::new();
+    static mut SETTINGS: Option<Mutex<()>> = None;
+    unsafe {
+        INIT.call_once(|| SETTINGS = Some(Mutex::new(())));
+        (*std::ptr::addr_of!(SETTINGS))
+            .as_ref()
+            .unwrap()
+            .lock()
+            .unwrap()
+    }
+}
+
+#[inline(never)]
+fn trace(frames: &mut Vec<backtrace::Frame>) {
+    backtrace::trace(|frame| {
+        frames.push(frame.clone());
+        true
+    });
+}
+
+fn names(frames: &[backtrace::Frame]) -> Vec<String> {
+    let mut names = Vec::new();
+    for frame in frames {
+        backtrace::resolve_frame(frame, |sym| {
+            names.extend(sym.name().map(|n| n.to_string()));
+        });
+    }
+    names
+}
+
+#[test]
+fn conservative_traces_reach_callers() {
+    let _settings = settings();
+    let mut fast = Vec::new();
+    trace(&mut fast);
+    backtrace::set_conservative(true);
+    let mut conservative = Vec::new();
+    trace(&mut conservative);
+    backtrace::set_conservative(false);
+
+    // The platform's unwinder looks up where functions start, unlike walking
+    // frame pointers.
+    if cfg!(target_os = "linux") {
+        assert!(conservative.iter().any(|f| f.symbol_address() != f.ip()));
+    }
+
+    let (fast, conservative) = (names(&fast), names(&conservative));
+    let expected = [
+        "conservative::trace",
+        "conservative::conservative_traces_reach_callers",
+    ];
+    for name in expected.iter() {
+        assert!(fast.iter().any(|n| n.contains(name)), "{:?}", fast);
+        assert!(
+            conservative.iter().any(|n| n.contains(name)),
+            "{:?}",
+            conservative
+        );
+    }
+}
+
+#[test]
+fn every_available_backend_reaches_callers() {
+    let _settings = settings();
+    let backends = [
+        Backend::Auto,
+        Backend::FramePointer,
+        Backend::Dwarf,
+        Backend::Unwind,
+    ];
+    assert!(Backend::Auto.is_available());
+    if cfg!(all(unix, not(target_os = "emscripten"))) {
+        assert!(Backend::Unwind.is_available());
+    }
+    for &backend in backends.iter().filter(|b| b.is_available()) {
+        backtrace::set_trace_backend(backend);
+        let mut frames = Vec::new();
+        trace(&mut frames);
+        backtrace::set_trace_backend(Backend::Auto);
+
+        let names = names(&frames);
+        let name = "conservative::every_available_backend_reaches_callers";
+        assert!(
+            names.iter().any(|n| n.contains(name)),
+            "{:?}: {:?}",
+            backend,
+            names
+        );
+    }
+}
+
+#[test]
+fn registers_are_only_recovered_by_the_dwarf_backend() {
+    let _settings = settings();
+    if Backend::Dwarf.is_available() {
+        backtrace::set_trace_backend(Backend::Dwarf);
+        let bt = Backtrace::builder().registers(true).capture_unresolved();
+        backtrace::set_trace_backend(Backend::Auto);
+
+        // The DWARF register number of the stack pointer.
+        let sp = if cfg!(target_arch = "x86_64") {
+            7
+        } else if cfg!(target_arch = "aarch64") {
+            31
+        } else {
+            2
+        };
+        assert!(!bt.frames().is_empty());
+        for frame in bt.frames() {
+            let registers = frame.registers().unwrap();
+            assert_eq!(registers.get(sp), Some(frame.sp() as usize));
+        }
+    }
+
+    backtrace::set_trace_backend(Backend::Unwind);
+    let bt = Backtrace::builder().registers(true).capture_unresolved();
+    backtrace::set_trace_backend(Backend::Auto);
+    assert!(bt.frames().iter().all(|f| f.registers().is_none()));
+    let bt = Backtrace::new_unresolved();
+    assert!(bt.frames().iter().all(|f| f.registers().is_none()));
+}
@@ -0,0 +1,94 @@
+use backtrace::remote::CoreDump;
+use std::env;
+use std::fs;
+use std::process::{self, Command, Stdio};
+
+const VAR: &str = "__BACKTRACE_CORE_DUMP_CHILD";
+
+mod common;
+
+fn main() {
+    // If we cannot re-exec this test, or the platform can't read core dumps,
+    // there's no point in trying to do it.
+    if common::cannot_reexec_the_test()
+        || !cfg!(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))
+    {
+        println!("test result: ok");
+        return;
+    }
+
+    if env::var(VAR).is_ok() {
+        child();
+    } else {
+        parent();
+    }
+}
+
+fn parent() {
+    let me = env::current_exe().unwrap();
+    let dir = env::temp_dir().join(format!("backtrace-core-dump-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // The child crashes in `dir` with core dumps enabled, so that the kernel
+    // writes its core there unless `core_pattern` sends it elsewhere.
+    Command::new("sh")
+        .arg("-c")
+        .arg("ulimit -c unlimited && exec \"$0\"")
+        .arg(&me)
+        .env(VAR, "1")
+        .current_dir(&dir)
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    let core = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            path.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("core")
+        });
+    let core = match core {
+        Some(core) => core,
+        None => {
+            println!("no core dump was written, see /proc/sys/kernel/core_pattern");
+            fs::remove_dir_all(&dir).unwrap();
+            println!("test result: ok");
+            return;
+        }
+    };
+
+    let mut dump = CoreDump::open(&core).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    // The child aborted, with `SIGABRT`.
+    assert_eq!(dump.signal(), Some(6));
+    let threads = dump.threads();
+    assert_eq!(threads.len(), 1);
+    let frames = dump.trace_thread(threads[0]).unwrap();
+    let mut names = Vec::new();
+    for frame in frames.iter() {
+        dump.resolve(frame, |symbol| {
+            if let Some(name) = symbol.name() {
+                names.push(format!("{:#}", name));
+            }
+        });
+    }
+    assert!(frames.len() > 2, "{:?}", frames);
+    assert!(names.iter().any(|n| n == "core_dump::crash"), "{:?}", names);
+    assert!(names.iter().any(|n| n == "core_dump::child"), "{:?}", names);
+    assert!(dump.trace_thread(0).is_err());
+    println!("test result: ok");
+}
+
+#[inline(never)]
+fn child() {
+    crash();
+}
+
+#[inline(never)]
+fn crash() {
+    process::abort();
+}
@@ -0,0 +1,101 @@
+use std::env;
+use std::process::Command;
+
+const VAR: &str = "__BACKTRACE_CRASH_HANDLER_CHILD";
+
+mod common;
+
+fn main() {
+    // If we cannot re-exec this test, there's no point in trying to do it.
+    if common::cannot_reexec_the_test() {
+        println!("test result: ok");
+        return;
+    }
+
+    match env::var(VAR) {
+        Ok(how) => imp::child(&how),
+        Err(_) => {
+            imp::parent();
+            println!("test result: ok");
+        }
+    }
+}
+
+fn run(how: &str) -> (std::process::ExitStatus, String) {
+    let me = env::current_exe().unwrap();
+    let result = Command::new(&me).env(VAR, how).output().unwrap();
+    let stderr = String::from_utf8_lossy(&result.stderr).into_owned();
+    (result.status, stderr)
+}
+
+fn fail(how: &str, status: std::process::ExitStatus, stderr: &str) -> ! {
+    println!("stderr:\n{}", stderr);
+    println!("code: {}", status);
+    panic!("the {} crash wasn't reported", how);
+}
+
+#[cfg(unix)]
+mod imp {
+    use backtrace::crash_handler::CrashHandler;
+    use std::os::unix::process::ExitStatusExt;
+
+    pub fn parent() {
+        check("abort", libc::SIGABRT, "SIGABRT");
+        check("segv", libc::SIGSEGV, "SIGSEGV");
+    }
+
+    fn check(how: &str, signal: i32, name: &str) {
+        let (status, stderr) = super::run(how);
+        let reported = format!("crashed with signal {} ({})", signal, name);
+        if status.signal() != Some(signal)
+            || !stderr.contains(&reported)
+            || !stderr.contains("stack backtrace (unresolved):\n   0: 0x")
+        {
+            super::fail(how, status, &stderr);
+        }
+    }
+
+    pub fn child(how: &str) {
+        CrashHandler::new().install().unwrap();
+        // The handlers can only be installed once.
+        assert!(CrashHandler::new().install().is_err());
+        match how {
+            "abort" => std::process::abort(),
+            _ => unsafe {
+                libc::raise(libc::SIGSEGV);
+            },
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use backtrace::exception_handler::ExceptionHandler;
+    use std::ptr;
+
+    pub fn parent() {
+        let (status, stderr) = super::run("access-violation");
+        if status.code() != Some(0xc000_0005u32 as i32)
+            || !stderr.contains("(EXCEPTION_ACCESS_VIOLATION)")
+            || !stderr.contains("accessing address 0x0")
+            || !stderr.contains("crash_handler::imp::crash")
+        {
+            super::fail("access violation", status, &stderr);
+        }
+    }
+
+    #[inline(never)]
+    fn crash() {
+        unsafe {
+            ptr::read_volatile(ptr::null::<u8>());
+        }
+    }
+
+    pub fn child(_how: &str) {
+        ExceptionHandler::new()
+            .vectored(true)
+            .install(|report| eprint!("{}", report))
+            .unwrap();
+        crash();
+    }
+}
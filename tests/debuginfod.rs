@@ -0,0 +1,63 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+
+// libc is stripped and without a local debug file on most distributions,
+// which makes resolving one of its functions look for its debug file on the
+// configured server.
+#[test]
+#[cfg(target_os = "linux")]
+fn stripped_library_fetched_from_server() {
+    let cache = std::env::temp_dir().join(format!("backtrace-debuginfod-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    std::env::set_var("DEBUGINFOD_CACHE_PATH", &cache);
+    std::env::set_var(
+        "DEBUGINFOD_URLS",
+        format!("http://{}/", listener.local_addr().unwrap()),
+    );
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0);
+            request.extend_from_slice(&buf[..n]);
+        }
+        stream
+            .write_all(b"HTTP/1.0 200 OK\r\nContent-Length: 8\r\n\r\nnot elf!")
+            .unwrap();
+        tx.send(String::from_utf8(request).unwrap()).unwrap();
+    });
+
+    // Addresses are looked up one byte earlier, as if they were return
+    // addresses.
+    let addr = libc::getpid as *const () as usize + 1;
+    let mut name = None;
+    backtrace::resolve(addr as *mut _, |sym| {
+        name = sym.name().map(|n| n.to_string());
+    });
+    // The downloaded file couldn't be parsed, so the library's own symbol
+    // table is used instead.
+    assert_eq!(name.as_deref(), Some("getpid"));
+
+    // Any request was made while resolving, so it's there by now if there
+    // was one at all.
+    let request = match rx.try_recv() {
+        Ok(request) => request,
+        Err(_) => return,
+    };
+    let path = request.split(' ').nth(1).unwrap();
+    assert!(path.starts_with("/buildid/"), "{}", request);
+    assert!(path.ends_with("/debuginfo"), "{}", request);
+    let build_id = &path["/buildid/".len()..path.len() - "/debuginfo".len()];
+    assert!(build_id.bytes().all(|b| b.is_ascii_hexdigit()));
+
+    let cached = std::fs::read(cache.join(build_id).join("debuginfo")).unwrap();
+    assert_eq!(cached, b"not elf!");
+    std::fs::remove_dir_all(&cache).unwrap();
+}
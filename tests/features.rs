@@ -0,0 +1,30 @@
+// Tests for builds with only one of the `trace` and `symbolize` features,
+// which CI runs with each of them on their own as well as with the defaults.
+
+#[test]
+#[cfg(feature = "trace")]
+fn trace_captures_addresses() {
+    let mut ips = Vec::new();
+    backtrace::trace(|frame| {
+        ips.push(frame.ip() as usize);
+        true
+    });
+    assert!(!ips.is_empty());
+    assert_ne!(ips[0], 0);
+}
+
+#[inline(never)]
+fn resolved() {}
+
+#[test]
+#[cfg(feature = "symbolize")]
+fn symbolize_resolves_addresses() {
+    let mut names = Vec::new();
+    backtrace::resolve_precise(resolved as *mut _, |symbol| {
+        names.extend(symbol.name().map(|name| format!("{:#}", name)));
+    });
+    // Not every platform has a symbolizer, but those that do find this.
+    if cfg!(any(unix, windows)) {
+        assert!(names.iter().any(|n| n.ends_with("resolved")), "{:?}", names);
+    }
+}
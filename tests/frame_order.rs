@@ -0,0 +1,38 @@
+// This is its own test binary as the frame order is a process-wide setting,
+// which would change how other tests print backtraces.
+
+use backtrace::{Backtrace, FrameOrder};
+
+#[inline(never)]
+fn capture() -> Backtrace {
+    Backtrace::new()
+}
+
+// Returns the numbers of the frames in `s`, in the order they're printed.
+fn numbers(s: &str) -> Vec<usize> {
+    s.lines()
+        .filter_map(|l| l.trim().split(':').next()?.parse().ok())
+        .collect()
+}
+
+#[test]
+fn outermost_first_reverses_frames() {
+    let bt = capture();
+    let innermost = format!("{:#?}", bt);
+    backtrace::set_frame_order(FrameOrder::OutermostFirst);
+    let outermost = format!("{:#?}", bt);
+    backtrace::set_frame_order(FrameOrder::InnermostFirst);
+    assert_eq!(format!("{:#?}", bt), innermost);
+
+    // Frames keep their numbers, with the innermost one printed last.
+    let mut reversed = numbers(&outermost);
+    reversed.reverse();
+    assert_eq!(reversed, numbers(&innermost));
+    assert_eq!(reversed.first(), Some(&0));
+
+    let position = |s: &str, name: &str| s.find(name).unwrap();
+    let (callee, caller) = ("frame_order::capture", "outermost_first_reverses_frames");
+    assert!(position(&innermost, callee) < position(&innermost, caller));
+    assert!(position(&outermost, callee) > position(&outermost, caller));
+    assert_eq!(innermost.lines().count(), outermost.lines().count());
+}
@@ -0,0 +1,64 @@
+// This is its own test binary as the panic hook is process-wide, and would
+// report the panics of other tests.
+
+use std::panic;
+use std::sync::{Arc, Mutex};
+
+#[inline(never)]
+fn explode() {
+    panic!("exploded with {}", 42);
+}
+
+#[test]
+fn hook_reports_panics_with_a_backtrace() {
+    // Replace the standard library's hook, which would print the panic, with
+    // one checking it's still called once wrapped.
+    let previous_called = Arc::new(Mutex::new(false));
+    let called = previous_called.clone();
+    panic::set_hook(Box::new(move |_| *called.lock().unwrap() = true));
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let sink = reports.clone();
+    backtrace::install_panic_hook_with(move |report| {
+        let location = report.location().map(|l| (l.file().to_string(), l.line()));
+        let text = format!("{}", report);
+        let frames = report.backtrace().frames().len();
+        sink.lock().unwrap().push((
+            report.message().map(str::to_string),
+            location,
+            report.thread_name().map(str::to_string),
+            text,
+            frames,
+        ));
+    });
+
+    assert!(panic::catch_unwind(explode).is_err());
+    let _ = panic::take_hook();
+
+    assert!(*previous_called.lock().unwrap());
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 1);
+    let (message, location, thread, text, frames) = &reports[0];
+    assert_eq!(message.as_deref(), Some("exploded with 42"));
+    let (file, line) = location.as_ref().unwrap();
+    assert!(file.ends_with("panic_hook.rs"), "{}", file);
+    assert!(*line > 0);
+    assert_eq!(
+        thread.as_deref(),
+        Some("hook_reports_panics_with_a_backtrace")
+    );
+    assert!(*frames > 0);
+    assert!(
+        text.starts_with("thread 'hook_reports_panics_with_a_backtrace' panicked at "),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("exploded with 42\nstack backtrace:\n"),
+        "{}",
+        text
+    );
+    if cfg!(not(feature = "trace-frame-pointer")) {
+        assert!(text.contains("explode"), "{}", text);
+    }
+}
@@ -0,0 +1,78 @@
+use backtrace::remote::Process;
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+const VAR: &str = "__BACKTRACE_REMOTE_CHILD";
+
+mod common;
+
+fn main() {
+    // If we cannot re-exec this test, or the platform can't trace other
+    // processes, there's no point in trying to do it.
+    if common::cannot_reexec_the_test()
+        || !cfg!(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))
+    {
+        println!("test result: ok");
+        return;
+    }
+
+    if env::var(VAR).is_ok() {
+        child();
+    } else {
+        parent();
+    }
+}
+
+fn parent() {
+    let me = env::current_exe().unwrap();
+    let mut child = Command::new(&me)
+        .env(VAR, "1")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.as_mut().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    assert_eq!(line, "ready\n");
+
+    let mut process = Process::new(child.id()).unwrap();
+    assert!(process.threads().unwrap().contains(&child.id()));
+    let frames = process.trace_thread(child.id()).unwrap();
+    let mut names = Vec::new();
+    for frame in frames.iter() {
+        process.resolve(frame, |symbol| {
+            if let Some(name) = symbol.name() {
+                names.push(format!("{:#}", name));
+            }
+        });
+    }
+    // The child keeps running once it's been traced.
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(frames.len() > 2, "{:?}", frames);
+    assert!(frames[0].module_base().is_some(), "{:?}", frames);
+    assert!(names.iter().any(|n| n == "remote::sleepy"), "{:?}", names);
+    assert!(names.iter().any(|n| n == "remote::child"), "{:?}", names);
+    println!("test result: ok");
+}
+
+fn child() {
+    println!("ready");
+    std::io::stdout().flush().unwrap();
+    sleepy();
+}
+
+#[inline(never)]
+fn sleepy() {
+    loop {
+        thread::sleep(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,24 @@
+// This is its own test binary as it changes the process's environment, which
+// would race with other tests formatting backtraces.
+
+use backtrace::Backtrace;
+use std::env;
+
+#[test]
+fn debug_honors_rust_backtrace() {
+    let bt = std::thread::spawn(Backtrace::new).join().unwrap();
+
+    env::set_var("RUST_BACKTRACE", "1");
+    let short = format!("{:?}", bt);
+    env::set_var("RUST_BACKTRACE", "full");
+    let full = format!("{:?}", bt);
+    env::remove_var("RUST_BACKTRACE");
+
+    assert_eq!(full, format!("{:#?}", bt));
+    assert_ne!(short, full);
+    assert!(short.lines().count() < full.lines().count());
+    // Short backtraces leave out addresses, which start every frame of a full
+    // one.
+    assert!(!short.contains(&format!("{:?}", bt.frames()[0].ip())));
+    assert!(full.contains(&format!("{:?}", bt.frames()[0].ip())));
+}
@@ -9,6 +9,8 @@ const ENABLED: bool = cfg!(all(
     target_os = "linux",
     // On ARM finding the enclosing function is simply returning the ip itself.
     not(target_arch = "arm"),
+    // Neither do frame records say which function they belong to.
+    not(feature = "trace-frame-pointer"),
 ));
 
 #[test]
@@ -42,3 +44,27 @@ fn backtrace_new_should_start_with_call_site_trace() {
     let frame_ip = b.frames().first().unwrap().symbol_address() as usize;
     assert_eq!(this_ip, frame_ip);
 }
+
+#[test]
+fn backtrace_builder_should_skip_and_limit_frames() {
+    if !ENABLED {
+        return;
+    }
+
+    #[inline(never)]
+    fn inner(skip: usize) -> Backtrace {
+        Backtrace::builder()
+            .skip(skip)
+            .max_frames(2)
+            .capture_unresolved()
+    }
+
+    let b = inner(0);
+    assert_eq!(b.frames().len(), 2);
+    assert_eq!(b.frames()[0].symbol_address() as usize, inner as usize);
+
+    let b = inner(1);
+    assert_eq!(b.frames().len(), 2);
+    let this_ip = backtrace_builder_should_skip_and_limit_frames as usize;
+    assert_eq!(b.frames()[0].symbol_address() as usize, this_ip);
+}
@@ -218,6 +218,77 @@ fn many_threads() {
     }
 }
 
+#[test]
+fn trace_raw_matches_trace() {
+    let mut traced = Vec::new();
+    let mut raw = [0; 256];
+    let n = backtrace::trace_raw(&mut raw);
+    backtrace::trace(|frame| {
+        traced.push(frame.ip() as usize);
+        true
+    });
+    // Only the frames of the functions doing the tracing differ.
+    assert!(n > 0);
+    assert_eq!(raw[n - 1], traced[traced.len() - 1]);
+
+    let mut short = [0; 2];
+    assert_eq!(backtrace::trace_raw(&mut short), 2);
+    assert_eq!(backtrace::trace_raw(&mut []), 0);
+}
+
+#[inline(never)]
+fn depth_at(n: usize) -> usize {
+    if n == 0 {
+        backtrace::depth()
+    } else {
+        let depth = depth_at(n - 1);
+        // Keeps the call from being a tail call, so each level keeps its
+        // frame.
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+        depth
+    }
+}
+
+#[test]
+fn depth_counts_frames() {
+    let mut traced = 0;
+    backtrace::trace(|_| {
+        traced += 1;
+        true
+    });
+    let depth = backtrace::depth();
+    // Only the frames of the functions doing the walk differ.
+    assert!(depth > 0);
+    assert!(
+        (depth as isize - traced as isize).abs() <= 2,
+        "{} {}",
+        depth,
+        traced
+    );
+
+    let shallow = depth_at(0);
+    let deep = depth_at(10);
+    assert!(deep > shallow, "{} {}", deep, shallow);
+    assert!(deep <= shallow + 10, "{} {}", deep, shallow);
+}
+
+#[test]
+fn frames_iterates_like_trace() {
+    let mut traced = Vec::new();
+    backtrace::trace(|frame| {
+        traced.push(frame.ip() as usize);
+        true
+    });
+    // Only the frames of the functions doing the tracing differ.
+    let ips = backtrace::frames()
+        .map(|f| f.ip() as usize)
+        .collect::<Vec<_>>();
+    assert_eq!(ips.last(), traced.last());
+
+    assert_eq!(backtrace::frames().take(2).count(), 2);
+    assert!(backtrace::frames().rev().next().is_some());
+}
+
 #[test]
 #[cfg(feature = "rustc-serialize")]
 fn is_rustc_serialize() {
@@ -321,3 +392,1869 @@ fn sp_smoke_test() {
         }
     }
 }
+
+#[test]
+fn display_matches_debug() {
+    let bt = backtrace::Backtrace::new();
+    assert_eq!(format!("{}", bt), format!("{:?}", bt));
+    assert_eq!(format!("{:#}", bt), format!("{:#?}", bt));
+}
+
+#[test]
+fn streamed_output_matches_display() {
+    use backtrace::PrintFmt;
+    use std::io;
+
+    let bt = backtrace::Backtrace::new();
+    let mut bytes = Vec::new();
+    bt.write_to(&mut bytes, PrintFmt::Full).unwrap();
+    let mut string = String::new();
+    bt.write_to_fmt(&mut string, PrintFmt::Full).unwrap();
+    assert_eq!(String::from_utf8(bytes).unwrap(), format!("{:#}", bt));
+    assert_eq!(string, format!("{:#}", bt));
+
+    // Unresolved frames are printed, but without their symbols.
+    let mut unresolved = Vec::new();
+    backtrace::Backtrace::new_unresolved()
+        .write_to(&mut unresolved, PrintFmt::Full)
+        .unwrap();
+    let unresolved = String::from_utf8(unresolved).unwrap();
+    assert!(unresolved.contains("<unknown>"), "{}", unresolved);
+    assert!(!unresolved.contains("streamed_output_matches_display"));
+
+    // A writer which fails after a few bytes.
+    struct Full(usize);
+
+    impl io::Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "full"));
+            }
+            let n = buf.len().min(self.0);
+            self.0 -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let err = bt.write_to(&mut Full(10), PrintFmt::Full).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+}
+
+#[test]
+fn identical_backtraces_compare_equal() {
+    let mut traces = Vec::new();
+    for resolve in [false, true, true].iter() {
+        let mut bt = backtrace::Backtrace::new_unresolved();
+        if *resolve {
+            bt.resolve();
+        }
+        traces.push(bt);
+    }
+    let other = backtrace::Backtrace::new();
+
+    // Whether symbols have been resolved doesn't change which stack a
+    // backtrace is.
+    assert_eq!(traces[0], traces[1]);
+    assert_eq!(traces[1], traces[2]);
+    assert_ne!(traces[1], other);
+    assert_eq!(traces[1].fingerprint(), traces[2].fingerprint());
+    assert_ne!(traces[1].fingerprint(), other.fingerprint());
+}
+
+#[test]
+fn normalized_backtraces_are_module_relative() {
+    #[inline(never)]
+    fn capture() -> backtrace::Backtrace {
+        backtrace::Backtrace::new_unresolved()
+    }
+
+    let traces = (0..2).map(|_| capture()).collect::<Vec<_>>();
+    let normalized = traces[0].normalize();
+    assert_eq!(normalized, traces[1].normalize());
+    assert_eq!(normalized.frames().len(), traces[0].frames().len());
+    assert_ne!(
+        normalized,
+        backtrace::Backtrace::new_unresolved().normalize()
+    );
+
+    for (frame, original) in normalized.frames().iter().zip(traces[0].frames()) {
+        match (frame.module(), original.module_snapshot()) {
+            (Some(i), Some(snapshot)) => {
+                let module = snapshot.module();
+                assert_eq!(normalized.modules()[i].path(), module.path());
+                assert_eq!(normalized.modules()[i].build_id(), module.build_id());
+                let base = module.base_address() as usize;
+                assert_eq!(frame.offset(), original.ip() as usize - base);
+            }
+            (None, None) => assert_eq!(frame.offset(), original.ip() as usize),
+            other => panic!("{:?}", other),
+        }
+    }
+    if !backtrace::modules().is_empty() {
+        assert!(!normalized.modules().is_empty());
+    }
+}
+
+#[test]
+fn rebuild_from_frames() {
+    let bt = backtrace::Backtrace::new();
+    let frames = bt.frames().to_vec();
+    assert_eq!(backtrace::Backtrace::from(bt.clone().into_frames()), bt);
+
+    let filtered = bt
+        .into_frames()
+        .into_iter()
+        .filter(|frame| !frame.symbols().is_empty())
+        .collect::<Vec<_>>();
+    let rebuilt = backtrace::Backtrace::from(filtered);
+    assert!(rebuilt.frames().len() <= frames.len());
+    assert!(rebuilt.frames().iter().all(|f| frames.contains(f)));
+}
+
+#[test]
+fn capture_into_reuses_buffer() {
+    let mut buffer = backtrace::BacktraceBuffer::new();
+    let mut captures = Vec::new();
+    for _ in 0..2 {
+        backtrace::Backtrace::capture_into(&mut buffer);
+        captures.push(buffer.frames().to_vec());
+    }
+    assert!(!captures[0].is_empty());
+    assert_eq!(captures[0].len(), captures[1].len());
+
+    buffer.backtrace_mut().resolve();
+    let is_caller = |frame: &backtrace::BacktraceFrame| {
+        frame.symbols().iter().any(|s| {
+            s.name().map_or(false, |n| {
+                n.to_string().contains("capture_into_reuses_buffer")
+            })
+        })
+    };
+    // Frame pointer walks don't know where the frames of this crate end, so
+    // the innermost frame may be one of them.
+    if cfg!(feature = "trace-frame-pointer") {
+        assert!(buffer.frames().iter().any(is_caller));
+    } else {
+        assert!(is_caller(&buffer.frames()[0]));
+    }
+
+    buffer.clear();
+    assert!(buffer.frames().is_empty());
+}
+
+#[test]
+fn trim_removes_runtime_frames() {
+    let names = |bt: &backtrace::Backtrace| {
+        bt.frames()
+            .iter()
+            .flat_map(|f| f.symbols())
+            .filter_map(|s| s.name().map(|n| n.to_string()))
+            .collect::<Vec<_>>()
+    };
+
+    let (full, trimmed) = thread::spawn(|| {
+        let bt = backtrace::Backtrace::new();
+        let mut trimmed = bt.clone();
+        trimmed.trim();
+        (bt, trimmed)
+    })
+    .join()
+    .unwrap();
+    let (full, trimmed) = (names(&full), names(&trimmed));
+    if !full
+        .iter()
+        .any(|n| n.contains("__rust_begin_short_backtrace"))
+    {
+        println!("no short backtrace marker, skipping");
+        return;
+    }
+
+    assert!(trimmed.len() < full.len());
+    assert!(trimmed.iter().all(|n| !n.contains("short_backtrace")));
+    assert!(trimmed
+        .last()
+        .unwrap()
+        .contains("trim_removes_runtime_frames"));
+}
+
+#[test]
+fn json_lists_every_frame() {
+    let bt = backtrace::Backtrace::new();
+    let json = bt.to_json();
+    assert!(json.starts_with("[{\"ip\":\"0x"));
+    assert!(json.ends_with("]}]"));
+    assert_eq!(json.matches("{\"ip\":").count(), bt.frames().len());
+    assert!(json.contains("json_lists_every_frame"));
+    assert!(json.contains("\"file\":"));
+}
+
+#[test]
+fn symbol_cache_matches_resolve() {
+    let mut ips = Vec::new();
+    backtrace::trace(|frame| {
+        ips.push(frame.ip());
+        true
+    });
+
+    let mut cache = backtrace::SymbolCache::new(2);
+    for ip in ips.iter().chain(ips.iter()) {
+        let mut expected = Vec::new();
+        backtrace::resolve(*ip, |sym| expected.push(sym.name().map(|n| n.to_string())));
+        let actual = cache
+            .resolve(*ip)
+            .iter()
+            .map(|sym| sym.name().map(|n| n.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(expected, actual);
+        assert!(cache.len() <= 2);
+    }
+
+    let mut bt = backtrace::Backtrace::new_unresolved();
+    bt.resolve_with_cache(&mut cache);
+    assert!(bt.frames().iter().any(|f| !f.symbols().is_empty()));
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+#[test]
+#[cfg(all(
+    any(target_os = "linux", target_os = "macos", windows),
+    not(target_env = "musl")
+))]
+fn resolve_in_library_loaded_later() {
+    // Resolve something first so the list of loaded libraries is cached
+    // before the library below is loaded. Addresses are treated as return
+    // addresses, so one past the start of a function is within it.
+    let mut resolved = false;
+    let addr = resolve_in_library_loaded_later as usize + 1;
+    backtrace::resolve(addr as *mut _, |_| resolved = true);
+    assert!(resolved);
+
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    if cfg!(windows) {
+        path.push("dylib_dep.dll");
+    } else if cfg!(target_os = "macos") {
+        path.push("libdylib_dep.dylib");
+    } else {
+        path.push("libdylib_dep.so");
+    }
+    unsafe {
+        let lib = libloading::Library::new(&path).unwrap();
+        let foo = *lib.get::<usize>(b"foo").unwrap();
+        // Batches of addresses check for new libraries once, before the first.
+        let mut names = Vec::new();
+        backtrace::resolve_many(&[addr as *mut _, (foo + 1) as *mut _], |i, sym| {
+            names.push((
+                i,
+                sym.name().and_then(|n| n.as_str()).map(|n| n.to_string()),
+            ));
+        });
+        assert!(names.contains(&(1, Some("foo".to_string()))), "{:?}", names);
+
+        let mut name = None;
+        backtrace::resolve((foo + 1) as *mut _, |sym| {
+            name = sym.name().and_then(|n| n.as_str()).map(|n| n.to_string());
+        });
+        assert_eq!(name.as_deref(), Some("foo"));
+    }
+}
+
+#[test]
+fn resolve_many_matches_resolve() {
+    let mut ips = Vec::new();
+    backtrace::trace(|frame| {
+        ips.push(frame.ip());
+        true
+    });
+
+    let mut expected = vec![Vec::new(); ips.len()];
+    for (i, ip) in ips.iter().enumerate() {
+        backtrace::resolve(*ip, |sym| {
+            expected[i].push(sym.name().map(|n| n.to_string()));
+        });
+    }
+
+    let mut actual = vec![Vec::new(); ips.len()];
+    backtrace::resolve_many(&ips, |i, sym| {
+        actual[i].push(sym.name().map(|n| n.to_string()));
+    });
+    assert_eq!(expected, actual);
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn frame_module_contains_ip() {
+    let mut frame = None;
+    backtrace::trace(|f| {
+        frame = Some(f.clone());
+        false
+    });
+    let frame = frame.unwrap();
+    let module = frame.module().expect("no module found for the first frame");
+    let ip = frame.ip() as usize;
+    let base = module.base_address() as usize;
+    assert!(base <= ip && ip < base + module.size());
+    assert_eq!(
+        module.path().canonicalize().unwrap(),
+        std::env::current_exe().unwrap().canonicalize().unwrap()
+    );
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn symbol_module_build_id_matches_module() {
+    let mut frame = None;
+    backtrace::trace(|f| {
+        frame = Some(f.clone());
+        false
+    });
+    let frame = frame.unwrap();
+    let module = frame.module().unwrap();
+    let mut ids = Vec::new();
+    backtrace::resolve_frame(&frame, |symbol| {
+        ids.push(symbol.module_build_id().map(|id| id.to_vec()));
+    });
+    assert!(!ids.is_empty());
+    for id in ids {
+        assert_eq!(id.as_deref(), module.build_id());
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn modules_include_frame_module() {
+    let mut frame = None;
+    backtrace::trace(|f| {
+        frame = Some(f.clone());
+        false
+    });
+    let expected = frame.unwrap().module().unwrap();
+    let modules = backtrace::modules();
+    let module = modules
+        .iter()
+        .find(|m| m.base_address() == expected.base_address())
+        .expect("no module found for the first frame");
+    assert_eq!(module.path(), expected.path());
+    assert_eq!(module.size(), expected.size());
+    assert_eq!(module.build_id(), expected.build_id());
+    // Modules don't overlap.
+    let mut ranges = modules
+        .iter()
+        .map(|m| (m.base_address() as usize, m.size()))
+        .collect::<Vec<_>>();
+    ranges.sort();
+    for pair in ranges.windows(2) {
+        assert!(pair[0].0 + pair[0].1 <= pair[1].0, "{:?}", modules);
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn offline_resolve_matches_resolve() {
+    let mut frames = Vec::new();
+    backtrace::trace(|f| {
+        frames.push(f.clone());
+        frames.len() < 4
+    });
+
+    for frame in frames {
+        let module = match frame.module() {
+            Some(module) => module,
+            None => continue,
+        };
+        let mut expected = Vec::new();
+        backtrace::resolve_frame(&frame, |sym| {
+            expected.push(sym.name().map(|n| n.to_string()));
+        });
+
+        let mut resolver = backtrace::offline::Resolver::new(module.path()).unwrap();
+        let mut actual = Vec::new();
+        let offset = frame.ip() as usize - module.base_address() as usize;
+        resolver.resolve_addr(offset, |sym| {
+            actual.push(sym.name().map(|n| n.to_string()));
+        });
+        assert_eq!(expected, actual);
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn debug_file_found_in_search_paths() {
+    use std::process::{Command, Stdio};
+
+    let mut frame = None;
+    backtrace::trace(|f| {
+        frame = Some(f.clone());
+        false
+    });
+    let frame = frame.unwrap();
+    let module = frame.module().unwrap();
+    let offset = frame.ip() as usize - module.base_address() as usize;
+
+    // A copy of this test without debug information, whose debug link names
+    // a copy of it with, which is only found in the search paths.
+    let dir = std::env::temp_dir().join(format!("backtrace-search-paths-{}", std::process::id()));
+    let debug_dir = dir.join("debug");
+    std::fs::create_dir_all(&debug_dir).unwrap();
+    let debug = debug_dir.join("smoke.debug");
+    let stripped = dir.join("smoke");
+    std::fs::copy(module.path(), &debug).unwrap();
+    let status = Command::new("objcopy")
+        .arg("--strip-debug")
+        .arg(format!("--add-gnu-debuglink={}", debug.display()))
+        .arg(&debug)
+        .arg(&stripped)
+        .stderr(Stdio::null())
+        .status();
+    if !matches!(status, Ok(status) if status.success()) {
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    let lines = || {
+        let mut resolver = backtrace::offline::Resolver::new(&stripped).unwrap();
+        let mut lines = Vec::new();
+        resolver.resolve_addr(offset, |sym| lines.push(sym.lineno()));
+        lines
+    };
+    assert!(lines().iter().all(|line| line.is_none()));
+    backtrace::set_search_paths(&[debug_dir]);
+    let found = lines();
+    backtrace::set_search_paths(&[]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(found.iter().any(|line| line.is_some()), "{:?}", found);
+}
+
+#[test]
+// Walking frame pointers from a signal handler loses the interrupted frame.
+#[cfg(all(
+    any(target_os = "linux", windows),
+    not(feature = "trace-frame-pointer")
+))]
+fn trace_other_thread() {
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::SeqCst};
+    use std::sync::Arc;
+
+    #[inline(never)]
+    fn spin_in_other_thread(id: &AtomicU64, stop: &AtomicBool) {
+        id.store(backtrace::current_thread_id(), SeqCst);
+        while !stop.load(SeqCst) {
+            std::hint::spin_loop();
+        }
+    }
+
+    let id = Arc::new(AtomicU64::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let (id, stop) = (id.clone(), stop.clone());
+        thread::spawn(move || spin_in_other_thread(&id, &stop))
+    };
+    while id.load(SeqCst) == 0 {
+        thread::yield_now();
+    }
+    let id = id.load(SeqCst);
+
+    let mut found = false;
+    let mut signal_frames = Vec::new();
+    let captured = backtrace::trace_thread(id, |frame| {
+        signal_frames.push(frame.is_signal_frame());
+        backtrace::resolve_frame(frame, |sym| {
+            if let Some(name) = sym.name().and_then(|n| n.as_str()) {
+                found |= name.contains("spin_in_other_thread");
+            }
+        });
+        true
+    });
+    assert!(backtrace::Backtrace::all_threads()
+        .iter()
+        .any(|(thread, _)| *thread == id));
+
+    stop.store(true, SeqCst);
+    thread.join().unwrap();
+    assert!(captured);
+    assert!(found);
+
+    // Threads which have exited can't be interrupted.
+    assert!(!backtrace::trace_thread(id, |_| panic!(
+        "walked an exited thread"
+    )));
+
+    // The trace starts at the frame the signal interrupted.
+    if cfg!(target_os = "linux") {
+        assert_eq!(signal_frames.iter().position(|&s| s), Some(0));
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn trace_thread_which_is_walking() {
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::SeqCst};
+    use std::sync::Arc;
+
+    // The thread is interrupted while it's walking its own stack, which it
+    // can't walk again from the signal handler.
+    let id = Arc::new(AtomicU64::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let (id, stop) = (id.clone(), stop.clone());
+        thread::spawn(move || unsafe {
+            backtrace::trace_unsynchronized(|_| {
+                id.store(backtrace::current_thread_id(), SeqCst);
+                while !stop.load(SeqCst) {
+                    std::hint::spin_loop();
+                }
+                false
+            })
+        })
+    };
+    while id.load(SeqCst) == 0 {
+        thread::yield_now();
+    }
+
+    let captured = backtrace::trace_thread(id.load(SeqCst), |_| panic!("walked a nested walk"));
+    stop.store(true, SeqCst);
+    thread.join().unwrap();
+    assert!(!captured);
+}
+
+#[test]
+fn resolve_registered_jit_code() {
+    let code = vec![0u8; 64];
+    let start = code.as_ptr() as usize;
+    let names = |addr: usize| {
+        let mut names = Vec::new();
+        backtrace::resolve(addr as *mut _, |sym| {
+            names.push(sym.name().map(|n| n.to_string()));
+        });
+        names
+    };
+
+    backtrace::jit::register(start..start + 32, "jit_first", None, Some(1));
+    backtrace::jit::register(start + 32..start + 64, "jit_second", None, None);
+    assert_eq!(names(start + 1), [Some("jit_first".to_string())]);
+    assert_eq!(names(start + 40), [Some("jit_second".to_string())]);
+
+    // Registering overlapping code replaces what was there before.
+    backtrace::jit::register(start + 16..start + 48, "jit_third", None, None);
+    assert_eq!(names(start + 40), [Some("jit_third".to_string())]);
+    assert!(!backtrace::jit::unregister(start));
+    assert!(!backtrace::jit::unregister(start + 32));
+
+    assert!(backtrace::jit::unregister(start + 16));
+    assert!(names(start + 40).is_empty());
+}
+
+#[test]
+#[cfg(all(feature = "perf-map", target_os = "linux"))]
+fn resolve_from_perf_map() {
+    let code = vec![0u8; 64];
+    let start = code.as_ptr() as usize;
+    let path = format!("/tmp/perf-{}.map", std::process::id());
+    std::fs::write(
+        &path,
+        format!(
+            "{:x} 20 jit_first\n{:x} 20 jit_second\n",
+            start,
+            start + 0x20
+        ),
+    )
+    .unwrap();
+
+    let names = |addr: usize| {
+        let mut names = Vec::new();
+        backtrace::resolve(addr as *mut _, |sym| {
+            names.push(sym.name().map(|n| n.to_string()));
+        });
+        names
+    };
+    let first = names(start + 0x10);
+    let second = names(start + 0x30);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(first, [Some("jit_first".to_string())]);
+    assert_eq!(second, [Some("jit_second".to_string())]);
+}
+
+#[test]
+fn annotated_backtrace_records_thread() {
+    use backtrace::AnnotatedBacktrace;
+    use std::time::SystemTime;
+
+    let before = SystemTime::now();
+    let bt = thread::Builder::new()
+        .name("annotated".to_string())
+        .spawn(AnnotatedBacktrace::new)
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(bt.thread_name(), Some("annotated"));
+    assert!(bt.time() >= before && bt.time() <= SystemTime::now());
+    assert!(!bt.backtrace().frames().is_empty());
+
+    let printed = format!("{:?}", bt);
+    let header = printed.lines().next().unwrap();
+    assert!(header.starts_with("backtrace of thread 'annotated' (id "));
+    assert!(header.ends_with("Z:"));
+}
+
+#[test]
+fn prune_matching_removes_frames() {
+    let bt = backtrace::Backtrace::new();
+    let has = |bt: &backtrace::Backtrace, pattern: &str| {
+        bt.frames()
+            .iter()
+            .flat_map(|f| f.symbols())
+            .any(|s| s.name().map_or(false, |n| n.to_string().contains(pattern)))
+    };
+    if !has(&bt, "prune_matching_removes_frames") {
+        println!("test function not found in backtrace, skipping");
+        return;
+    }
+
+    let pruned = bt.prune_matching(&["prune_matching_removes_frames"]);
+    assert!(!has(&pruned, "prune_matching_removes_frames"));
+    assert!(pruned.frames().len() < bt.frames().len());
+
+    let all = bt.filter(|_| true);
+    assert_eq!(all.frames(), bt.frames());
+    assert!(bt.filter(|_| false).frames().is_empty());
+}
+
+#[test]
+fn source_context_prints_surrounding_lines() {
+    use backtrace::{Backtrace, BacktraceFmt, PrintFmt};
+    use std::fmt;
+
+    struct WithSource(Backtrace);
+
+    impl fmt::Display for WithSource {
+        fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut print_path =
+                |fmt: &mut fmt::Formatter<'_>, path: backtrace::BytesOrWideString<'_>| {
+                    fmt::Display::fmt(&path, fmt)
+                };
+            let mut f = BacktraceFmt::new(fmt, PrintFmt::Short, &mut print_path);
+            f.source_context(1);
+            for frame in self.0.frames() {
+                f.frame().backtrace_frame(frame)?;
+            }
+            f.finish()
+        }
+    }
+
+    let line = line!() + 1;
+    let printed = WithSource(Backtrace::new()).to_string();
+    if !printed.contains("tests/smoke.rs") {
+        println!("no line information, skipping");
+        return;
+    }
+    let lines = printed.lines().collect::<Vec<_>>();
+    let i = lines
+        .iter()
+        .position(|l| l.contains(&format!("> {} |", line)))
+        .unwrap();
+    assert!(lines[i].contains("WithSource(Backtrace::new())"));
+    assert!(lines[i - 1].contains(&format!("  {} |", line - 1)));
+    assert!(lines[i + 1].contains(&format!("  {} |", line + 1)));
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn offset_in_module_is_relative_to_bias() {
+    let mut frame = None;
+    backtrace::trace(|f| {
+        frame = Some(f.clone());
+        false
+    });
+    let frame = frame.unwrap();
+    let bias = frame
+        .module_base()
+        .expect("no module found for the first frame") as usize;
+
+    let mut offsets = Vec::new();
+    backtrace::resolve_frame(&frame, |sym| offsets.push(sym.offset_in_module()));
+    assert!(!offsets.is_empty());
+    for offset in offsets {
+        assert_eq!(offset, Some(frame.ip() as usize - 1 - bias));
+    }
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn symtab_fallback_has_offset() {
+    extern "C" {
+        fn getpid() -> i32;
+    }
+
+    // The system's libc usually comes without debug information, leaving
+    // only its dynamic symbol table to resolve this address with. Like for
+    // return addresses, `resolve` looks up the byte before the address.
+    let addr = getpid as *const () as usize + 2;
+    let mut found = false;
+    backtrace::resolve(addr as *mut _, |sym| {
+        if let Some(offset) = sym.offset_in_symbol() {
+            assert_eq!(offset, 1);
+            assert!(sym.name().is_some());
+            assert!(sym.lineno().is_none());
+            found = true;
+        }
+    });
+    if !found {
+        println!("libc has debug information, skipping");
+    }
+}
+
+#[inline(never)]
+fn bounded() -> usize {
+    line!() as usize
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn symbols_have_function_bounds() {
+    let start = bounded as usize;
+    assert!(bounded() > 0);
+    // An address in the middle of the function, which has more than one
+    // instruction.
+    let addr = start + 2;
+    let mut bounds = Vec::new();
+    backtrace::resolve(addr as *mut _, |sym| {
+        bounds.push((sym.function_start(), sym.size()));
+    });
+    assert!(!bounds.is_empty());
+    for (function_start, size) in bounds {
+        let function_start = function_start.expect("no function start") as usize;
+        let size = size.expect("no function size");
+        assert_eq!(function_start, start);
+        assert!(
+            addr - 1 < function_start + size,
+            "{:#x} {}",
+            function_start,
+            size
+        );
+    }
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn symtab_fallback_has_function_bounds() {
+    extern "C" {
+        fn getpid() -> i32;
+    }
+
+    let start = getpid as *const () as usize;
+    let mut found = false;
+    backtrace::resolve((start + 2) as *mut _, |sym| {
+        if sym.offset_in_symbol().is_some() {
+            assert_eq!(sym.function_start(), Some(start as *mut _));
+            assert!(sym.size().unwrap() > 1);
+            found = true;
+        }
+    });
+    if !found {
+        println!("libc has debug information, skipping");
+    }
+}
+
+#[test]
+fn verify_succeeds() {
+    let report = backtrace::verify();
+    assert!(report.is_ok(), "{}", report);
+    assert_eq!(report.functions().len(), 3);
+    assert!(report.frames() >= 3);
+}
+
+#[test]
+fn capture_hook_sees_every_capture() {
+    use std::cell::Cell;
+
+    // Other tests capture backtraces concurrently, so only count the ones
+    // taken on this thread.
+    thread_local!(static CAPTURES: Cell<usize> = Cell::new(0));
+
+    fn hook(bt: &backtrace::Backtrace) {
+        assert!(!bt.frames().is_empty());
+        CAPTURES.with(|c| c.set(c.get() + 1));
+        // Captures from within the hook don't call it again.
+        let _ = backtrace::Backtrace::new_unresolved();
+    }
+
+    backtrace::set_capture_hook(hook);
+    let _ = backtrace::Backtrace::new();
+    let _ = backtrace::Backtrace::new_unresolved();
+    let _ = backtrace::Backtrace::builder().max_frames(1).capture();
+    let mut buffer = backtrace::BacktraceBuffer::new();
+    backtrace::Backtrace::capture_into(&mut buffer);
+    assert_eq!(CAPTURES.with(|c| c.get()), 4);
+}
+
+#[test]
+fn frames_resolve_on_demand() {
+    let bt = backtrace::Backtrace::new_unresolved();
+    let printed = format!("{:#?}", bt);
+    assert!(printed.contains("frames_resolve_on_demand"), "{}", printed);
+    assert!(bt.frames().iter().all(|f| f.symbols().is_empty()));
+
+    let mut frames = bt.into_frames();
+    frames[0].resolve();
+    assert!(!frames[0].symbols().is_empty());
+    assert!(frames[1..].iter().all(|f| f.symbols().is_empty()));
+}
+
+#[test]
+fn frames_record_stack_and_frame_pointers() {
+    let bt = backtrace::Backtrace::new_unresolved();
+    let sps = bt
+        .frames()
+        .iter()
+        .map(|f| f.sp() as usize)
+        .filter(|&sp| sp != 0)
+        .collect::<Vec<_>>();
+    // The stack grows down, so callers have higher stack pointers.
+    assert!(sps.windows(2).all(|w| w[0] <= w[1]), "{:x?}", sps);
+
+    if cfg!(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )) {
+        assert!(!sps.is_empty());
+        assert!(bt.frames().iter().any(|f| !f.fp().is_null()));
+    }
+}
+
+#[test]
+fn bytes_round_trip() {
+    let bt = backtrace::Backtrace::new();
+    let bytes = bt.to_bytes();
+    let decoded = backtrace::Backtrace::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded, bt);
+    for (a, b) in decoded.frames().iter().zip(bt.frames()) {
+        assert_eq!(a.module_base_address(), b.module_base_address());
+        assert_eq!(a.sp(), b.sp());
+        assert_eq!(a.fp(), b.fp());
+        assert_eq!(a.symbols(), b.symbols());
+    }
+
+    // Unresolved frames only store their addresses.
+    let unresolved = backtrace::Backtrace::new_unresolved().to_bytes();
+    assert!(unresolved.len() < bytes.len());
+
+    assert!(backtrace::Backtrace::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+    assert!(backtrace::Backtrace::from_bytes(b"").is_none());
+
+    // How the backtrace was cut short is kept too.
+    let truncated = backtrace::Backtrace::builder()
+        .max_frames(1)
+        .count_dropped(true)
+        .capture_unresolved();
+    let decoded = backtrace::Backtrace::from_bytes(&truncated.to_bytes()).unwrap();
+    assert_eq!(decoded, truncated);
+    assert!(decoded.is_truncated());
+    assert!(!decoded.is_reentrant());
+    assert_eq!(decoded.frames_dropped(), truncated.frames_dropped());
+    assert!(decoded.frames_dropped() > 0);
+
+    backtrace::trace(|_| {
+        let reentrant = backtrace::Backtrace::new_unresolved();
+        let decoded = backtrace::Backtrace::from_bytes(&reentrant.to_bytes()).unwrap();
+        assert!(decoded.is_reentrant());
+        false
+    });
+}
+
+#[test]
+#[cfg(all(
+    feature = "minidump",
+    target_os = "linux",
+    target_env = "gnu",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn minidump_from_context() {
+    extern "C" {
+        fn getcontext(ucp: *mut u8) -> i32;
+    }
+
+    let u32_at = |b: &[u8], at: usize| u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]]);
+    let u64_at =
+        |b: &[u8], at: usize| u64::from(u32_at(b, at)) | u64::from(u32_at(b, at + 4)) << 32;
+
+    // Comfortably larger than a `ucontext_t`.
+    let mut context = vec![0u64; 1024];
+    let local = 0u8;
+    let dump = unsafe {
+        assert_eq!(getcontext(context.as_mut_ptr() as *mut u8), 0);
+        backtrace::minidump::from_context(context.as_ptr() as *const _).unwrap()
+    };
+    assert_eq!(&dump[..4], b"MDMP");
+    assert_eq!(u32_at(&dump, 8), 4);
+
+    let directory = u32_at(&dump, 12) as usize;
+    let mut streams = std::collections::HashMap::new();
+    for i in 0..4 {
+        let entry = directory + i * 12;
+        let rva = u32_at(&dump, entry + 8) as usize;
+        streams.insert(u32_at(&dump, entry), rva);
+    }
+
+    // The stack of the thread contains the local variable above.
+    let threads = streams[&3];
+    assert_eq!(u32_at(&dump, threads), 1);
+    let stack_start = u64_at(&dump, threads + 28) as usize;
+    let stack_size = u32_at(&dump, threads + 36) as usize;
+    let local = &local as *const u8 as usize;
+    assert!(stack_start <= local && local < stack_start + stack_size);
+
+    // The module list includes the test executable.
+    let modules = streams[&4];
+    let count = u32_at(&dump, modules) as usize;
+    let here = minidump_from_context as *const () as usize;
+    assert!((0..count).any(|i| {
+        let module = modules + 4 + i * 108;
+        let base = u64_at(&dump, module) as usize;
+        let size = u32_at(&dump, module + 8) as usize;
+        base <= here && here < base + size
+    }));
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn trace_from_getcontext() {
+    extern "C" {
+        fn getcontext(ucp: *mut u8) -> i32;
+    }
+
+    // Comfortably larger than a `ucontext_t`.
+    let mut context = vec![0u64; 1024];
+    let mut ips = Vec::new();
+    let complete = unsafe {
+        assert_eq!(getcontext(context.as_mut_ptr() as *mut u8), 0);
+        backtrace::trace_from_context(context.as_ptr() as *const _, |frame| {
+            ips.push(frame.ip());
+            true
+        })
+    };
+
+    // Only our own unwinders can start from a context.
+    let supported = cfg!(any(
+        all(
+            feature = "trace-frame-pointer",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ),
+        all(
+            feature = "unwind-dwarf",
+            any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "riscv64"
+            )
+        ),
+    ));
+    if !supported {
+        assert!(!complete);
+        assert!(ips.is_empty());
+        return;
+    }
+
+    // `getcontext` saves the registers it returns with, so the first frame is
+    // in this function.
+    assert!(ips.len() > 1);
+    let mut name = None;
+    backtrace::resolve(ips[0], |symbol| {
+        name = symbol.name().map(|n| n.to_string());
+    });
+    assert!(name.unwrap().contains("trace_from_getcontext"));
+}
+
+#[test]
+fn with_config_scopes_capture_options() {
+    use backtrace::{Backtrace, CaptureConfig};
+
+    let lazy = CaptureConfig::new().resolve(false);
+    let shallow = CaptureConfig::new().max_frames(2);
+    backtrace::with_config(lazy, || {
+        let bt = Backtrace::new();
+        assert!(bt.frames().iter().all(|f| f.symbols().is_empty()));
+
+        // The innermost configuration applies, and the outer one is restored
+        // afterwards.
+        backtrace::with_config(shallow, || {
+            let bt = Backtrace::new();
+            assert!(bt.frames().len() <= 2);
+            assert!(!bt.frames()[0].symbols().is_empty());
+        });
+        let bt = Backtrace::new();
+        assert!(bt.frames().len() > 2);
+        assert!(bt.frames().iter().all(|f| f.symbols().is_empty()));
+    });
+
+    // Other threads aren't affected.
+    backtrace::with_config(CaptureConfig::new().resolve(false), || {
+        std::thread::spawn(|| {
+            let bt = Backtrace::new();
+            assert!(bt.frames().iter().any(|f| !f.symbols().is_empty()));
+        })
+        .join()
+        .unwrap();
+    });
+}
+
+#[test]
+fn truncated_backtraces_count_dropped_frames() {
+    let bt = backtrace::Backtrace::new_unresolved();
+    assert_eq!(bt.frames_dropped(), 0);
+    if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        assert!(!bt.is_truncated());
+    }
+
+    // The walk stops at the limit, so the frames past it aren't counted.
+    let limited = backtrace::Backtrace::builder()
+        .max_frames(1)
+        .capture_unresolved();
+    assert_eq!(limited.frames().len(), 1);
+    assert_eq!(limited.frames_dropped(), 0);
+    assert!(limited.is_truncated());
+
+    let counted = backtrace::Backtrace::builder()
+        .max_frames(1)
+        .count_dropped(true)
+        .capture_unresolved();
+    assert_eq!(counted.frames().len(), 1);
+    assert!(counted.frames_dropped() > 0);
+    assert!(counted.is_truncated());
+    let printed = format!("{:#?}", counted);
+    let omitted = format!("... {} frames omitted", counted.frames_dropped());
+    assert!(printed.contains(&omitted), "{}", printed);
+}
+
+#[test]
+fn fixed_backtrace_matches_backtrace() {
+    #[inline(never)]
+    fn capture() -> (backtrace::BacktraceFixed<64>, backtrace::Backtrace) {
+        (
+            backtrace::BacktraceFixed::new(),
+            backtrace::Backtrace::new_unresolved(),
+        )
+    }
+
+    let (fixed, bt) = capture();
+    let ips = bt
+        .frames()
+        .iter()
+        .map(|f| f.ip() as usize)
+        .collect::<Vec<_>>();
+    assert!(!fixed.ips().is_empty());
+    // Both are captured from the same function, so the frames of its callers
+    // are the same, and the next frame in is `capture` in both.
+    assert_eq!(fixed.frames_dropped(), 0);
+    let common = fixed
+        .ips()
+        .iter()
+        .rev()
+        .zip(ips.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    assert!(common > 0);
+    let mut name = None;
+    backtrace::resolve(
+        fixed.ips()[fixed.ips().len() - common - 1] as *mut _,
+        |sym| {
+            name = sym.name().map(|n| n.to_string());
+        },
+    );
+    let name = name.unwrap();
+    assert!(name.contains("capture"), "{}", name);
+
+    let small = backtrace::BacktraceFixed::<1>::new();
+    assert_eq!(small.ips().len(), 1);
+    assert!(small.frames_dropped() > 0);
+    let printed = format!("{:?}", small);
+    let omitted = format!("... {} frames omitted", small.frames_dropped());
+    assert!(printed.contains(&omitted), "{}", printed);
+}
+
+#[test]
+fn call_tree_aggregates_weights() {
+    use backtrace::tree::CallTree;
+    use backtrace::Backtrace;
+
+    #[inline(never)]
+    fn leaf_a() -> Backtrace {
+        Backtrace::new_unresolved()
+    }
+    #[inline(never)]
+    fn leaf_b() -> Backtrace {
+        Backtrace::new_unresolved()
+    }
+
+    let mut tree = CallTree::new();
+    let a = leaf_a();
+    tree.insert(&a, 10);
+    tree.insert(&a, 5);
+    tree.insert(&leaf_b(), 1);
+    assert_eq!(tree.weight(), 16);
+    // Every backtrace came from the same thread, so they share the root.
+    assert_eq!(tree.roots().len(), 1);
+    assert_eq!(tree.roots()[0].weight(), 16);
+
+    let leaf = tree
+        .iter()
+        .find(|n| n.address() == a.frames()[0].ip())
+        .unwrap();
+    assert_eq!(leaf.weight(), 15);
+    assert_eq!(leaf.self_weight(), 15);
+    assert!(leaf.children().is_empty());
+    assert_eq!(leaf.depth(), a.frames().len() - 1);
+    // Weights of children add up to their parent's, less what ended there.
+    for node in tree.iter() {
+        let children = node.children().iter().map(|n| n.weight()).sum::<u64>();
+        assert_eq!(node.weight(), children + node.self_weight());
+    }
+
+    let mut merged = CallTree::new();
+    merged.merge(&tree);
+    merged.merge(&tree);
+    assert_eq!(merged.weight(), 32);
+    assert_eq!(merged.iter().count(), tree.iter().count());
+    merged.resolve();
+    let leaf = merged
+        .iter()
+        .find(|n| n.address() == a.frames()[0].ip())
+        .unwrap();
+    assert_eq!(leaf.weight(), 30);
+    let mut a = a;
+    a.resolve();
+    let name = |symbols: &[backtrace::BacktraceSymbol]| {
+        symbols
+            .iter()
+            .map(|s| s.name().map(|n| n.to_string()))
+            .collect::<Vec<_>>()
+    };
+    assert!(!leaf.symbols().is_empty());
+    assert_eq!(name(leaf.symbols()), name(a.frames()[0].symbols()));
+}
+
+#[test]
+fn folded_stacks() {
+    use backtrace::tree::CallTree;
+    use backtrace::Backtrace;
+
+    let bt = Backtrace::new_unresolved();
+    let folded = bt.to_folded();
+    assert!(folded.ends_with(" 1\n"), "{}", folded);
+    assert_eq!(folded.lines().count(), 1);
+    // Frames are listed from the outermost, so this test comes after the
+    // test harness calling it.
+    let path = folded.trim_end().rsplitn(2, ' ').nth(1).unwrap();
+    let names = path.split(';').collect::<Vec<_>>();
+    let this = names
+        .iter()
+        .position(|n| n.contains("folded_stacks"))
+        .unwrap();
+    assert!(names[..this].iter().any(|n| n.contains("call_once")));
+    // Unresolved backtraces are resolved while they're formatted.
+    assert!(bt.frames().iter().all(|f| f.symbols().is_empty()));
+
+    let mut tree = CallTree::new();
+    tree.insert(&bt, 3);
+    tree.insert(&bt, 4);
+    tree.insert(&Backtrace::new_unresolved(), 5);
+    let folded = tree.to_folded();
+    let mut total = 0;
+    for line in folded.lines() {
+        let (weight, path) = {
+            let mut parts = line.rsplitn(2, ' ');
+            (parts.next().unwrap(), parts.next().unwrap())
+        };
+        assert!(path.contains("folded_stacks"), "{}", line);
+        total += weight.parse::<u64>().unwrap();
+    }
+    assert_eq!(folded.lines().count(), 2);
+    assert_eq!(total, 12);
+    assert!(folded.contains(&format!("{} 7\n", path)), "{}", folded);
+}
+
+#[test]
+fn resolve_after_preload_and_unload() {
+    fn names(bt: &backtrace::Backtrace) -> Vec<Option<String>> {
+        let mut bt = bt.clone();
+        bt.resolve();
+        bt.frames()
+            .iter()
+            .flat_map(|f| f.symbols())
+            .map(|sym| sym.name().map(|n| n.to_string()))
+            .collect()
+    }
+
+    let bt = backtrace::Backtrace::new_unresolved();
+    backtrace::preload();
+    let expected = names(&bt);
+    assert!(expected.iter().any(|n| n.is_some()));
+
+    backtrace::unload();
+    assert_eq!(names(&bt), expected);
+    backtrace::unload();
+    backtrace::unload();
+    assert_eq!(names(&bt), expected);
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", windows), not(target_env = "musl")))]
+fn frames_of_unloaded_library_are_stale() {
+    use std::cell::RefCell;
+
+    thread_local!(static CAPTURED: RefCell<Option<backtrace::Backtrace>> = RefCell::new(None));
+
+    fn capture(_outer: (&'static str, u32), _inner: (&'static str, u32)) {
+        let bt = backtrace::Backtrace::new_unresolved();
+        CAPTURED.with(|c| *c.borrow_mut() = Some(bt));
+    }
+
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    if cfg!(windows) {
+        path.push("dylib_dep.dll");
+    } else {
+        path.push("libdylib_dep.so");
+    }
+    let in_lib = |frame: &backtrace::BacktraceFrame| {
+        frame
+            .module_snapshot()
+            .map_or(false, |m| m.module().path().file_name() == path.file_name())
+    };
+    let mut bt = unsafe {
+        let lib = libloading::Library::new(&path).unwrap();
+        let foo = lib
+            .get::<extern "C" fn((&'static str, u32), fn((&'static str, u32), (&'static str, u32)))>(
+                b"foo",
+            )
+            .unwrap();
+        foo(("", 0), capture);
+        CAPTURED.with(|c| c.borrow_mut().take()).unwrap()
+    };
+
+    let lib_frames = bt.frames().iter().filter(|f| in_lib(f)).count();
+    assert!(lib_frames > 0);
+    assert!(bt
+        .frames()
+        .iter()
+        .filter(|f| in_lib(f))
+        .all(|f| !f.module_snapshot().unwrap().is_loaded()));
+
+    bt.resolve();
+    for frame in bt.frames() {
+        assert_eq!(frame.is_stale(), in_lib(frame));
+        if frame.is_stale() {
+            assert!(frame.symbols().is_empty());
+        }
+    }
+    assert!(bt.frames().iter().any(|f| f.symbols().iter().any(|s| s
+        .name()
+        .map_or(false, |n| n.to_string().contains("capture")))));
+}
+
+#[test]
+fn symbol_language() {
+    use backtrace::Language;
+
+    let bt = backtrace::Backtrace::new();
+    let language = bt
+        .frames()
+        .iter()
+        .flat_map(|frame| frame.symbols())
+        .find(|symbol| {
+            symbol
+                .name()
+                .map_or(false, |name| name.to_string().contains("symbol_language"))
+        })
+        .and_then(|symbol| symbol.language());
+    // Some platforms have no symbols to find in the first place.
+    if let Some(language) = language {
+        assert_eq!(language, Language::Rust);
+    }
+}
+
+#[test]
+fn trace_with_limit_stops_early() {
+    use backtrace::TraceLimit;
+    use std::time::Duration;
+
+    let mut depth = 0;
+    backtrace::trace(|_| {
+        depth += 1;
+        true
+    });
+
+    let mut frames = 0;
+    let truncated = backtrace::trace_with_limit(TraceLimit::new().max_frames(1), |_| {
+        frames += 1;
+        true
+    });
+    assert_eq!(frames, depth.min(1));
+    assert_eq!(truncated, depth > 1);
+
+    let mut frames = 0;
+    let truncated = backtrace::trace_with_limit(TraceLimit::new(), |_| {
+        frames += 1;
+        true
+    });
+    assert!(!truncated);
+    assert!(frames > 0 || depth == 0);
+
+    let limit = TraceLimit::new().timeout(Duration::from_secs(0));
+    let truncated = backtrace::trace_with_limit(limit, |_| panic!("walked past the timeout"));
+    assert_eq!(truncated, depth > 0);
+
+    let bt = backtrace::Backtrace::builder()
+        .limit(TraceLimit::new().max_frames(1))
+        .capture_unresolved();
+    if depth > 1 {
+        assert!(bt.is_truncated());
+        assert_eq!(bt.frames_dropped(), 0);
+    }
+}
+
+#[test]
+fn inline_depths() {
+    #[inline(always)]
+    fn inlined() -> backtrace::Backtrace {
+        backtrace::Backtrace::new()
+    }
+
+    let bt = inlined();
+    for frame in bt.frames() {
+        let symbols = frame.symbols();
+        for (i, symbol) in symbols.iter().enumerate() {
+            assert_eq!(symbol.inline_depth(), symbols.len() - 1 - i);
+            assert_eq!(symbol.is_inline(), i + 1 < symbols.len());
+        }
+    }
+
+    // Whether the function is actually inlined, and whether the debug
+    // information says so, depends on the platform.
+    let inlined = bt.frames().iter().flat_map(|f| f.symbols()).find(|s| {
+        s.name().map_or(false, |name| {
+            name.to_string().contains("inline_depths::inlined")
+        })
+    });
+    if let Some(symbol) = inlined {
+        if symbol.is_inline() {
+            assert!(format!("{}", bt).contains("::inlined [inlined]"));
+        }
+    }
+}
+
+#[test]
+fn resolve_precise_addresses() {
+    #[inline(never)]
+    fn target() {}
+
+    // The first instruction of a function belongs to it, while as a return
+    // address it would point just past the call before it.
+    let addr = target as *mut std::ffi::c_void;
+    let mut precise = None;
+    backtrace::resolve_precise(addr, |symbol| {
+        precise = symbol.name().map(|name| name.to_string());
+    });
+    // Some platforms have no symbols to find in the first place.
+    if let Some(name) = precise {
+        assert!(name.contains("target"), "{}", name);
+    }
+
+    backtrace::trace(|frame| {
+        let ip = frame.ip() as usize;
+        let adjusted = frame.ip_for_symbolication() as usize;
+        if frame.is_signal_frame() || ip == 0 {
+            assert_eq!(adjusted, ip);
+        } else {
+            assert!(adjusted < ip && ip - adjusted <= 4);
+        }
+        true
+    });
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn resolve_in_registered_image() {
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::os::unix::io::FromRawFd;
+
+    thread_local!(static CAPTURED: RefCell<Option<backtrace::Backtrace>> = RefCell::new(None));
+
+    fn capture(_outer: (&'static str, u32), _inner: (&'static str, u32)) {
+        let bt = backtrace::Backtrace::new_unresolved();
+        CAPTURED.with(|c| *c.borrow_mut() = Some(bt));
+    }
+
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("libdylib_dep.so");
+    let image = std::fs::read(&path).unwrap();
+
+    // Load the library from memory, leaving no file behind to read it from.
+    let fd = unsafe { libc::syscall(libc::SYS_memfd_create, "plugin\0".as_ptr(), 0) };
+    if fd < 0 {
+        return;
+    }
+    let mut file = unsafe { std::fs::File::from_raw_fd(fd as i32) };
+    file.write_all(&image).unwrap();
+    let lib = unsafe { libloading::Library::new(format!("/proc/self/fd/{}", fd)).unwrap() };
+    drop(file);
+
+    let bt = unsafe {
+        let foo = lib
+            .get::<extern "C" fn((&'static str, u32), fn((&'static str, u32), (&'static str, u32)))>(
+                b"foo",
+            )
+            .unwrap();
+        foo(("", 0), capture);
+        CAPTURED.with(|c| c.borrow_mut().take()).unwrap()
+    };
+    let foo = bt
+        .frames()
+        .iter()
+        .find(|f| {
+            f.module_snapshot()
+                .map_or(false, |m| m.module().path().starts_with("/proc/self/fd"))
+        })
+        .expect("no frame in the library");
+    let base = foo.module_snapshot().unwrap().module().base_address() as usize;
+    let names = |frame: &backtrace::BacktraceFrame| {
+        let mut names = Vec::new();
+        backtrace::resolve(frame.ip(), |symbol| {
+            names.extend(symbol.name().map(|n| n.to_string()));
+        });
+        names
+    };
+
+    assert!(!names(foo).iter().any(|n| n == "foo"));
+    backtrace::register_image(base, image);
+    assert!(names(foo).iter().any(|n| n == "foo"), "{:?}", names(foo));
+    assert!(backtrace::unregister_image(base));
+    assert!(!backtrace::unregister_image(base));
+}
+
+#[test]
+fn sampled_captures() {
+    use backtrace::sampled::{maybe_capture, RateLimiter};
+
+    assert!(maybe_capture(0.0).is_none());
+    assert!(maybe_capture(f64::NAN).is_none());
+    let bt = maybe_capture(1.0).unwrap();
+    // The frames of this crate are omitted like for `Backtrace::new`, except
+    // where the unwinder doesn't know where functions start.
+    if !cfg!(feature = "trace-frame-pointer") {
+        assert!(!bt.frames().iter().any(|f| f.symbols().iter().any(|s| s
+            .name()
+            .map_or(false, |n| n.to_string().contains("maybe_capture")))));
+    }
+
+    let sampled = (0..1000).filter(|_| maybe_capture(0.5).is_some()).count();
+    assert!(sampled > 300 && sampled < 700);
+
+    let limiter = RateLimiter::new(0.0, 2);
+    assert!(limiter.capture().is_some());
+    assert!(limiter.try_acquire());
+    assert!(limiter.capture().is_none());
+    assert!(!limiter.try_acquire());
+}
+
+#[test]
+fn intern_backtraces() {
+    use backtrace::intern::BacktraceInterner;
+    use backtrace::Backtrace;
+
+    #[inline(never)]
+    fn capture() -> Backtrace {
+        Backtrace::new_unresolved()
+    }
+
+    let mut interner = BacktraceInterner::new();
+    assert!(interner.is_empty());
+    let ids = (0..10)
+        .map(|_| interner.intern(&capture()))
+        .collect::<Vec<_>>();
+    let other = interner.intern(&Backtrace::new_unresolved());
+    assert!(ids.iter().all(|id| *id == ids[0]));
+    assert_ne!(ids[0], other);
+    assert_eq!(interner.len(), 2);
+    assert_eq!(ids[0].index(), 0);
+    assert_eq!(interner.ids().collect::<Vec<_>>(), [ids[0], other]);
+
+    // Both backtraces share every frame but their innermost few.
+    let frames = interner.frames(ids[0]).unwrap().len();
+    assert!(interner.frame_count() < frames * 2);
+
+    interner.resolve();
+    let bt = interner.get(ids[0]).unwrap();
+    assert_eq!(bt.frames().len(), frames);
+    assert!(bt.frames().iter().any(|f| !f.symbols().is_empty()));
+}
+
+#[test]
+fn try_resolve_explains_failures() {
+    use backtrace::ResolveError;
+
+    let mut ip = None;
+    backtrace::trace(|frame| {
+        ip = Some(frame.ip());
+        false
+    });
+    let symbols = backtrace::try_resolve(ip.unwrap()).unwrap();
+    assert!(!symbols.is_empty());
+
+    let err = backtrace::try_resolve(std::ptr::null_mut()).unwrap_err();
+    if cfg!(all(unix, not(target_os = "emscripten"))) {
+        assert_eq!(err, ResolveError::NoModule);
+    }
+    assert!(!err.to_string().is_empty());
+}
+
+#[test]
+fn lazy_backtrace() {
+    use backtrace::LazyBacktrace;
+    use std::sync::Arc;
+
+    fn is_send_sync<T: Send + Sync>() {}
+    is_send_sync::<LazyBacktrace>();
+
+    let lazy = Arc::new(LazyBacktrace::new());
+    assert!(!lazy.is_resolved());
+    let eager = backtrace::Backtrace::new();
+    // Only the frames of the functions capturing the backtraces differ.
+    assert_eq!(
+        lazy.ips().last(),
+        eager.frames().last().map(|f| f.ip() as usize).as_ref()
+    );
+
+    let clone = (*lazy).clone();
+    assert!(!clone.is_resolved());
+
+    let threads = (0..4)
+        .map(|_| {
+            let lazy = lazy.clone();
+            thread::spawn(move || lazy.get().frames().len())
+        })
+        .collect::<Vec<_>>();
+    for t in threads {
+        assert_eq!(t.join().unwrap(), lazy.ips().len());
+    }
+    assert!(lazy.is_resolved());
+    assert!(lazy
+        .get()
+        .frames()
+        .iter()
+        .any(|f| f.symbols().iter().any(|s| s.name().is_some())));
+
+    let clone = (*lazy).clone();
+    assert!(clone.is_resolved());
+    assert_eq!(clone.into_backtrace().frames().len(), lazy.ips().len());
+}
+
+#[test]
+fn new_from_caller_skips_wrappers() {
+    use backtrace::Backtrace;
+
+    #[inline(never)]
+    fn wrapper_inner(skip: usize) -> Backtrace {
+        let bt = Backtrace::new_from_caller(skip);
+        // Do something after the call so that it isn't a tail call, which
+        // would remove this frame.
+        assert!(!bt.frames().is_empty());
+        bt
+    }
+
+    #[inline(never)]
+    fn wrapper_outer(skip: usize) -> Backtrace {
+        let bt = wrapper_inner(skip);
+        assert!(!bt.frames().is_empty());
+        bt
+    }
+
+    fn names(bt: &Backtrace) -> Vec<String> {
+        bt.frames()
+            .iter()
+            .flat_map(|f| f.symbols())
+            .filter_map(|s| s.name().map(|n| n.to_string()))
+            .collect()
+    }
+
+    let from_outer = wrapper_outer(0);
+    let names_outer = names(&from_outer);
+    assert!(!names_outer.iter().any(|n| n.contains("wrapper_inner")));
+    assert!(
+        names_outer[0].contains("wrapper_outer"),
+        "{:?}",
+        names_outer
+    );
+
+    let from_test = wrapper_outer(1);
+    let names_test = names(&from_test);
+    assert!(!names_test.iter().any(|n| n.contains("wrapper_")));
+}
+
+#[test]
+fn path_map_rewrites_filenames() {
+    use backtrace::{Backtrace, BacktraceFmt, PathMap, PrintFmt};
+    use std::fmt;
+    use std::path::Path;
+
+    let root = env!("CARGO_MANIFEST_DIR");
+    let here = Path::new(root).join("tests").join("smoke.rs");
+    let file_of = |bt: &Backtrace| {
+        bt.frames()
+            .iter()
+            .flat_map(|f| f.symbols())
+            .filter_map(|s| s.filename())
+            .find(|f| f.ends_with("smoke.rs"))
+            .map(|f| f.to_path_buf())
+    };
+    let before = match file_of(&Backtrace::new()) {
+        Some(file) => file,
+        None => {
+            println!("no line information, skipping");
+            return;
+        }
+    };
+    if before != here {
+        println!("paths aren't absolute, skipping");
+        return;
+    }
+
+    // Relative paths are resolved against the working directory, which is
+    // the manifest directory, so other tests can still read their sources.
+    backtrace::set_path_map(PathMap::new().map(root, ""));
+    let after = file_of(&Backtrace::new());
+    backtrace::set_path_map(PathMap::new());
+    assert_eq!(after.as_deref(), Some(Path::new("tests/smoke.rs")));
+
+    struct Mapped(Backtrace, PathMap);
+
+    impl fmt::Display for Mapped {
+        fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut print_path =
+                |fmt: &mut fmt::Formatter<'_>, path: backtrace::BytesOrWideString<'_>| {
+                    fmt::Display::fmt(&path, fmt)
+                };
+            let mut f = BacktraceFmt::new(fmt, PrintFmt::Short, &mut print_path);
+            f.path_map(&self.1);
+            for frame in self.0.frames() {
+                f.frame().backtrace_frame(frame)?;
+            }
+            f.finish()
+        }
+    }
+
+    let map = PathMap::new().map(root, "$ROOT");
+    let printed = Mapped(Backtrace::new(), map).to_string();
+    let mapped = Path::new("$ROOT").join("tests").join("smoke.rs");
+    assert!(
+        printed.contains(&mapped.display().to_string()),
+        "{}",
+        printed
+    );
+    assert!(!printed.contains(root));
+}
+
+#[test]
+fn source_map_rewrites_rust_src_paths() {
+    use backtrace::PathMap;
+    use std::path::{Path, PathBuf};
+
+    // `Option::map` is instantiated in this crate, with the line information
+    // of the standard library's sources.
+    let mut frames = Vec::new();
+    Some(()).map(|()| {
+        backtrace::trace(|f| {
+            frames.push(f.clone());
+            true
+        })
+    });
+    let files = || {
+        let mut files = Vec::new();
+        for frame in frames.iter() {
+            backtrace::resolve_frame(frame, |sym| {
+                files.extend(sym.filename().map(|f| f.to_path_buf()));
+            });
+        }
+        files
+    };
+    let before = files();
+    let rest = match before.iter().find_map(|f| {
+        let rest = f.strip_prefix("/rustc").ok()?;
+        let hash = rest.iter().next()?;
+        Some(rest.strip_prefix(hash).ok()?.to_path_buf())
+    }) {
+        Some(rest) => rest,
+        None => {
+            println!("no paths of the standard library, skipping");
+            return;
+        }
+    };
+
+    backtrace::set_source_map(PathMap::new().map_rust_src("/opt/rust-src"));
+    let after = files();
+    backtrace::set_source_map(PathMap::new());
+    let expected: PathBuf = Path::new("/opt/rust-src").join(rest);
+    assert!(after.contains(&expected), "{:?}", after);
+    assert!(
+        !after.iter().any(|f| f.starts_with("/rustc")),
+        "{:?}",
+        after
+    );
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn stack_bounds_contain_locals() {
+    fn check() {
+        let local = 0u8;
+        let addr = &local as *const u8 as usize;
+        let (low, high) = backtrace::stack_bounds().expect("no stack bounds");
+        assert!(
+            low <= addr && addr < high,
+            "{:#x} not in {:#x}..{:#x}",
+            addr,
+            low,
+            high
+        );
+        // Later calls return the bounds that were remembered.
+        assert_eq!(backtrace::stack_bounds(), Some((low, high)));
+    }
+
+    check();
+    let main = backtrace::stack_bounds();
+    let other = std::thread::spawn(|| {
+        check();
+        backtrace::stack_bounds()
+    })
+    .join()
+    .unwrap();
+    assert_ne!(main, other);
+}
+
+#[test]
+fn collapse_recursion_in_output() {
+    use backtrace::Backtrace;
+
+    #[inline(never)]
+    fn recurse(depth: usize) -> Backtrace {
+        let bt = if depth == 0 {
+            Backtrace::new_unresolved()
+        } else {
+            recurse(depth - 1)
+        };
+        // Keeps the call from being turned into a jump.
+        assert!(!bt.frames().is_empty());
+        bt
+    }
+
+    let bt = recurse(40);
+    assert!(bt.frames().len() > 40);
+    let full = format!("{:#?}", bt);
+    let json = bt.to_json();
+    assert!(!full.contains(" repeat "));
+
+    backtrace::set_collapse_recursion(Some(3));
+    let collapsed = format!("{:#?}", bt);
+    let collapsed_json = bt.to_json();
+    backtrace::set_collapse_recursion(None);
+
+    assert!(collapsed.contains(" times]"), "{}", collapsed);
+    assert!(collapsed.lines().count() < full.lines().count());
+    assert!(collapsed_json.contains("{\"repeat\":{\"start\":"));
+    assert!(collapsed_json.len() < json.len());
+    // The frames after the recursion keep their numbers.
+    let last = |s: &str| {
+        s.lines()
+            .rev()
+            .find_map(|l| l.trim().split(':').next()?.parse::<usize>().ok())
+    };
+    assert_eq!(last(&collapsed), last(&full));
+    // The backtrace itself still has all of its frames.
+    assert_eq!(format!("{:#?}", bt), full);
+}
+
+#[test]
+fn reentrant_capture_is_empty() {
+    use backtrace::Backtrace;
+
+    assert!(!backtrace::is_reentrant());
+    let mut nested = None;
+    let mut symbols = 0;
+    backtrace::trace(|frame| {
+        assert!(backtrace::is_reentrant());
+        nested = Some(Backtrace::new());
+        let mut ips = [0; 4];
+        assert_eq!(backtrace::trace_raw(&mut ips), 0);
+        // Resolving while walking isn't nested.
+        backtrace::resolve_frame(frame, |_| symbols += 1);
+        false
+    });
+    assert!(symbols > 0);
+    let nested = nested.unwrap();
+    assert!(nested.is_reentrant());
+    assert!(nested.is_truncated());
+    assert!(nested.frames().is_empty());
+    assert!(!backtrace::is_reentrant());
+
+    // A capture from within `resolve` walks the stack, but leaves its frames
+    // unresolved.
+    let mut resolved = None;
+    let ip = reentrant_capture_is_empty as usize as *mut std::ffi::c_void;
+    backtrace::resolve((ip as usize + 1) as *mut _, |_| {
+        if resolved.is_none() {
+            assert!(backtrace::is_reentrant());
+            resolved = Some(Backtrace::new());
+        }
+    });
+    if let Some(mut bt) = resolved {
+        assert!(!bt.is_reentrant());
+        assert!(!bt.frames().is_empty());
+        assert!(bt.frames().iter().all(|f| f.symbols().is_empty()));
+        bt.resolve();
+        assert!(bt.frames().iter().any(|f| !f.symbols().is_empty()));
+    }
+
+    let bt = Backtrace::new();
+    assert!(!bt.is_reentrant());
+    assert!(!bt.frames().is_empty());
+}
+
+#[test]
+fn trace_with_diagnostics_reports_why_it_stopped() {
+    use backtrace::{StopReason, TraceLimit};
+
+    let mut frames = 0;
+    let reason = backtrace::trace_with_diagnostics(TraceLimit::new(), |_| {
+        frames += 1;
+        true
+    });
+    assert!(frames > 0);
+    // The unwind information of the thread's entry point marks the end of the
+    // stack, while frame pointers may not lead there.
+    if cfg!(all(
+        target_os = "linux",
+        not(feature = "trace-frame-pointer")
+    )) {
+        assert_eq!(reason, StopReason::EndOfStack);
+    }
+
+    let stop = backtrace::trace_with_diagnostics(TraceLimit::new(), |_| false);
+    assert_eq!(stop, StopReason::Stopped);
+    let limited = backtrace::trace_with_diagnostics(TraceLimit::new().max_frames(1), |_| true);
+    assert_eq!(limited, StopReason::LimitReached);
+
+    let mut nested = None;
+    backtrace::trace(|_| {
+        nested = Some(backtrace::trace_with_diagnostics(TraceLimit::new(), |_| {
+            true
+        }));
+        false
+    });
+    assert_eq!(nested, Some(StopReason::Reentrant));
+}
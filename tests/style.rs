@@ -0,0 +1,114 @@
+// This is its own test binary as the style is a process-wide setting, which
+// would change how other tests print backtraces. The tests here change it too,
+// so they take turns.
+
+use backtrace::{Backtrace, BacktraceStyle};
+use std::sync::{Mutex, MutexGuard, Once};
+
+// Held by each test while it changes the style. `Mutex::new` isn't a
+// `const fn` on the oldest Rust supported, so it's created on first use.
+fn settings() -> MutexGuard<'static, ()> {
+    static INIT: Once = Once::new();
+    static mut SETTINGS: Option<Mutex<()>> = None;
+    unsafe {
+        INIT.call_once(|| SETTINGS = Some(Mutex::new(())));
+        (*std::ptr::addr_of!(SETTINGS))
+            .as_ref()
+            .unwrap()
+            .lock()
+            .unwrap()
+    }
+}
+
+#[inline(never)]
+fn capture() -> Backtrace {
+    Backtrace::new()
+}
+
+// Returns the lines of `s` which mention `name`.
+fn lines<'a>(s: &'a str, name: &str) -> Vec<&'a str> {
+    s.lines().filter(|l| l.contains(name)).collect()
+}
+
+#[test]
+fn colors_highlight_user_code() {
+    let _settings = settings();
+    let bt = capture();
+    let plain = format!("{:#?}", bt);
+    backtrace::set_backtrace_style(
+        BacktraceStyle::new()
+            .color(true)
+            .user_code(env!("CARGO_MANIFEST_DIR")),
+    );
+    let colored = format!("{:#?}", bt);
+    backtrace::set_backtrace_style(BacktraceStyle::new());
+    assert_eq!(format!("{:#?}", bt), plain);
+    assert!(!plain.contains('\x1b'));
+
+    // Removing the escape codes leaves the same output.
+    let stripped = colored
+        .replace("\x1b[1m", "")
+        .replace("\x1b[2m", "")
+        .replace("\x1b[0m", "");
+    assert_eq!(stripped, plain);
+
+    // This crate's frames are bold, along with their locations.
+    let user = lines(&colored, "style::capture");
+    assert!(!user.is_empty(), "{}", colored);
+    for line in user {
+        assert!(line.contains("\x1b[1mstyle::capture"), "{:?}", line);
+        assert!(line.ends_with("\x1b[0m"), "{:?}", line);
+    }
+    for line in lines(&colored, "tests/style.rs") {
+        assert!(line.contains("\x1b[1m"), "{:?}", line);
+    }
+
+    // Frames in the standard library are dimmed.
+    let std = lines(&colored, "core::ops::function::FnOnce::call_once");
+    assert!(!std.is_empty(), "{}", colored);
+    for line in std {
+        assert!(line.contains("\x1b[2m"), "{:?}", line);
+    }
+}
+
+#[test]
+fn user_code_matches_whole_components() {
+    use std::path::Path;
+
+    let style = BacktraceStyle::new()
+        .user_code("/work/app")
+        .user_code("src");
+    assert!(style.is_user_code(Path::new("/work/app/src/main.rs")));
+    assert!(style.is_user_code(Path::new("src/lib.rs")));
+    assert!(!style.is_user_code(Path::new("/work/apple/main.rs")));
+    assert!(!style.is_user_code(Path::new("/work/src/lib.rs")));
+}
+
+#[test]
+fn crates_annotate_and_group_symbols() {
+    let _settings = settings();
+    let bt = capture();
+    let plain = format!("{:?}", bt);
+    backtrace::set_backtrace_style(BacktraceStyle::new().crate_names(true));
+    let annotated = format!("{:?}", bt);
+    backtrace::set_backtrace_style(BacktraceStyle::new().group_crates(true));
+    let grouped = format!("{:?}", bt);
+    backtrace::set_backtrace_style(BacktraceStyle::new());
+
+    let line = lines(&annotated, "style::capture")[0];
+    assert!(line.ends_with("style::capture (style)"), "{:?}", line);
+    assert_eq!(annotated.lines().count(), plain.lines().count());
+
+    // Each run of symbols from the same crate gets a header line naming it.
+    let headers = grouped
+        .lines()
+        .filter(|l| l.trim_start().starts_with("-- "))
+        .map(|l| l.trim())
+        .collect::<Vec<_>>();
+    assert!(headers.contains(&"-- style --"), "{}", grouped);
+    assert!(headers.windows(2).all(|w| w[0] != w[1]), "{:?}", headers);
+    assert_eq!(
+        grouped.lines().count(),
+        plain.lines().count() + headers.len()
+    );
+}
@@ -0,0 +1,81 @@
+// This is its own test binary as the symbolizer is a process-wide setting,
+// which would change how other tests are resolved.
+
+use backtrace::{Backtrace, NativeSymbolizer, Symbol, SymbolizeBackend};
+use std::ffi::c_void;
+use std::path::Path;
+
+#[inline(never)]
+fn marked() -> Backtrace {
+    Backtrace::new_unresolved()
+}
+
+// Resolves frames in `marked` to a made up symbol, and everything else
+// natively.
+struct Marked;
+
+impl SymbolizeBackend for Marked {
+    fn resolve(&self, addr: *mut c_void, cb: &mut dyn FnMut(&Symbol)) {
+        let mut native = None;
+        NativeSymbolizer.resolve(addr, &mut |sym| {
+            if native.is_none() {
+                native = sym.name().map(|n| n.to_string());
+            }
+        });
+        match native {
+            Some(ref name) if name.contains("marked") => cb(&Symbol::new(b"custom::marked")
+                .with_addr(marked as *const () as *mut c_void)
+                .with_filename(Path::new("marked.rs"))
+                .with_lineno(7)
+                .with_colno(3)),
+            _ => NativeSymbolizer.resolve(addr, cb),
+        }
+    }
+}
+
+fn names(bt: &Backtrace) -> Vec<String> {
+    let mut bt = bt.clone();
+    bt.resolve();
+    bt.frames()
+        .iter()
+        .flat_map(|f| f.symbols())
+        .filter_map(|s| s.name().map(|n| n.to_string()))
+        .collect()
+}
+
+#[test]
+fn custom_symbolizer_replaces_native() {
+    let bt = marked();
+    let native = names(&bt);
+    assert!(native.iter().any(|n| n.contains("marked")), "{:?}", native);
+    assert!(!native.iter().any(|n| n == "custom::marked"));
+
+    backtrace::set_symbolizer(Box::new(Marked));
+    let custom = names(&bt);
+    let mut resolved = bt.clone();
+    resolved.resolve();
+    backtrace::set_symbolizer(Box::new(NativeSymbolizer));
+
+    assert!(custom.iter().any(|n| n == "custom::marked"), "{:?}", custom);
+    assert!(!custom.iter().any(|n| n.contains("symbolizer::marked")));
+    // Frames other than the marked ones are still resolved natively.
+    assert!(custom
+        .iter()
+        .any(|n| n.contains("custom_symbolizer_replaces_native")));
+
+    let sym = resolved
+        .frames()
+        .iter()
+        .flat_map(|f| f.symbols())
+        .find(|s| {
+            s.name()
+                .map_or(false, |n| n.to_string() == "custom::marked")
+        })
+        .unwrap();
+    assert_eq!(sym.addr(), Some(marked as *const () as *mut c_void));
+    assert_eq!(sym.filename(), Some(Path::new("marked.rs")));
+    assert_eq!(sym.lineno(), Some(7));
+    assert_eq!(sym.colno(), Some(3));
+
+    assert_eq!(names(&bt), native);
+}